@@ -0,0 +1,197 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+//! Throughput of each converter on synthetic inputs of a fixed size, in records and megabytes of
+//! input per second, e.g. `cargo bench --bench converters -- fq2pq`.
+
+use std::fs;
+use std::io::{sink, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput};
+use parquet::basic::Compression;
+
+use brrrr_lib::csv_writer;
+use brrrr_lib::fasta_ops::FastaOptions;
+use brrrr_lib::fastq_ops::FastqOptions;
+use brrrr_lib::gff_ops::GffOptions;
+use brrrr_lib::json_writer;
+use brrrr_lib::parquet_writer::{self, BioFileCompression};
+use brrrr_lib::selftest::Lcg;
+
+/// The number of records in each synthetic input.
+const RECORD_COUNT: u64 = 20_000;
+
+/// Synthetic FASTA, FASTQ, and GFF inputs, written once to a temporary directory.
+struct Inputs {
+    dir: tempfile::TempDir,
+    fasta: PathBuf,
+    fastq: PathBuf,
+    gff: PathBuf,
+}
+
+impl Inputs {
+    fn create() -> Inputs {
+        let dir = tempfile::tempdir().expect("Error creating the bench directory.");
+        let mut rng = Lcg(42);
+
+        // Contig-like FASTA records, wrapped at 80 bases.
+        let fasta = dir.path().join("bench.fa");
+        let mut file = fs::File::create(&fasta).unwrap();
+        for i in 0..RECORD_COUNT {
+            let len = 500 + rng.next(1_500);
+            writeln!(file, ">contig{} sample=bench", i).unwrap();
+            for line in rng.sequence(len, b"ACGT").chunks(80) {
+                file.write_all(line).unwrap();
+                writeln!(file).unwrap();
+            }
+        }
+
+        // Short-read FASTQ records, 150 bases with Illumina-range qualities.
+        let fastq = dir.path().join("bench.fq");
+        let mut file = fs::File::create(&fastq).unwrap();
+        for i in 0..RECORD_COUNT {
+            let sequence = rng.sequence(150, b"ACGT");
+            let quality: Vec<u8> = (0..150).map(|_| b'#' + rng.next(40) as u8).collect();
+            writeln!(file, "@read{}", i).unwrap();
+            file.write_all(&sequence).unwrap();
+            writeln!(file, "\n+").unwrap();
+            file.write_all(&quality).unwrap();
+            writeln!(file).unwrap();
+        }
+
+        // Gene and exon GFF features across a few chromosomes.
+        let gff = dir.path().join("bench.gff");
+        let mut file = fs::File::create(&gff).unwrap();
+        writeln!(file, "##gff-version 3").unwrap();
+        for i in 0..RECORD_COUNT {
+            let start = 1 + rng.next(1_000_000);
+            let end = start + rng.next(5_000);
+            let (feature, parent) = match i % 4 {
+                0 => ("gene", String::new()),
+                _ => ("exon", format!(";Parent=g{}", i / 4)),
+            };
+            writeln!(
+                file,
+                "chr{}\tbench\t{}\t{}\t{}\t.\t{}\t.\tID=f{};Name=n{}{}",
+                1 + rng.next(22),
+                feature,
+                start,
+                end,
+                ["+", "-"][rng.next(2)],
+                i,
+                i,
+                parent
+            )
+            .unwrap();
+        }
+
+        Inputs {
+            dir,
+            fasta,
+            fastq,
+            gff,
+        }
+    }
+
+    fn output(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+}
+
+/// Benchmarks `convert` on `input` twice, once reporting records per second and once megabytes
+/// of input per second.
+fn bench_throughput<F: FnMut()>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    input: &Path,
+    mut convert: F,
+) {
+    let bytes = fs::metadata(input).unwrap().len();
+
+    group.throughput(Throughput::Elements(RECORD_COUNT));
+    group.bench_function(format!("{}/records", name), |b| b.iter(&mut convert));
+
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function(format!("{}/bytes", name), |b| b.iter(&mut convert));
+}
+
+fn open(path: &Path) -> BufReader<fs::File> {
+    BufReader::new(fs::File::open(path).unwrap())
+}
+
+fn bench_parquet_converters(c: &mut Criterion) {
+    let inputs = Inputs::create();
+    let mut group = c.benchmark_group("parquet_converters");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+
+    let output = inputs.output("bench.fa.parquet");
+    bench_throughput(&mut group, "fa2pq", &inputs.fasta, || {
+        parquet_writer::fa2pq(
+            &inputs.fasta,
+            &output,
+            Compression::SNAPPY,
+            BioFileCompression::UNCOMPRESSED,
+            &FastaOptions::default(),
+        )
+        .unwrap()
+    });
+
+    let output = inputs.output("bench.fq.parquet");
+    bench_throughput(&mut group, "fq2pq", &inputs.fastq, || {
+        parquet_writer::fq2pq(
+            &inputs.fastq,
+            &output,
+            Compression::SNAPPY,
+            BioFileCompression::UNCOMPRESSED,
+            &FastqOptions::default(),
+        )
+        .unwrap()
+    });
+
+    let output = inputs.output("bench.gff.parquet");
+    bench_throughput(&mut group, "gff2pq", &inputs.gff, || {
+        parquet_writer::gff2pq(
+            &inputs.gff,
+            &output,
+            Compression::SNAPPY,
+            &GffOptions::default(),
+        )
+        .unwrap()
+    });
+
+    group.finish();
+}
+
+fn bench_text_converters(c: &mut Criterion) {
+    let inputs = Inputs::create();
+    let mut group = c.benchmark_group("text_converters");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+
+    bench_throughput(&mut group, "fa2jsonl", &inputs.fasta, || {
+        json_writer::fa2jsonl(open(&inputs.fasta), &mut sink(), &FastaOptions::default()).unwrap()
+    });
+    bench_throughput(&mut group, "fq2jsonl", &inputs.fastq, || {
+        json_writer::fq2jsonl(open(&inputs.fastq), &mut sink(), &FastqOptions::default()).unwrap()
+    });
+    bench_throughput(&mut group, "gff2jsonl", &inputs.gff, || {
+        json_writer::gff2jsonl(open(&inputs.gff), &mut sink(), &GffOptions::default()).unwrap()
+    });
+    bench_throughput(&mut group, "fa2csv", &inputs.fasta, || {
+        csv_writer::fa2csv(open(&inputs.fasta), &mut sink(), &FastaOptions::default()).unwrap()
+    });
+    bench_throughput(&mut group, "fq2csv", &inputs.fastq, || {
+        csv_writer::fq2csv(open(&inputs.fastq), &mut sink(), &FastqOptions::default()).unwrap()
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_parquet_converters, bench_text_converters);
+criterion_main!(benches);
@@ -3,6 +3,7 @@
 
 use criterion::{criterion_group, criterion_main, Criterion};
 
+use brrrr_lib::fasta_ops::FastaOptions;
 use parquet::basic::Compression;
 use std::env;
 use std::fs::File;
@@ -12,13 +13,17 @@ use std::time::Duration;
 extern crate brrrr_lib;
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let path: &'static str = env!("BENCH_DATA");
+    let path: &'static str = option_env!("BENCH_DATA").unwrap_or("benches/data");
 
     c.bench_function("Write 10000 records from FASTA to jsonl.", |b| {
         b.iter(|| {
             let filename = format!("./{}/10000.fasta", path);
             let f = File::open(filename).expect("Error opening file.");
-            let _ = brrrr_lib::json_writer::fa2jsonl(BufReader::new(f), &mut sink());
+            let _ = brrrr_lib::json_writer::fa2jsonl(
+                BufReader::new(f),
+                &mut sink(),
+                &FastaOptions::default(),
+            );
         })
     });
 }
@@ -29,7 +34,7 @@ fn bench_fasta_parquet_output(c: &mut Criterion) {
         .sample_size(10)
         .measurement_time(Duration::from_secs(30));
 
-    let path: &'static str = env!("BENCH_DATA");
+    let path: &'static str = option_env!("BENCH_DATA").unwrap_or("benches/data");
     let temp_path = env::temp_dir();
 
     let test_cases = vec![
@@ -69,6 +74,7 @@ fn bench_fasta_parquet_output(c: &mut Criterion) {
                         &out_file,
                         pq_compression,
                         f_compression,
+                        &FastaOptions::default(),
                     );
                 })
             },
@@ -0,0 +1,122 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::io::{BufRead, Write};
+
+use crate::errors::BrrrrError;
+use crate::stats::{try_for_each_record, Composition};
+
+/// Oligos shorter than this use the Wallace rule for their melting temperature.
+const WALLACE_MAX_LENGTH: u64 = 14;
+
+/// Returns the bases of `sequence`, or None if it's empty or has a base other than A, C, G, or T,
+/// which the formulas below have no value for. Case is ignored.
+fn unambiguous(sequence: &[u8]) -> Option<Composition> {
+    let mut composition = Composition::default();
+    composition.add(sequence);
+
+    if composition.total() == 0 || composition.n + composition.other > 0 {
+        None
+    } else {
+        Some(composition)
+    }
+}
+
+/// Returns the melting temperature of a DNA oligo in °C, or None if it's empty or ambiguous.
+///
+/// Oligos shorter than 14 bases use the Wallace rule, `2 * (A + T) + 4 * (G + C)`. Longer ones
+/// use `64.9 + 41 * (G + C - 16.4) / length`. Both assume about 50 mM Na+, and neither accounts
+/// for nearest-neighbour stacking, so treat them as estimates for primer design.
+pub fn melting_temperature(sequence: &[u8]) -> Option<f64> {
+    let c = unambiguous(sequence)?;
+    let at = (c.a + c.t) as f64;
+    let gc = (c.g + c.c) as f64;
+
+    if c.total() < WALLACE_MAX_LENGTH {
+        Some(2.0 * at + 4.0 * gc)
+    } else {
+        Some(64.9 + 41.0 * (gc - 16.4) / c.total() as f64)
+    }
+}
+
+/// Returns the molecular weight of a single-stranded DNA oligo in g/mol, or None if it's empty or
+/// ambiguous. The oligo is taken to have no 5' phosphate, as synthesized oligos usually don't.
+pub fn molecular_weight(sequence: &[u8]) -> Option<f64> {
+    let c = unambiguous(sequence)?;
+
+    Some(
+        c.a as f64 * 313.21 + c.t as f64 * 304.2 + c.c as f64 * 289.18 + c.g as f64 * 329.21
+            - 61.96,
+    )
+}
+
+/// Writes the length, GC fraction, melting temperature, and molecular weight of every record of
+/// a FASTA or FASTQ input as tab-separated lines, with a header. Values that can't be computed,
+/// e.g. the melting temperature of an oligo with an N, are written as `.`.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `output` where the table is written.
+pub fn write_oligo_table<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
+    writeln!(output, "id\tlength\tgc\ttm\tmolecular_weight")?;
+
+    try_for_each_record(input, |id, sequence| {
+        let mut composition = Composition::default();
+        composition.add(sequence);
+
+        let format = |value: Option<f64>, precision: usize| match value {
+            Some(v) => format!("{:.*}", precision, v),
+            None => ".".to_string(),
+        };
+
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{}\t{}",
+            id,
+            sequence.len(),
+            format(composition.gc(), 4),
+            format(melting_temperature(sequence), 1),
+            format(molecular_weight(sequence), 2),
+        )?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_melting_temperature() {
+        // The Wallace rule: 2 * 4 + 4 * 4.
+        assert_eq!(melting_temperature(b"acgtACGT"), Some(24.0));
+        // 64.9 + 41 * (10 - 16.4) / 20.
+        let tm = melting_temperature(b"ACGTACGTACGTACGTACGA").unwrap();
+        assert!((tm - 51.78).abs() < 1e-9);
+
+        assert_eq!(melting_temperature(b""), None);
+        assert_eq!(melting_temperature(b"ACNT"), None);
+    }
+
+    #[test]
+    fn test_molecular_weight() {
+        let mw = molecular_weight(b"ACGT").unwrap();
+        assert!((mw - 1173.84).abs() < 1e-9);
+        assert_eq!(molecular_weight(b"ACRT"), None);
+    }
+
+    #[test]
+    fn test_write_oligo_table() {
+        let fasta = b">p1\nACGT\n>p2\nACNT\n" as &[u8];
+        let mut output = Vec::new();
+        write_oligo_table(fasta, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id\tlength\tgc\ttm\tmolecular_weight\n\
+             p1\t4\t0.5000\t12.0\t1173.84\n\
+             p2\t4\t0.2500\t.\t.\n"
+        );
+    }
+}
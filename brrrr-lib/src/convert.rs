@@ -0,0 +1,402 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use parquet::basic::Compression;
+
+use crate::csv_writer;
+use crate::errors::BrrrrError;
+use crate::fasta_ops::FastaOptions;
+use crate::fastq_ops::FastqOptions;
+use crate::gff_ops::GffOptions;
+use crate::input;
+use crate::json_writer;
+use crate::output::{CompressedWriter, OutputCompression};
+use crate::parquet_reader::{self, GffOutputOptions};
+use crate::parquet_writer::{self, BioFileCompression};
+use crate::tabular::{CsvOptions, InferOptions};
+
+/// The formats [`convert`] reads and writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Fasta,
+    Fastq,
+    Gff,
+    Parquet,
+    Jsonl,
+    Csv,
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileFormat::Fasta => "FASTA",
+            FileFormat::Fastq => "FASTQ",
+            FileFormat::Gff => "GFF",
+            FileFormat::Parquet => "Parquet",
+            FileFormat::Jsonl => "jsonl",
+            FileFormat::Csv => "csv",
+        })
+    }
+}
+
+/// The format and compression of a file, as told from its extensions, e.g. `reads.fq.gz` is
+/// gzipped FASTQ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileType {
+    pub format: FileFormat,
+    /// The compression of the file as a whole, `.gz` or `.zst`, or None.
+    pub compression: OutputCompression,
+}
+
+impl FileType {
+    /// Returns the type of the file at `path` from its extensions. An extension that isn't one
+    /// of the formats, e.g. `.txt`, is an error.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, BrrrrError> {
+        let path = path.as_ref();
+        let (compression, stem) = match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => (
+                OutputCompression::Gzip,
+                Path::new(path.file_stem().unwrap_or_default()),
+            ),
+            Some("zst") => (
+                OutputCompression::Zstd,
+                Path::new(path.file_stem().unwrap_or_default()),
+            ),
+            _ => (OutputCompression::None, path),
+        };
+
+        let extension = stem
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let format = match extension.to_ascii_lowercase().as_str() {
+            "fa" | "fasta" | "fna" | "faa" => FileFormat::Fasta,
+            "fq" | "fastq" => FileFormat::Fastq,
+            "gff" | "gff3" => FileFormat::Gff,
+            "parquet" | "pq" => FileFormat::Parquet,
+            "jsonl" | "ndjson" => FileFormat::Jsonl,
+            "csv" => FileFormat::Csv,
+            _ => {
+                return Err(invalid(format!(
+                    "can't tell the format of {} from its extension",
+                    path.display()
+                )))
+            }
+        };
+
+        Ok(FileType {
+            format,
+            compression,
+        })
+    }
+}
+
+/// Returns an invalid input error with `message`.
+fn invalid(message: String) -> BrrrrError {
+    io::Error::new(io::ErrorKind::InvalidInput, message).into()
+}
+
+/// Writes `output` through a [`CompressedWriter`] with `compression`, running `write` on it.
+fn write_compressed<F>(
+    output: &Path,
+    compression: OutputCompression,
+    write: F,
+) -> Result<(), BrrrrError>
+where
+    F: FnOnce(&mut CompressedWriter<BufWriter<File>>) -> Result<(), BrrrrError>,
+{
+    let mut writer =
+        CompressedWriter::new(BufWriter::new(File::create(output)?), compression, None)?;
+    write(&mut writer)?;
+    Ok(writer.finish()?.flush()?)
+}
+
+/// Converts `input` to `output`, picking the converters from their extensions, see
+/// [`FileType::from_path`], with the default options. The pairs it converts, by input row:
+///
+/// | from \ to | FASTA | FASTQ | GFF | Parquet | jsonl | csv |
+/// |------------|-------|-------|-----|---------|-------|-----|
+/// | FASTA      |       |       |     | yes     | yes   | yes |
+/// | FASTQ      |       |       |     | yes     | yes   | yes |
+/// | GFF        |       |       |     | yes     | yes   |     |
+/// | Parquet    | yes   | yes   | yes |         | yes   | yes |
+/// | jsonl      |       |       |     | yes     |       |     |
+/// | csv        |       |       |     | yes     |       |     |
+///
+/// A FASTA, FASTQ, or GFF input or a text output may be gzipped or zstd compressed, except that a
+/// gzipped GFF output is BGZF and a GFF output can't be zstd compressed. A Parquet file, or a
+/// jsonl or csv input, can't be compressed as a whole. A Parquet input with columns csv can't
+/// hold, e.g. the attributes of a GFF file, can't go to csv. Any other pair is an error, before
+/// anything is written.
+///
+/// # Arguments
+///
+/// * `input` the path to the input.
+/// * `output` the path to the output.
+/// * `parquet_compression` the codec inside a Parquet output.
+pub fn convert<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
+) -> Result<(), BrrrrError> {
+    let from = FileType::from_path(&input)?;
+    let to = FileType::from_path(&output)?;
+    let (input, output) = (input.as_ref(), output.as_ref());
+
+    if from.format == FileFormat::Parquet && from.compression != OutputCompression::None {
+        return Err(invalid(String::from(
+            "a Parquet input can't be compressed as a whole",
+        )));
+    }
+    if to.format == FileFormat::Parquet && to.compression != OutputCompression::None {
+        return Err(invalid(String::from(
+            "a Parquet output can't be compressed as a whole, it uses its own codec",
+        )));
+    }
+
+    let input_compression = match from.compression {
+        OutputCompression::Gzip => BioFileCompression::GZIP,
+        OutputCompression::Zstd => BioFileCompression::ZSTD,
+        _ => BioFileCompression::UNCOMPRESSED,
+    };
+    let reader = || -> Result<_, BrrrrError> {
+        Ok(BufReader::new(
+            input_compression.decoder(input::open(input)?)?,
+        ))
+    };
+
+    match (from.format, to.format) {
+        (FileFormat::Fasta, FileFormat::Parquet) => parquet_writer::fa2pq(
+            &input,
+            &output,
+            parquet_compression,
+            input_compression,
+            &FastaOptions::default(),
+        ),
+        (FileFormat::Fastq, FileFormat::Parquet) => parquet_writer::fq2pq(
+            input,
+            output,
+            parquet_compression,
+            input_compression,
+            &FastqOptions::default(),
+        ),
+        (FileFormat::Gff, FileFormat::Parquet) => parquet_writer::write_gff_output(
+            reader()?,
+            output,
+            parquet_compression,
+            &GffOptions::default(),
+        ),
+        (FileFormat::Jsonl | FileFormat::Csv, FileFormat::Parquet)
+            if from.compression != OutputCompression::None =>
+        {
+            Err(invalid(format!(
+                "a {} input to Parquet can't be compressed",
+                from.format
+            )))
+        }
+        (FileFormat::Jsonl, FileFormat::Parquet) => {
+            parquet_writer::jsonl2pq(input, output, parquet_compression, &InferOptions::default())
+        }
+        (FileFormat::Csv, FileFormat::Parquet) => parquet_writer::csv2pq(
+            input,
+            output,
+            parquet_compression,
+            &CsvOptions::default(),
+            &InferOptions::default(),
+        ),
+        (FileFormat::Parquet, FileFormat::Gff) => {
+            let options = match to.compression {
+                OutputCompression::Zstd => {
+                    return Err(invalid(String::from(
+                        "a GFF output from Parquet can be gzipped, but not zstd compressed",
+                    )))
+                }
+                compression => GffOutputOptions {
+                    bgzip: compression != OutputCompression::None,
+                    ..Default::default()
+                },
+            };
+            parquet_reader::pq2gff(input, output, &options)
+        }
+        (FileFormat::Parquet, FileFormat::Fasta) => write_compressed(output, to.compression, |w| {
+            parquet_reader::write_fasta(input, w)
+        }),
+        (FileFormat::Parquet, FileFormat::Fastq) => write_compressed(output, to.compression, |w| {
+            parquet_reader::write_fastq(input, w)
+        }),
+        (FileFormat::Parquet, FileFormat::Jsonl) => write_compressed(output, to.compression, |w| {
+            parquet_reader::write_jsonl(input, w)
+        }),
+        (FileFormat::Parquet, FileFormat::Csv) => write_compressed(output, to.compression, |w| {
+            parquet_reader::write_csv(input, w)
+        }),
+        (FileFormat::Fasta, FileFormat::Jsonl) => write_compressed(output, to.compression, |w| {
+            json_writer::fa2jsonl(reader()?, w, &FastaOptions::default())
+        }),
+        (FileFormat::Fastq, FileFormat::Jsonl) => write_compressed(output, to.compression, |w| {
+            json_writer::fq2jsonl(reader()?, w, &FastqOptions::default())
+        }),
+        (FileFormat::Gff, FileFormat::Jsonl) => write_compressed(output, to.compression, |w| {
+            json_writer::gff2jsonl(reader()?, w, &GffOptions::default())
+        }),
+        (FileFormat::Fasta, FileFormat::Csv) => write_compressed(output, to.compression, |w| {
+            csv_writer::fa2csv(reader()?, w, &FastaOptions::default())
+        }),
+        (FileFormat::Fastq, FileFormat::Csv) => write_compressed(output, to.compression, |w| {
+            csv_writer::fq2csv(reader()?, w, &FastqOptions::default())
+        }),
+        _ => Err(invalid(format!(
+            "can't convert {} to {}",
+            from.format, to.format
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_file_type() {
+        let file_type = |path| FileType::from_path(path).unwrap();
+
+        assert_eq!(
+            file_type("reads.fq.gz"),
+            FileType {
+                format: FileFormat::Fastq,
+                compression: OutputCompression::Gzip,
+            }
+        );
+        assert_eq!(file_type("genes.GFF3").format, FileFormat::Gff);
+        assert_eq!(
+            file_type("dir.d/out.jsonl.zst").compression,
+            OutputCompression::Zstd
+        );
+        assert_eq!(
+            file_type("seqs.parquet").compression,
+            OutputCompression::None
+        );
+
+        assert!(FileType::from_path("notes.txt").is_err());
+        assert!(FileType::from_path("reads.gz").is_err());
+        assert!(FileType::from_path("reads").is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let temp_dir = env::temp_dir();
+        let fasta = temp_dir.join("test_convert.fa.gz");
+        let parquet = temp_dir.join("test_convert.parquet");
+        let round_trip = temp_dir.join("test_convert.fa");
+        let jsonl = temp_dir.join("test_convert.jsonl.zst");
+
+        // Two gzip members, as bgzip or concatenating .gz files writes.
+        let mut file = File::create(&fasta).unwrap();
+        for part in [&b">a\nACGT\n"[..], &b">b\nGG\n"[..]] {
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut file, flate2::Compression::default());
+            encoder.write_all(part).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        convert(&fasta, &parquet, Compression::SNAPPY).unwrap();
+        convert(&parquet, &round_trip, Compression::SNAPPY).unwrap();
+        assert_eq!(fs::read(&round_trip).unwrap(), b">a\nACGT\n>b\nGG\n");
+
+        convert(&fasta, &jsonl, Compression::SNAPPY).unwrap();
+        let mut text = String::new();
+        zstd::stream::read::Decoder::new(File::open(&jsonl).unwrap())
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text.lines().count(), 2);
+
+        let fasta_gz = temp_dir.join("test_convert.out.fa.gz");
+        convert(&parquet, &fasta_gz, Compression::SNAPPY).unwrap();
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(&fasta_gz).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, ">a\nACGT\n>b\nGG\n");
+
+        let pq_jsonl = temp_dir.join("test_convert.out.jsonl");
+        convert(&parquet, &pq_jsonl, Compression::SNAPPY).unwrap();
+        let text = fs::read_to_string(&pq_jsonl).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with(r#"{"id":"a","#));
+
+        let pq_csv = temp_dir.join("test_convert.out.csv");
+        convert(&parquet, &pq_csv, Compression::SNAPPY).unwrap();
+        let text = fs::read_to_string(&pq_csv).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.starts_with("id,"));
+
+        let back = temp_dir.join("test_convert.back.parquet");
+        convert(&pq_csv, &back, Compression::SNAPPY).unwrap();
+        convert(&pq_jsonl, &back, Compression::SNAPPY).unwrap();
+
+        // GFF attributes are a map column, which csv can't hold.
+        let gff = temp_dir.join("test_convert.gff");
+        let gff_parquet = temp_dir.join("test_convert.gff.parquet");
+        fs::write(&gff, b"sq0\tNOODLES\tgene\t8\t13\t.\t+\t.\tID=g0\n").unwrap();
+        convert(&gff, &gff_parquet, Compression::SNAPPY).unwrap();
+        assert!(convert(
+            &gff_parquet,
+            &temp_dir.join("test_convert.gff.csv"),
+            Compression::SNAPPY
+        )
+        .is_err());
+
+        match convert(
+            &fasta,
+            &temp_dir.join("test_convert.fq"),
+            Compression::SNAPPY,
+        ) {
+            Err(BrrrrError::Io(e)) => assert_eq!(e.to_string(), "can't convert FASTA to FASTQ"),
+            r => panic!("expected an io error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_convert_rejected() {
+        let temp_dir = env::temp_dir().join("test_convert_rejected");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("in.fa"), b">a\nACGT\n").unwrap();
+
+        // Each pair is rejected before the output is created, whether or not the input exists.
+        for (from, to) in [
+            ("in.fa", "out.fq"),
+            ("in.fa", "out.gff"),
+            ("in.fa", "out.fa"),
+            ("in.fq", "out.fa"),
+            ("in.gff", "out.fa"),
+            ("in.gff", "out.csv"),
+            ("in.jsonl", "out.csv"),
+            ("in.csv", "out.fa"),
+            ("in.parquet", "out.parquet"),
+            ("in.fa", "out.parquet.gz"),
+            ("in.parquet.gz", "out.fa"),
+            ("in.parquet", "out.gff.zst"),
+            ("in.jsonl.gz", "out.parquet"),
+            ("in.csv.zst", "out.parquet"),
+        ] {
+            let output = temp_dir.join(to);
+            let _ = fs::remove_file(&output);
+
+            match convert(temp_dir.join(from), output.clone(), Compression::SNAPPY) {
+                Err(BrrrrError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+                r => panic!("expected {} to {} to be rejected, got {:?}", from, to, r),
+            }
+            assert!(!output.exists(), "{} to {} wrote its output", from, to);
+        }
+    }
+}
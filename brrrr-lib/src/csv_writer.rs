@@ -7,13 +7,15 @@ use std::io::{self, BufRead, ErrorKind, Write};
 use serde::ser::Serialize;
 
 use crate::errors::BrrrrError;
-use crate::types::{FastaRecord, FastqRecord};
+use crate::fasta_ops::{self, FastaOptions, FastaRecords};
+use crate::fastq_ops::{self, FastqOptions};
+use crate::input::{self, InputFormat};
+use crate::types::FastqRecord;
 use crate::writer;
 
 use writer::RecordWriter;
 
 use noodles::fasta;
-use noodles::fastq;
 
 /// CsvRecordWriter holds a writer, and outputs FASTA records as newline delimited json.
 pub struct CsvRecordWriter<W: Write> {
@@ -42,21 +44,33 @@ impl<W: Write> writer::RecordWriter for CsvRecordWriter<W> {
 ///
 /// * `input` an input that implements the Read trait.
 /// * `output` an output that implements the Write trait.
-pub fn fa2csv<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
+/// * `options` the options applied to the FASTA records before they're written. Of the extra
+///   columns, only the header (`with_header`) is written.
+pub fn fa2csv<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    options: &FastaOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut input, InputFormat::Fasta)?;
+    }
+
     let mut reader = fasta::Reader::new(input);
     let record_writer = &mut CsvRecordWriter::new(output);
+    let mut pipeline = options.pipeline();
 
-    for read_record in reader.records() {
+    for read_record in pipeline.kept(FastaRecords::new(&mut reader, options.with_header)) {
         let record = read_record?;
-        let write_op = record_writer.write_serde_record(FastaRecord::from(record));
+        let write_op = record_writer.write_serde_record(record);
 
         if let Err(e) = write_op {
             match e.kind() {
                 ErrorKind::BrokenPipe => break,
-                _ => return Err(BrrrrError::IOError(e)),
+                _ => return Err(BrrrrError::Io(e)),
             }
         }
     }
+    fasta_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
     Ok(())
 }
 
@@ -66,21 +80,33 @@ pub fn fa2csv<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), Brrr
 ///
 /// * `input` an input that implements the BufRead trait.
 /// * `output` an output that implements the Write trait.
-pub fn fq2csv<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
-    let mut reader = fastq::Reader::new(input);
+/// * `options` the options applied to the FASTQ records before they're written.
+pub fn fq2csv<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    options: &FastqOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut input, InputFormat::Fastq)?;
+    }
+
     let record_writer = &mut CsvRecordWriter::new(output);
+    let mut pipeline = options.pipeline();
 
-    for read_record in reader.records() {
+    for read_record in pipeline.kept(fastq_ops::records(input, options)) {
         let record = read_record?;
+
         let write_op = record_writer.write_serde_record(FastqRecord::from(record));
 
         if let Err(e) = write_op {
             match e.kind() {
                 ErrorKind::BrokenPipe => break,
-                _ => return Err(BrrrrError::IOError(e)),
+                _ => return Err(BrrrrError::Io(e)),
             }
         }
     }
+
+    fastq_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
     Ok(())
 }
 
@@ -93,7 +119,7 @@ mod tests {
         let input = b">A\nATCG\n" as &[u8];
 
         let mut output = Vec::new();
-        fa2csv(input, &mut output).unwrap();
+        fa2csv(input, &mut output, &FastaOptions::default()).unwrap();
 
         let output_str = String::from_utf8(output).unwrap();
         let expected_output = "id,description,sequence\nA,,ATCG\n".to_string();
@@ -1,16 +1,187 @@
+#[cfg(feature = "parquet")]
 use arrow;
+#[cfg(feature = "parquet")]
 use parquet;
+use std::error::Error;
+use std::fmt;
 use std::io;
 use thiserror;
 
+use crate::input::InputFormat;
+
+/// The errors returned by brrrr.
+///
+/// Errors from the underlying io, arrow, and parquet libraries convert into their variant with
+/// `?`, and are available from `source()`, so library users can match on the kind of failure.
 #[derive(Debug, thiserror::Error)]
 pub enum BrrrrError {
+    /// Reading or writing failed.
     #[error("io error")]
-    IOError(#[from] io::Error),
+    Io(#[source] io::Error),
+
+    /// An input record couldn't be parsed.
+    #[error("parse error, {0}")]
+    Parse(ParseError),
 
+    /// Building arrow arrays or record batches failed.
+    #[cfg(feature = "parquet")]
     #[error("arrow error")]
-    ArrowError(#[from] arrow::error::ArrowError),
+    Arrow(#[from] arrow::error::ArrowError),
 
+    /// Reading or writing parquet failed.
+    #[cfg(feature = "parquet")]
     #[error("parquet error")]
-    ParquetError(#[from] parquet::errors::ParquetError),
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    /// A parquet input doesn't have the columns expected for the conversion.
+    #[error("schema mismatch: {0}")]
+    SchemaMismatch(String),
+
+    /// A record refers to something that doesn't exist, e.g. a sequence that isn't in the FASTA.
+    #[error("reference error: {0}")]
+    Reference(String),
+
+    /// An input looks like a different format than the conversion reads, e.g. FASTA given to a
+    /// FASTQ converter.
+    #[error("format mismatch: expected {expected} input, but it looks like {detected}")]
+    FormatMismatch {
+        expected: InputFormat,
+        detected: InputFormat,
+    },
+}
+
+impl BrrrrError {
+    /// Returns the process exit code for the error, so scripts can tell failures apart:
+    ///
+    /// * 1 for io errors, e.g. a file that doesn't exist.
+    /// * 3 for schema mismatches.
+    /// * 4 for reference errors.
+    /// * 5 for arrow and parquet errors.
+    /// * 6 for format mismatches.
+    /// * 7 for parse errors, i.e. corrupt or malformed input.
+    ///
+    /// 2 is left to clap, which exits with it on invalid arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BrrrrError::Io(_) => 1,
+            BrrrrError::Parse(_) => 7,
+            BrrrrError::SchemaMismatch(_) => 3,
+            BrrrrError::Reference(_) => 4,
+            BrrrrError::FormatMismatch { .. } => 6,
+            #[cfg(feature = "parquet")]
+            BrrrrError::Arrow(_) | BrrrrError::Parquet(_) => 5,
+        }
+    }
+}
+
+impl From<io::Error> for BrrrrError {
+    /// Converts an io::Error, unwrapping the ParseError that record iterators carry inside
+    /// `InvalidData` errors.
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<ParseError>()) {
+            if let Ok(parse_error) = e.into_inner().unwrap().downcast::<ParseError>() {
+                return BrrrrError::Parse(*parse_error);
+            }
+            unreachable!("the inner error was checked to be a ParseError");
+        }
+
+        BrrrrError::Io(e)
+    }
+}
+
+/// A record that couldn't be parsed, along with where it was found when that's known.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The 1-based line number of the record in the input.
+    pub line: Option<u64>,
+    /// The id of the record.
+    pub id: Option<String>,
+    /// What was wrong with the record.
+    pub message: String,
+}
+
+impl ParseError {
+    /// Creates a new ParseError without a location.
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            line: None,
+            id: None,
+            message: message.into(),
+        }
+    }
+
+    /// Sets the 1-based line number the record was found on.
+    pub fn with_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Sets the id of the record.
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, &self.id) {
+            (Some(line), Some(id)) => write!(f, "line {} (record {}): ", line, id)?,
+            (Some(line), None) => write!(f, "line {}: ", line)?,
+            (None, Some(id)) => write!(f, "record {}: ", id)?,
+            (None, None) => {}
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Attaches the 1-based `line` to an error from parsing that line. A ParseError keeps any line it
+/// already has, and any other error becomes a ParseError with its message.
+pub(crate) fn at_line(e: io::Error, line: u64) -> io::Error {
+    if e.kind() != io::ErrorKind::InvalidData {
+        return e;
+    }
+
+    let mut parse_error = match e.into_inner() {
+        Some(inner) => match inner.downcast::<ParseError>() {
+            Ok(parse_error) => *parse_error,
+            Err(inner) => ParseError::new(inner.to_string()),
+        },
+        None => ParseError::new("invalid data"),
+    };
+    parse_error.line.get_or_insert(line);
+
+    parse_error.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_from_io() {
+        let e = at_line(ParseError::new("invalid start: \"x\"").into(), 3);
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let e = BrrrrError::from(e);
+        assert!(matches!(
+            e,
+            BrrrrError::Parse(ParseError { line: Some(3), .. })
+        ));
+        assert_eq!(e.to_string(), "parse error, line 3: invalid start: \"x\"");
+        assert_eq!(e.exit_code(), 7);
+
+        let e = BrrrrError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(e, BrrrrError::Io(_)));
+        assert_eq!(e.source().unwrap().to_string(), "missing");
+    }
 }
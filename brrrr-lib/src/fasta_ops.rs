@@ -0,0 +1,1101 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use noodles::fasta;
+use noodles::fasta::fai;
+use noodles::fasta::record::{Definition, Sequence};
+
+use crate::errors::{BrrrrError, ParseError};
+use crate::gff_ops::{self, GffOptions};
+use crate::id_list::IdList;
+use crate::intervals::{self, Interval, IntervalFormat, Region};
+use crate::output::TrailingNewline;
+use crate::partition::PartitionBy;
+use crate::pipeline::{Pipeline, Stage};
+use crate::sequence::{max_homopolymer, reverse_complement};
+use crate::translation::{self, CodonTable, Frames};
+use crate::types::FastaRecord;
+
+/// Options for FASTA conversions, e.g. extra per-record columns.
+#[derive(Default, Clone, Debug)]
+pub struct FastaOptions {
+    /// Add a `gc_skew` column with (G - C) / (G + C) for each record.
+    pub with_gc_skew: bool,
+    /// Add a `complexity` column with the Shannon entropy of each record.
+    pub with_entropy: bool,
+    /// Measure the entropy as the lowest over windows of this many bases, instead of over the
+    /// whole sequence.
+    pub entropy_window: Option<usize>,
+    /// Add a `max_homopolymer` column with the longest single-base run of each record.
+    pub with_max_homopolymer: bool,
+    /// Drop records whose longest single-base run is longer than this.
+    pub max_homopolymer_filter: Option<usize>,
+    /// Add an `orf_length` column with the length of the longest ORF in any of the six frames.
+    pub with_orf: bool,
+    /// Also add `orf_start` and `orf_frame` columns locating the longest ORF.
+    pub with_orf_position: bool,
+    /// The codon table used to find ORFs.
+    pub codon_table: CodonTable,
+    /// Only count ORFs that begin with a start codon.
+    pub orf_require_start: bool,
+    /// Also write per-record QC metrics to a separate Parquet file at this path.
+    pub stats_parquet: Option<PathBuf>,
+    /// Skip the `;` comment lines of older FASTA files, instead of reading them as sequence.
+    pub allow_comments: bool,
+    /// Read the input even if its first line looks like another format, e.g. FASTQ, instead of
+    /// failing with `BrrrrError::FormatMismatch`.
+    pub no_sniff: bool,
+    /// Uppercase the written sequence, dropping soft-masking. Computed columns, e.g. `gc_skew`,
+    /// ignore case either way, so they don't change.
+    pub fold_case: bool,
+    /// Reverse each record's sequence, without complementing the bases.
+    pub reverse: bool,
+    /// Write a directory of Parquet files, `part-00000.parquet`, `part-00001.parquet`, ..., each
+    /// about this many bytes or fewer, instead of one file. Ignored by `fa2pq_bytes`.
+    pub max_file_size: Option<u64>,
+    /// Write a directory of Parquet files with this many records each, instead of one file.
+    /// Ignored by `fa2pq_bytes`.
+    pub max_records: Option<usize>,
+    /// Write the Parquet into Hive-style partition directories, e.g. `length_bucket=100-500/`,
+    /// instead of one file. Ignored by `fa2pq_bytes`.
+    pub partition_by: Option<PartitionBy>,
+    /// End each record's id at the first of this character in its header, instead of at the
+    /// first whitespace, with the rest of the header as the description. See
+    /// [`FastaRecord::split_id`].
+    pub id_delimiter: Option<char>,
+    /// Keep only the records whose id is in the list, or with its `exclude`, drop them. The id
+    /// is matched after `id_delimiter` splits it.
+    pub id_list: Option<Arc<IdList>>,
+    /// Add a `header` column with each record's header line, after the `>`, as it was in the
+    /// input, e.g. for tools that need more than the id and description.
+    pub with_header: bool,
+    /// Build each Parquet batch from records whose sequences add up to about this many bytes,
+    /// instead of from a fixed number of records, so memory use doesn't depend on record length.
+    pub batch_bytes: Option<usize>,
+    /// Keep a checkpoint in the directory of an output split by `max_records` or
+    /// `max_file_size`, and if an interrupted run left one there, carry on from it instead of
+    /// starting over. The input is read again, but the records already written are skipped, so
+    /// the other options must match the interrupted run's. Can't be used with `stats_parquet`.
+    pub resume: bool,
+    /// Skip this many of the records that would be written, e.g. with `limit` to take a window
+    /// of the output.
+    pub skip: usize,
+    /// Stop after writing this many records, without reading the rest of the input.
+    pub limit: Option<usize>,
+}
+
+impl FastaOptions {
+    /// Returns whether `record` passes the filters in the options.
+    pub fn keep(&self, record: &FastaRecord) -> bool {
+        self.max_homopolymer_filter.is_none_or(|max| {
+            let run = record
+                .max_homopolymer
+                .unwrap_or_else(|| max_homopolymer(record.sequence.as_bytes()));
+            run <= max
+        })
+    }
+
+    /// Returns the pipeline applying the options to each record.
+    pub fn pipeline(&self) -> Pipeline<'_, FastaRecord> {
+        let mut pipeline = Pipeline::default();
+        if let Some(delimiter) = self.id_delimiter {
+            pipeline.add(Stage::Transform, move |mut record: FastaRecord| {
+                record.split_id(delimiter);
+                Ok(Some(record))
+            });
+        }
+        if self.fold_case {
+            pipeline.add(Stage::Transform, |mut record: FastaRecord| {
+                record.sequence.make_ascii_uppercase();
+                Ok(Some(record))
+            });
+        }
+        if self.reverse {
+            pipeline.add(Stage::Transform, |mut record: FastaRecord| {
+                record.sequence = record.sequence.chars().rev().collect();
+                Ok(Some(record))
+            });
+        }
+        if self.with_max_homopolymer || self.max_homopolymer_filter.is_some() {
+            pipeline.add(Stage::Compute, |mut record: FastaRecord| {
+                record.max_homopolymer = Some(max_homopolymer(record.sequence.as_bytes()));
+                Ok(Some(record))
+            });
+        }
+        // The id list goes first, so it sees every listed id even if another filter drops it.
+        if let Some(ids) = &self.id_list {
+            pipeline.add_filter(move |record| ids.keep(&record.id));
+        }
+        pipeline.add_filter(move |record| self.keep(record));
+        pipeline.set_window(self.skip, self.limit);
+        pipeline
+    }
+}
+
+/// Reports the number of records the filters in FastaOptions dropped to stderr, and the ids of
+/// `id_list` that were missing.
+pub(crate) fn report_filtered(filtered: usize, id_list: Option<&IdList>) {
+    if filtered > 0 {
+        eprintln!("Filtered {} records.", filtered);
+    }
+    if let Some(ids) = id_list {
+        ids.report();
+    }
+}
+
+/// Iterates over the records of a FASTA reader, like `fasta::Reader::records`, also keeping each
+/// record's verbatim header line if `with_header` is set. The noodles records only keep the
+/// parsed id and description.
+pub struct FastaRecords<'a, R> {
+    reader: &'a mut fasta::Reader<R>,
+    with_header: bool,
+    line: String,
+}
+
+impl<'a, R: BufRead> FastaRecords<'a, R> {
+    /// Creates an iterator over the records of `reader`.
+    pub fn new(reader: &'a mut fasta::Reader<R>, with_header: bool) -> Self {
+        Self {
+            reader,
+            with_header,
+            line: String::new(),
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for FastaRecords<'a, R> {
+    type Item = io::Result<FastaRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.line.clear();
+        match self.reader.read_definition(&mut self.line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e)),
+        }
+
+        let definition: Definition = match self.line.parse() {
+            Ok(d) => d,
+            Err(e) => return Some(Err(io::Error::new(ErrorKind::InvalidData, e))),
+        };
+
+        let mut sequence = Vec::new();
+        if let Err(e) = self.reader.read_sequence(&mut sequence) {
+            return Some(Err(e));
+        }
+
+        let mut record =
+            FastaRecord::from(fasta::Record::new(definition, Sequence::from(sequence)));
+        if self.with_header {
+            record.header = Some(self.line[1..].to_string());
+        }
+        Some(Ok(record))
+    }
+}
+
+/// Wraps a FASTA input, skipping the lines that start with `;`, which older FASTA files use for
+/// comments. The noodles reader would otherwise read them as sequence.
+pub struct SkipComments<R> {
+    inner: R,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> SkipComments<R> {
+    /// Creates a reader that skips the comment lines of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for SkipComments<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for SkipComments<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Input is passed on a whole line at a time, so every line can be checked from its start.
+        if self.position >= self.line.len() {
+            self.position = 0;
+            loop {
+                self.line.clear();
+                if self.inner.read_until(b'\n', &mut self.line)? == 0
+                    || !self.line.starts_with(b";")
+                {
+                    break;
+                }
+            }
+        }
+
+        Ok(&self.line[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt;
+    }
+}
+
+/// Returns the path of the `.fai` index that sits next to `input`.
+fn fai_path<P: AsRef<Path>>(input: P) -> PathBuf {
+    let mut path = OsString::from(input.as_ref().as_os_str());
+    path.push(".fai");
+    PathBuf::from(path)
+}
+
+fn region_error(region: &Region, message: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("{} for region {}", message, region.name()),
+    )
+}
+
+fn missing_sequence(region: &Region) -> BrrrrError {
+    BrrrrError::Reference(format!(
+        "sequence {} not found for region {}",
+        region.seqname,
+        region.name()
+    ))
+}
+
+/// Returns the 0-based, half-open bounds of `region` in a sequence of length `len`, clamping
+/// the end to the sequence.
+fn bounds(region: &Region, len: i64) -> io::Result<(i64, i64)> {
+    let end = region.end.unwrap_or(len).min(len);
+
+    if region.start > end {
+        return Err(region_error(
+            region,
+            "start is past the end of the sequence",
+        ));
+    }
+
+    Ok((region.start, end))
+}
+
+/// Reads `region` from an indexed FASTA without reading the rest of the sequence.
+fn read_indexed<R: Read + Seek>(
+    reader: &mut R,
+    record: &fai::Record,
+    region: &Region,
+) -> io::Result<Vec<u8>> {
+    let len = i64::try_from(record.len()).unwrap_or(i64::MAX);
+    let (start, end) = bounds(region, len)?;
+
+    let line_bases = record.line_bases();
+    let line_width = record.line_width();
+    let position = |i: u64| record.offset() + (i / line_bases) * line_width + (i % line_bases);
+
+    if start == end {
+        return Ok(Vec::new());
+    }
+
+    // The end is read up to just past its last base, not at position(end), which is past the
+    // line break when the region ends a line, and past the end of a file without a final one.
+    let first = position(start as u64);
+    let last = position(end as u64 - 1) + 1;
+
+    let mut raw = vec![0; (last - first) as usize];
+    reader.seek(SeekFrom::Start(first))?;
+    reader.read_exact(&mut raw)?;
+    raw.retain(|b| *b != b'\n' && *b != b'\r');
+
+    Ok(raw)
+}
+
+/// Slices `region` out of a whole sequence.
+fn slice(sequence: &[u8], region: &Region) -> io::Result<Vec<u8>> {
+    let (start, end) = bounds(region, sequence.len() as i64)?;
+    Ok(sequence[start as usize..end as usize].to_vec())
+}
+
+/// Writes the subsequence of each region in `regions` as FASTA, in the order given.
+///
+/// If a `.fai` index exists next to `input` (e.g. `genome.fa.fai`), each region is read directly
+/// from its offset. Otherwise, the file is scanned once, and each region is written as soon as it
+/// and the regions before it have been read. Regions on the reverse strand are
+/// reverse-complemented, and each record is named for its region, e.g. `chr1:1000-2000`.
+///
+/// # Arguments
+///
+/// * `input` the path to an uncompressed FASTA file.
+/// * `regions` the regions to extract.
+/// * `output` an output that implements the Write trait.
+pub fn subseq<P: AsRef<Path>, W: Write>(
+    input: P,
+    regions: &[Region],
+    output: &mut W,
+) -> Result<(), BrrrrError> {
+    let named = regions.iter().map(|r| Ok((r.name(), r.clone())));
+    write_regions(input, named, output)
+}
+
+/// Translates each record of a FASTA input to protein, like `transeq`, writing FASTA.
+///
+/// With a single frame, each record keeps its id. With [`Frames::All`], each record is written
+/// six times, once per frame, with the frame appended to its id, e.g. `seq1_-2`.
+///
+/// # Arguments
+///
+/// * `input` a nucleotide FASTA input, that implements the BufRead trait.
+/// * `output` an output that implements the Write trait.
+/// * `frames` the frames to translate each record in.
+/// * `table` the codon table to translate with.
+pub fn translate<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    frames: Frames,
+    table: CodonTable,
+) -> Result<(), BrrrrError> {
+    let mut reader = fasta::Reader::new(input);
+    let mut writer = fasta::Writer::new(output);
+
+    for record in reader.records() {
+        let record = record?;
+
+        for frame in frames.frames() {
+            let name = match frames {
+                Frames::One(_) => record.name().to_string(),
+                Frames::All => format!("{}_{}", record.name(), frame),
+            };
+            let definition = Definition::new(name, record.description().map(String::from));
+            let protein = translation::translate(record.sequence().as_ref(), frame, table);
+            writer.write_record(&fasta::Record::new(definition, Sequence::from(protein)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Translates each record of a FASTA input in all six frames, writing FASTA, e.g. for
+/// prokaryotic gene finding.
+///
+/// Without `min_orf`, each record is written once per frame, like [`translate`] with
+/// [`Frames::All`], e.g. `seq1_-2`. With `min_orf`, each frame is instead split at its stops, and
+/// each stretch between stops of at least `min_orf` amino acids is written as its own record,
+/// with the frame and its 1-based bases on the forward strand, e.g. `seq1_-2_101-400`.
+///
+/// # Arguments
+///
+/// * `input` a nucleotide FASTA input, that implements the BufRead trait.
+/// * `output` an output that implements the Write trait.
+/// * `table` the codon table to translate with.
+/// * `min_orf` the shortest ORF to write, in amino acids, or None to write whole frames.
+pub fn sixframe<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    table: CodonTable,
+    min_orf: Option<usize>,
+) -> Result<(), BrrrrError> {
+    let min_orf = match min_orf {
+        Some(min_orf) => min_orf,
+        None => return translate(input, output, Frames::All, table),
+    };
+
+    let mut reader = fasta::Reader::new(input);
+    let mut writer = fasta::Writer::new(output);
+
+    for record in reader.records() {
+        let record = record?;
+        let len = record.sequence().len();
+
+        for frame in Frames::All.frames() {
+            let protein = translation::translate(record.sequence().as_ref(), frame, table);
+            let offset = frame.unsigned_abs() as usize - 1;
+
+            let mut start = 0;
+            for orf in protein.split(|aa| *aa == b'*') {
+                if !orf.is_empty() && orf.len() >= min_orf {
+                    // The bases of the ORF on the strand it was read from, then on the forward
+                    // strand.
+                    let begin = offset + 3 * start;
+                    let end = begin + 3 * orf.len();
+                    let (begin, end) = if frame > 0 {
+                        (begin, end)
+                    } else {
+                        (len - end, len - begin)
+                    };
+
+                    let name = format!("{}_{}_{}-{}", record.name(), frame, begin + 1, end);
+                    let definition = Definition::new(name, record.description().map(String::from));
+                    writer.write_record(&fasta::Record::new(
+                        definition,
+                        Sequence::from(orf.to_vec()),
+                    ))?;
+                }
+                start += orf.len() + 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the sequence of each feature in a GFF or BED input as FASTA, like `bedtools getfasta`.
+///
+/// Features on the `-` strand are reverse-complemented. GFF features are named by the value of
+/// their `name_attribute` attribute, and BED features by their name column; features without one
+/// are named for their region. Sequences are read the same way as [`subseq`].
+///
+/// # Arguments
+///
+/// * `input` the path to an uncompressed genome FASTA file.
+/// * `features` an input with the features, that implements the BufRead trait.
+/// * `format` the format of `features`.
+/// * `name_attribute` the GFF attribute to name records by.
+/// * `output` an output that implements the Write trait.
+pub fn getfasta<P: AsRef<Path>, R: BufRead, W: Write>(
+    input: P,
+    features: R,
+    format: IntervalFormat,
+    name_attribute: &str,
+    output: &mut W,
+) -> Result<(), BrrrrError> {
+    let named: Box<dyn Iterator<Item = NamedRegion>> = match format {
+        IntervalFormat::Gff => Box::new(gff_ops::records(features, &GffOptions::default())?.map(
+            move |record| {
+                let record = record?;
+                let name = record
+                    .attributes()
+                    .iter()
+                    .find(|e| e.key() == name_attribute)
+                    .map(|e| e.value().to_string());
+
+                let region = Region::from(Interval::from(record));
+                Ok((name.unwrap_or_else(|| region.name()), region))
+            },
+        )),
+        IntervalFormat::Bed => Box::new(intervals::records(features, format)?.map(|interval| {
+            let interval = interval?;
+            let name = interval.name.clone();
+
+            let region = Region::from(interval);
+            Ok((name.unwrap_or_else(|| region.name()), region))
+        })),
+    };
+
+    write_regions(input, named, output)
+}
+
+/// A region, paired with the name of the record to write it as.
+type NamedRegion = Result<(String, Region), BrrrrError>;
+
+/// Writes the subsequence of each region as a FASTA record with the paired name, in the order
+/// given.
+///
+/// With an index, the regions are read one at a time. Without one, they're looked up by seqname
+/// as the file is scanned, and written once every region before them has been.
+fn write_regions<P, I, W>(input: P, regions: I, output: &mut W) -> Result<(), BrrrrError>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = NamedRegion>,
+    W: Write,
+{
+    let index_path = fai_path(&input);
+    let mut writer = fasta::Writer::new(output);
+
+    if index_path.exists() {
+        let index = fai::read(index_path)?;
+        let records: HashMap<&str, &fai::Record> = index.iter().map(|r| (r.name(), r)).collect();
+        let mut reader = File::open(&input).map(BufReader::new)?;
+
+        for named in regions {
+            let (name, region) = named?;
+            let record = records
+                .get(region.seqname.as_str())
+                .ok_or_else(|| missing_sequence(&region))?;
+            let sequence = read_indexed(&mut reader, record, &region)?;
+
+            if !write_region(&mut writer, &name, &region, sequence)? {
+                break;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let regions = regions.into_iter().collect::<Result<Vec<_>, _>>()?;
+    let mut by_seqname: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (_, region)) in regions.iter().enumerate() {
+        by_seqname.entry(&region.seqname).or_default().push(i);
+    }
+
+    let mut reader = File::open(&input)
+        .map(BufReader::new)
+        .map(fasta::Reader::new)?;
+    let mut found: Vec<Option<Vec<u8>>> = vec![None; regions.len()];
+    let mut next = 0;
+
+    for result in reader.records() {
+        let record = result?;
+
+        for &i in by_seqname.get(record.name()).into_iter().flatten() {
+            if i >= next {
+                found[i] = Some(slice(record.sequence().as_ref(), &regions[i].1)?);
+            }
+        }
+
+        while let Some(sequence) = found.get_mut(next).and_then(Option::take) {
+            let (name, region) = &regions[next];
+            if !write_region(&mut writer, name, region, sequence)? {
+                return Ok(());
+            }
+            next += 1;
+        }
+    }
+
+    match regions.get(next) {
+        Some((_, region)) => Err(missing_sequence(region)),
+        None => Ok(()),
+    }
+}
+
+/// Writes `sequence` as a FASTA record named `name`, reverse-complemented if `region` is on the
+/// reverse strand. Returns false if the output was closed, e.g. by `head`.
+fn write_region<W: Write>(
+    writer: &mut fasta::Writer<W>,
+    name: &str,
+    region: &Region,
+    sequence: Vec<u8>,
+) -> io::Result<bool> {
+    let sequence = if region.reverse {
+        reverse_complement(&sequence)
+    } else {
+        sequence
+    };
+
+    let record = fasta::Record::new(Definition::new(name, None), Sequence::from(sequence));
+
+    match writer.write_record(&record) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// How fa_split balances its parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitBy {
+    /// Give each part about the same number of records.
+    Records,
+    /// Give each part about the same number of bases.
+    Bases,
+}
+
+/// How fa_split assigns records to parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitAssignment {
+    /// Give each part a contiguous run of records, preserving the input order across parts.
+    Contiguous,
+    /// Deal records out across parts in turn. Splitting by bases deals each record to the part
+    /// with the fewest bases so far.
+    RoundRobin,
+}
+
+/// Options for splitting a FASTA into parts.
+#[derive(Clone, Debug)]
+pub struct SplitOptions {
+    /// The number of parts to write.
+    pub parts: usize,
+    /// What to balance across the parts.
+    pub by: SplitBy,
+    /// How records are assigned to parts.
+    pub assignment: SplitAssignment,
+    /// End each part with a newline after its last record.
+    pub trailing_newline: bool,
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        Self {
+            parts: 2,
+            by: SplitBy::Records,
+            assignment: SplitAssignment::Contiguous,
+            trailing_newline: true,
+        }
+    }
+}
+
+/// Returns the path of part `i` of a split, e.g. `prefix.000.fa`.
+fn part_path(prefix: &str, i: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{:03}.fa", prefix, i))
+}
+
+/// Splits a FASTA file into `options.parts` files named `prefix.000.fa`, `prefix.001.fa`, and so
+/// on, returning their paths. Every part is written, even if it ends up empty, and asking for no
+/// parts is an error.
+///
+/// The assignment is deterministic. Contiguous splits read the input twice, first to total the
+/// records or bases.
+///
+/// # Arguments
+///
+/// * `input` the path to an uncompressed FASTA file.
+/// * `prefix` the prefix of the output paths.
+/// * `options` how to split the input.
+pub fn split<P: AsRef<Path>>(
+    input: P,
+    prefix: &str,
+    options: &SplitOptions,
+) -> Result<Vec<PathBuf>, BrrrrError> {
+    let parts = options.parts;
+    if parts == 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "the number of parts must be at least 1",
+        )
+        .into());
+    }
+    let size = |record: &fasta::Record| match options.by {
+        SplitBy::Records => 1,
+        SplitBy::Bases => record.sequence().len() as u64,
+    };
+
+    let total = match options.assignment {
+        SplitAssignment::Contiguous => {
+            let mut reader = File::open(&input)
+                .map(BufReader::new)
+                .map(fasta::Reader::new)?;
+            let mut total = 0;
+            for result in reader.records() {
+                total += size(&result?);
+            }
+            total
+        }
+        SplitAssignment::RoundRobin => 0,
+    };
+
+    let paths: Vec<PathBuf> = (0..parts).map(|i| part_path(prefix, i)).collect();
+    let mut writers = paths
+        .iter()
+        .map(|path| {
+            let writer = io::BufWriter::new(File::create(path)?);
+            Ok(TrailingNewline::new(writer, options.trailing_newline))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut reader = File::open(&input)
+        .map(BufReader::new)
+        .map(fasta::Reader::new)?;
+    let mut sizes = vec![0u64; parts];
+    let mut seen = 0u64;
+
+    for (i, result) in reader.records().enumerate() {
+        let record = result?;
+        let record_size = size(&record);
+
+        let part = match (options.assignment, options.by) {
+            (SplitAssignment::RoundRobin, SplitBy::Records) => i % parts,
+            (SplitAssignment::RoundRobin, SplitBy::Bases) => {
+                (0..parts).min_by_key(|p| sizes[*p]).unwrap_or_default()
+            }
+            (SplitAssignment::Contiguous, _) => {
+                // The part holding the midpoint of the record, so parts split around it evenly.
+                let midpoint = seen * 2 + record_size;
+                ((midpoint * parts as u64) / (total * 2).max(1)) as usize
+            }
+        }
+        .min(parts - 1);
+
+        seen += record_size;
+        sizes[part] += record_size;
+        fasta::Writer::new(&mut writers[part]).write_record(&record)?;
+    }
+
+    for writer in writers {
+        writer.into_inner().flush()?;
+    }
+
+    Ok(paths)
+}
+
+/// Concatenates the FASTA files in `inputs` into `output`, returning the number of records
+/// renamed.
+///
+/// Ids must be unique across the inputs. If `dedup_ids` is set, a record whose id was already
+/// written is renamed by appending `_1`, `_2`, and so on, otherwise the collision is an error.
+///
+/// # Arguments
+///
+/// * `inputs` the paths to uncompressed FASTA files.
+/// * `output` where to write the concatenated FASTA.
+/// * `dedup_ids` whether to rename duplicate ids instead of erroring.
+pub fn cat<P: AsRef<Path>, W: Write>(
+    inputs: &[P],
+    output: W,
+    dedup_ids: bool,
+) -> Result<usize, BrrrrError> {
+    let mut writer = fasta::Writer::new(output);
+    let mut seen = HashSet::new();
+    let mut renamed = 0;
+
+    for input in inputs {
+        let mut reader = File::open(input)
+            .map(BufReader::new)
+            .map(fasta::Reader::new)?;
+
+        for result in reader.records() {
+            let record = result?;
+
+            if seen.insert(record.name().to_string()) {
+                writer.write_record(&record)?;
+                continue;
+            }
+
+            if !dedup_ids {
+                return Err(BrrrrError::Parse(
+                    ParseError::new(format!("duplicate id in {}", input.as_ref().display()))
+                        .with_id(record.name()),
+                ));
+            }
+
+            let name = (1..)
+                .map(|n| format!("{}_{}", record.name(), n))
+                .find(|name| !seen.contains(name))
+                .expect("an unused suffix exists");
+            seen.insert(name.clone());
+            renamed += 1;
+
+            let definition = Definition::new(name, record.description().map(String::from));
+            writer.write_record(&fasta::Record::new(definition, record.sequence().clone()))?;
+        }
+    }
+
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+    use crate::sequence::gc_content;
+
+    #[test]
+    fn test_fold_case() {
+        let record = FastaRecord {
+            id: "a".into(),
+            description: None,
+            sequence: "ACgtnn".into(),
+            ..Default::default()
+        };
+
+        let preserved = FastaOptions::default()
+            .pipeline()
+            .run(record.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(preserved.sequence, "ACgtnn");
+
+        let options = FastaOptions {
+            fold_case: true,
+            ..Default::default()
+        };
+        let folded = options.pipeline().run(record).unwrap().unwrap();
+        assert_eq!(folded.sequence, "ACGTNN");
+
+        // Computed columns see the same bases either way.
+        assert_eq!(
+            gc_content(preserved.sequence.as_bytes()),
+            gc_content(folded.sequence.as_bytes())
+        );
+        assert_eq!(
+            max_homopolymer(preserved.sequence.as_bytes()),
+            max_homopolymer(folded.sequence.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_max_homopolymer_filter() {
+        let record = |sequence: &str| FastaRecord {
+            id: "a".into(),
+            sequence: sequence.into(),
+            ..Default::default()
+        };
+        let options = FastaOptions {
+            max_homopolymer_filter: Some(4),
+            ..Default::default()
+        };
+        let mut pipeline = options.pipeline();
+
+        // A run of exactly the maximum is kept, and one base longer, in any case, is dropped.
+        assert!(pipeline.run(record("CAAAAG")).unwrap().is_some());
+        assert!(pipeline.run(record("CAAaaAG")).unwrap().is_none());
+
+        // The run is measured once, by the pipeline, only if the filter or column needs it.
+        let kept = pipeline.run(record("CAAG")).unwrap().unwrap();
+        assert_eq!(kept.max_homopolymer, Some(2));
+        let plain = FastaOptions::default().pipeline().run(record("CAAG"));
+        assert_eq!(plain.unwrap().unwrap().max_homopolymer, None);
+
+        let input = b">a\nCAAAAG\n>b\nCTTTTTG\n>c\nACGT\n" as &[u8];
+        let mut output = Vec::new();
+        crate::json_writer::fa2jsonl(input, &mut output, &options).unwrap();
+        let ids: Vec<_> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<FastaRecord>(l).unwrap().id)
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let record = FastaRecord {
+            id: "a".into(),
+            sequence: "AACGt".into(),
+            ..Default::default()
+        };
+
+        let options = FastaOptions {
+            reverse: true,
+            ..Default::default()
+        };
+        let reversed = options.pipeline().run(record).unwrap().unwrap();
+        assert_eq!(reversed.sequence, "tGCAA");
+    }
+
+    #[test]
+    fn test_translate() {
+        let input = b">s1 desc\nATGGCCTAAGC\n" as &[u8];
+
+        let mut output = Vec::new();
+        translate(input, &mut output, Frames::One(-1), CodonTable::Standard).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">s1 desc\nA*A\n");
+
+        let mut output = Vec::new();
+        translate(input, &mut output, Frames::All, CodonTable::Standard).unwrap();
+        let ids: Vec<_> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix('>').map(String::from))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "s1_1 desc",
+                "s1_2 desc",
+                "s1_3 desc",
+                "s1_-1 desc",
+                "s1_-2 desc",
+                "s1_-3 desc"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sixframe() {
+        let input = b">s1\nATGGCCTAAGC\n" as &[u8];
+
+        let mut output = Vec::new();
+        sixframe(input, &mut output, CodonTable::Standard, None).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 12);
+
+        // Frame 1 is MA*, and frame -1, read from GCTTAGGCCAT, is A*A.
+        let mut output = Vec::new();
+        sixframe(input, &mut output, CodonTable::Standard, Some(2)).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">s1_1_1-6\nMA\n>s1_2_2-10\nWPK\n>s1_3_3-11\nGLS\n>s1_-2_2-10\nLRP\n>s1_-3_1-9\nLGH\n"
+        );
+    }
+
+    #[test]
+    fn test_skip_comments() {
+        let input = b";comment\n>a desc\nAC\n;more\nGT\n>b\nGG" as &[u8];
+
+        let mut reader = fasta::Reader::new(SkipComments::new(input));
+        let records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].sequence().as_ref(), b"ACGT");
+        assert_eq!(records[1].sequence().as_ref(), b"GG");
+    }
+
+    #[test]
+    fn test_subseq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_subseq.fa");
+        fs::write(&input, b">sq0 first\nACGTA\nCCGGT\nAA\n>sq1\nTTTT\n").unwrap();
+        let _ = fs::remove_file(fai_path(&input));
+
+        let regions: Vec<Region> = vec![
+            "sq1:2-3".parse().unwrap(),
+            "sq0:4-8".parse().unwrap(),
+            "sq0:4-8:-".parse().unwrap(),
+            "sq0:11-100".parse().unwrap(),
+        ];
+        let expected = ">sq1:2-3\nTT\n>sq0:4-8\nTACCG\n>sq0:4-8/rc\nCGGTA\n>sq0:11-100\nAA\n";
+
+        let mut scanned = Vec::new();
+        subseq(&input, &regions, &mut scanned).unwrap();
+        assert_eq!(String::from_utf8(scanned).unwrap(), expected);
+
+        // The regions before a missing sequence are written before it's found to be missing.
+        let missing: Vec<Region> = vec!["sq0:1-2".parse().unwrap(), "sq2".parse().unwrap()];
+        let mut partial = Vec::new();
+        assert!(matches!(
+            subseq(&input, &missing, &mut partial),
+            Err(BrrrrError::Reference(_))
+        ));
+        assert_eq!(partial, b">sq0:1-2\nAC\n");
+
+        fs::write(fai_path(&input), "sq0\t12\t11\t5\t6\nsq1\t4\t31\t4\t5\n").unwrap();
+        let mut indexed = Vec::new();
+        subseq(&input, &regions, &mut indexed).unwrap();
+        fs::remove_file(fai_path(&input)).unwrap();
+        assert_eq!(String::from_utf8(indexed).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_subseq_without_trailing_newline() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_subseq_without_trailing_newline.fa");
+        fs::write(&input, b">sq0\nACGTA\nCCGGT").unwrap();
+        fs::write(fai_path(&input), "sq0\t10\t5\t5\t6\n").unwrap();
+
+        let regions: Vec<Region> = vec![
+            "sq0:6-10".parse().unwrap(),
+            "sq0:3-5".parse().unwrap(),
+            "sq0".parse().unwrap(),
+        ];
+        let mut indexed = Vec::new();
+        subseq(&input, &regions, &mut indexed).unwrap();
+        fs::remove_file(fai_path(&input)).unwrap();
+        assert_eq!(
+            String::from_utf8(indexed).unwrap(),
+            ">sq0:6-10\nCCGGT\n>sq0:3-5\nGTA\n>sq0\nACGTACCGGT\n"
+        );
+    }
+
+    #[test]
+    fn test_getfasta() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_getfasta.fa");
+        fs::write(&input, b">sq0\nACGTACCGGT\n").unwrap();
+
+        let gff = b"sq0\tNOODLES\tgene\t1\t4\t.\t+\t.\tgene_id=g0
+sq0\tNOODLES\tgene\t5\t8\t.\t-\t.\tID=g1
+" as &[u8];
+        let mut output = Vec::new();
+        getfasta(&input, gff, IntervalFormat::Gff, "gene_id", &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">g0\nACGT\n>sq0:5-8/rc\nCGGT\n"
+        );
+
+        let bed = b"sq0\t4\t8\tb0\t0\t-\n" as &[u8];
+        let mut output = Vec::new();
+        getfasta(&input, bed, IntervalFormat::Bed, "ID", &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">b0\nCGGT\n");
+    }
+
+    #[test]
+    fn test_split() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_split.fa");
+        fs::write(&input, b">a\nAAAAAA\n>b\nC\n>c\nGG\n>d\nTTT\n").unwrap();
+        let prefix = temp_dir.join("test_split").display().to_string();
+
+        let ids = |paths: &[PathBuf]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|p| {
+                    let mut reader = File::open(p)
+                        .map(BufReader::new)
+                        .map(fasta::Reader::new)
+                        .unwrap();
+                    reader
+                        .records()
+                        .map(|r| r.unwrap().name().to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect()
+        };
+
+        let options = SplitOptions::default();
+        let paths = split(&input, &prefix, &options).unwrap();
+        assert!(paths[0].ends_with("test_split.000.fa"));
+        assert_eq!(ids(&paths), vec!["a,b", "c,d"]);
+
+        let options = SplitOptions {
+            parts: 3,
+            assignment: SplitAssignment::RoundRobin,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&split(&input, &prefix, &options).unwrap()),
+            vec!["a,d", "b", "c"]
+        );
+
+        let options = SplitOptions {
+            by: SplitBy::Bases,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&split(&input, &prefix, &options).unwrap()),
+            vec!["a", "b,c,d"]
+        );
+
+        let options = SplitOptions {
+            by: SplitBy::Bases,
+            assignment: SplitAssignment::RoundRobin,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&split(&input, &prefix, &options).unwrap()),
+            vec!["a", "b,c,d"]
+        );
+
+        let options = SplitOptions {
+            trailing_newline: false,
+            ..Default::default()
+        };
+        let paths = split(&input, &prefix, &options).unwrap();
+        assert_eq!(fs::read(&paths[1]).unwrap(), b">c\nGG\n>d\nTTT");
+
+        let options = SplitOptions {
+            parts: 0,
+            ..Default::default()
+        };
+        assert!(split(&input, &prefix, &options).is_err());
+    }
+
+    #[test]
+    fn test_cat() {
+        let temp_dir = env::temp_dir();
+        let first = temp_dir.join("test_cat_first.fa");
+        let second = temp_dir.join("test_cat_second.fa");
+        fs::write(&first, b">a\nAC\n>a_1\nGT\n").unwrap();
+        fs::write(&second, b">a desc\nCC\n>b\nGG\n").unwrap();
+
+        let mut output = Vec::new();
+        let renamed = cat(&[&first, &second], &mut output, true).unwrap();
+        assert_eq!(renamed, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">a\nAC\n>a_1\nGT\n>a_2 desc\nCC\n>b\nGG\n"
+        );
+
+        let e = cat(&[&first, &second], &mut Vec::new(), false).unwrap_err();
+        assert!(matches!(e, BrrrrError::Parse(ParseError { id: Some(ref id), .. }) if id == "a"));
+    }
+}
@@ -0,0 +1,648 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use itertools::Either;
+use noodles::fastq;
+
+use crate::errors::ParseError;
+use crate::id_list::IdList;
+use crate::partition::PartitionBy;
+use crate::pipeline::{Pipeline, Stage};
+use crate::sequence::{mean_quality, PHRED_OFFSET};
+
+/// Options for FASTQ conversions, e.g. extra per-record columns.
+#[derive(Default, Clone, Debug)]
+pub struct FastqOptions {
+    /// Add a `mean_quality` column with the mean Phred score of each record.
+    pub with_mean_qual: bool,
+    /// Add a `gc` column with the GC fraction of each read, the same as the stats Parquet's.
+    /// N counts toward a read's length, and an empty read's GC is null.
+    pub with_gc: bool,
+    /// Drop reads whose mean Phred score is below this.
+    pub min_mean_qual: Option<f64>,
+    /// The offset the quality scores are encoded with, e.g. 64 for the Phred+64 of Illumina 1.3
+    /// to 1.7, or None for Phred+33. `mean_quality` and `min_mean_qual` decode scores with it.
+    pub phred_offset: Option<u8>,
+    /// Keep only the reads whose id, the header up to the first whitespace, is in the list, or
+    /// with its `exclude`, drop them.
+    pub id_list: Option<Arc<IdList>>,
+    /// Also write per-record QC metrics to a separate Parquet file at this path.
+    pub stats_parquet: Option<PathBuf>,
+    /// Skip reads that can't be parsed, instead of failing on the first.
+    pub skip_errors: bool,
+    /// With `skip_errors`, fail once more than this fraction of reads couldn't be parsed, e.g.
+    /// because the input isn't FASTQ at all.
+    pub max_error_rate: Option<f64>,
+    /// Read the input even if its first line looks like another format, e.g. FASTA, instead of
+    /// failing with `BrrrrError::FormatMismatch`.
+    pub no_sniff: bool,
+    /// Read reads whose sequence and quality are wrapped over several lines. See
+    /// [`UnwrapFastq`]. This is slower, so it's off unless asked for.
+    pub multiline: bool,
+    /// Accept a lone `*` quality, which some tools write for a read without qualities, instead
+    /// of failing it for not matching the sequence's length. Parquet outputs write the quality as
+    /// null, in a nullable quality column, and text outputs keep the `*`.
+    pub allow_star_qual: bool,
+    /// With `allow_star_qual`, replace a `*` quality with this character repeated to the
+    /// sequence's length, instead of writing null.
+    pub fill_qual: Option<u8>,
+    /// Uppercase the written sequence, dropping soft-masking. Computed columns ignore case
+    /// either way, so they don't change.
+    pub fold_case: bool,
+    /// Reverse each read's sequence, and its quality with it, without complementing the bases.
+    pub reverse: bool,
+    /// Write a directory of Parquet files, `part-00000.parquet`, `part-00001.parquet`, ..., with
+    /// this many reads each, instead of one file. Ignored by `fq2pq_bytes`.
+    pub max_records: Option<usize>,
+    /// Write the Parquet into Hive-style partition directories, e.g. `length_bucket=100-500/`,
+    /// instead of one file. Ignored by `fq2pq_bytes`.
+    pub partition_by: Option<PartitionBy>,
+    /// Build each Parquet batch from reads whose sequences add up to about this many bytes,
+    /// instead of from a fixed number of reads, so memory use doesn't depend on read length.
+    pub batch_bytes: Option<usize>,
+    /// Keep a checkpoint in the directory of an output split by `max_records`, and if an
+    /// interrupted run left one there, carry on from it instead of starting over. The input is
+    /// read again, but the reads already written are skipped, so the other options must match the
+    /// interrupted run's. Can't be used with `stats_parquet`.
+    pub resume: bool,
+    /// Skip this many of the reads that would be written, e.g. with `limit` to take a window of
+    /// the output. The `number` column counts the written reads, so it starts at 0 after the
+    /// skipped ones, rather than at `skip`.
+    pub skip: usize,
+    /// Stop after writing this many reads, without reading the rest of the input.
+    pub limit: Option<usize>,
+}
+
+impl FastqOptions {
+    /// Returns whether `record` passes the filters in the options. Empty reads, and reads with a
+    /// placeholder quality, have no mean quality, and fail any minimum.
+    pub fn keep(&self, record: &fastq::Record) -> bool {
+        self.min_mean_qual.is_none_or(|min| {
+            !is_placeholder_quality(record.sequence(), record.quality_scores())
+                && mean_quality(record.quality_scores(), self.quality_offset())
+                    .is_some_and(|q| q >= min)
+        })
+    }
+
+    /// Returns the offset the quality scores are encoded with, [`PHRED_OFFSET`] unless the
+    /// options set another.
+    pub fn quality_offset(&self) -> u8 {
+        self.phred_offset.unwrap_or(PHRED_OFFSET)
+    }
+
+    /// Returns the pipeline applying the options to each record.
+    pub fn pipeline(&self) -> Pipeline<'_, fastq::Record> {
+        let mut pipeline = Pipeline::default();
+        if self.fold_case {
+            pipeline.add(Stage::Transform, |mut record: fastq::Record| {
+                record.sequence_mut().make_ascii_uppercase();
+                Ok(Some(record))
+            });
+        }
+        if self.reverse {
+            pipeline.add(Stage::Transform, |mut record: fastq::Record| {
+                record.sequence_mut().reverse();
+                record.quality_scores_mut().reverse();
+                Ok(Some(record))
+            });
+        }
+        // The id list goes first, so it sees every listed id even if another filter drops it.
+        if let Some(ids) = &self.id_list {
+            pipeline.add_filter(move |record: &fastq::Record| {
+                let id = record.name().split(u8::is_ascii_whitespace).next();
+                ids.keep(&String::from_utf8_lossy(id.unwrap_or_default()))
+            });
+        }
+        pipeline.add_filter(move |record| self.keep(record));
+        pipeline.set_window(self.skip, self.limit);
+        pipeline
+    }
+}
+
+/// Returns whether `quality` is the `*` placeholder some tools write for a read without
+/// qualities, rather than the quality of a single base.
+pub fn is_placeholder_quality(sequence: &[u8], quality: &[u8]) -> bool {
+    quality == b"*" && sequence.len() != 1
+}
+
+/// Returns an error identifying the read if `sequence` and `quality` have different lengths.
+pub fn check_quality_length(id: &str, sequence: &[u8], quality: &[u8]) -> Result<(), ParseError> {
+    if sequence.len() == quality.len() {
+        Ok(())
+    } else {
+        Err(ParseError::new(format!(
+            "sequence length {} doesn't match quality length {}",
+            sequence.len(),
+            quality.len()
+        ))
+        .with_id(id))
+    }
+}
+
+/// The number of reads between checks of the error rate.
+const ERROR_RATE_CHECK_INTERVAL: u64 = 1000;
+
+/// Iterates over the reads of a FASTQ input, skipping the reads that can't be parsed if the
+/// options allow it. A read whose sequence and quality have different lengths can't be parsed.
+pub struct FastqRecords<R> {
+    reader: fastq::Reader<Either<R, UnwrapFastq<R>>>,
+    skip_errors: bool,
+    max_error_rate: Option<f64>,
+    allow_star_qual: bool,
+    fill_qual: Option<u8>,
+    records: u64,
+    errors: u64,
+    done: bool,
+    /// A read found while resyncing after an error, to be returned next.
+    pending: Option<fastq::Record>,
+}
+
+/// Returns the reads of a FASTQ input, with the error handling in `options` applied.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `options` the options with the error handling, i.e. `skip_errors` and `max_error_rate`.
+pub fn records<R: BufRead>(input: R, options: &FastqOptions) -> FastqRecords<R> {
+    FastqRecords {
+        reader: fastq::Reader::new(if options.multiline {
+            Either::Right(UnwrapFastq::new(input))
+        } else {
+            Either::Left(input)
+        }),
+        skip_errors: options.skip_errors,
+        max_error_rate: options.max_error_rate,
+        allow_star_qual: options.allow_star_qual,
+        fill_qual: options.fill_qual,
+        records: 0,
+        errors: 0,
+        done: false,
+        pending: None,
+    }
+}
+
+impl<R: BufRead> FastqRecords<R> {
+    /// Skips the rest of the line a read failed on, and any lines after it up to the start of the
+    /// next read, so a corrupt read counts as one error.
+    ///
+    /// A quality line can start with `@` too, so a read only starts at an `@` line followed by a
+    /// sequence line and a `+` line. Those lines can't be put back once they're read, so the read
+    /// they start is returned, with the quality line after them, or None at the end of the input.
+    fn resync(&mut self) -> io::Result<Option<fastq::Record>> {
+        let inner = self.reader.get_mut();
+        let mut line = Vec::new();
+        inner.read_until(b'\n', &mut line)?;
+
+        let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+        loop {
+            while lines.len() < 3 {
+                let mut line = Vec::new();
+                if !read_line(inner, &mut line)? {
+                    return Ok(None);
+                }
+                lines.push_back(line);
+            }
+            if lines[0].starts_with(b"@") && lines[2].starts_with(b"+") {
+                break;
+            }
+            lines.pop_front();
+        }
+
+        let mut quality = Vec::new();
+        read_line(inner, &mut quality)?;
+        let mut record = fastq::Record::new(&lines[0][1..], lines[1].as_slice(), quality);
+        record.description_mut().extend_from_slice(&lines[2][1..]);
+
+        Ok(Some(record))
+    }
+
+    /// Returns an error if more than the maximum fraction of reads couldn't be parsed.
+    fn check_error_rate(&self) -> io::Result<()> {
+        let total = self.records + self.errors;
+        match self.max_error_rate {
+            Some(max) if total > 0 && self.errors as f64 / total as f64 > max => {
+                Err(ParseError::new(format!(
+                    "{} of {} reads couldn't be parsed, more than the maximum error rate of {}",
+                    self.errors, total, max
+                ))
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for FastqRecords<R> {
+    type Item = io::Result<fastq::Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            if (self.records + self.errors).is_multiple_of(ERROR_RATE_CHECK_INTERVAL) {
+                if let Err(e) = self.check_error_rate() {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+
+            let mut record = fastq::Record::default();
+            let read = match self.pending.take() {
+                Some(pending) => {
+                    record = pending;
+                    Ok(1)
+                }
+                None => self.reader.read_record(&mut record),
+            };
+            match read {
+                Ok(0) => {
+                    self.done = true;
+                    report_skipped(self.errors);
+                    return self.check_error_rate().err().map(Err);
+                }
+                Ok(_)
+                    if self.allow_star_qual
+                        && is_placeholder_quality(record.sequence(), record.quality_scores()) =>
+                {
+                    if let Some(fill) = self.fill_qual {
+                        let len = record.sequence().len();
+                        *record.quality_scores_mut() = vec![fill; len];
+                    }
+                    self.records += 1;
+                    return Some(Ok(record));
+                }
+                Ok(_) => {
+                    let name = String::from_utf8_lossy(record.name());
+                    match check_quality_length(&name, record.sequence(), record.quality_scores()) {
+                        Ok(()) => {
+                            self.records += 1;
+                            return Some(Ok(record));
+                        }
+                        Err(_) if self.skip_errors => self.errors += 1,
+                        Err(e) => return Some(Err(e.into())),
+                    }
+                }
+                Err(e) if self.skip_errors && e.kind() == io::ErrorKind::InvalidData => {
+                    self.errors += 1;
+                    match self.resync() {
+                        Ok(pending) => self.pending = pending,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Reads the next line of `input`, without its line ending, into `line`, returning false at the
+/// end of the input. Both LF and CRLF line endings are removed.
+pub(crate) fn read_line<R: BufRead>(input: &mut R, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+    if input.read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    if line.ends_with(b"\n") {
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+    }
+    Ok(true)
+}
+
+/// How [`WrappedReads::next_read`] found the next read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WrappedRead {
+    /// There are no more reads.
+    End,
+    /// A whole read was read.
+    Whole,
+    /// The input ended partway through the read.
+    CutShort,
+}
+
+/// Delimits the reads of a FASTQ input whose sequences and qualities may be wrapped over several
+/// lines, for [`UnwrapFastq`] and for counting reads.
+///
+/// A sequence ends at the `+` line. A quality line can start with `@` or `+` too, so a quality
+/// instead ends once it's as long as its sequence, or at a lone `*`, which some tools write for a
+/// read without qualities.
+pub(crate) struct WrappedReads<R> {
+    inner: R,
+    line: Vec<u8>,
+    /// The number of lines read so far.
+    pub(crate) lines: u64,
+}
+
+impl<R: BufRead> WrappedReads<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: Vec::new(),
+            lines: 0,
+        }
+    }
+
+    /// Reads the next line into `line`, returning false at the end of the input.
+    fn read_line(&mut self) -> io::Result<bool> {
+        let read = read_line(&mut self.inner, &mut self.line)?;
+        self.lines += u64::from(read);
+        Ok(read)
+    }
+
+    /// Appends the next read to `read` as four lines, the header, the joined sequence, the `+`
+    /// line, and the joined quality. A read cut short by the end of the input is appended as far
+    /// as it goes. The header isn't checked.
+    pub(crate) fn next_read(&mut self, read: &mut Vec<u8>) -> io::Result<WrappedRead> {
+        if !self.read_line()? {
+            return Ok(WrappedRead::End);
+        }
+        read.extend_from_slice(&self.line);
+        read.push(b'\n');
+
+        let mut sequence_len = 0;
+        loop {
+            if !self.read_line()? {
+                return Ok(WrappedRead::CutShort);
+            }
+            if self.line.starts_with(b"+") {
+                break;
+            }
+            sequence_len += self.line.len();
+            read.extend_from_slice(&self.line);
+        }
+        read.push(b'\n');
+        read.extend_from_slice(&self.line);
+        read.push(b'\n');
+
+        // At least one line is read, so an empty read's empty quality line isn't left behind.
+        let mut quality_len = 0;
+        let mut found = WrappedRead::CutShort;
+        while self.read_line()? {
+            quality_len += self.line.len();
+            read.extend_from_slice(&self.line);
+            if quality_len >= sequence_len || self.line == b"*" {
+                found = WrappedRead::Whole;
+                break;
+            }
+        }
+        read.push(b'\n');
+
+        Ok(found)
+    }
+}
+
+/// Wraps a FASTQ input whose sequences and qualities may be wrapped over several lines, joining
+/// each onto one line, as the noodles reader expects. Reads are delimited as [`WrappedReads`]
+/// does, and passed on a whole read at a time.
+pub struct UnwrapFastq<R> {
+    reads: WrappedReads<R>,
+    read: Vec<u8>,
+    position: usize,
+}
+
+impl<R: BufRead> UnwrapFastq<R> {
+    /// Creates a reader that joins the wrapped reads of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            reads: WrappedReads::new(inner),
+            read: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Read for UnwrapFastq<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for UnwrapFastq<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.read.len() {
+            self.read.clear();
+            self.position = 0;
+            // A read cut short is passed on as it is, for the parser to fail on.
+            self.reads.next_read(&mut self.read)?;
+        }
+
+        Ok(&self.read[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt;
+    }
+}
+
+/// Reports the number of reads that couldn't be parsed, and were skipped, to stderr.
+fn report_skipped(skipped: u64) {
+    if skipped > 0 {
+        eprintln!("Skipped {} reads that couldn't be parsed.", skipped);
+    }
+}
+
+/// Reports the number of reads the filters in FastqOptions dropped to stderr, and the ids of
+/// `id_list` that were missing.
+pub(crate) fn report_filtered(filtered: usize, id_list: Option<&IdList>) {
+    if filtered > 0 {
+        eprintln!("Filtered {} reads.", filtered);
+    }
+    if let Some(ids) = id_list {
+        ids.report();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FastqRecord;
+
+    #[test]
+    fn test_keep() {
+        let options = FastqOptions {
+            min_mean_qual: Some(20.0),
+            ..Default::default()
+        };
+
+        assert!(options.keep(&fastq::Record::new("r0", "AC", "55")));
+        assert!(!options.keep(&fastq::Record::new("r1", "AC", "5+")));
+        assert!(!options.keep(&fastq::Record::new("r2", "", "")));
+        assert!(FastqOptions::default().keep(&fastq::Record::new("r2", "", "")));
+
+        // T is Phred 51 at +33, but 20 at +64.
+        let phred64 = FastqOptions {
+            min_mean_qual: Some(30.0),
+            phred_offset: Some(64),
+            ..Default::default()
+        };
+        assert!(options.keep(&fastq::Record::new("r3", "AC", "TT")));
+        assert!(!phred64.keep(&fastq::Record::new("r3", "AC", "TT")));
+        assert!(phred64.keep(&fastq::Record::new("r4", "AC", "hh")));
+    }
+
+    #[test]
+    fn test_reverse() {
+        let options = FastqOptions {
+            reverse: true,
+            ..Default::default()
+        };
+
+        let record = options
+            .pipeline()
+            .run(fastq::Record::new("r0", "AACG", "!#%'"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.sequence(), b"GCAA");
+        assert_eq!(record.quality_scores(), b"'%#!");
+    }
+
+    #[test]
+    fn test_records_quality_length() {
+        let input = b"@r0\nACGT\n+\n555\n@r1\nAC\n+\n55\n" as &[u8];
+
+        let e = records(input, &FastqOptions::default()).next().unwrap();
+        assert_eq!(
+            e.unwrap_err().to_string(),
+            "record r0: sequence length 4 doesn't match quality length 3"
+        );
+
+        let options = FastqOptions {
+            skip_errors: true,
+            ..Default::default()
+        };
+        let names: Vec<_> = records(input, &options)
+            .map(|r| r.unwrap().name().to_vec())
+            .collect();
+        assert_eq!(names, vec![b"r1".to_vec()]);
+    }
+
+    #[test]
+    fn test_records_crlf() {
+        let input = b"@r0 one\r\nACGT\r\n+r0\r\nIIII\r\n@r1\r\nAC\r\n+\r\n55\r\n" as &[u8];
+
+        let reads: Vec<_> = records(input, &FastqOptions::default())
+            .map(|r| FastqRecord::from(r.unwrap()))
+            .collect();
+        assert_eq!(reads[0].id, "r0 one");
+        assert_eq!(reads[0].sequence, "ACGT");
+        assert_eq!(reads[0].quality, "IIII");
+        assert_eq!(reads[1].sequence, "AC");
+        assert_eq!(reads[1].quality, "55");
+    }
+
+    #[test]
+    fn test_records_multiline() {
+        // The second read's wrapped quality starts with @, and the third's with +, like headers
+        // and separators.
+        let input = b"@r0 one\nACGT\nAC\n+\nIIII\nII\n@r1\nAC\r\nG\r\n+r1\r\n@I\r\nI\r\n\
+                      @r2\nAC\n+\n+I\n@r3\n\n+\n\n@r4\nACGT\n+\n*\n" as &[u8];
+        let options = FastqOptions {
+            multiline: true,
+            allow_star_qual: true,
+            ..Default::default()
+        };
+
+        let reads: Vec<_> = records(input, &options)
+            .map(|r| FastqRecord::from(r.unwrap()))
+            .collect();
+        let sequences: Vec<_> = reads.iter().map(|r| r.sequence.as_str()).collect();
+        assert_eq!(sequences, vec!["ACGTAC", "ACG", "AC", "", "ACGT"]);
+        let qualities: Vec<_> = reads.iter().map(|r| r.quality.as_str()).collect();
+        assert_eq!(qualities, vec!["IIIIII", "@II", "+I", "", "*"]);
+
+        // Without the option, the wrapped sequence doesn't parse.
+        assert!(records(input, &FastqOptions::default())
+            .next()
+            .unwrap()
+            .is_err());
+
+        // A read cut short still fails.
+        let input = b"@r0\nACGT\n+\nII\n" as &[u8];
+        assert!(records(input, &options).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_records_star_quality() {
+        let input = b"@r0\nACGT\n+\n*\n@r1\nA\n+\n*\n" as &[u8];
+
+        assert!(records(input, &FastqOptions::default())
+            .next()
+            .unwrap()
+            .is_err());
+
+        let options = FastqOptions {
+            allow_star_qual: true,
+            ..Default::default()
+        };
+        let reads: Vec<_> = records(input, &options)
+            .map(|r| FastqRecord::from(r.unwrap()))
+            .collect();
+        assert!(reads[0].has_placeholder_quality());
+        assert!(!reads[1].has_placeholder_quality());
+
+        let options = FastqOptions {
+            allow_star_qual: true,
+            fill_qual: Some(b'I'),
+            ..Default::default()
+        };
+        let read = records(input, &options).next().unwrap().unwrap();
+        assert_eq!(read.quality_scores(), b"IIII");
+    }
+
+    #[test]
+    fn test_records_skip_errors() {
+        let input = b"@r0\nAC\n+\n55\nbad\nlines\n@r1\nAC\n-\n55\n@r2\nGG\n+\n55\n" as &[u8];
+
+        assert!(records(input, &FastqOptions::default()).any(|r| r.is_err()));
+
+        let options = FastqOptions {
+            skip_errors: true,
+            ..Default::default()
+        };
+        let names: Vec<_> = records(input, &options)
+            .map(|r| r.unwrap().name().to_vec())
+            .collect();
+        assert_eq!(names, vec![b"r0".to_vec(), b"r2".to_vec()]);
+
+        // A quality line starting with @ isn't taken for the next read, and the read found after
+        // an error keeps its description.
+        let at_quality =
+            b"@r0\nAC\n+\n55\nbad\n@5\n@r1 x\nAC\n+r1\n55\n@r2\nGG\n+\n55\n" as &[u8];
+        let found: Vec<_> = records(at_quality, &options).map(Result::unwrap).collect();
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[1].name(), b"r1 x");
+        assert_eq!(found[1].description(), b"r1");
+        assert_eq!(found[1].quality_scores(), b"55");
+
+        // The read after bad lines fails, but r1 without a + line doesn't start a read, so 1 of
+        // the 3 reads fails, more than a quarter.
+        let options = FastqOptions {
+            skip_errors: true,
+            max_error_rate: Some(0.25),
+            ..Default::default()
+        };
+        let results: Vec<_> = records(input, &options).collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+
+        let options = FastqOptions {
+            skip_errors: true,
+            max_error_rate: Some(0.5),
+            ..Default::default()
+        };
+        assert!(records(input, &options).all(|r| r.is_ok()));
+    }
+}
@@ -0,0 +1,393 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{Array, BooleanArray, Float64Array, StringArray};
+use arrow::compute::{self, and, filter_record_batch};
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::{ArrowReader, ArrowWriter, ParquetFileArrowReader};
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::SerializedFileReader;
+
+use crate::errors::BrrrrError;
+
+/// The number of rows read from the input at once.
+const BATCH_SIZE: usize = 8192;
+
+/// The comparison of a `Predicate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// The operators a filter may use, longest first so `<=` isn't read as `<`.
+const OPERATORS: [(&str, CompareOp); 7] = [
+    ("==", CompareOp::Eq),
+    ("!=", CompareOp::Ne),
+    ("<=", CompareOp::Le),
+    (">=", CompareOp::Ge),
+    ("=", CompareOp::Eq),
+    ("<", CompareOp::Lt),
+    (">", CompareOp::Gt),
+];
+
+/// A row filter that compares a column to a literal, e.g. `end > 5000` or `strand == "+"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    /// The column compared.
+    pub column: String,
+    /// Compare the length of the column's strings, e.g. `length(sequence)`, not the strings.
+    pub length: bool,
+    pub op: CompareOp,
+    /// The literal, parsed as a number if the column is numeric, or if `length` is set.
+    pub value: String,
+}
+
+impl FromStr for Predicate {
+    type Err = io::Error;
+
+    /// Parses `<column> <op> <literal>`, where op is one of `==`, `!=`, `<`, `<=`, `>`, or `>=`,
+    /// the column may be wrapped in `length(...)`, and the literal may be quoted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid filter {:?}: {}", s, reason),
+            )
+        };
+
+        let at = s
+            .find(['=', '!', '<', '>'])
+            .ok_or_else(|| invalid("expected <column> <op> <value>, e.g. end > 5000"))?;
+        let (column, rest) = s.split_at(at);
+        let (op, value) = OPERATORS
+            .iter()
+            .find_map(|(token, op)| rest.strip_prefix(token).map(|value| (*op, value)))
+            .ok_or_else(|| invalid("expected one of ==, !=, <, <=, >, >="))?;
+
+        let column = column.trim();
+        let (column, length) = match column
+            .strip_prefix("length(")
+            .and_then(|c| c.strip_suffix(')'))
+        {
+            Some(inner) => (inner.trim(), true),
+            None => (column, false),
+        };
+        if column.is_empty() {
+            return Err(invalid("missing the column"));
+        }
+
+        let value = value.trim();
+        let quoted = value.len() >= 2
+            && (value.starts_with('"') && value.ends_with('"')
+                || value.starts_with('\'') && value.ends_with('\''));
+        let value = if quoted {
+            &value[1..value.len() - 1]
+        } else if value.is_empty() {
+            return Err(invalid("missing the value"));
+        } else {
+            value
+        };
+
+        Ok(Predicate {
+            column: column.to_string(),
+            length,
+            op,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Predicate {
+    /// Returns which rows of `batch` pass. Rows where the column is null don't.
+    fn evaluate(&self, batch: &RecordBatch) -> Result<BooleanArray, BrrrrError> {
+        let index = batch.schema().index_of(&self.column).map_err(|_| {
+            BrrrrError::SchemaMismatch(format!("no column {:?} to filter on", self.column))
+        })?;
+        let mut column = batch.column(index).clone();
+        if self.length {
+            column = compute::kernels::length::length(column.as_ref())?;
+        }
+
+        match column.data_type() {
+            DataType::Utf8 => {
+                let strings = column.as_any().downcast_ref::<StringArray>().unwrap();
+                Ok(self.compare_utf8(strings)?)
+            }
+            t if DataType::is_numeric(t) => {
+                let value = self.value.parse::<f64>().map_err(|_| {
+                    BrrrrError::SchemaMismatch(format!(
+                        "{:?} is compared to {:?}, which isn't a number",
+                        self.column, self.value
+                    ))
+                })?;
+                let numbers = compute::cast(&column, &DataType::Float64)?;
+                let numbers = numbers.as_any().downcast_ref::<Float64Array>().unwrap();
+                Ok(self.compare_f64(numbers, value)?)
+            }
+            t => Err(BrrrrError::SchemaMismatch(format!(
+                "can't filter on {:?}, a {} column",
+                self.column, t
+            ))),
+        }
+    }
+
+    fn compare_f64(&self, array: &Float64Array, value: f64) -> arrow::error::Result<BooleanArray> {
+        match self.op {
+            CompareOp::Eq => compute::eq_scalar(array, value),
+            CompareOp::Ne => compute::neq_scalar(array, value),
+            CompareOp::Lt => compute::lt_scalar(array, value),
+            CompareOp::Le => compute::lt_eq_scalar(array, value),
+            CompareOp::Gt => compute::gt_scalar(array, value),
+            CompareOp::Ge => compute::gt_eq_scalar(array, value),
+        }
+    }
+
+    fn compare_utf8(&self, array: &StringArray) -> arrow::error::Result<BooleanArray> {
+        let value = self.value.as_str();
+        match self.op {
+            CompareOp::Eq => compute::eq_utf8_scalar(array, value),
+            CompareOp::Ne => compute::neq_utf8_scalar(array, value),
+            CompareOp::Lt => compute::lt_utf8_scalar(array, value),
+            CompareOp::Le => compute::lt_eq_utf8_scalar(array, value),
+            CompareOp::Gt => compute::gt_utf8_scalar(array, value),
+            CompareOp::Ge => compute::gt_eq_utf8_scalar(array, value),
+        }
+    }
+}
+
+/// The file format `df` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    Parquet,
+    /// CSV with a header row. Map columns, e.g. a GFF file's `attribute`, can't be written as
+    /// CSV, so they must be left out with `FrameOptions::select`.
+    Csv,
+}
+
+/// The columns and rows `df` keeps.
+#[derive(Debug, Default, Clone)]
+pub struct FrameOptions {
+    /// The columns to keep, in this order. All of them are kept if this is empty.
+    pub select: Vec<String>,
+    /// Keep only the rows that pass all of these.
+    pub filters: Vec<Predicate>,
+}
+
+enum FrameWriter {
+    Parquet(ArrowWriter<File>),
+    Csv(Box<arrow::csv::Writer<File>>),
+}
+
+/// df selects columns and filters rows of a Parquet file, and writes the result to Parquet or
+/// CSV, in the order of the input.
+///
+/// Filters may use columns that aren't selected.
+///
+/// # Arguments
+///
+/// * `input` - The path to the input Parquet file.
+/// * `output` - The path to the output file.
+/// * `format` - The format of the output.
+/// * `compression` - The compression of a Parquet output.
+/// * `options` - The columns to keep, and the filters rows must pass.
+pub fn df<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    format: FrameFormat,
+    compression: Compression,
+    options: &FrameOptions,
+) -> Result<(), BrrrrError> {
+    let file_reader = SerializedFileReader::new(File::open(input)?)?;
+    let mut reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+    let schema = reader.get_schema()?;
+
+    let columns = if options.select.is_empty() {
+        (0..schema.fields().len()).collect()
+    } else {
+        options
+            .select
+            .iter()
+            .map(|name| {
+                schema.index_of(name).map_err(|_| {
+                    BrrrrError::SchemaMismatch(format!("no column {:?} to select", name))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let output_file = File::create(output)?;
+    let mut writer = match format {
+        FrameFormat::Parquet => {
+            let props = WriterProperties::builder()
+                .set_compression(compression)
+                .set_statistics_enabled(true)
+                .build();
+            let output_schema = Arc::new(schema.project(&columns)?);
+            FrameWriter::Parquet(ArrowWriter::try_new(
+                output_file,
+                output_schema,
+                Some(props),
+            )?)
+        }
+        FrameFormat::Csv => FrameWriter::Csv(Box::new(arrow::csv::Writer::new(output_file))),
+    };
+
+    for batch in reader.get_record_reader(BATCH_SIZE)? {
+        let batch = batch?;
+
+        let mut keep: Option<BooleanArray> = None;
+        for filter in &options.filters {
+            let passed = filter.evaluate(&batch)?;
+            keep = Some(match keep {
+                Some(keep) => and(&keep, &passed)?,
+                None => passed,
+            });
+        }
+        let batch = match keep {
+            Some(keep) => filter_record_batch(&batch, &keep)?,
+            None => batch,
+        };
+        let batch = batch.project(&columns)?;
+
+        match &mut writer {
+            FrameWriter::Parquet(writer) => writer.write(&batch)?,
+            FrameWriter::Csv(writer) => writer.write(&batch)?,
+        }
+    }
+
+    if let FrameWriter::Parquet(writer) = writer {
+        writer.close()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{as_primitive_array, as_string_array, ArrayRef, Int64Array};
+    use arrow::datatypes::{Field, Int64Type, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    use super::*;
+
+    fn write_input(path: &Path) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("sequence", DataType::Utf8, false),
+            Field::new("start", DataType::Int64, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+            Arc::new(StringArray::from(vec!["ACGT", "A", "ACGTACGT", "AC"])),
+            Arc::new(Int64Array::from(vec![Some(10), Some(20), None, Some(40)])),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_parse_predicate() {
+        let predicate: Predicate = "length(sequence) >= 100".parse().unwrap();
+        assert_eq!(predicate.column, "sequence");
+        assert!(predicate.length);
+        assert_eq!(predicate.op, CompareOp::Ge);
+        assert_eq!(predicate.value, "100");
+
+        let predicate: Predicate = "strand == '+'".parse().unwrap();
+        assert_eq!(
+            (predicate.op, predicate.value.as_str()),
+            (CompareOp::Eq, "+")
+        );
+        let predicate: Predicate = "id!=\"\"".parse().unwrap();
+        assert_eq!(
+            (predicate.op, predicate.value.as_str()),
+            (CompareOp::Ne, "")
+        );
+
+        assert!("start 100".parse::<Predicate>().is_err());
+        assert!("> 100".parse::<Predicate>().is_err());
+        assert!("start >".parse::<Predicate>().is_err());
+    }
+
+    #[test]
+    fn test_df() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.parquet");
+        let output = dir.path().join("output.parquet");
+        write_input(&input);
+
+        let options = FrameOptions {
+            select: vec![String::from("start"), String::from("id")],
+            filters: vec![
+                "start > 10".parse().unwrap(),
+                "length(sequence) < 4".parse().unwrap(),
+            ],
+        };
+        df(
+            &input,
+            &output,
+            FrameFormat::Parquet,
+            Compression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let file_reader = SerializedFileReader::new(File::open(&output).unwrap()).unwrap();
+        let mut reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+        let batch = reader
+            .get_record_reader(16)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.schema().field(0).name(), "start");
+        let ids = as_string_array(batch.column(1));
+        assert_eq!(ids.iter().collect::<Vec<_>>(), vec![Some("b"), Some("d")]);
+        let starts = as_primitive_array::<Int64Type>(batch.column(0));
+        assert_eq!(starts.values(), &[20, 40]);
+
+        let csv = dir.path().join("output.csv");
+        let options = FrameOptions {
+            select: vec![String::from("id")],
+            filters: vec!["sequence = ACGT".parse().unwrap()],
+        };
+        df(
+            &input,
+            &csv,
+            FrameFormat::Csv,
+            Compression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&csv).unwrap(), "id\na\n");
+
+        let missing = FrameOptions {
+            select: vec![String::from("quality")],
+            ..Default::default()
+        };
+        let err = df(
+            &input,
+            &output,
+            FrameFormat::Parquet,
+            Compression::UNCOMPRESSED,
+            &missing,
+        );
+        assert!(matches!(err, Err(BrrrrError::SchemaMismatch(_))));
+    }
+}
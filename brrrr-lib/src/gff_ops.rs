@@ -0,0 +1,943 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use noodles::core::Position;
+use noodles::gff;
+use noodles::gff::record::attributes::Entry;
+use noodles::gff::record::Strand;
+use noodles::gff::Directive;
+use noodles::gff::Line;
+
+use crate::errors::{BrrrrError, ParseError};
+use crate::id_list::IdList;
+use crate::intervals::Region;
+use crate::pipeline::Pipeline;
+use crate::stats::ReportFormat;
+
+/// The number of records held in memory at once while sorting. Inputs with more records than
+/// this are sorted in runs that are spilled to temporary files and merged.
+pub const SORT_RUN_SIZE: usize = 2usize.pow(20);
+
+/// The arrow type of the score column of a GFF Parquet output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ScoreType {
+    /// 64-bit integers, parsed from the score text. A score that isn't written as an integer, e.g.
+    /// 2.5 or 1e3, is an error, rather than being truncated.
+    Int,
+    /// 64-bit floats.
+    #[default]
+    Float,
+    /// The score as text, with `.` for a missing score.
+    Str,
+}
+
+/// GffOptions controls how GFF records are read before they're written to the output.
+#[derive(Default, Clone, Debug)]
+pub struct GffOptions {
+    /// Sort features by (seqname, start, end) before writing.
+    pub sort: bool,
+    /// Keep only features whose source column equals this, e.g. `ensembl`.
+    pub source: Option<String>,
+    /// Keep only features overlapping any of these regions, if there are any.
+    pub regions: Vec<Region>,
+    /// Keep only features whose `ID` attribute is in the list, or with its `exclude`, drop
+    /// them. Features without an `ID` aren't in any list.
+    pub id_list: Option<Arc<IdList>>,
+    /// Write a directory of Parquet files, `part-00000.parquet`, `part-00001.parquet`, ..., with
+    /// this many features each, instead of one file. Only `gff2pq` splits its output.
+    pub max_records: Option<usize>,
+    /// The type of the score column of a Parquet output.
+    pub score_type: ScoreType,
+    /// Write each feature's attributes to a Parquet output sorted by key, instead of in hash
+    /// order, which changes from run to run.
+    pub sort_attributes: bool,
+    /// Write each attribute value to a Parquet output as a list, split on commas, e.g.
+    /// `Parent=geneA,geneB` as `["geneA", "geneB"]`, instead of as one string.
+    pub multivalue_attributes: bool,
+    /// Write the `##` directive and `#` comment lines of the input to this file, so `pq2gff`
+    /// can prepend them to its output.
+    pub keep_directives: Option<PathBuf>,
+    /// Read the input even if its first line looks like another format, e.g. FASTA, instead of
+    /// failing with `BrrrrError::FormatMismatch`.
+    pub no_sniff: bool,
+    /// Keep a checkpoint in the directory of an output split by `max_records`, and if an
+    /// interrupted run left one there, carry on from it instead of starting over. The input is
+    /// read again, but the features already written are skipped, so the other options must match
+    /// the interrupted run's.
+    pub resume: bool,
+    /// Skip this many of the features that would be written, e.g. with `limit` to take a window
+    /// of the output.
+    pub skip: usize,
+    /// Stop after writing this many features. Unless they're sorted, the rest of the input isn't
+    /// read.
+    pub limit: Option<usize>,
+}
+
+impl GffOptions {
+    /// Returns whether `record` passes the filters in the options.
+    pub fn keep(&self, record: &gff::Record) -> bool {
+        let start = usize::from(record.start()) as i64 - 1;
+        let end = usize::from(record.end()) as i64;
+
+        self.source
+            .as_ref()
+            .is_none_or(|source| record.source() == source)
+            && (self.regions.is_empty()
+                || self
+                    .regions
+                    .iter()
+                    .any(|r| r.overlaps(record.reference_sequence_name(), start, end)))
+    }
+
+    /// Returns the pipeline applying the options to each record.
+    pub fn pipeline(&self) -> Pipeline<'_, gff::Record> {
+        let mut pipeline = Pipeline::default();
+        // The id list goes first, so it sees every listed id even if another filter drops it.
+        if let Some(ids) = &self.id_list {
+            pipeline.add_filter(move |record: &gff::Record| {
+                let id = record.attributes().iter().find(|e| e.key() == "ID");
+                ids.keep(id.map_or("", |e| e.value()))
+            });
+        }
+        pipeline.add_filter(move |record| self.keep(record));
+        pipeline.set_window(self.skip, self.limit);
+        pipeline
+    }
+}
+
+/// Reports the number of features the filters in GffOptions dropped to stderr, and the ids of
+/// `id_list` that were missing.
+pub(crate) fn report_filtered(filtered: usize, id_list: Option<&IdList>) {
+    if filtered > 0 {
+        eprintln!("Filtered {} features.", filtered);
+    }
+    if let Some(ids) = id_list {
+        ids.report();
+    }
+}
+
+/// A boxed stream of GFF records.
+pub type GffRecords<'a> = Box<dyn Iterator<Item = io::Result<gff::Record>> + 'a>;
+
+/// A boxed stream of GFF records, each with the text of its score column, e.g. `1e3` or `.`,
+/// which the record itself only keeps as an f32.
+pub(crate) type ScoredRecords<'a> =
+    Box<dyn Iterator<Item = io::Result<(gff::Record, String)>> + 'a>;
+
+/// Returns the records from a GFF input, with `options` applied.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `options` the options to apply to the record stream.
+pub fn records<'a, R: BufRead + 'a>(input: R, options: &GffOptions) -> io::Result<GffRecords<'a>> {
+    let records = scored_records(input, options)?;
+    Ok(Box::new(records.map(|r| r.map(|(record, _)| record))))
+}
+
+/// Returns the records from a GFF input, with `options` applied, like [`records`], but with the
+/// text of each record's score column.
+pub(crate) fn scored_records<'a, R: BufRead + 'a>(
+    input: R,
+    options: &GffOptions,
+) -> io::Result<ScoredRecords<'a>> {
+    let records = RecordIter::with_options(input, options)?;
+
+    if options.sort {
+        sort_records(records, SORT_RUN_SIZE)
+    } else {
+        Ok(Box::new(records))
+    }
+}
+
+/// Returns the records from a GFF input that `pipeline` keeps, like [`scored_records`] with
+/// [`Pipeline::kept_with`]. With `sort`, the pipeline's steps drop records before the sort, so
+/// they're never sorted or spilled, and its window is applied after, to the sorted records.
+pub(crate) fn kept_records<'p, 'a, R: BufRead + 'p>(
+    input: R,
+    options: &GffOptions,
+    pipeline: &'p mut Pipeline<'a, gff::Record>,
+) -> io::Result<ScoredRecords<'p>> {
+    let records = RecordIter::with_options(input, options)?;
+
+    if !options.sort {
+        return Ok(Box::new(pipeline.kept_with(records)));
+    }
+
+    let filtered = records.filter_map(|record| match record {
+        Ok((record, score)) => pipeline
+            .apply(record)
+            .map(|kept| kept.map(|record| (record, score)))
+            .transpose(),
+        Err(e) => Some(Err(e)),
+    });
+    let sorted = sort_records(filtered, SORT_RUN_SIZE)?;
+
+    let windowed = sorted.map_while(move |record| {
+        if pipeline.done() {
+            return None;
+        }
+        let kept = record.map(|(record, score)| pipeline.window(record).map(|r| (r, score)));
+        Some(kept.transpose())
+    });
+
+    Ok(Box::new(windowed.flatten()))
+}
+
+/// Iterates over the records of a GFF input, stopping at EOF or a `##FASTA` directive.
+struct RecordIter<R> {
+    reader: gff::Reader<R>,
+    buf: String,
+    line_number: u64,
+    /// Where directive and comment lines are written, if they're kept.
+    directives: Option<BufWriter<File>>,
+}
+
+impl<R: BufRead> RecordIter<R> {
+    fn new(input: R) -> Self {
+        Self {
+            reader: gff::Reader::new(input),
+            buf: String::new(),
+            line_number: 0,
+            directives: None,
+        }
+    }
+
+    /// Creates an iterator over the records of `input`, keeping its directives if the options
+    /// ask for it.
+    fn with_options(input: R, options: &GffOptions) -> io::Result<Self> {
+        let mut records = Self::new(input);
+        if let Some(path) = &options.keep_directives {
+            records.directives = Some(BufWriter::new(File::create(path)?));
+        }
+        Ok(records)
+    }
+
+    /// Writes the line in the buffer to the directives file, if there is one.
+    fn keep_line(&mut self) -> io::Result<()> {
+        match &mut self.directives {
+            Some(directives) => writeln!(directives, "{}", self.buf),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes the directives file, if there is one, at the end of the input.
+    fn finish(&mut self) -> Option<io::Result<(gff::Record, String)>> {
+        match self.directives.take()?.flush() {
+            Ok(()) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RecordIter<R> {
+    type Item = io::Result<(gff::Record, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return self.finish(),
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            self.line_number += 1;
+
+            match self.buf.parse() {
+                Ok(Line::Record(r)) => {
+                    let score = self.buf.split('\t').nth(5).unwrap_or(".");
+                    return Some(Ok((r, score.to_string())));
+                }
+                Ok(Line::Directive(Directive::StartOfFasta)) => return self.finish(),
+                Ok(Line::Directive(_)) | Ok(Line::Comment(_)) => {
+                    if let Err(e) = self.keep_line() {
+                        return Some(Err(e));
+                    }
+                }
+                Err(e) => {
+                    let error = ParseError::new(e.to_string()).with_line(self.line_number);
+                    return Some(Err(error.into()));
+                }
+            }
+        }
+    }
+}
+
+/// Compares two records by (seqname, start, end).
+fn cmp_position((a, _): &(gff::Record, String), (b, _): &(gff::Record, String)) -> Ordering {
+    a.reference_sequence_name()
+        .cmp(b.reference_sequence_name())
+        .then_with(|| a.start().cmp(&b.start()))
+        .then_with(|| a.end().cmp(&b.end()))
+}
+
+/// Sorts `records` with an external merge sort, holding at most `run_size` records in memory.
+///
+/// Each sorted run is written to an anonymous temporary file, and the runs are merged lazily as
+/// the returned iterator is consumed. If the input fits in a single run, nothing is spilled.
+fn sort_records<'a, I>(records: I, run_size: usize) -> io::Result<ScoredRecords<'a>>
+where
+    I: Iterator<Item = io::Result<(gff::Record, String)>>,
+{
+    let mut runs = Vec::new();
+    let mut run = Vec::with_capacity(run_size.min(SORT_RUN_SIZE));
+
+    for record in records {
+        run.push(record?);
+
+        if run.len() == run_size {
+            runs.push(spill_run(&mut run)?);
+        }
+    }
+
+    if runs.is_empty() {
+        run.sort_by(cmp_position);
+        return Ok(Box::new(run.into_iter().map(Ok)));
+    }
+
+    if !run.is_empty() {
+        runs.push(spill_run(&mut run)?);
+    }
+
+    let mut merge = MergeIter {
+        runs: runs
+            .into_iter()
+            .map(|f| RecordIter::new(BufReader::new(f)))
+            .collect(),
+        heap: BinaryHeap::new(),
+        pending: None,
+    };
+
+    for i in 0..merge.runs.len() {
+        merge.advance(i)?;
+    }
+
+    Ok(Box::new(merge))
+}
+
+/// Sorts and writes `run` to a temporary file, leaving `run` empty.
+fn spill_run(run: &mut Vec<(gff::Record, String)>) -> io::Result<File> {
+    run.sort_by(cmp_position);
+
+    let mut writer = BufWriter::new(tempfile::tempfile()?);
+    for (record, score) in run.drain(..) {
+        write_record(&mut writer, &record, &score)?;
+    }
+
+    let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+
+    Ok(file)
+}
+
+/// Writes `record` as a GFF line, with `score` as its score column.
+///
+/// noodles writes empty attributes as `.`, which it can't parse back, so the attributes column is
+/// omitted instead.
+fn write_record<W: Write>(writer: &mut W, record: &gff::Record, score: &str) -> io::Result<()> {
+    let line = record.to_string();
+    let mut columns: Vec<_> = line.split('\t').collect();
+    columns[5] = score;
+    if record.attributes().is_empty() {
+        columns.truncate(8);
+    }
+
+    writeln!(writer, "{}", columns.join("\t"))
+}
+
+/// The head record of a sorted run, ordered so that `BinaryHeap` pops the smallest first.
+struct RunHead {
+    record: (gff::Record, String),
+    run: usize,
+}
+
+impl Ord for RunHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_position(&other.record, &self.record).then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for RunHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RunHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RunHead {}
+
+/// A k-way merge over sorted runs.
+struct MergeIter {
+    runs: Vec<RecordIter<BufReader<File>>>,
+    heap: BinaryHeap<RunHead>,
+    pending: Option<usize>,
+}
+
+impl MergeIter {
+    /// Pushes the next record from run `i` onto the heap.
+    fn advance(&mut self, i: usize) -> io::Result<()> {
+        if let Some(record) = self.runs[i].next() {
+            self.heap.push(RunHead {
+                record: record?,
+                run: i,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for MergeIter {
+    type Item = io::Result<(gff::Record, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Refill from the run the previous record came from before popping the next one.
+        if let Some(i) = self.pending.take() {
+            if let Err(e) = self.advance(i) {
+                return Some(Err(e));
+            }
+        }
+
+        let head = self.heap.pop()?;
+        self.pending = Some(head.run);
+
+        Some(Ok(head.record))
+    }
+}
+
+/// How the attributes of merged features are combined.
+#[derive(Debug, Copy, Clone)]
+pub enum MergeAttributes {
+    /// The merged feature has no attributes.
+    Drop,
+    /// Each key's distinct values are joined with `,`, in the order they were first seen.
+    Concat,
+}
+
+/// The output format of a merge.
+#[derive(Debug, Copy, Clone)]
+pub enum MergeFormat {
+    /// GFF, with 1-based closed coordinates.
+    Gff,
+    /// BED6 (chrom, start, end, name, score, strand) with 0-based half-open coordinates. The
+    /// name is the feature type.
+    Bed,
+}
+
+/// MergeOptions controls how features are merged.
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// The largest gap between two features that still merges them. At 0, overlapping and
+    /// book-ended features are merged.
+    pub distance: usize,
+    /// How the attributes of the merged features are combined.
+    pub attributes: MergeAttributes,
+    /// The output format.
+    pub format: MergeFormat,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            distance: 0,
+            attributes: MergeAttributes::Drop,
+            format: MergeFormat::Gff,
+        }
+    }
+}
+
+/// A run of merged features.
+struct MergedFeature {
+    seqname: String,
+    source: String,
+    ty: String,
+    start: Position,
+    end: Position,
+    strand: Strand,
+    attributes: Vec<(String, Vec<String>)>,
+}
+
+impl MergedFeature {
+    fn new(record: gff::Record, attrs: MergeAttributes) -> Self {
+        let mut feature = Self {
+            seqname: record.reference_sequence_name().to_string(),
+            source: record.source().to_string(),
+            ty: record.ty().to_string(),
+            start: record.start(),
+            end: record.end(),
+            strand: record.strand(),
+            attributes: Vec::new(),
+        };
+        feature.add_attributes(&record, attrs);
+        feature
+    }
+
+    fn add_attributes(&mut self, record: &gff::Record, attrs: MergeAttributes) {
+        if let MergeAttributes::Drop = attrs {
+            return;
+        }
+
+        for entry in record.attributes().iter() {
+            match self.attributes.iter_mut().find(|(k, _)| k == entry.key()) {
+                Some((_, values)) => {
+                    if !values.iter().any(|v| v == entry.value()) {
+                        values.push(entry.value().to_string());
+                    }
+                }
+                None => self
+                    .attributes
+                    .push((entry.key().to_string(), vec![entry.value().to_string()])),
+            }
+        }
+    }
+
+    fn write<W: Write>(self, writer: &mut W, format: MergeFormat) -> io::Result<()> {
+        match format {
+            MergeFormat::Gff => {
+                let record = gff::Record::builder()
+                    .set_reference_sequence_name(self.seqname)
+                    .set_source(self.source)
+                    .set_type(self.ty)
+                    .set_start(self.start)
+                    .set_end(self.end)
+                    .set_strand(self.strand)
+                    .build();
+
+                let line = record.to_string();
+                let line = line.strip_suffix("\t.").unwrap_or(&line);
+
+                if self.attributes.is_empty() {
+                    writeln!(writer, "{}", line)
+                } else {
+                    writeln!(writer, "{}\t{}", line, format_attributes(&self.attributes))
+                }
+            }
+            MergeFormat::Bed => writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t0\t{}",
+                self.seqname,
+                usize::from(self.start) - 1,
+                usize::from(self.end),
+                self.ty,
+                self.strand.as_ref(),
+            ),
+        }
+    }
+}
+
+/// Formats multi-valued attributes as a GFF3 attributes column.
+///
+/// noodles percent-encodes a `,` inside a value, so each value is encoded on its own and the
+/// values are joined with a literal `,`.
+fn format_attributes(attributes: &[(String, Vec<String>)]) -> String {
+    attributes
+        .iter()
+        .map(|(key, values)| {
+            let values: Vec<String> = values
+                .iter()
+                .map(|v| {
+                    let entry = Entry::new(key.as_str(), v.as_str()).to_string();
+                    entry
+                        .split_once('=')
+                        .map_or(entry.clone(), |(_, v)| v.to_string())
+                })
+                .collect();
+            let entry = Entry::new(key.as_str(), "").to_string();
+            format!("{}{}", entry, values.join(","))
+        })
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+/// Merges overlapping or nearby features of the same type on the same strand into single
+/// features, like `bedtools merge`.
+///
+/// The input is sorted first, and the merged features are written sorted by (seqname, start,
+/// end). Merged features keep the source of their first feature, and have no score or phase.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `output` an output that implements the Write trait.
+/// * `options` the options that control the merge.
+pub fn merge<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    options: &MergeOptions,
+) -> Result<(), BrrrrError> {
+    let sort_options = GffOptions {
+        sort: true,
+        ..Default::default()
+    };
+    let records = records(input, &sort_options)?;
+
+    // Features that can still be extended, keyed by (type, strand), and the finished features on
+    // the current seqname.
+    let mut open: HashMap<(String, Strand), MergedFeature> = HashMap::new();
+    let mut done: Vec<MergedFeature> = Vec::new();
+    let mut seqname: Option<String> = None;
+
+    for record in records {
+        let record = record?;
+
+        if seqname.as_deref() != Some(record.reference_sequence_name()) {
+            done.extend(open.drain().map(|(_, f)| f));
+            if let Err(e) = write_merged(output, &mut done, options.format) {
+                match e.kind() {
+                    ErrorKind::BrokenPipe => return Ok(()),
+                    _ => return Err(BrrrrError::from(e)),
+                }
+            }
+            seqname = Some(record.reference_sequence_name().to_string());
+        }
+
+        let key = (record.ty().to_string(), record.strand());
+        let start = usize::from(record.start());
+
+        match open.get_mut(&key) {
+            Some(feature) if start <= usize::from(feature.end) + 1 + options.distance => {
+                feature.end = feature.end.max(record.end());
+                feature.add_attributes(&record, options.attributes);
+            }
+            _ => {
+                let feature = MergedFeature::new(record, options.attributes);
+                if let Some(finished) = open.insert(key, feature) {
+                    done.push(finished);
+                }
+            }
+        }
+    }
+
+    done.extend(open.drain().map(|(_, f)| f));
+    if let Err(e) = write_merged(output, &mut done, options.format) {
+        match e.kind() {
+            ErrorKind::BrokenPipe => return Ok(()),
+            _ => return Err(BrrrrError::from(e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Sorts and writes the finished features of a seqname, leaving `done` empty.
+fn write_merged<W: Write>(
+    writer: &mut W,
+    done: &mut Vec<MergedFeature>,
+    format: MergeFormat,
+) -> io::Result<()> {
+    done.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end)));
+
+    for feature in done.drain(..) {
+        feature.write(writer, format)?;
+    }
+
+    Ok(())
+}
+
+/// The feature counts of a GFF input, by type, source, and seqname.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct GffSummary {
+    /// The total number of features.
+    pub features: u64,
+    /// The number of features of each type, e.g. `gene` or `exon`.
+    pub types: HashMap<String, u64>,
+    /// The number of features from each source, e.g. `ensembl`.
+    pub sources: HashMap<String, u64>,
+    /// The number of features on each seqname.
+    pub seqnames: HashMap<String, u64>,
+}
+
+impl GffSummary {
+    /// Writes the counts to `output`, each group sorted by name.
+    pub fn write<W: Write>(&self, format: ReportFormat, output: &mut W) -> io::Result<()> {
+        let groups = [
+            ("type", &self.types),
+            ("source", &self.sources),
+            ("seqname", &self.seqnames),
+        ];
+
+        match format {
+            ReportFormat::Json => {
+                let mut value = serde_json::Map::new();
+                value.insert(String::from("features"), self.features.into());
+                for (group, counts) in groups {
+                    let counts: serde_json::Map<String, serde_json::Value> = sorted(counts)
+                        .into_iter()
+                        .map(|(name, count)| (name.to_string(), count.into()))
+                        .collect();
+                    value.insert(format!("{}s", group), counts.into());
+                }
+                serde_json::to_writer(&mut *output, &value)?;
+                writeln!(output)
+            }
+            ReportFormat::Table => {
+                writeln!(output, "features\t{}", self.features)?;
+                for (group, counts) in groups {
+                    for (name, count) in sorted(counts) {
+                        writeln!(output, "{}\t{}\t{}", group, name, count)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn sorted(counts: &HashMap<String, u64>) -> Vec<(&str, u64)> {
+    let mut counts: Vec<(&str, u64)> = counts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    counts.sort_unstable();
+    counts
+}
+
+/// Counts the features of a GFF input by type, source, and seqname in a single pass.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+pub fn summary<R: BufRead>(input: R) -> Result<GffSummary, BrrrrError> {
+    let mut summary = GffSummary::default();
+
+    for record in RecordIter::new(input) {
+        let (record, _) = record?;
+
+        summary.features += 1;
+        *summary.types.entry(record.ty().to_string()).or_default() += 1;
+        *summary
+            .sources
+            .entry(record.source().to_string())
+            .or_default() += 1;
+        *summary
+            .seqnames
+            .entry(record.reference_sequence_name().to_string())
+            .or_default() += 1;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Stage;
+
+    const UNSORTED: &[u8] = b"##gff-version 3
+sq1\tNOODLES\tgene\t5\t9\t.\t+\t.\tID=d
+sq0\tNOODLES\tgene\t8\t13\t.\t+\t.\tID=b
+sq0\tNOODLES\tgene\t1\t20\t.\t+\t.\tID=a
+sq1\tNOODLES\tgene\t1\t3\t.\t-\t.\tID=c
+sq0\tNOODLES\tgene\t8\t10\t.\t+\t.\tID=e
+";
+
+    fn starts(records: ScoredRecords) -> Vec<(String, usize, usize)> {
+        records
+            .map(|r| {
+                let (r, _) = r.unwrap();
+                (
+                    r.reference_sequence_name().to_string(),
+                    usize::from(r.start()),
+                    usize::from(r.end()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_records() {
+        let expected = vec![
+            (String::from("sq0"), 1, 20),
+            (String::from("sq0"), 8, 10),
+            (String::from("sq0"), 8, 13),
+            (String::from("sq1"), 1, 3),
+            (String::from("sq1"), 5, 9),
+        ];
+
+        let options = GffOptions {
+            sort: true,
+            ..Default::default()
+        };
+        let in_memory = scored_records(UNSORTED, &options).unwrap();
+        assert_eq!(starts(in_memory), expected);
+
+        // A run size of 2 forces the input through spilled runs and the merge.
+        let spilled = sort_records(RecordIter::new(UNSORTED), 2).unwrap();
+        assert_eq!(starts(spilled), expected);
+
+        // Spilled records keep the text of their scores.
+        let scored = b"sq0\t.\tgene\t5\t9\t0.10\t+\t.\tID=b\nsq0\t.\tgene\t1\t9\t1e3\t+\t.\n";
+        let scores: Vec<_> = sort_records(RecordIter::new(&scored[..]), 1)
+            .unwrap()
+            .map(|r| r.unwrap().1)
+            .collect();
+        assert_eq!(scores, vec!["1e3", "0.10"]);
+    }
+
+    #[test]
+    fn test_filter_before_sort() {
+        let options = GffOptions {
+            sort: true,
+            regions: vec!["sq0".parse().unwrap()],
+            skip: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        // A step that sees records in the input's order runs before the sort.
+        let seen = std::cell::RefCell::new(Vec::new());
+        let mut pipeline = options.pipeline();
+        pipeline.add(Stage::Transform, |record: gff::Record| {
+            seen.borrow_mut().push(usize::from(record.start()));
+            Ok(Some(record))
+        });
+
+        // The window skips the first sorted sq0 feature and keeps the second.
+        let kept = starts(kept_records(UNSORTED, &options, &mut pipeline).unwrap());
+        assert_eq!(kept, vec![(String::from("sq0"), 8, 10)]);
+        assert_eq!(*seen.borrow(), vec![5, 8, 1, 1, 8]);
+        assert_eq!(pipeline.dropped(), 2);
+    }
+
+    #[test]
+    fn test_keep_directives() {
+        let input = b"##gff-version 3
+##sequence-region sq0 1 20
+# a comment
+sq0\tNOODLES\tgene\t1\t20\t.\t+\t.\tID=a
+##FASTA
+>sq0
+ACGT
+" as &[u8];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("directives");
+        let options = GffOptions {
+            keep_directives: Some(path.clone()),
+            ..Default::default()
+        };
+        assert_eq!(starts(scored_records(input, &options).unwrap()).len(), 1);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "##gff-version 3\n##sequence-region sq0 1 20\n# a comment\n"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let input = b"sq0\tNOODLES\texon\t1\t10\t.\t+\t.\tID=a;Parent=g0
+sq0\tNOODLES\texon\t11\t15\t.\t+\t.\tID=b;Parent=g0
+sq0\tNOODLES\texon\t5\t8\t.\t-\t.\tID=c
+sq0\tNOODLES\tgene\t1\t30\t.\t+\t.\tID=g0
+sq0\tNOODLES\texon\t18\t20\t.\t+\t.\tID=d
+sq1\tNOODLES\texon\t1\t5\t.\t+\t.\tID=e
+" as &[u8];
+
+        let mut output = Vec::new();
+        merge(input, &mut output, &MergeOptions::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "sq0\tNOODLES\texon\t1\t15\t.\t+\t.
+sq0\tNOODLES\tgene\t1\t30\t.\t+\t.
+sq0\tNOODLES\texon\t5\t8\t.\t-\t.
+sq0\tNOODLES\texon\t18\t20\t.\t+\t.
+sq1\tNOODLES\texon\t1\t5\t.\t+\t.
+"
+        );
+
+        let options = MergeOptions {
+            distance: 2,
+            attributes: MergeAttributes::Concat,
+            format: MergeFormat::Bed,
+        };
+        let mut output = Vec::new();
+        merge(input, &mut output, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "sq0\t0\t20\texon\t0\t+
+sq0\t0\t30\tgene\t0\t+
+sq0\t4\t8\texon\t0\t-
+sq1\t0\t5\texon\t0\t+
+"
+        );
+
+        let options = MergeOptions {
+            attributes: MergeAttributes::Concat,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        merge(input, &mut output, &options).unwrap();
+        let first = String::from_utf8(output).unwrap();
+        assert_eq!(
+            first.lines().next(),
+            Some("sq0\tNOODLES\texon\t1\t15\t.\t+\t.\tID=a,b;Parent=g0")
+        );
+    }
+
+    #[test]
+    fn test_keep() {
+        let input = b"sq0\tensembl\tgene\t1\t10\t.\t+\t.\tID=a
+sq0\thavana\tgene\t5\t20\t.\t+\t.\tID=b
+" as &[u8];
+
+        let options = GffOptions {
+            source: Some(String::from("havana")),
+            ..Default::default()
+        };
+        let kept: Vec<_> = records(input, &options)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .filter(|r| options.keep(r))
+            .collect();
+
+        assert_eq!(kept.len(), 1);
+        assert_eq!(usize::from(kept[0].start()), 5);
+
+        let options = GffOptions {
+            regions: vec!["sq0:11-15".parse().unwrap(), "sq1".parse().unwrap()],
+            ..Default::default()
+        };
+        let kept: Vec<_> = records(input, &options)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .filter(|r| options.keep(r))
+            .collect();
+
+        assert_eq!(kept.len(), 1);
+        assert_eq!(usize::from(kept[0].start()), 5);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = summary(UNSORTED).unwrap();
+
+        assert_eq!(summary.features, 5);
+        assert_eq!(summary.types.get("gene"), Some(&5));
+        assert_eq!(summary.seqnames.get("sq0"), Some(&3));
+        assert_eq!(summary.seqnames.get("sq1"), Some(&2));
+
+        let mut output = Vec::new();
+        summary.write(ReportFormat::Json, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"features\":5,\"types\":{\"gene\":5},\"sources\":{\"NOODLES\":5},\"seqnames\":{\"sq0\":3,\"sq1\":2}}\n"
+        );
+
+        let mut output = Vec::new();
+        summary.write(ReportFormat::Table, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "features\t5\ntype\tgene\t5\nsource\tNOODLES\t5\nseqname\tsq0\t3\nseqname\tsq1\t2\n"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A list of record ids the converters keep, or with `exclude` drop, matched exactly. It also
+/// flags each listed id once it's seen, to report the ones that were missing, so the options that
+/// hold it share it through an `Arc` rather than cloning it.
+#[derive(Debug, Default)]
+pub struct IdList {
+    ids: HashMap<String, AtomicBool>,
+    exclude: bool,
+    found: AtomicUsize,
+}
+
+impl IdList {
+    /// Creates a list of `ids` to keep, or to drop if `exclude` is set.
+    pub fn new<I: IntoIterator<Item = String>>(ids: I, exclude: bool) -> Self {
+        Self {
+            ids: ids
+                .into_iter()
+                .map(|id| (id, AtomicBool::new(false)))
+                .collect(),
+            exclude,
+            found: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reads a list of ids from `input`, one per line. Whitespace around each id is trimmed, and
+    /// blank lines are skipped.
+    pub fn from_reader<R: BufRead>(input: R, exclude: bool) -> io::Result<Self> {
+        let mut ids = Vec::new();
+        for line in input.lines() {
+            let line = line?;
+            let id = line.trim();
+            if !id.is_empty() {
+                ids.push(id.to_string());
+            }
+        }
+        Ok(Self::new(ids, exclude))
+    }
+
+    /// Reads a list of ids from the file at `path`, like [`IdList::from_reader`].
+    pub fn read<P: AsRef<Path>>(path: P, exclude: bool) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?), exclude)
+    }
+
+    /// Returns whether to keep the record with `id`.
+    pub fn keep(&self, id: &str) -> bool {
+        let seen = self.ids.get(id);
+        if let Some(seen) = seen {
+            if !seen.swap(true, Ordering::Relaxed) {
+                self.found.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        seen.is_some() != self.exclude
+    }
+
+    /// Returns the number of listed ids.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns whether no ids are listed.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the number of listed ids seen so far.
+    pub fn found(&self) -> usize {
+        self.found.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of listed ids not seen so far.
+    pub fn missing(&self) -> usize {
+        self.len() - self.found()
+    }
+
+    /// Reports the number of listed ids found and missing to stderr.
+    pub(crate) fn report(&self) {
+        eprintln!(
+            "Found {} of {} listed ids, {} missing.",
+            self.found(),
+            self.len(),
+            self.missing()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id_list() {
+        let ids = IdList::from_reader(b"a\n b \n\nc\na\n" as &[u8], false).unwrap();
+        assert_eq!(ids.len(), 3);
+
+        let kept: Vec<_> = ["a", "x", "b", "a"]
+            .iter()
+            .copied()
+            .filter(|id| ids.keep(id))
+            .collect();
+        assert_eq!(kept, ["a", "b", "a"]);
+        assert_eq!((ids.found(), ids.missing()), (2, 1));
+
+        let ids = IdList::new(vec!["a".to_string()], true);
+        assert!(!ids.keep("a"));
+        assert!(ids.keep("b"));
+        assert_eq!((ids.found(), ids.missing()), (1, 0));
+    }
+
+    #[test]
+    fn test_id_list_is_shareable() {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<IdList>();
+        shareable::<crate::fastq_ops::FastqOptions>();
+    }
+}
@@ -0,0 +1,419 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::errors::BrrrrError;
+
+/// The scheme of S3 inputs, e.g. `s3://bucket/reads.fa`.
+const S3_SCHEME: &str = "s3://";
+
+/// The schemes of web inputs, e.g. `https://example.org/reads.fa.gz`.
+const HTTP_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+/// The first two bytes of every gzip stream, including bgzipped ones.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How much of an input `maybe_gunzip` reads ahead when its first read is too short to sniff.
+const PEEK_BYTES: usize = 8 * 1024;
+
+/// Returns whether `input` names a remote object, e.g. `s3://bucket/reads.fa` or
+/// `https://example.org/reads.fa`, rather than a local file.
+pub fn is_remote<P: AsRef<Path>>(input: P) -> bool {
+    input.as_ref().to_str().is_some_and(|s| {
+        s.starts_with(S3_SCHEME) || HTTP_SCHEMES.iter().any(|scheme| s.starts_with(scheme))
+    })
+}
+
+/// Opens `input` for reading: a local file, an `s3://bucket/key` object when brrrr is built with
+/// the `s3` feature, or an `http://` or `https://` URL when it's built with the `http` feature.
+/// Remote inputs are streamed, not downloaded first.
+pub fn open<P: AsRef<Path>>(input: P) -> Result<Box<dyn Read>, BrrrrError> {
+    if !is_remote(&input) {
+        return Ok(Box::new(File::open(input)?));
+    }
+
+    let url = input.as_ref().to_string_lossy();
+    if url.starts_with(S3_SCHEME) {
+        #[cfg(feature = "s3")]
+        {
+            return Ok(Box::new(remote::ObjectReader::s3(&url)?));
+        }
+
+        #[cfg(not(feature = "s3"))]
+        {
+            return Err(missing_feature(&url, "s3"));
+        }
+    }
+
+    #[cfg(feature = "http")]
+    {
+        Ok(Box::new(remote::ObjectReader::http(&url)?))
+    }
+
+    #[cfg(not(feature = "http"))]
+    {
+        Err(missing_feature(&url, "http"))
+    }
+}
+
+/// Returns the error for a remote input that brrrr wasn't built to read.
+#[cfg(not(all(feature = "s3", feature = "http")))]
+fn missing_feature(url: &str, feature: &str) -> BrrrrError {
+    BrrrrError::Io(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "can't read {}, brrrr was built without the {} feature",
+            url, feature
+        ),
+    ))
+}
+
+/// Opens a text input at `input` for reading, decompressing it if it's a gzipped remote input,
+/// since a URL's extension can't be relied on, e.g. `https://example.org/reads.fa.gz`.
+pub fn open_text<P: AsRef<Path>>(input: P) -> Result<Box<dyn BufRead>, BrrrrError> {
+    if is_remote(&input) {
+        Ok(maybe_gunzip(open(input)?)?)
+    } else {
+        Ok(Box::new(BufReader::new(File::open(input)?)))
+    }
+}
+
+/// Reads `reader` through a gzip decoder if it starts with the gzip magic bytes, or as it is if
+/// not, for inputs with no file extension to go by, e.g. stdin.
+///
+/// The start of the input is peeked at, not consumed, and the first `fill_buf` of the returned
+/// reader holds all of what was peeked, so [`check_format`] can sniff it afterwards.
+pub fn maybe_gunzip<'a, R: Read + 'a>(reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let buffered = reader.fill_buf()?;
+    let gzipped = buffered.starts_with(&GZIP_MAGIC);
+
+    // A pipe may hand over a single byte, too few to tell anything from. Then up to a buffer's
+    // worth is read, and handed back first.
+    if buffered.len() == 1 {
+        let mut head = Vec::new();
+        (&mut reader)
+            .take(PEEK_BYTES as u64)
+            .read_to_end(&mut head)?;
+        let gzipped = head.starts_with(&GZIP_MAGIC);
+        let capacity = head.len().max(PEEK_BYTES);
+        let reader = BufReader::with_capacity(capacity, Cursor::new(head).chain(reader));
+        return Ok(gunzip_if(gzipped, reader));
+    }
+
+    Ok(gunzip_if(gzipped, reader))
+}
+
+/// Returns `reader` through a gzip decoder if `gzipped`, or as it is if not.
+fn gunzip_if<'a, R: BufRead + 'a>(gzipped: bool, reader: R) -> Box<dyn BufRead + 'a> {
+    if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        Box::new(reader)
+    }
+}
+
+/// Opens stdin for reading, decompressing it if it's gzipped, e.g. `cat reads.fq.gz | brrrr
+/// fq2jsonl -`.
+pub fn stdin() -> io::Result<Box<dyn BufRead>> {
+    maybe_gunzip(io::stdin().lock())
+}
+
+/// The text formats brrrr converts, as told apart by `sniff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Fasta,
+    Fastq,
+    Gff,
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InputFormat::Fasta => "FASTA",
+            InputFormat::Fastq => "FASTQ",
+            InputFormat::Gff => "GFF",
+        })
+    }
+}
+
+/// Returns the format of a text input from the start of it, `bytes`, by its first line that isn't
+/// blank or a `#` comment: `>` starts FASTA, `@` starts FASTQ, and nine tab-separated fields are
+/// GFF. Returns None if that line is none of them, or isn't in `bytes`.
+pub fn sniff(bytes: &[u8]) -> Option<InputFormat> {
+    let line = bytes
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .find(|line| !line.iter().all(u8::is_ascii_whitespace) && !line.starts_with(b"#"))?;
+
+    match line[0] {
+        b'>' => Some(InputFormat::Fasta),
+        b'@' => Some(InputFormat::Fastq),
+        _ if line.split(|&b| b == b'\t').count() == 9 => Some(InputFormat::Gff),
+        _ => None,
+    }
+}
+
+/// Checks that `input` looks like `expected`, so e.g. a FASTA input given to a FASTQ converter
+/// fails with a clear error, instead of a parse error on its first record.
+///
+/// Only what's already buffered is looked at, and nothing is consumed. An input that doesn't
+/// look like any of the formats passes, and is left to the parser.
+pub fn check_format<R: BufRead>(input: &mut R, expected: InputFormat) -> Result<(), BrrrrError> {
+    match sniff(input.fill_buf()?) {
+        Some(detected) if detected != expected => {
+            Err(BrrrrError::FormatMismatch { expected, detected })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(any(feature = "s3", feature = "http"))]
+mod remote {
+    use std::io::{self, Read};
+
+    use bytes::{Buf, Bytes};
+    use futures::stream::{BoxStream, StreamExt};
+    use object_store::path::Path as ObjectPath;
+    use object_store::ObjectStore;
+    use tokio::runtime::{Builder, Runtime};
+
+    /// Streams a remote object through a blocking Read, so the synchronous parsers can consume
+    /// it. Each read waits on the next chunk of the download only once the previous one is used
+    /// up.
+    pub(super) struct ObjectReader {
+        runtime: Runtime,
+        stream: BoxStream<'static, object_store::Result<Bytes>>,
+        chunk: Bytes,
+    }
+
+    impl ObjectReader {
+        /// Starts downloading `location` from `store`.
+        fn open(store: impl ObjectStore, location: ObjectPath) -> io::Result<Self> {
+            let runtime = Builder::new_current_thread().enable_all().build()?;
+            let stream = runtime
+                .block_on(store.get(&location))
+                .map_err(io::Error::other)?
+                .into_stream();
+
+            Ok(Self {
+                runtime,
+                stream,
+                chunk: Bytes::new(),
+            })
+        }
+
+        /// Starts downloading `url`, an `s3://bucket/key`. Credentials and the region come from
+        /// the environment, e.g. `AWS_ACCESS_KEY_ID` and `AWS_REGION`.
+        #[cfg(feature = "s3")]
+        pub(super) fn s3(url: &str) -> io::Result<Self> {
+            use object_store::aws::AmazonS3Builder;
+
+            let (bucket, key) = url
+                .strip_prefix(super::S3_SCHEME)
+                .and_then(|location| location.split_once('/'))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("expected s3://bucket/key, got {}", url),
+                    )
+                })?;
+
+            let store = AmazonS3Builder::from_env()
+                .with_bucket_name(bucket)
+                .build()
+                .map_err(io::Error::other)?;
+
+            Self::open(store, ObjectPath::from(key))
+        }
+
+        /// Starts downloading `url`, e.g. `https://example.org/reads.fa.gz`.
+        #[cfg(feature = "http")]
+        pub(super) fn http(url: &str) -> io::Result<Self> {
+            use object_store::http::HttpBuilder;
+            use object_store::ClientOptions;
+
+            // The store is rooted at the whole URL and the object's location is left empty, so
+            // the request goes to the URL as given. Splitting off its path would have it
+            // percent-encoded again, and any query string encoded into the path.
+            let store = HttpBuilder::new()
+                .with_url(url)
+                .with_client_options(ClientOptions::new().with_allow_http(true))
+                .build()
+                .map_err(io::Error::other)?;
+
+            Self::open(store, ObjectPath::default())
+        }
+    }
+
+    impl Read for ObjectReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.chunk.is_empty() {
+                match self.runtime.block_on(self.stream.next()) {
+                    Some(chunk) => self.chunk = chunk.map_err(io::Error::other)?,
+                    None => return Ok(0),
+                }
+            }
+
+            let n = buf.len().min(self.chunk.len());
+            buf[..n].copy_from_slice(&self.chunk[..n]);
+            self.chunk.advance(n);
+
+            Ok(n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_remote() {
+        assert!(is_remote("s3://bucket/reads.fa"));
+        assert!(is_remote("https://example.org/reads.fa.gz"));
+        assert!(is_remote("http://example.org/reads.fa"));
+        assert!(!is_remote("reads.fa"));
+        assert!(!is_remote("https.fa"));
+        assert!(!is_remote("/data/s3://reads.fa"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_requests_the_url_as_given() {
+        use std::io::Write;
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(&stream);
+            let mut request_line = String::new();
+            request.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            let body = ">a\nACGT\n";
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            request_line
+        });
+
+        let url = format!(
+            "http://127.0.0.1:{}/data/my%20reads.fa?token=a%2Fb&part=1",
+            port
+        );
+        let mut fasta = String::new();
+        remote::ObjectReader::http(&url)
+            .unwrap()
+            .read_to_string(&mut fasta)
+            .unwrap();
+
+        assert_eq!(fasta, ">a\nACGT\n");
+        assert_eq!(
+            server.join().unwrap(),
+            "GET /data/my%20reads.fa?token=a%2Fb&part=1 HTTP/1.1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\n>r1 desc\nACGT\n"), Some(InputFormat::Fasta));
+        assert_eq!(
+            sniff(b"@r1\r\nACGT\r\n+\r\nIIII\r\n"),
+            Some(InputFormat::Fastq)
+        );
+        assert_eq!(
+            sniff(b"##gff-version 3\n# c\nchr1\ts\tgene\t1\t9\t.\t+\t.\tID=g\n"),
+            Some(InputFormat::Gff)
+        );
+        assert_eq!(sniff(b"chr1\t0\t10\n"), None);
+        assert_eq!(sniff(b"# only a comment\n"), None);
+        assert_eq!(sniff(b""), None);
+
+        let mut input = &b">r1\nACGT\n"[..];
+        assert!(check_format(&mut input, InputFormat::Fasta).is_ok());
+        assert!(matches!(
+            check_format(&mut input, InputFormat::Fastq),
+            Err(BrrrrError::FormatMismatch {
+                expected: InputFormat::Fastq,
+                detected: InputFormat::Fasta
+            })
+        ));
+        // Nothing was consumed.
+        assert_eq!(input, b">r1\nACGT\n");
+    }
+
+    /// Hands over one byte per read, as a slow pipe might.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_maybe_gunzip() {
+        use std::io::Write;
+
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let text = b"@r1\nACGT\n+\nIIII\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        for input in [&text[..], &gzipped[..]] {
+            let mut read = Vec::new();
+            maybe_gunzip(Trickle(input))
+                .unwrap()
+                .read_to_end(&mut read)
+                .unwrap();
+            assert_eq!(read, text);
+        }
+
+        let mut read = Vec::new();
+        maybe_gunzip(&b"@"[..])
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, b"@");
+        assert!(maybe_gunzip(&b""[..])
+            .unwrap()
+            .fill_buf()
+            .unwrap()
+            .is_empty());
+
+        // What's peeked at is all there to sniff, however the input hands it over.
+        let gff = b"##gff-version 3\nchr1\ts\tgene\t1\t9\t.\t+\t.\tID=g\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(gff).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        for input in [&gff[..], &gzipped[..]] {
+            let mut reader = maybe_gunzip(input).unwrap();
+            assert_eq!(sniff(reader.fill_buf().unwrap()), Some(InputFormat::Gff));
+            let mut reader = maybe_gunzip(Trickle(input)).unwrap();
+            assert_eq!(sniff(reader.fill_buf().unwrap()), Some(InputFormat::Gff));
+        }
+    }
+}
@@ -0,0 +1,354 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use coitrees::{COITree, GenericInterval, IntervalTree};
+use noodles::gff;
+
+use crate::gff_ops::{self, GffOptions};
+use crate::types::{parse_bed_lines, BedRecord};
+
+/// The formats intervals can be read from.
+#[derive(Debug, Copy, Clone)]
+pub enum IntervalFormat {
+    Gff,
+    Bed,
+}
+
+/// An interval on a sequence, with 0-based, half-open coordinates.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub seqname: String,
+    pub start: i64,
+    pub end: i64,
+    pub name: Option<String>,
+    pub strand: Option<String>,
+}
+
+impl Interval {
+    /// Returns whether the interval covers no bases, e.g. a BED line whose start and end are
+    /// equal.
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+impl From<gff::Record> for Interval {
+    /// Converts a GFF record, naming it by its `ID` or `Name` attribute, or else its type.
+    fn from(src: gff::Record) -> Interval {
+        let attribute = |key: &str| {
+            src.attributes()
+                .iter()
+                .find(|e| e.key() == key)
+                .map(|e| e.value().to_string())
+        };
+        let name = attribute("ID")
+            .or_else(|| attribute("Name"))
+            .unwrap_or_else(|| src.ty().to_string());
+
+        let strand = match src.strand().as_ref() {
+            "." => None,
+            s => Some(s.to_string()),
+        };
+
+        Interval {
+            seqname: src.reference_sequence_name().to_string(),
+            start: usize::from(src.start()) as i64 - 1,
+            end: usize::from(src.end()) as i64,
+            name: Some(name),
+            strand,
+        }
+    }
+}
+
+impl From<BedRecord> for Interval {
+    fn from(src: BedRecord) -> Interval {
+        Interval {
+            seqname: src.chrom,
+            start: src.start,
+            end: src.end,
+            name: src.name,
+            strand: src.strand,
+        }
+    }
+}
+
+/// A region of a sequence, e.g. from a `chr1:1000-2000` string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub seqname: String,
+    /// The 0-based start of the region.
+    pub start: i64,
+    /// The exclusive end of the region, or `None` for the rest of the sequence.
+    pub end: Option<i64>,
+    /// Whether the region is on the reverse strand.
+    pub reverse: bool,
+}
+
+impl Region {
+    /// Returns whether the region overlaps the 0-based, half-open `start..end` on `seqname`,
+    /// regardless of strand.
+    pub fn overlaps(&self, seqname: &str, start: i64, end: i64) -> bool {
+        self.seqname == seqname && start < self.end.unwrap_or(i64::MAX) && end > self.start
+    }
+
+    /// Returns the region as a 1-based `seqname:start-end` string, with a `/rc` suffix for
+    /// regions on the reverse strand.
+    pub fn name(&self) -> String {
+        let mut name = match self.end {
+            Some(end) => format!("{}:{}-{}", self.seqname, self.start + 1, end),
+            None if self.start == 0 => self.seqname.clone(),
+            None => format!("{}:{}", self.seqname, self.start + 1),
+        };
+
+        if self.reverse {
+            name.push_str("/rc");
+        }
+
+        name
+    }
+}
+
+impl FromStr for Region {
+    type Err = io::Error;
+
+    /// Parses a 1-based, closed `seqname[:start[-end]][:strand]` region, e.g. `chr1:1000-2000` or
+    /// `chr1:1000-2000:-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid region: {:?}", s),
+            )
+        };
+
+        let (rest, reverse) = match s.rsplit_once(':') {
+            Some((rest, "-")) => (rest, true),
+            Some((rest, "+")) => (rest, false),
+            _ => (s, false),
+        };
+
+        let (seqname, range) = match rest.rsplit_once(':') {
+            Some((seqname, range)) if !range.is_empty() => (seqname, Some(range)),
+            _ => (rest, None),
+        };
+
+        if seqname.is_empty() {
+            return Err(invalid());
+        }
+
+        let (start, end) = match range {
+            None => (0, None),
+            Some(range) => {
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (start, Some(end)),
+                    None => (range, None),
+                };
+                let parse = |v: &str| v.replace(',', "").parse::<i64>().map_err(|_| invalid());
+
+                let start = parse(start)?;
+                let end = end.map(parse).transpose()?;
+
+                if start < 1 || end.is_some_and(|e| e < start) {
+                    return Err(invalid());
+                }
+
+                (start - 1, end)
+            }
+        };
+
+        Ok(Region {
+            seqname: seqname.to_string(),
+            start,
+            end,
+            reverse,
+        })
+    }
+}
+
+impl From<Interval> for Region {
+    /// Converts an interval, which is on the reverse strand if its strand is `-`.
+    fn from(src: Interval) -> Region {
+        Region {
+            reverse: src.strand.as_deref() == Some("-"),
+            seqname: src.seqname,
+            start: src.start,
+            end: Some(src.end),
+        }
+    }
+}
+
+/// A boxed stream of intervals.
+pub type Intervals<'a> = Box<dyn Iterator<Item = io::Result<Interval>> + 'a>;
+
+/// Returns the intervals in a GFF or BED input.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `format` the format of the input.
+pub fn records<'a, R: BufRead + 'a>(input: R, format: IntervalFormat) -> io::Result<Intervals<'a>> {
+    match format {
+        IntervalFormat::Gff => {
+            let records = gff_ops::records(input, &GffOptions::default())?;
+            Ok(Box::new(records.map(|r| r.map(Interval::from))))
+        }
+        IntervalFormat::Bed => Ok(Box::new(parse_bed_lines(input, |line| {
+            line.parse::<BedRecord>().map(Interval::from)
+        }))),
+    }
+}
+
+/// Converts a 0-based coordinate to the `i32` coitrees uses.
+fn to_i32(position: i64) -> io::Result<i32> {
+    i32::try_from(position).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("position {} is too large to index", position),
+        )
+    })
+}
+
+/// IntervalIndex answers overlap queries over a set of intervals using one interval tree per
+/// seqname.
+pub struct IntervalIndex {
+    intervals: Vec<Interval>,
+    trees: HashMap<String, COITree<usize, u32>>,
+}
+
+impl IntervalIndex {
+    /// Creates a new IntervalIndex over `intervals`. Empty intervals are kept, but never
+    /// overlap anything.
+    pub fn new(intervals: Vec<Interval>) -> io::Result<Self> {
+        let mut by_seqname: HashMap<&str, Vec<coitrees::Interval<usize>>> = HashMap::new();
+
+        for (i, interval) in intervals.iter().enumerate() {
+            if interval.is_empty() {
+                continue;
+            }
+
+            // coitrees intervals are closed, so the last base is end - 1.
+            let first = to_i32(interval.start)?;
+            let last = to_i32(interval.end - 1)?;
+
+            by_seqname
+                .entry(&interval.seqname)
+                .or_default()
+                .push(coitrees::Interval::new(first, last, i));
+        }
+
+        let trees = by_seqname
+            .into_iter()
+            .map(|(seqname, nodes)| (seqname.to_string(), COITree::new(&nodes)))
+            .collect();
+
+        Ok(IntervalIndex { intervals, trees })
+    }
+
+    /// Returns the intervals overlapping `interval`, in the order they were indexed. An empty
+    /// interval overlaps nothing.
+    pub fn overlaps(&self, interval: &Interval) -> io::Result<Vec<&Interval>> {
+        let tree = match self.trees.get(&interval.seqname) {
+            Some(t) if !interval.is_empty() => t,
+            _ => return Ok(Vec::new()),
+        };
+
+        let first = to_i32(interval.start)?;
+        let last = to_i32(interval.end - 1)?;
+
+        let mut hits = Vec::new();
+        tree.query(first, last, |node| hits.push(*node.metadata()));
+        hits.sort_unstable();
+
+        Ok(hits.into_iter().map(|i| &self.intervals[i]).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlaps() {
+        let bed =
+            b"track name=test\nsq0\t0\t10\ta\nsq0\t10\t20\tb\t0\t+\nsq1\t0\t5\tc\n" as &[u8];
+        let intervals = records(bed, IntervalFormat::Bed)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let index = IntervalIndex::new(intervals).unwrap();
+
+        // GFF 10-11 is 0-based [9, 11), which touches both a and b.
+        let gff = b"sq0\tNOODLES\tgene\t10\t11\t.\t+\t.\tID=g0\n" as &[u8];
+        let feature = records(gff, IntervalFormat::Gff)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.name.as_deref(), Some("g0"));
+
+        let names: Vec<_> = index
+            .overlaps(&feature)
+            .unwrap()
+            .iter()
+            .map(|i| i.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        // Half-open ends don't overlap the next interval's start.
+        let edge = Interval {
+            seqname: String::from("sq0"),
+            start: 5,
+            end: 10,
+            ..Default::default()
+        };
+        assert_eq!(index.overlaps(&edge).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_intervals() {
+        // sq0 10 10 is an insertion point between a's last base and b's first.
+        let bed = b"sq0\t0\t10\ta\nsq0\t10\t10\tempty\nsq0\t10\t20\tb\n" as &[u8];
+        let intervals = records(bed, IntervalFormat::Bed)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert!(intervals[1].is_empty());
+        let index = IntervalIndex::new(intervals.clone()).unwrap();
+
+        let names: Vec<_> = index
+            .overlaps(&intervals[0])
+            .unwrap()
+            .iter()
+            .map(|i| i.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a"]);
+        assert!(index.overlaps(&intervals[1]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let region: Region = "chr1:1,000-2000:-".parse().unwrap();
+        assert_eq!(
+            region,
+            Region {
+                seqname: String::from("chr1"),
+                start: 999,
+                end: Some(2000),
+                reverse: true,
+            }
+        );
+        assert_eq!(region.name(), "chr1:1000-2000/rc");
+
+        let region: Region = "chr1".parse().unwrap();
+        assert_eq!(region.end, None);
+        assert_eq!(region.name(), "chr1");
+
+        assert!("chr1:0-10".parse::<Region>().is_err());
+        assert!("chr1:20-10".parse::<Region>().is_err());
+    }
+}
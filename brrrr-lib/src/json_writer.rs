@@ -7,7 +7,10 @@ use std::io::{self, BufRead, ErrorKind, Write};
 use serde::ser::Serialize;
 
 use crate::errors::BrrrrError;
-use crate::types::FastaRecord;
+use crate::fasta_ops::{self, FastaOptions, FastaRecords};
+use crate::fastq_ops::{self, FastqOptions};
+use crate::gff_ops::{self, GffOptions};
+use crate::input::{self, InputFormat};
 use crate::types::FastqRecord;
 use crate::types::GffRecord;
 use crate::types::SamRecord;
@@ -17,8 +20,6 @@ use writer::RecordWriter;
 
 use noodles::bam;
 use noodles::fasta;
-use noodles::fastq;
-use noodles::gff;
 use noodles::sam;
 
 /// JsonRecordWriter holds a writer, and outputs FASTA records as newline delimited JSON.
@@ -49,12 +50,22 @@ impl<W: Write> writer::RecordWriter for JsonRecordWriter<W> {
 ///
 /// * `input` an input that implements the BufRead trait.
 /// * `output` an output that implements the Write trait.
-pub fn fq2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
-    let mut reader = fastq::Reader::new(input);
+/// * `options` the options applied to the FASTQ records before they're written.
+pub fn fq2jsonl<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    options: &FastqOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut input, InputFormat::Fastq)?;
+    }
+
     let record_writer = &mut JsonRecordWriter::new(output);
+    let mut pipeline = options.pipeline();
 
-    for read_record in reader.records() {
+    for read_record in pipeline.kept(fastq_ops::records(input, options)) {
         let record = read_record?;
+
         let write_op = record_writer.write_serde_record(FastqRecord::from(record));
 
         if let Err(e) = write_op {
@@ -64,6 +75,8 @@ pub fn fq2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), Br
             }
         }
     }
+
+    fastq_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
     Ok(())
 }
 
@@ -73,13 +86,24 @@ pub fn fq2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), Br
 ///
 /// * `input` an input that implements the Read trait.
 /// * `output` an output that implements the Write trait.
-pub fn fa2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
+/// * `options` the options applied to the FASTA records before they're written. Of the extra
+///   columns, only the header (`with_header`) is written.
+pub fn fa2jsonl<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    options: &FastaOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut input, InputFormat::Fasta)?;
+    }
+
     let mut reader = fasta::Reader::new(input);
     let record_writer = &mut JsonRecordWriter::new(output);
+    let mut pipeline = options.pipeline();
 
-    for read_record in reader.records() {
+    for read_record in pipeline.kept(FastaRecords::new(&mut reader, options.with_header)) {
         let record = read_record?;
-        let write_op = record_writer.write_serde_record(FastaRecord::from(record));
+        let write_op = record_writer.write_serde_record(record);
 
         if let Err(e) = write_op {
             match e.kind() {
@@ -88,6 +112,7 @@ pub fn fa2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), Br
             }
         }
     }
+    fasta_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
     Ok(())
 }
 
@@ -97,12 +122,22 @@ pub fn fa2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), Br
 ///
 /// * `input` an input that implements the Read trait.
 /// * `output` an output that implements the Write trait.
-pub fn gff2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), BrrrrError> {
-    let mut reader = gff::Reader::new(input);
+/// * `options` the options applied to the GFF records before they're written.
+pub fn gff2jsonl<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    options: &GffOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut input, InputFormat::Gff)?;
+    }
+
     let record_writer = &mut JsonRecordWriter::new(output);
+    let mut pipeline = options.pipeline();
+
+    for read_record in gff_ops::kept_records(input, options, &mut pipeline)? {
+        let (record, _) = read_record?;
 
-    for read_record in reader.records() {
-        let record = read_record?;
         let write_op = record_writer.write_serde_record(GffRecord::from(record));
 
         if let Err(e) = write_op {
@@ -112,6 +147,8 @@ pub fn gff2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), B
             }
         }
     }
+
+    gff_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
     Ok(())
 }
 
@@ -147,20 +184,146 @@ pub fn bam2jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<(), B
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::sync::Arc;
 
     use super::*;
+    use crate::id_list::IdList;
+    use crate::types::FastaRecord;
 
     #[test]
     fn test_fa2jsonl() {
         let input = b">A\nATCG\n" as &[u8];
 
         let mut output = Vec::new();
-        fa2jsonl(input, &mut output).unwrap();
+        fa2jsonl(input, &mut output, &FastaOptions::default()).unwrap();
 
         let output_str = String::from_utf8(output).unwrap();
         let expected_output =
             "{\"id\":\"A\",\"description\":null,\"sequence\":\"ATCG\"}\n".to_string();
         assert_eq!(output_str, expected_output);
+
+        let input = b">gi|123|ref|NC_000.1|\tHomo sapiens  \nATCG\n" as &[u8];
+        let options = FastaOptions {
+            with_header: true,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        fa2jsonl(input, &mut output, &options).unwrap();
+
+        let record: FastaRecord = serde_json::from_slice(&output).unwrap();
+        assert_eq!(record.id, "gi|123|ref|NC_000.1|");
+        assert_eq!(record.description.as_deref(), Some("Homo sapiens"));
+        assert_eq!(
+            record.header.as_deref(),
+            Some("gi|123|ref|NC_000.1|\tHomo sapiens  ")
+        );
+    }
+
+    #[test]
+    fn test_fa2jsonl_combined_options() {
+        // a's run of A is too long in either case, b is skipped by the window, and c is kept.
+        let input = b">a\nAAaacg\n>b\nacgt\n>c x\nggAt\n" as &[u8];
+        let options = FastaOptions {
+            fold_case: true,
+            reverse: true,
+            max_homopolymer_filter: Some(3),
+            skip: 1,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        fa2jsonl(input, &mut output, &options).unwrap();
+
+        let records: Vec<FastaRecord> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "c");
+        assert_eq!(records[0].sequence, "TAGG");
+    }
+
+    #[test]
+    fn test_fq2jsonl_combined_options() {
+        // Reversing doesn't change a read's mean quality, so only r2, at 35, passes.
+        let input = b"@r1\naCGt\n+\n!!II\n@r2\nacgg\n+\nII5I\n@r3\nttAA\n+\n5555\n" as &[u8];
+        let options = FastqOptions {
+            fold_case: true,
+            reverse: true,
+            min_mean_qual: Some(30.0),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        fq2jsonl(input, &mut output, &options).unwrap();
+
+        let records: Vec<FastqRecord> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "r2");
+        assert_eq!(records[0].sequence, "GGCA");
+        assert_eq!(records[0].quality, "I5II");
+    }
+
+    #[test]
+    fn test_id_list() {
+        let ids = || vec!["b".to_string(), "x".to_string()];
+
+        let fasta = b">a\nAC\n>b one\nGG\n>c\nTT\n" as &[u8];
+        for (exclude, expected) in [(false, vec!["b"]), (true, vec!["a", "c"])] {
+            let options = FastaOptions {
+                id_list: Some(Arc::new(IdList::new(ids(), exclude))),
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            fa2jsonl(fasta, &mut output, &options).unwrap();
+
+            let written: Vec<String> = serde_json::Deserializer::from_slice(&output)
+                .into_iter::<FastaRecord>()
+                .map(|record| record.unwrap().id)
+                .collect();
+            assert_eq!(written, expected);
+
+            let ids = options.id_list.unwrap();
+            assert_eq!((ids.found(), ids.missing()), (1, 1));
+        }
+
+        let fastq = b"@a\nAC\n+\nII\n@b/1 one\nGG\n+\nII\n" as &[u8];
+        let options = FastqOptions {
+            id_list: Some(Arc::new(IdList::new(vec!["b/1".to_string()], false))),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        fq2jsonl(fastq, &mut output, &options).unwrap();
+        assert_eq!(output.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        // Listed reads another filter drops are still found, rather than reported missing.
+        let listed = Arc::new(IdList::new(vec!["a".to_string(), "b".to_string()], false));
+        let options = FastqOptions {
+            min_mean_qual: Some(20.0),
+            id_list: Some(listed.clone()),
+            ..Default::default()
+        };
+        let fastq = b"@a\nAC\n+\n!!\n@b\nGG\n+\nII\n" as &[u8];
+        let mut output = Vec::new();
+        fq2jsonl(fastq, &mut output, &options).unwrap();
+        assert_eq!(output.iter().filter(|&&b| b == b'\n').count(), 1);
+        assert_eq!((listed.found(), listed.missing()), (2, 0));
+
+        let gff = b"chr1\t.\tgene\t1\t9\t.\t+\t.\tID=b\n\
+            chr1\t.\texon\t1\t9\t.\t+\t.\tParent=b\n" as &[u8];
+        let options = GffOptions {
+            id_list: Some(Arc::new(IdList::new(ids(), false))),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        gff2jsonl(gff, &mut output, &options).unwrap();
+        assert_eq!(output.iter().filter(|&&b| b == b'\n').count(), 1);
     }
 
     #[test]
@@ -0,0 +1,329 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, ErrorKind};
+
+use crate::errors::BrrrrError;
+use crate::stats::for_each_sequence;
+
+/// The longest k-mer that fits in a u64 at two bits per base.
+pub const MAX_K: usize = 32;
+
+/// Returns the two-bit code of `base`, or None if it isn't A, C, G, or T. Case is ignored.
+fn encode(base: u8) -> Option<u64> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// A k-mer of a sequence, two bits per base.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Kmer {
+    /// The 0-based offset of the k-mer in the sequence.
+    pub position: usize,
+    /// The k-mer as read on the forward strand.
+    pub forward: u64,
+    /// The reverse complement of the k-mer.
+    pub reverse: u64,
+}
+
+impl Kmer {
+    /// Returns the lesser of the forward and reverse complement encodings, so a k-mer and its
+    /// reverse complement count as one.
+    pub fn canonical(&self) -> u64 {
+        self.forward.min(self.reverse)
+    }
+}
+
+/// Iterates over the k-mers of a sequence, skipping any that contain a base other than A, C, G,
+/// or T.
+pub struct Kmers<'a> {
+    sequence: &'a [u8],
+    k: usize,
+    mask: u64,
+    next: usize,
+    forward: u64,
+    reverse: u64,
+    valid: usize,
+}
+
+impl<'a> Iterator for Kmers<'a> {
+    type Item = Kmer;
+
+    fn next(&mut self) -> Option<Kmer> {
+        while self.next < self.sequence.len() {
+            let i = self.next;
+            self.next += 1;
+
+            match encode(self.sequence[i]) {
+                Some(code) => {
+                    self.forward = ((self.forward << 2) | code) & self.mask;
+                    self.reverse = (self.reverse >> 2) | ((3 - code) << (2 * (self.k - 1)));
+                    self.valid += 1;
+                }
+                None => {
+                    self.valid = 0;
+                    continue;
+                }
+            }
+
+            if self.valid >= self.k {
+                return Some(Kmer {
+                    position: i + 1 - self.k,
+                    forward: self.forward,
+                    reverse: self.reverse,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a> Kmers<'a> {
+    /// Returns the k-mers of `sequence`, for a `k` already checked by [`check_k`].
+    fn new(sequence: &'a [u8], k: usize) -> Self {
+        Kmers {
+            sequence,
+            k,
+            mask: if k == MAX_K {
+                u64::MAX
+            } else {
+                (1 << (2 * k)) - 1
+            },
+            next: 0,
+            forward: 0,
+            reverse: 0,
+            valid: 0,
+        }
+    }
+}
+
+/// Returns an error unless `k` is between 1 and [`MAX_K`].
+fn check_k(k: usize) -> Result<(), BrrrrError> {
+    if (1..=MAX_K).contains(&k) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("k must be between 1 and {}, not {}", MAX_K, k),
+        )
+        .into())
+    }
+}
+
+/// Returns the k-mers of `sequence`. `k` must be between 1 and [`MAX_K`].
+pub fn kmers(sequence: &[u8], k: usize) -> Result<Kmers<'_>, BrrrrError> {
+    check_k(k)?;
+    Ok(Kmers::new(sequence, k))
+}
+
+/// Decodes a k-mer back to its bases.
+pub fn decode(kmer: u64, k: usize) -> Vec<u8> {
+    (0..k)
+        .rev()
+        .map(|i| b"ACGT"[((kmer >> (2 * i)) & 3) as usize])
+        .collect()
+}
+
+/// Counts the canonical k-mers across a FASTA or FASTQ input.
+///
+/// The table holds every distinct k-mer, so memory grows with the size and error rate of the
+/// input, at roughly 16 bytes plus hash table overhead per distinct k-mer. A few hundred million
+/// distinct k-mers, as from a lightly-sequenced human genome, needs several gigabytes.
+pub fn count<R: BufRead>(input: R, k: usize) -> Result<HashMap<u64, u64>, BrrrrError> {
+    check_k(k)?;
+    let mut counts: HashMap<u64, u64> = HashMap::new();
+
+    for_each_sequence(input, |sequence| {
+        for kmer in Kmers::new(sequence, k) {
+            *counts.entry(kmer.canonical()).or_insert(0) += 1;
+        }
+    })?;
+
+    Ok(counts)
+}
+
+/// Returns the k-mer spectrum of a FASTA or FASTQ input: for each multiplicity, how many distinct
+/// canonical k-mers occur that many times, sorted by multiplicity. This is the histogram that
+/// genome size and coverage estimators like GenomeScope take.
+///
+/// See [`count`] for the memory the count table needs.
+pub fn spectrum<R: BufRead>(input: R, k: usize) -> Result<Vec<(u64, u64)>, BrrrrError> {
+    let mut histogram: HashMap<u64, u64> = HashMap::new();
+    for multiplicity in count(input, k)?.into_values() {
+        *histogram.entry(multiplicity).or_insert(0) += 1;
+    }
+
+    let mut spectrum: Vec<(u64, u64)> = histogram.into_iter().collect();
+    spectrum.sort_unstable();
+
+    Ok(spectrum)
+}
+
+/// Scrambles a k-mer encoding with the splitmix64 finalizer, so minimizers aren't biased toward
+/// k-mers that sort first, e.g. poly-A.
+fn hash(kmer: u64) -> u64 {
+    let mut x = kmer;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// A minimizer of a sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Minimizer {
+    /// The 0-based offset of the k-mer in the sequence.
+    pub position: usize,
+    /// The selected k-mer, on the strand it was selected from.
+    pub kmer: u64,
+    /// Whether the k-mer was selected from the forward strand.
+    pub forward: bool,
+}
+
+/// Returns the (w, k) minimizers of `sequence`: for every window of `w` consecutive k-mers, the
+/// k-mer with the lowest hash, the leftmost on ties. A k-mer that is the minimizer of several
+/// windows in a row is returned once. Windows don't span bases other than A, C, G, or T.
+///
+/// If `canonical` is set, each k-mer is hashed as the lesser of it and its reverse complement,
+/// so a sequence and its reverse complement share minimizers, and the minimizer records which
+/// strand won. Otherwise only the forward strand is used.
+///
+/// `w` must be at least 1, and `k` between 1 and [`MAX_K`].
+pub fn minimizers(
+    sequence: &[u8],
+    w: usize,
+    k: usize,
+    canonical: bool,
+) -> Result<Vec<Minimizer>, BrrrrError> {
+    if w == 0 {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "w must be at least 1").into());
+    }
+    check_k(k)?;
+
+    let mut found: Vec<Minimizer> = Vec::new();
+    // Candidates for the current window, as (hash, minimizer), with increasing hashes.
+    let mut window: VecDeque<(u64, Minimizer)> = VecDeque::new();
+    let mut run_start = 0;
+    let mut last_position = None;
+
+    for kmer in Kmers::new(sequence, k) {
+        if last_position.is_none_or(|p| kmer.position != p + 1) {
+            window.clear();
+            run_start = kmer.position;
+        }
+        last_position = Some(kmer.position);
+
+        let forward = !canonical || kmer.forward <= kmer.reverse;
+        let value = if forward { kmer.forward } else { kmer.reverse };
+        let h = hash(value);
+        let candidate = Minimizer {
+            position: kmer.position,
+            kmer: value,
+            forward,
+        };
+
+        while window.back().is_some_and(|(back, _)| *back > h) {
+            window.pop_back();
+        }
+        window.push_back((h, candidate));
+
+        if kmer.position + 1 < run_start + w {
+            continue;
+        }
+        while window
+            .front()
+            .is_some_and(|(_, m)| m.position + w <= kmer.position)
+        {
+            window.pop_front();
+        }
+
+        let (_, minimizer) = window[0];
+        if found
+            .last()
+            .is_none_or(|m| m.position != minimizer.position)
+        {
+            found.push(minimizer);
+        }
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmers() {
+        let found: Vec<_> = kmers(b"ACGNtac", 2)
+            .unwrap()
+            .map(|k| (k.position, decode(k.forward, 2), decode(k.reverse, 2)))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, b"AC".to_vec(), b"GT".to_vec()),
+                (1, b"CG".to_vec(), b"CG".to_vec()),
+                (4, b"TA".to_vec(), b"TA".to_vec()),
+                (5, b"AC".to_vec(), b"GT".to_vec()),
+            ]
+        );
+
+        let long = b"ACGTACGTACGTACGTACGTACGTACGTACGTA";
+        let found: Vec<_> = kmers(long, MAX_K)
+            .unwrap()
+            .map(|k| decode(k.forward, MAX_K))
+            .collect();
+        assert_eq!(found, vec![long[..32].to_vec(), long[1..].to_vec()]);
+
+        assert!(kmers(long, 0).is_err());
+        assert!(kmers(long, MAX_K + 1).is_err());
+    }
+
+    #[test]
+    fn test_spectrum() {
+        // AAA and its reverse complement TTT are one canonical k-mer.
+        let fasta = b">a\nAAAA\n>b\nTTTC\n" as &[u8];
+        assert_eq!(spectrum(fasta, 3).unwrap(), vec![(1, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_minimizers() {
+        let sequence = b"ACGTTGCAAGGCTTAC";
+        let forward = minimizers(sequence, 3, 4, true).unwrap();
+
+        // Every window of three 4-mers holds at least one minimizer. It can hold more, since
+        // neighbouring windows may pick other k-mers in it.
+        for start in 0..=sequence.len() - 4 - 2 {
+            let count = forward
+                .iter()
+                .filter(|m| (start..start + 3).contains(&m.position))
+                .count();
+            assert!(count >= 1, "window at {} has no minimizer", start);
+        }
+
+        // Canonical minimizers are the same k-mers on the reverse complement.
+        let reverse =
+            minimizers(&crate::sequence::reverse_complement(sequence), 3, 4, true).unwrap();
+        let mut expected: Vec<_> = forward
+            .iter()
+            .map(|m| (sequence.len() - 4 - m.position, m.kmer))
+            .collect();
+        let mut found: Vec<_> = reverse.iter().map(|m| (m.position, m.kmer)).collect();
+        expected.sort_unstable();
+        found.sort_unstable();
+        assert_eq!(found, expected);
+
+        assert!(minimizers(b"ACGNACG", 1, 4, true).unwrap().is_empty());
+        assert!(minimizers(b"ACG", 3, 4, true).unwrap().is_empty());
+        assert_eq!(minimizers(b"ACGT", 3, 2, false).unwrap().len(), 1);
+        assert!(minimizers(b"ACGT", 0, 2, false).is_err());
+        assert!(minimizers(b"ACGT", 3, 0, false).is_err());
+    }
+}
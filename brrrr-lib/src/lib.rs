@@ -19,11 +19,12 @@
 //! ```rust
 //! use std::io::stdout;
 //!
+//! use brrrr_lib::fasta_ops::FastaOptions;
 //! use brrrr_lib::json_writer::fa2jsonl;
 //!
 //! fn main() {
 //!     let example_input = b">A\nATCG\n>B\nGCTA" as &[u8];
-//!     fa2jsonl(example_input, &mut stdout()).expect("Error... :(");
+//!     fa2jsonl(example_input, &mut stdout(), &FastaOptions::default()).expect("Error... :(");
 //! }
 //! ```
 //!
@@ -39,16 +40,86 @@ pub mod json_writer;
 pub mod csv_writer;
 
 /// parquet_writer holds a writer, and outputs FASTA and GFF records as parquet.
+#[cfg(feature = "parquet")]
 pub mod parquet_writer;
 
+/// record_batches builds Arrow record batches from records, for the parquet writers and for
+/// applications that use Arrow directly.
+#[cfg(feature = "parquet")]
+pub mod record_batches;
+
 /// parquet_reader is like parquet_writer, but for reading parquet in.
+#[cfg(feature = "parquet")]
 pub mod parquet_reader;
 
+/// convert picks the converters for an input and output from their file extensions.
+#[cfg(feature = "parquet")]
+pub mod convert;
+
 /// Interface for the generic writer object.
 pub mod writer;
 
 /// Types used within the library.
 pub mod types;
 
+/// gff_ops holds operations applied to GFF record streams, e.g. sorting and merging.
+pub mod gff_ops;
+
+/// intervals holds an interval type shared by GFF and BED inputs, and overlap queries over it.
+pub mod intervals;
+
+/// fasta_ops holds operations on FASTA inputs, e.g. extracting subsequences.
+pub mod fasta_ops;
+
+/// fastq_ops holds options applied to FASTQ inputs.
+pub mod fastq_ops;
+
+/// sequence holds helpers for working with raw sequences.
+pub mod sequence;
+
+/// translation holds codon tables, and ORF finding over nucleotide sequences.
+pub mod translation;
+
+/// tabular holds schema inference and column building for generic tabular inputs.
+#[cfg(feature = "parquet")]
+pub mod tabular;
+
+/// frame selects columns and filters rows of Parquet files, for the df subcommand.
+#[cfg(feature = "df")]
+pub mod frame;
+
+/// partition holds the keys that partition Parquet outputs into Hive-style directories.
+pub mod partition;
+
+/// id_list holds lists of record ids for the converters to keep or drop.
+pub mod id_list;
+
+/// pipeline holds the ordered stages every converter passes records through.
+pub mod pipeline;
+
+/// stats holds whole-file reports over FASTA and FASTQ inputs, e.g. base composition.
+pub mod stats;
+
+/// kmers holds two-bit k-mer encoding, and counting over FASTA and FASTQ inputs.
+pub mod kmers;
+
+/// chemistry holds oligo properties, e.g. melting temperature and molecular weight.
+#[cfg(feature = "chemistry")]
+pub mod chemistry;
+
+/// tabix builds tabix indexes of bgzipped, position-sorted text outputs, e.g. GFF.
+pub mod tabix;
+
+/// input opens converter inputs, either local files or, with the s3 and http features, S3 objects
+/// and URLs.
+pub mod input;
+
+/// output compresses converter outputs as a whole, e.g. with gzip or zstd.
+pub mod output;
+
+/// selftest round trips synthetic inputs through the converters and their reverse converters.
+#[cfg(feature = "parquet")]
+pub mod selftest;
+
 /// Custom brrrr errors.
 pub mod errors;
@@ -0,0 +1,209 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use noodles::bgzf;
+
+/// The level gzip and BGZF outputs are compressed at unless another is asked for.
+const DEFAULT_DEFLATE_LEVEL: u32 = 6;
+
+/// How a text output is compressed, as a whole. This is separate from the codec Parquet uses
+/// inside its files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    /// Blocked gzip, which any gzip reader can read, and which tabix can index.
+    Bgzf,
+}
+
+/// A writer that compresses what's written to it, as an `OutputCompression` says.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Bgzf(bgzf::Writer<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Wraps `writer` to compress with `compression`, at `level`, from 0, no compression, to 9,
+    /// the smallest and slowest, or at the codec's default if there's no level. zstd always
+    /// compresses, so it takes 0 as its fastest level, 1.
+    pub fn new(writer: W, compression: OutputCompression, level: Option<u32>) -> io::Result<Self> {
+        let deflate_level = level.unwrap_or(DEFAULT_DEFLATE_LEVEL);
+
+        Ok(match compression {
+            OutputCompression::None => CompressedWriter::Plain(writer),
+            OutputCompression::Gzip => CompressedWriter::Gzip(GzEncoder::new(
+                writer,
+                flate2::Compression::new(deflate_level),
+            )),
+            // zstd reads level 0 as its own default, currently 3, so that's only used without one.
+            OutputCompression::Zstd => CompressedWriter::Zstd(zstd::stream::write::Encoder::new(
+                writer,
+                level.map_or(0, |l| l.max(1) as i32),
+            )?),
+            OutputCompression::Bgzf => {
+                let level = u8::try_from(deflate_level)
+                    .ok()
+                    .and_then(|l| bgzf::writer::CompressionLevel::try_from(l).ok())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid BGZF compression level {}", deflate_level),
+                        )
+                    })?;
+                CompressedWriter::Bgzf(
+                    bgzf::Writer::builder(writer)
+                        .set_compression_level(level)
+                        .build(),
+                )
+            }
+        })
+    }
+
+    /// Writes the end of the compressed stream, e.g. gzip's trailer or BGZF's end-of-file block,
+    /// and returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::Plain(writer) => Ok(writer),
+            CompressedWriter::Gzip(writer) => writer.finish(),
+            CompressedWriter::Zstd(writer) => writer.finish(),
+            CompressedWriter::Bgzf(writer) => writer.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Gzip(writer) => writer.write(buf),
+            CompressedWriter::Zstd(writer) => writer.write(buf),
+            CompressedWriter::Bgzf(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Gzip(writer) => writer.flush(),
+            CompressedWriter::Zstd(writer) => writer.flush(),
+            CompressedWriter::Bgzf(writer) => writer.flush(),
+        }
+    }
+}
+
+/// A text writer that can leave off the newline at the end of its output, for downstream tools
+/// that don't expect one. Each newline is held back until more is written after it, so the last
+/// one is dropped when the writer is.
+pub struct TrailingNewline<W: Write> {
+    writer: W,
+    trailing_newline: bool,
+    pending: bool,
+}
+
+impl<W: Write> TrailingNewline<W> {
+    /// Wraps `writer`, which ends its output with a newline only if `trailing_newline` is set.
+    pub fn new(writer: W, trailing_newline: bool) -> Self {
+        Self {
+            writer,
+            trailing_newline,
+            pending: false,
+        }
+    }
+
+    /// Returns the inner writer, without any newline held back.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Write for TrailingNewline<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.trailing_newline || buf.is_empty() {
+            return self.writer.write(buf);
+        }
+
+        if self.pending {
+            self.writer.write_all(b"\n")?;
+            self.pending = false;
+        }
+        match buf.split_last() {
+            Some((b'\n', rest)) => {
+                self.writer.write_all(rest)?;
+                self.pending = true;
+            }
+            _ => self.writer.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn test_compressed_writer() {
+        let text = b"{\"id\":\"a\"}\n";
+
+        for compression in [
+            OutputCompression::None,
+            OutputCompression::Gzip,
+            OutputCompression::Zstd,
+            OutputCompression::Bgzf,
+        ] {
+            let mut writer = CompressedWriter::new(Vec::new(), compression, Some(9)).unwrap();
+            writer.write_all(text).unwrap();
+            let written = writer.finish().unwrap();
+
+            let mut read = Vec::new();
+            match compression {
+                OutputCompression::None => read = written,
+                OutputCompression::Gzip | OutputCompression::Bgzf => {
+                    MultiGzDecoder::new(written.as_slice())
+                        .read_to_end(&mut read)
+                        .unwrap();
+                }
+                OutputCompression::Zstd => read = zstd::stream::decode_all(&written[..]).unwrap(),
+            }
+            assert_eq!(read, text, "{:?}", compression);
+        }
+
+        assert!(CompressedWriter::new(Vec::new(), OutputCompression::Bgzf, Some(10)).is_err());
+
+        // zstd can't store a stream uncompressed, so level 0 is its fastest, not its default.
+        let zstd = |level| {
+            let compression = OutputCompression::Zstd;
+            let mut writer = CompressedWriter::new(Vec::new(), compression, level).unwrap();
+            writer.write_all(&text.repeat(1000)).unwrap();
+            writer.finish().unwrap()
+        };
+        assert_eq!(zstd(Some(0)), zstd(Some(1)));
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        for (trailing_newline, expected) in [(true, "a\n\nb\n"), (false, "a\n\nb")] {
+            let mut writer = TrailingNewline::new(Vec::new(), trailing_newline);
+            for chunk in ["a\n", "\n", "", "b", "\n"] {
+                writer.write_all(chunk.as_bytes()).unwrap();
+            }
+            assert_eq!(writer.into_inner(), expected.as_bytes());
+        }
+    }
+}
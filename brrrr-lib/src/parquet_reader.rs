@@ -1,6 +1,8 @@
 // (c) Copyright 2022 Trent Hauck
 // All Rights Reserved
 
+use arrow::datatypes::Schema;
+use noodles::bgzf;
 use noodles::core;
 use noodles::fasta;
 use noodles::fastq;
@@ -9,13 +11,18 @@ use noodles::gff::record::attributes::Entry;
 use noodles::gff::record::Attributes;
 use noodles::gff::record::Phase;
 use noodles::gff::record::Strand;
-use parquet::file::reader::SerializedFileReader;
-use parquet::record::RowAccessor;
-use std::io;
-use std::io::ErrorKind;
-use std::{fs::File, path::Path};
-
-use crate::errors::BrrrrError;
+use parquet::arrow::{parquet_to_arrow_schema, ArrowReader, ParquetFileArrowReader};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::{Field, Row, RowAccessor};
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::errors::{BrrrrError, ParseError};
+use crate::fastq_ops;
+use crate::record_batches::write_schema;
+use crate::tabix::TabixIndexer;
 
 /// pq2fa reads an input parquet file, and converts the `id`, `sequence`, and `description` columns
 /// into a FASTA file with the format: `>{id} {description}\n{sequence}`.
@@ -25,9 +32,20 @@ use crate::errors::BrrrrError;
 /// * `input` - The path to the input Parquet file.
 /// * `output` - The path to the output FASTA file.
 pub fn pq2fa<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
-    let output_file = File::create(output)?;
-    let handle = io::BufWriter::new(output_file);
-    let mut writer = fasta::Writer::new(handle);
+    let mut handle = io::BufWriter::new(File::create(output)?);
+    write_fasta(input, &mut handle)?;
+    Ok(handle.flush()?)
+}
+
+/// Writes the records of the Parquet file `input` as FASTA to `output`, as [`pq2fa`] does, e.g.
+/// to compress them on the way.
+///
+/// # Arguments
+///
+/// * `input` - The path to the input Parquet file.
+/// * `output` - Where the FASTA records are written.
+pub fn write_fasta<P: AsRef<Path>, W: Write>(input: P, output: &mut W) -> Result<(), BrrrrError> {
+    let mut writer = fasta::Writer::new(output);
 
     if let Ok(file) = File::open(&input) {
         let reader = SerializedFileReader::new(file)?;
@@ -60,12 +78,9 @@ pub fn pq2fa<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
                     writer.write_record(&record)?
                 }
                 (_, _, _) => {
-                    return Err(BrrrrError::IOError(io::Error::new(
-                        io::ErrorKind::Unsupported,
-                        format!(
-                            "Unexpected parsing for id: {}",
-                            id.unwrap_or(&String::from("unknown id")),
-                        ),
+                    return Err(BrrrrError::SchemaMismatch(format!(
+                        "expected id and sequence columns for id: {}",
+                        id.unwrap_or(&String::from("unknown id")),
                     )))
                 }
             };
@@ -82,9 +97,19 @@ pub fn pq2fa<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
 /// * `input` - The path to the input Parquet file.
 /// * `output` - The path to the output FASTQ file.
 pub fn pq2fq<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
-    let output_file = File::create(output)?;
-    let handle = io::BufWriter::new(output_file);
-    let mut writer = fastq::Writer::new(handle);
+    let mut handle = io::BufWriter::new(File::create(output)?);
+    write_fastq(input, &mut handle)?;
+    Ok(handle.flush()?)
+}
+
+/// Writes the records of the Parquet file `input` as FASTQ to `output`, as [`pq2fq`] does.
+///
+/// # Arguments
+///
+/// * `input` - The path to the input Parquet file.
+/// * `output` - Where the FASTQ records are written.
+pub fn write_fastq<P: AsRef<Path>, W: Write>(input: P, output: &mut W) -> Result<(), BrrrrError> {
+    let mut writer = fastq::Writer::new(output);
 
     if let Ok(file) = File::open(&input) {
         let reader = SerializedFileReader::new(file)?;
@@ -95,11 +120,18 @@ pub fn pq2fq<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
             let mut sequence = None;
             let mut quality = None;
 
-            for (e, (key, _)) in row.get_column_iter().enumerate() {
+            for (e, (key, value)) in row.get_column_iter().enumerate() {
                 match key.as_str() {
                     "id" => id = Some(row.get_string(e)?),
                     "sequence" => sequence = Some(row.get_string(e)?),
-                    "quality" => quality = Some(row.get_string(e)?),
+                    // A null quality is written as the `*` placeholder, but a quality that isn't
+                    // a string is an error.
+                    "quality" => {
+                        quality = Some(match value {
+                            Field::Null => None,
+                            _ => Some(row.get_string(e)?),
+                        })
+                    }
                     "description" => {
                         description = match row.get_string(e) {
                             Ok(v) => Some(v.to_string()),
@@ -112,7 +144,15 @@ pub fn pq2fq<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
 
             match (id, description, sequence, quality) {
                 (Some(i), _, Some(s), Some(q)) => {
-                    let record = fastq::Record::new(i.as_bytes(), s.as_bytes(), q.as_bytes());
+                    let q = match q {
+                        Some(q) => {
+                            fastq_ops::check_quality_length(i, s.as_bytes(), q.as_bytes())
+                                .map_err(BrrrrError::Parse)?;
+                            q.as_bytes()
+                        }
+                        None => b"*",
+                    };
+                    let record = fastq::Record::new(i.as_bytes(), s.as_bytes(), q);
                     writer.write_record(&record)?;
                 }
                 (_, _, _, _) => {
@@ -126,110 +166,271 @@ pub fn pq2fq<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
     Ok(())
 }
 
+/// The number of rows read from Parquet in each batch by the jsonl and csv writers.
+const BATCH_SIZE: usize = 8192;
+
+/// Writes the rows of the Parquet file `input` to `output` as jsonl, one object per row.
+///
+/// # Arguments
+///
+/// * `input` - The path to the input Parquet file.
+/// * `output` - Where the jsonl is written.
+pub fn write_jsonl<P: AsRef<Path>, W: Write>(input: P, output: &mut W) -> Result<(), BrrrrError> {
+    let file_reader = SerializedFileReader::new(File::open(input)?)?;
+    let mut reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+
+    let mut writer = arrow::json::LineDelimitedWriter::new(output);
+    for batch in reader.get_record_reader(BATCH_SIZE)? {
+        writer.write(batch?)?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes the rows of the Parquet file `input` to `output` as CSV, with a header row. A column
+/// CSV can't hold, e.g. the attribute map of a GFF file, is an error.
+///
+/// # Arguments
+///
+/// * `input` - The path to the input Parquet file.
+/// * `output` - Where the CSV is written.
+pub fn write_csv<P: AsRef<Path>, W: Write>(input: P, output: &mut W) -> Result<(), BrrrrError> {
+    let file_reader = SerializedFileReader::new(File::open(input)?)?;
+    let mut reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+
+    let mut writer = arrow::csv::Writer::new(output);
+    for batch in reader.get_record_reader(BATCH_SIZE)? {
+        writer.write(&batch?)?;
+    }
+
+    Ok(())
+}
+
+/// How `pq2gff` writes its output.
+#[derive(Debug, Default, Clone)]
+pub struct GffOutputOptions {
+    /// A file of directive and comment lines to write before the records, as `gff2pq` writes
+    /// with `GffOptions::keep_directives`.
+    pub directives: Option<PathBuf>,
+    /// Compress the output with BGZF, the blocked gzip that tabix and samtools read.
+    pub bgzip: bool,
+    /// Also write a tabix index of the output to `<output>.tbi`. This needs `bgzip`, and the
+    /// input sorted by seqname and start, e.g. with `gff2pq --sort`.
+    pub tabix: bool,
+}
+
 /// pq2gff reads an input parquet file and convers it to GFF.
 ///
 /// # Arguments
 ///
 /// * `input` - The path to the input Parquet file.
 /// * `output` - The path to the output GFF file.
+/// * `options` - The directives to prepend, and whether to bgzip and index the output.
+pub fn pq2gff<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    options: &GffOutputOptions,
+) -> Result<(), BrrrrError> {
+    if options.tabix && !options.bgzip {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "a tabix index needs a bgzipped output",
+        )
+        .into());
+    }
 
-pub fn pq2gff<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
-    let output_file = File::create(output)?;
-    let handle = io::BufWriter::new(output_file);
-    let mut writer = gff::Writer::new(handle);
-
+    let output_file = File::create(&output)?;
+    let mut rows = None;
     if let Ok(file) = File::open(&input) {
-        let reader = SerializedFileReader::new(file)?;
+        rows = Some(SerializedFileReader::new(file)?.into_iter());
+    }
+    let records = rows.into_iter().flatten().map(|row| gff_record(&row));
 
-        for row in reader.into_iter() {
-            let mut gff_record_builder = gff::Record::builder();
+    if !options.bgzip {
+        let mut handle = io::BufWriter::new(output_file);
+        if let Some(directives) = &options.directives {
+            io::copy(&mut File::open(directives)?, &mut handle)?;
+        }
+        let mut writer = gff::Writer::new(handle);
+        for record in records {
+            writer.write_record(&record?)?;
+        }
+        return Ok(());
+    }
 
-            for (e, (key, _)) in row.get_column_iter().enumerate() {
-                match key.as_str() {
-                    "seqname" => {
-                        gff_record_builder = gff_record_builder
-                            .set_reference_sequence_name(row.get_string(e)?.to_string())
-                    }
-                    "source" => {
-                        gff_record_builder =
-                            gff_record_builder.set_source(row.get_string(e)?.to_string())
-                    }
-                    "feature" => {
-                        gff_record_builder =
-                            gff_record_builder.set_type(row.get_string(e)?.to_string())
-                    }
-                    "start" => {
-                        let int_position = row.get_long(e)?;
-                        let position =
-                            core::Position::new(int_position as usize).ok_or_else(|| {
-                                io::Error::new(ErrorKind::Other, "Error parsing start.")
-                            })?;
-
-                        gff_record_builder = gff_record_builder.set_start(position);
-                    }
-                    "end" => {
-                        let int_position = row.get_long(e)?;
-                        let position =
-                            core::Position::new(int_position as usize).ok_or_else(|| {
-                                io::Error::new(ErrorKind::NotFound, "Error parsing end.")
-                            })?;
-
-                        gff_record_builder = gff_record_builder.set_end(position);
-                    }
-                    "score" => match row.get_long(e) {
-                        Ok(score) => {
-                            gff_record_builder = gff_record_builder.set_score(score as f32);
-                        }
-                        _ => continue,
-                    },
-                    "stand" => {
-                        let strand = row.get_string(e)?;
-                        let n_strand = strand.parse::<Strand>().expect("unable to parse strand");
-                        gff_record_builder = gff_record_builder.set_strand(n_strand);
-                    }
-                    "frame" => {
-                        let frame = row.get_string(e);
-                        match frame {
-                            Ok(f) => {
-                                let phase = f.parse::<Phase>().expect("unable to parse phase");
-                                gff_record_builder = gff_record_builder.set_phase(phase);
-                            }
-                            _ => continue,
-                        }
-                    }
-                    "attribute" => {
-                        let parquet_map = row.get_map(e)?;
-                        let entries: Vec<Entry> = parquet_map
-                            .entries()
-                            .iter()
-                            .map(|entry| {
-                                let (key, value) = &entry;
-
-                                // TODO(trent): why is does this need trim_matches?
-                                Entry::new(
-                                    key.to_string().trim_matches('"'),
-                                    value.to_string().trim_matches('"'),
-                                )
-                            })
-                            .collect();
-
-                        let attributes = Attributes::from(entries);
-                        gff_record_builder = gff_record_builder.set_attributes(attributes);
-                    }
-                    _ => continue,
+    let mut writer = bgzf::Writer::new(output_file);
+    if let Some(directives) = &options.directives {
+        io::copy(&mut File::open(directives)?, &mut writer)?;
+    }
+    let mut index = options.tabix.then(TabixIndexer::default);
+    for record in records {
+        let record = record?;
+        let chunk_start = writer.virtual_position();
+        writeln!(writer, "{}", record)?;
+        if let Some(index) = index.as_mut() {
+            index.add(
+                record.reference_sequence_name(),
+                usize::from(record.start()) as u64 - 1,
+                usize::from(record.end()) as u64,
+                chunk_start,
+                writer.virtual_position(),
+            )?;
+        }
+    }
+    writer.finish()?;
+
+    if let Some(index) = index {
+        let mut index_path = output.as_ref().as_os_str().to_owned();
+        index_path.push(".tbi");
+        index.write(File::create(index_path)?)?;
+    }
+
+    Ok(())
+}
+
+/// Builds a GFF record from a row of a GFF Parquet file.
+fn gff_record(row: &Row) -> Result<gff::Record, BrrrrError> {
+    let mut gff_record_builder = gff::Record::builder();
+
+    for (e, (key, value)) in row.get_column_iter().enumerate() {
+        match key.as_str() {
+            "seqname" => {
+                gff_record_builder =
+                    gff_record_builder.set_reference_sequence_name(row.get_string(e)?.to_string())
+            }
+            // A null source, strand, or frame is written as `.`.
+            "source" => {
+                if let Some(source) = optional_string(row, e, value)? {
+                    gff_record_builder = gff_record_builder.set_source(source.to_string())
                 }
             }
+            "feature" => {
+                gff_record_builder = gff_record_builder.set_type(row.get_string(e)?.to_string())
+            }
+            "start" => {
+                let int_position = row.get_long(e)?;
+                let position = core::Position::new(int_position as usize)
+                    .ok_or_else(|| io::Error::other("Error parsing start."))?;
+
+                gff_record_builder = gff_record_builder.set_start(position);
+            }
+            "end" => {
+                let int_position = row.get_long(e)?;
+                let position = core::Position::new(int_position as usize)
+                    .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "Error parsing end."))?;
 
-            writer.write_record(&gff_record_builder.build())?;
+                gff_record_builder = gff_record_builder.set_end(position);
+            }
+            // The score is a float, an int, or text, depending on gff2pq --score-type.
+            "score" => {
+                let score = match value {
+                    Field::Null => None,
+                    Field::Double(s) => Some(*s as f32),
+                    Field::Long(s) => Some(*s as f32),
+                    Field::Str(s) if s == "." => None,
+                    Field::Str(s) => Some(s.parse().map_err(|_| {
+                        BrrrrError::Parse(ParseError::new(format!("invalid score {:?}", s)))
+                    })?),
+                    _ => Some(row.get_double(e)? as f32),
+                };
+                if let Some(score) = score {
+                    gff_record_builder = gff_record_builder.set_score(score);
+                }
+            }
+            "strand" => {
+                if let Some(strand) = optional_string(row, e, value)? {
+                    let strand = strand.parse::<Strand>().map_err(|_| {
+                        BrrrrError::Parse(ParseError::new(format!("invalid strand {:?}", strand)))
+                    })?;
+                    gff_record_builder = gff_record_builder.set_strand(strand);
+                }
+            }
+            "frame" => {
+                if let Some(frame) = optional_string(row, e, value)? {
+                    let phase = frame.parse::<Phase>().map_err(|_| {
+                        BrrrrError::Parse(ParseError::new(format!("invalid frame {:?}", frame)))
+                    })?;
+                    gff_record_builder = gff_record_builder.set_phase(phase);
+                }
+            }
+            "attribute" => {
+                let parquet_map = row.get_map(e)?;
+                let entries: Vec<Entry> = parquet_map
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| Entry::new(attribute_text(key), attribute_text(value)))
+                    .collect();
+
+                let attributes = Attributes::from(entries);
+                gff_record_builder = gff_record_builder.set_attributes(attributes);
+            }
+            _ => continue,
         }
     }
 
-    Ok(())
+    Ok(gff_record_builder.build())
+}
+
+/// Returns the string in column `e` of `row`, or None if it's null. A column of another type is
+/// an error, rather than being taken for a missing value.
+fn optional_string<'a>(
+    row: &'a Row,
+    e: usize,
+    value: &Field,
+) -> Result<Option<&'a String>, BrrrrError> {
+    match value {
+        Field::Null => Ok(None),
+        _ => Ok(Some(row.get_string(e)?)),
+    }
+}
+
+/// Returns the text of an attribute key or value, with the items of a value written by
+/// `gff2pq --multivalue-attrs` joined by commas again.
+fn attribute_text(field: &Field) -> String {
+    match field {
+        Field::Str(text) => text.clone(),
+        Field::ListInternal(list) => list
+            .elements()
+            .iter()
+            .map(attribute_text)
+            .collect::<Vec<_>>()
+            .join(","),
+        field => field.to_string(),
+    }
+}
+
+/// Returns the Arrow schema of a Parquet file, as written, including any optional columns.
+///
+/// # Arguments
+///
+/// * `input` - The path to the Parquet file.
+pub fn read_schema<P: AsRef<Path>>(input: P) -> Result<Schema, BrrrrError> {
+    let reader = SerializedFileReader::new(File::open(input)?)?;
+    let metadata = reader.metadata().file_metadata();
+    Ok(parquet_to_arrow_schema(
+        metadata.schema_descr(),
+        metadata.key_value_metadata(),
+    )?)
+}
+
+/// Writes the Arrow schema of a Parquet file as JSON, with the name, type, and nullability of
+/// each field, so consumers can see which optional columns a file has without reading it.
+///
+/// # Arguments
+///
+/// * `input` - The path to the Parquet file.
+/// * `output` - The path to the output JSON file.
+pub fn write_schema_json<P: AsRef<Path>>(input: P, output: P) -> Result<(), BrrrrError> {
+    let schema = read_schema(input)?;
+    let mut writer = io::BufWriter::new(File::create(output)?);
+    write_schema(&schema, &mut writer)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{env, io::BufReader};
+    use std::env;
+    use std::io::{BufReader, Read};
 
     use itertools::Itertools;
     use noodles::fasta::{self, record::Definition, record::Sequence};
@@ -237,6 +438,9 @@ mod tests {
     use parquet::basic::Compression;
 
     use super::*;
+    use crate::fasta_ops::FastaOptions;
+    use crate::fastq_ops::FastqOptions;
+    use crate::gff_ops::GffOptions;
     use crate::parquet_writer::{fa2pq, fq2pq, gff2pq, BioFileCompression};
 
     #[test]
@@ -252,15 +456,21 @@ mod tests {
         let mut writer = gff::Writer::new(File::create(&initital_gff).expect("error"));
         writer.write_record(&gff_record).expect("error");
 
-        gff2pq(&initital_gff, &initial_parquet, Compression::UNCOMPRESSED).expect("gff2pq failed");
+        gff2pq(
+            &initital_gff,
+            &initial_parquet,
+            Compression::UNCOMPRESSED,
+            &GffOptions::default(),
+        )
+        .expect("gff2pq failed");
         assert!(&initial_parquet.exists());
-        pq2gff(&initial_parquet, &second_gff).expect("pq2gff failed");
+        pq2gff(&initial_parquet, &second_gff, &GffOutputOptions::default()).expect("pq2gff failed");
 
         let mut reader = gff::Reader::new(BufReader::new(File::open(&second_gff).expect("error")));
         let recs = reader.records().collect_vec();
         assert_eq!(recs.len(), 1);
 
-        let actual_record = recs.get(0);
+        let actual_record = recs.first();
         if let Some(ar) = actual_record {
             assert!(ar.is_ok(), "{:?}", ar);
 
@@ -273,15 +483,14 @@ mod tests {
 
                     let mut found_keys: Vec<&str> = found_gff_record
                         .attributes()
-                        .into_iter()
-                        .map(|e| e.key())
-                        .collect();
-                    let mut gff_keys: Vec<&str> = gff_record
-                        .attributes()
-                        .into_iter()
+                        .iter()
                         .map(|e| e.key())
                         .collect();
-                    assert_eq!(found_keys.sort(), gff_keys.sort());
+                    let mut gff_keys: Vec<&str> =
+                        gff_record.attributes().iter().map(|e| e.key()).collect();
+                    found_keys.sort();
+                    gff_keys.sort();
+                    assert_eq!(found_keys, gff_keys);
 
                     assert_eq!(found_gff_record.source(), gff_record.source());
                     assert_eq!(found_gff_record.ty(), gff_record.ty());
@@ -289,6 +498,7 @@ mod tests {
                     assert_eq!(found_gff_record.end(), gff_record.end());
                     assert_eq!(found_gff_record.score(), gff_record.score());
                     assert_eq!(found_gff_record.phase(), gff_record.phase());
+                    assert_eq!(found_gff_record.strand(), gff_record.strand());
                 }
                 _ => panic!("could not match gff"),
             }
@@ -307,7 +517,14 @@ mod tests {
         let mut writer = fastq::Writer::new(File::create(&initital_fasta).expect("error"));
         writer.write_record(&r).expect("error");
 
-        fq2pq(&initital_fasta, &initial_parquet, Compression::UNCOMPRESSED).expect("fa2pq failed");
+        fq2pq(
+            &initital_fasta,
+            &initial_parquet,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &FastqOptions::default(),
+        )
+        .expect("fq2pq failed");
         assert!(&initial_parquet.exists());
         pq2fq(&initial_parquet, &second_fasta).expect("fa2pq failed");
 
@@ -317,12 +534,107 @@ mod tests {
         let recs = reader.records().collect_vec();
         assert_eq!(recs.len(), 1);
 
-        let actual_record = recs.get(0);
+        let actual_record = recs.first();
         if let Some(ar) = actual_record {
             assert!(ar.is_ok());
         }
     }
 
+    #[test]
+    fn test_pq2fq_quality() {
+        use arrow::array::{ArrayRef, Int64Array, StringArray};
+        use arrow::record_batch::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+        use std::sync::Arc;
+
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_pq2fq_quality.parquet");
+        let output = temp_dir.join("test_pq2fq_quality.fq");
+        let write = |quality: ArrayRef| {
+            let batch = RecordBatch::try_from_iter(vec![
+                (
+                    "id",
+                    Arc::new(StringArray::from(vec!["r0", "r1"])) as ArrayRef,
+                ),
+                ("sequence", Arc::new(StringArray::from(vec!["ACGT", "GG"]))),
+                ("quality", quality),
+            ])
+            .unwrap();
+            let mut writer =
+                ArrowWriter::try_new(File::create(&input).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        };
+
+        // A null quality is the placeholder.
+        write(Arc::new(StringArray::from(vec![Some("IIII"), None])));
+        pq2fq(&input, &output).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "@r0\nACGT\n+\nIIII\n@r1\nGG\n+\n*\n"
+        );
+
+        // A quality of the wrong type isn't.
+        write(Arc::new(Int64Array::from(vec![1, 2])));
+        assert!(pq2fq(&input, &output).is_err());
+    }
+
+    #[test]
+    fn test_pq2gff_column_types() {
+        use arrow::array::{ArrayRef, Int64Array, StringArray};
+        use arrow::record_batch::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+        use std::sync::Arc;
+
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_pq2gff_column_types.parquet");
+        let output = temp_dir.join("test_pq2gff_column_types.gff");
+        let write = |source: ArrayRef, strand: ArrayRef| {
+            let batch = RecordBatch::try_from_iter(vec![
+                (
+                    "seqname",
+                    Arc::new(StringArray::from(vec!["chr1", "chr1"])) as ArrayRef,
+                ),
+                ("source", source),
+                ("feature", Arc::new(StringArray::from(vec!["gene", "gene"]))),
+                ("start", Arc::new(Int64Array::from(vec![1, 5]))),
+                ("end", Arc::new(Int64Array::from(vec![3, 9]))),
+                ("strand", strand),
+            ])
+            .unwrap();
+            let mut writer =
+                ArrowWriter::try_new(File::create(&input).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        };
+        let strings = |values: Vec<Option<&str>>| Arc::new(StringArray::from(values)) as ArrayRef;
+
+        // Null sources and strands are written as `.`.
+        write(
+            strings(vec![Some("src"), None]),
+            strings(vec![None, Some("-")]),
+        );
+        pq2gff(&input, &output, &GffOutputOptions::default()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "chr1\tsrc\tgene\t1\t3\t.\t.\t.\t.\nchr1\t.\tgene\t5\t9\t.\t-\t.\t.\n"
+        );
+
+        // Columns of the wrong type aren't taken for nulls, and a bad strand is a parse error.
+        write(
+            Arc::new(Int64Array::from(vec![1, 2])),
+            strings(vec![None, None]),
+        );
+        assert!(pq2gff(&input, &output, &GffOutputOptions::default()).is_err());
+        write(
+            strings(vec![None, None]),
+            strings(vec![Some("+"), Some("x")]),
+        );
+        let error = pq2gff(&input, &output, &GffOutputOptions::default()).unwrap_err();
+        assert!(matches!(error, BrrrrError::Parse(_)));
+        assert_eq!(error.to_string(), "parse error, invalid strand \"x\"");
+    }
+
     #[test]
     fn parquet_fasta_base_test() {
         let temp_dir = env::temp_dir();
@@ -343,6 +655,7 @@ mod tests {
             &initial_parquet,
             Compression::UNCOMPRESSED,
             BioFileCompression::UNCOMPRESSED,
+            &FastaOptions::default(),
         )
         .expect("fa2pq failed");
 
@@ -355,7 +668,7 @@ mod tests {
         let recs = reader.records().collect_vec();
         assert_eq!(recs.len(), 1);
 
-        let actual_record = recs.get(0);
+        let actual_record = recs.first();
         if let Some(ar) = actual_record {
             assert!(ar.is_ok());
 
@@ -365,4 +678,131 @@ mod tests {
             };
         }
     }
+
+    #[test]
+    fn test_write_schema_json() {
+        let temp_dir = env::temp_dir();
+        let fasta = temp_dir.join("test_write_schema_json.fasta");
+        let parquet = temp_dir.join("test_write_schema_json.parquet");
+        let json = temp_dir.join("test_write_schema_json.json");
+        std::fs::write(&fasta, ">a\nACGT\n").unwrap();
+
+        let options = FastaOptions {
+            with_gc_skew: true,
+            ..Default::default()
+        };
+        fa2pq(
+            &fasta,
+            &parquet,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+        write_schema_json(&parquet, &json).unwrap();
+
+        let value: serde_json::Value = serde_json::from_reader(File::open(&json).unwrap()).unwrap();
+        let fields = value["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[3]["name"], "gc_skew");
+        assert_eq!(fields[3]["nullable"], true);
+        assert_eq!(fields[3]["type"]["name"], "floatingpoint");
+    }
+
+    #[test]
+    fn test_pq2gff_tabix() {
+        let temp_dir = env::temp_dir();
+        let gff = temp_dir.join("test_pq2gff_tabix.gff");
+        let parquet = temp_dir.join("test_pq2gff_tabix.parquet");
+        let output = temp_dir.join("test_pq2gff_tabix.gff.gz");
+        let lines = [
+            "chr1\tbrrrr\tgene\t5\t20\t.\t+\t.\tID=a",
+            "chr1\tbrrrr\tgene\t30\t40\t.\t+\t.\tID=b",
+            "chr2\tbrrrr\tgene\t1\t9\t.\t-\t.\tID=c",
+        ];
+        std::fs::write(&gff, lines.join("\n") + "\n").unwrap();
+
+        let options = GffOutputOptions {
+            bgzip: true,
+            tabix: true,
+            ..Default::default()
+        };
+        gff2pq(
+            &gff,
+            &parquet,
+            Compression::UNCOMPRESSED,
+            &GffOptions::default(),
+        )
+        .unwrap();
+        pq2gff(&parquet, &output, &options).unwrap();
+
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(&output).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, lines.join("\n") + "\n");
+
+        let mut index = Vec::new();
+        flate2::read::MultiGzDecoder::new(
+            File::open(temp_dir.join("test_pq2gff_tabix.gff.gz.tbi")).unwrap(),
+        )
+        .read_to_end(&mut index)
+        .unwrap();
+        assert_eq!(&index[..8], b"TBI\x01\x02\0\0\0");
+
+        // A tabix index needs a bgzipped output, and sorted records.
+        let plain = GffOutputOptions {
+            tabix: true,
+            ..Default::default()
+        };
+        assert!(pq2gff(&parquet, &output, &plain).is_err());
+
+        std::fs::write(&gff, format!("{}\n{}\n", lines[1], lines[0])).unwrap();
+        gff2pq(
+            &gff,
+            &parquet,
+            Compression::UNCOMPRESSED,
+            &GffOptions::default(),
+        )
+        .unwrap();
+        assert!(pq2gff(&parquet, &output, &options).is_err());
+    }
+
+    #[test]
+    fn test_pq2gff_multivalue() {
+        let temp_dir = env::temp_dir();
+        let gff = temp_dir.join("test_pq2gff_multivalue.gff");
+        let parquet = temp_dir.join("test_pq2gff_multivalue.parquet");
+        let output = temp_dir.join("test_pq2gff_multivalue.out.gff");
+
+        let line = "chr1\t.\texon\t1\t9\t.\t+\t.\tID=e0;Parent=t1,t2,t3";
+        std::fs::write(&gff, format!("{}\n", line)).unwrap();
+        let options = GffOptions {
+            sort_attributes: true,
+            multivalue_attributes: true,
+            ..Default::default()
+        };
+        gff2pq(&gff, &parquet, Compression::UNCOMPRESSED, &options).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&parquet).unwrap()).unwrap();
+        let row = reader.get_row_iter(None).unwrap().next().unwrap();
+        let entries = row.get_map(8).unwrap().entries();
+        let parents = match &entries[1] {
+            (Field::Str(key), Field::ListInternal(list)) if key == "Parent" => list.elements(),
+            entry => panic!("unexpected entry {:?}", entry),
+        };
+        assert_eq!(parents.len(), 3);
+        assert_eq!(parents[1], Field::Str("t2".to_string()));
+
+        // pq2gff joins the items again, and writes the same record as from a single string.
+        pq2gff(&parquet, &output, &GffOutputOptions::default()).unwrap();
+        let multivalue = std::fs::read_to_string(&output).unwrap();
+        let options = GffOptions {
+            sort_attributes: true,
+            ..Default::default()
+        };
+        gff2pq(&gff, &parquet, Compression::UNCOMPRESSED, &options).unwrap();
+        pq2gff(&parquet, &output, &GffOutputOptions::default()).unwrap();
+        assert_eq!(multivalue, std::fs::read_to_string(&output).unwrap());
+    }
 }
@@ -1,158 +1,628 @@
 // (c) Copyright 2020 Trent Hauck
 // All Rights Reserved
 
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::BufRead;
 use std::io::BufReader;
-use std::path::Path;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
 use std::result::Result;
+use std::sync::mpsc::{self, SyncSender};
 use std::sync::Arc;
+use std::thread;
 
-use flate2::bufread::GzDecoder;
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
 use itertools::Itertools;
-use noodles::fasta;
-use noodles::fastq;
-use noodles::gff;
+use zstd::stream::read::Decoder as ZstdDecoder;
 
 use arrow::array::*;
+use arrow::compute::take;
 use arrow::datatypes::*;
 use arrow::record_batch::RecordBatch;
 use parquet::arrow::arrow_writer::ArrowWriter;
 use parquet::basic::Compression;
 use parquet::file::properties::WriterProperties;
+use serde::Deserialize;
 
 use crate::errors::BrrrrError;
-use crate::types::{FastaRecord, FastqRecord, GffRecord};
+use crate::fasta_ops::{self, FastaOptions};
+use crate::fastq_ops::{self, FastqOptions};
+use crate::gff_ops::{self, GffOptions};
+use crate::input::{self, InputFormat};
+use crate::intervals::{self, IntervalFormat, IntervalIndex};
+use crate::kmers::{self, Minimizer};
+use crate::partition::{PartitionBy, PartitionKey};
+use crate::record_batches::{
+    build_fastq_batch, build_gff_batch, fastq_schema, gff_schema, write_schema, FastaBatches,
+};
+use crate::sequence::{gc_content, mean_quality, n_count};
+use crate::stats::{self, CollapsedSequence, GcHistogram};
+use crate::tabular::{
+    csv_error, jsonl_lines, parse_jsonl_line, ColumnBuilder, CsvOptions, InferOptions,
+    SchemaInference,
+};
+use crate::types::{
+    parse_bed_lines, BedGraphRecord, FastqRecord, GffRecord, PeakFormat, PeakRecord, WigRecords,
+};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum BioFileCompression {
     UNCOMPRESSED,
     GZIP,
+    BZIP2,
+    ZSTD,
+}
+
+impl BioFileCompression {
+    /// Returns the compression of a file starting with `bytes`, from the magic numbers of gzip,
+    /// bzip2, and zstd, or UNCOMPRESSED if it has none of them.
+    pub fn detect(bytes: &[u8]) -> BioFileCompression {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            BioFileCompression::GZIP
+        } else if bytes.starts_with(b"BZh") {
+            BioFileCompression::BZIP2
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            BioFileCompression::ZSTD
+        } else {
+            BioFileCompression::UNCOMPRESSED
+        }
+    }
+
+    /// Returns the name of the compression, as `--input-compression` takes it, e.g. `gzip`.
+    pub fn name(self) -> &'static str {
+        match self {
+            BioFileCompression::UNCOMPRESSED => "uncompressed",
+            BioFileCompression::GZIP => "gzip",
+            BioFileCompression::BZIP2 => "bzip2",
+            BioFileCompression::ZSTD => "zstd",
+        }
+    }
+
+    /// Wraps `reader` to decompress what's read from it.
+    pub(crate) fn decoder<'a>(self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            BioFileCompression::GZIP => Box::new(MultiGzDecoder::new(BufReader::new(reader))),
+            BioFileCompression::BZIP2 => Box::new(MultiBzDecoder::new(BufReader::new(reader))),
+            BioFileCompression::ZSTD => Box::new(ZstdDecoder::new(reader)?),
+            BioFileCompression::UNCOMPRESSED => reader,
+        })
+    }
+}
+
+/// What a conversion would read and write, as `dry_run` finds without writing anything.
+#[derive(Debug)]
+pub struct DryRun {
+    /// The compression of the input, from its first bytes.
+    pub detected_compression: BioFileCompression,
+    /// The format of the decompressed input, from its first line, or None if it doesn't look
+    /// like FASTA, FASTQ, or GFF.
+    pub detected_format: Option<InputFormat>,
+    /// The schema of the Parquet output.
+    pub schema: Schema,
+}
+
+impl DryRun {
+    /// Writes the detected compression and format of the input, and the output schema as JSON.
+    pub fn write<W: Write>(&self, output: &mut W) -> Result<(), BrrrrError> {
+        writeln!(
+            output,
+            "input compression: {}",
+            self.detected_compression.name()
+        )?;
+        match self.detected_format {
+            Some(format) => writeln!(output, "input format: {}", format)?,
+            None => writeln!(output, "input format: unknown")?,
+        }
+        writeln!(output, "output schema:")?;
+        write_schema(&self.schema, output)
+    }
+}
+
+/// Checks that a conversion of `input` to `output` with `schema` could start, without writing
+/// anything, for a dry run.
+///
+/// The input is opened, decompressed as `bio_file_compression` says, and its first line read.
+/// The directory the output would be written to must exist and not be read-only.
+///
+/// # Arguments
+/// * `input` The path to the input, or an `s3://bucket/key` with the s3 feature.
+/// * `output` The path the output would be written to.
+/// * `bio_file_compression` The compression the input would be read with.
+/// * `schema` The schema of the output, e.g. from `record_batches::fastq_schema`.
+pub fn dry_run<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    bio_file_compression: BioFileCompression,
+    schema: Schema,
+) -> Result<DryRun, BrrrrError> {
+    let mut magic = Vec::new();
+    input::open(&input)?.take(4).read_to_end(&mut magic)?;
+
+    let mut reader = BufReader::new(bio_file_compression.decoder(input::open(&input)?)?);
+    let detected_format = input::sniff(reader.fill_buf()?);
+
+    let output = output.as_ref();
+    let dir = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match fs::metadata(dir) {
+        Ok(metadata) if metadata.is_dir() && !metadata.permissions().readonly() => {}
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "can't write {:?}, {:?} isn't a writable directory",
+                    output, dir
+                ),
+            )
+            .into())
+        }
+    }
+
+    Ok(DryRun {
+        detected_compression: BioFileCompression::detect(&magic),
+        detected_format,
+        schema,
+    })
 }
 
 /// Converts a GFF file to Parquet.
 ///
+/// Features are written in input order, unless `options` sorts them.
+///
 /// # Arguments
 /// * `input` The path to the input GFF file.
 /// * `output` The path to the output parquet file.
 /// * `parquet_compression` The parquet compression to use.
+/// * `options` The options applied to the GFF records before they're written.
 pub fn gff2pq<P: AsRef<Path>>(
     input: P,
     output: P,
     parquet_compression: Compression,
+    options: &GffOptions,
+) -> Result<(), BrrrrError> {
+    let reader = BufReader::new(fs::File::open(input)?);
+    write_gff_output(reader, output.as_ref(), parquet_compression, options)
+}
+
+/// Writes the GFF records of `reader` to `output`, a single Parquet file, or a directory of them
+/// if `options` splits the output.
+pub(crate) fn write_gff_output<R: BufRead>(
+    mut reader: R,
+    output: &Path,
+    parquet_compression: Compression,
+    options: &GffOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Gff)?;
+    }
+
+    let split = Split {
+        max_records: options.max_records,
+        resume: options.resume,
+        ..Default::default()
+    };
+    let output = Output::create(output, split, None)?;
+    write_gff_records(reader, output, parquet_compression, options)
+}
+
+/// Converts a GFF input to Parquet in memory, returning the bytes of the Parquet file, for
+/// library users that can't or don't want to write to a file.
+///
+/// # Arguments
+/// * `reader` The GFF input.
+/// * `parquet_compression` The parquet compression to use.
+/// * `options` The options applied to the GFF records before they're written.
+pub fn gff2pq_bytes<R: BufRead>(
+    mut reader: R,
+    parquet_compression: Compression,
+    options: &GffOptions,
+) -> Result<Vec<u8>, BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Gff)?;
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    write_gff_records(
+        reader,
+        Output::Single(&mut buffer),
+        parquet_compression,
+        options,
+    )?;
+    Ok(buffer.into_inner())
+}
+
+/// Writes the GFF records of `input` as Parquet to `output`.
+fn write_gff_records<R: BufRead, W: Write + Send>(
+    input: R,
+    output: Output<W>,
+    parquet_compression: Compression,
+    options: &GffOptions,
+) -> Result<(), BrrrrError> {
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let file_schema = Arc::new(gff_schema(options));
+    let mut pipeline = options.pipeline();
+    let resumed = output.resumed_records();
+
+    let records = gff_ops::kept_records(input, options, &mut pipeline)?
+        .map(|record| {
+            let (record, score) = record?;
+            Ok(GffRecord {
+                score_text: Some(score),
+                ..GffRecord::from(record)
+            })
+        })
+        .skip(resumed);
+
+    write_parquet(
+        records,
+        file_schema.clone(),
+        |records| build_gff_batch(records, &file_schema, options),
+        output,
+        props.build(),
+        Chunk::Records(PARQUET_CHUNK_SIZE),
+    )?;
+    gff_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
+
+    Ok(())
+}
+
+/// The number of records in each batch written by `write_parquet`.
+const PARQUET_CHUNK_SIZE: usize = 2usize.pow(20);
+
+/// When `write_parquet` ends a batch.
+enum Chunk<T> {
+    /// After this many records.
+    Records(usize),
+    /// Once the sizes of its records, by the function, add up to at least this many bytes.
+    Bytes(usize, fn(&T) -> usize),
+}
+
+impl<T> Chunk<T> {
+    /// Returns the chunk for `batch_bytes`, if it was given, or else one of `PARQUET_CHUNK_SIZE`
+    /// records.
+    fn new(batch_bytes: Option<usize>, size: fn(&T) -> usize) -> Self {
+        match batch_bytes {
+            Some(bytes) => Chunk::Bytes(bytes, size),
+            None => Chunk::Records(PARQUET_CHUNK_SIZE),
+        }
+    }
+
+    /// Returns how much `record` counts toward the limit of a batch.
+    fn size(&self, record: &T) -> usize {
+        match self {
+            Chunk::Records(_) => 1,
+            Chunk::Bytes(_, size) => size(record),
+        }
+    }
+
+    /// Returns the total size at which a batch ends.
+    fn limit(&self) -> usize {
+        match self {
+            Chunk::Records(limit) | Chunk::Bytes(limit, _) => (*limit).max(1),
+        }
+    }
+}
+
+/// Writes `records` as Parquet to `output`, building a batch with `build_batch` each time `chunk`
+/// is full.
+///
+/// The converters that build batches from records share this, so only their schema and batch
+/// builder differ. Any state that runs across batches, e.g. the FASTQ read number, lives in
+/// `build_batch`, so it carries on from one batch to the next. The batches are written on a
+/// separate thread, see [`write_on_thread`].
+fn write_parquet<T, I, F, W>(
+    records: I,
+    schema: SchemaRef,
+    mut build_batch: F,
+    output: Output<W>,
+    props: WriterProperties,
+    chunk: Chunk<T>,
+) -> Result<(), BrrrrError>
+where
+    I: Iterator<Item = Result<T, BrrrrError>>,
+    F: FnMut(&[T]) -> Result<RecordBatch, BrrrrError>,
+    W: Write + Send,
+{
+    let writer = BatchWriter::try_new(output, schema, props)?;
+
+    write_on_thread(writer, |batches| {
+        let mut batch = Vec::new();
+        let mut size = 0;
+
+        for record in records {
+            let record = record?;
+            size += chunk.size(&record);
+            batch.push(record);
+
+            if size >= chunk.limit() {
+                send_batch(batches, build_batch(&batch)?)?;
+                batch.clear();
+                size = 0;
+            }
+        }
+        if !batch.is_empty() {
+            send_batch(batches, build_batch(&batch)?)?;
+        }
+        Ok(())
+    })
+}
+
+/// The number of built batches that can wait for the writer thread. Parsing blocks once this
+/// many are queued, so memory stays bounded however much faster parsing is than writing.
+const WRITE_QUEUE_DEPTH: usize = 2;
+
+/// Writes the batches `produce` sends with [`send_batch`] to `writer` on a dedicated thread, so
+/// parsing and building batches overlaps with compressing and writing them.
+///
+/// The queue between them is bounded by [`WRITE_QUEUE_DEPTH`]. The output is only closed if
+/// `produce` succeeds; if either side fails, the writer's error is returned first, since a failed
+/// writer makes the producer fail too.
+fn write_on_thread<W, P>(mut writer: BatchWriter<W>, produce: P) -> Result<(), BrrrrError>
+where
+    W: Write + Send,
+    P: FnOnce(&SyncSender<RecordBatch>) -> Result<(), BrrrrError>,
+{
+    let (sender, receiver) = mpsc::sync_channel::<RecordBatch>(WRITE_QUEUE_DEPTH);
+
+    let (written, produced) = thread::scope(|scope| {
+        let handle = scope.spawn(move || {
+            for batch in receiver {
+                writer.write(&batch)?;
+            }
+            Ok::<_, BrrrrError>(writer)
+        });
+
+        let produced = produce(&sender);
+        // Hanging up ends the writer's loop.
+        drop(sender);
+
+        let written = handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        (written, produced)
+    });
+
+    let writer = written?;
+    produced?;
+    writer.close()
+}
+
+/// Queues `batch` for the writer thread of [`write_on_thread`], waiting if the queue is full.
+fn send_batch(batches: &SyncSender<RecordBatch>, batch: RecordBatch) -> Result<(), BrrrrError> {
+    batches
+        .send(batch)
+        .map_err(|_| BrrrrError::from(io::Error::other("the parquet writer stopped")))
+}
+
+/// Converts a bedGraph file to Parquet.
+///
+/// The input is the 4-column bedGraph format (`chrom`, `start`, `end`, `value`). `track` and
+/// `browser` lines are skipped. Coordinates are written as they appear, i.e. 0-based and
+/// half-open, so a feature covering the first base of a chromosome has `start` 0 and `end` 1.
+///
+/// # Arguments
+/// * `input` The path to the input bedGraph file.
+/// * `output` The path to the output parquet file.
+/// * `parquet_compression` The parquet compression to use.
+pub fn bedgraph2pq<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
 ) -> Result<(), BrrrrError> {
     let props = WriterProperties::builder()
         .set_compression(parquet_compression)
         .set_statistics_enabled(true);
 
     let file_schema = Schema::new(vec![
-        Field::new("seqname", DataType::Utf8, false),
-        Field::new("source", DataType::Utf8, true),
-        Field::new("feature", DataType::Utf8, false),
+        Field::new("chrom", DataType::Utf8, false),
         Field::new("start", DataType::Int64, false),
         Field::new("end", DataType::Int64, false),
-        Field::new("score", DataType::Int64, true),
-        Field::new("strand", DataType::Utf8, false),
-        Field::new("frame", DataType::Utf8, true),
-        Field::new(
-            "attribute",
-            DataType::Map(
-                Box::new(Field::new(
-                    "entries",
-                    DataType::Struct(vec![
-                        Field::new("keys", DataType::Utf8, false),
-                        Field::new("values", DataType::Utf8, true),
-                    ]),
-                    false,
-                )),
-                false,
-            ),
-            false,
-        ),
+        Field::new("value", DataType::Float64, false),
     ]);
 
     let input_file = fs::File::open(input)?;
-    let mut reader = gff::Reader::new(BufReader::new(input_file));
+    let records = parse_bed_lines(BufReader::new(input_file), str::parse::<BedGraphRecord>);
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+    let chunk_size = 2usize.pow(20);
+
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut chrom_builder = StringBuilder::new(2048);
+        let mut start_builder = Int64Builder::new(2048);
+        let mut end_builder = Int64Builder::new(2048);
+        let mut value_builder = Float64Builder::new(2048);
+
+        for chunk_i in chunk {
+            let record = chunk_i?;
+
+            chrom_builder.append_value(record.chrom)?;
+            start_builder.append_value(record.start)?;
+            end_builder.append_value(record.end)?;
+            value_builder.append_value(record.value)?;
+        }
+
+        let rb = RecordBatch::try_new(
+            Arc::new(file_schema.clone()),
+            vec![
+                Arc::new(chrom_builder.finish()),
+                Arc::new(start_builder.finish()),
+                Arc::new(end_builder.finish()),
+                Arc::new(value_builder.finish()),
+            ],
+        )?;
+
+        writer.write(&rb)?;
+    }
+
+    writer.close()?;
 
-    let records = reader.records();
+    Ok(())
+}
+
+/// Converts a narrowPeak or broadPeak file to Parquet.
+///
+/// Peaks are BED6 with extra typed columns: `signal_value`, `p_value`, and `q_value`, plus the
+/// `peak` offset for narrowPeak. The `-1` placeholders for unassigned values are written as null.
+/// Coordinates are kept as written: 0-based and half-open.
+///
+/// # Arguments
+/// * `input` The path to the input peak file.
+/// * `output` The path to the output parquet file.
+/// * `parquet_compression` The parquet compression to use.
+/// * `format` The peak format of the input.
+pub fn peak2pq<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
+    format: PeakFormat,
+) -> Result<(), BrrrrError> {
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let mut fields = vec![
+        Field::new("chrom", DataType::Utf8, false),
+        Field::new("start", DataType::Int64, false),
+        Field::new("end", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("score", DataType::Int64, false),
+        Field::new("strand", DataType::Utf8, true),
+        Field::new("signal_value", DataType::Float64, false),
+        Field::new("p_value", DataType::Float64, true),
+        Field::new("q_value", DataType::Float64, true),
+    ];
+    if let PeakFormat::Narrow = format {
+        fields.push(Field::new("peak", DataType::Int64, true));
+    }
+    let file_schema = Schema::new(fields);
+
+    let input_file = fs::File::open(input)?;
+    let records = parse_bed_lines(BufReader::new(input_file), |line| {
+        PeakRecord::parse(line, format)
+    });
 
     let file = fs::File::create(output)?;
     let mut writer =
         ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
     let chunk_size = 2usize.pow(20);
 
-    for chunk in records.into_iter().chunks(chunk_size).into_iter() {
-        let mut seqname_builder = StringBuilder::new(2048);
-        let mut source_builder = StringBuilder::new(2048);
-        let mut feature_builder = StringBuilder::new(2048);
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut chrom_builder = StringBuilder::new(2048);
         let mut start_builder = Int64Builder::new(2048);
         let mut end_builder = Int64Builder::new(2048);
+        let mut name_builder = StringBuilder::new(2048);
         let mut score_builder = Int64Builder::new(2048);
         let mut strand_builder = StringBuilder::new(2048);
-        let mut frame_builder = StringBuilder::new(2048);
-
-        let key_builder = StringBuilder::new(2048);
-        let value_builder = StringBuilder::new(2048);
-        let mut attribute_builder = MapBuilder::new(None, key_builder, value_builder);
+        let mut signal_value_builder = Float64Builder::new(2048);
+        let mut p_value_builder = Float64Builder::new(2048);
+        let mut q_value_builder = Float64Builder::new(2048);
+        let mut peak_builder = Int64Builder::new(2048);
 
         for chunk_i in chunk {
             let record = chunk_i?;
 
-            let gff_type = GffRecord::from(record);
+            chrom_builder.append_value(record.chrom)?;
+            start_builder.append_value(record.start)?;
+            end_builder.append_value(record.end)?;
+            name_builder.append_option(record.name)?;
+            score_builder.append_value(record.score)?;
+            strand_builder.append_option(record.strand)?;
+            signal_value_builder.append_value(record.signal_value)?;
+            p_value_builder.append_option(record.p_value)?;
+            q_value_builder.append_option(record.q_value)?;
+            peak_builder.append_option(record.peak)?;
+        }
 
-            seqname_builder.append_value(gff_type.seqname)?;
-            source_builder.append_value(gff_type.source)?;
-            feature_builder.append_value(gff_type.feature)?;
-            start_builder.append_value(gff_type.start as i64)?;
-            end_builder.append_value(gff_type.end as i64)?;
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(chrom_builder.finish()),
+            Arc::new(start_builder.finish()),
+            Arc::new(end_builder.finish()),
+            Arc::new(name_builder.finish()),
+            Arc::new(score_builder.finish()),
+            Arc::new(strand_builder.finish()),
+            Arc::new(signal_value_builder.finish()),
+            Arc::new(p_value_builder.finish()),
+            Arc::new(q_value_builder.finish()),
+        ];
+        if let PeakFormat::Narrow = format {
+            columns.push(Arc::new(peak_builder.finish()));
+        }
 
-            match gff_type.score {
-                Some(score) => score_builder.append_value(score as i64)?,
-                None => score_builder.append_null()?,
-            }
+        let rb = RecordBatch::try_new(Arc::new(file_schema.clone()), columns)?;
 
-            strand_builder.append_value(gff_type.strand)?;
+        writer.write(&rb)?;
+    }
 
-            match gff_type.frame {
-                Some(frame) => frame_builder.append_value(frame)?,
-                None => frame_builder.append_null()?,
-            }
+    writer.close()?;
 
-            let record_key_builder = attribute_builder.keys();
-            for k in gff_type.attribute.keys() {
-                record_key_builder.append_value(k)?;
-            }
+    Ok(())
+}
 
-            let record_value_builder = attribute_builder.values();
-            for v in gff_type.attribute.values() {
-                record_value_builder.append_value(v)?;
-            }
+/// Converts a WIG file to Parquet.
+///
+/// Both `variableStep` and `fixedStep` declarations are supported, and each data line becomes a
+/// `chrom`, `position`, `span`, `value` row. Positions are 1-based, as in WIG, and each value
+/// covers `span` bases starting at `position`.
+///
+/// # Arguments
+/// * `input` The path to the input WIG file.
+/// * `output` The path to the output parquet file.
+/// * `parquet_compression` The parquet compression to use.
+pub fn wig2pq<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
+) -> Result<(), BrrrrError> {
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
 
-            attribute_builder.append(true)?;
-        }
+    let file_schema = Schema::new(vec![
+        Field::new("chrom", DataType::Utf8, false),
+        Field::new("position", DataType::Int64, false),
+        Field::new("span", DataType::Int64, false),
+        Field::new("value", DataType::Float64, false),
+    ]);
+
+    let input_file = fs::File::open(input)?;
+    let records = WigRecords::new(BufReader::new(input_file));
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+    let chunk_size = 2usize.pow(20);
 
-        let seqname_array = seqname_builder.finish();
-        let source_array = source_builder.finish();
-        let feature_array = feature_builder.finish();
-        let start_array = start_builder.finish();
-        let end_array = end_builder.finish();
-        let score_array = score_builder.finish();
-        let strand_array = strand_builder.finish();
-        let frame_array = frame_builder.finish();
-        let attribute_array = attribute_builder.finish();
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut chrom_builder = StringBuilder::new(2048);
+        let mut position_builder = Int64Builder::new(2048);
+        let mut span_builder = Int64Builder::new(2048);
+        let mut value_builder = Float64Builder::new(2048);
+
+        for chunk_i in chunk {
+            let record = chunk_i?;
+
+            chrom_builder.append_value(record.chrom)?;
+            position_builder.append_value(record.position)?;
+            span_builder.append_value(record.span)?;
+            value_builder.append_value(record.value)?;
+        }
 
         let rb = RecordBatch::try_new(
             Arc::new(file_schema.clone()),
             vec![
-                Arc::new(seqname_array),
-                Arc::new(source_array),
-                Arc::new(feature_array),
-                Arc::new(start_array),
-                Arc::new(end_array),
-                Arc::new(score_array),
-                Arc::new(strand_array),
-                Arc::new(frame_array),
-                Arc::new(attribute_array),
+                Arc::new(chrom_builder.finish()),
+                Arc::new(position_builder.finish()),
+                Arc::new(span_builder.finish()),
+                Arc::new(value_builder.finish()),
             ],
         )?;
 
@@ -164,59 +634,96 @@ pub fn gff2pq<P: AsRef<Path>>(
     Ok(())
 }
 
-fn write_records_to_file<P: AsRef<Path>, R: BufRead>(
-    mut reader: fasta::Reader<R>,
+/// Joins the features of one interval file to the overlapping intervals of another, and writes
+/// the pairs to Parquet, like `bedtools intersect -wa -wb`.
+///
+/// The `right` input is indexed in memory, and `left` is streamed. Each overlapping pair is one
+/// row, and left features without an overlap aren't written. All coordinates in the output are
+/// 0-based and half-open, including those read from GFF.
+///
+/// # Arguments
+/// * `left` The path to the features to join from.
+/// * `right` The path to the intervals to join to.
+/// * `output` The path to the output parquet file.
+/// * `parquet_compression` The parquet compression to use.
+/// * `left_format` The format of `left`.
+/// * `right_format` The format of `right`.
+pub fn join2pq<P: AsRef<Path>>(
+    left: P,
+    right: P,
     output: P,
     parquet_compression: Compression,
+    left_format: IntervalFormat,
+    right_format: IntervalFormat,
 ) -> Result<(), BrrrrError> {
-    let file_schema = Schema::new(vec![
-        Field::new("id", DataType::Utf8, false),
-        Field::new("description", DataType::Utf8, true),
-        Field::new("sequence", DataType::Utf8, false),
-    ]);
-
     let props = WriterProperties::builder()
         .set_compression(parquet_compression)
         .set_statistics_enabled(true);
 
+    let file_schema = Schema::new(vec![
+        Field::new("seqname", DataType::Utf8, false),
+        Field::new("start", DataType::Int64, false),
+        Field::new("end", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("strand", DataType::Utf8, true),
+        Field::new("overlap_start", DataType::Int64, false),
+        Field::new("overlap_end", DataType::Int64, false),
+        Field::new("overlap_name", DataType::Utf8, true),
+        Field::new("overlap_strand", DataType::Utf8, true),
+    ]);
+
+    let right_file = fs::File::open(right)?;
+    let right_intervals = intervals::records(BufReader::new(right_file), right_format)?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let index = IntervalIndex::new(right_intervals)?;
+
+    let left_file = fs::File::open(left)?;
+    let records = intervals::records(BufReader::new(left_file), left_format)?;
+
     let file = fs::File::create(output)?;
     let mut writer =
         ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
-
     let chunk_size = 2usize.pow(20);
-    for chunk in reader.records().into_iter().chunks(chunk_size).into_iter() {
-        let mut id_builder = Vec::with_capacity(chunk_size);
-        let mut description_builder = StringBuilder::new(2048);
-        let mut seq_builder = Vec::with_capacity(chunk_size);
+
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut seqname_builder = StringBuilder::new(2048);
+        let mut start_builder = Int64Builder::new(2048);
+        let mut end_builder = Int64Builder::new(2048);
+        let mut name_builder = StringBuilder::new(2048);
+        let mut strand_builder = StringBuilder::new(2048);
+        let mut overlap_start_builder = Int64Builder::new(2048);
+        let mut overlap_end_builder = Int64Builder::new(2048);
+        let mut overlap_name_builder = StringBuilder::new(2048);
+        let mut overlap_strand_builder = StringBuilder::new(2048);
 
         for chunk_i in chunk {
-            let record = match chunk_i {
-                Ok(r) => FastaRecord::from(r),
-                Err(error) => panic!("{}", error),
-            };
+            let feature = chunk_i?;
 
-            id_builder.push(record.id);
-            match record.description {
-                Some(x) => description_builder
-                    .append_value(x)
-                    .expect("Couldn't append description."),
-                _ => description_builder
-                    .append_null()
-                    .expect("Couldn't append null description."),
+            for overlap in index.overlaps(&feature)? {
+                seqname_builder.append_value(&feature.seqname)?;
+                start_builder.append_value(feature.start)?;
+                end_builder.append_value(feature.end)?;
+                name_builder.append_option(feature.name.as_ref())?;
+                strand_builder.append_option(feature.strand.as_ref())?;
+                overlap_start_builder.append_value(overlap.start)?;
+                overlap_end_builder.append_value(overlap.end)?;
+                overlap_name_builder.append_option(overlap.name.as_ref())?;
+                overlap_strand_builder.append_option(overlap.strand.as_ref())?;
             }
-            seq_builder.push(record.sequence);
         }
 
-        let id_array = StringArray::from(id_builder);
-        let desc_array = description_builder.finish();
-        let seq_array = StringArray::from(seq_builder);
-
         let rb = RecordBatch::try_new(
             Arc::new(file_schema.clone()),
             vec![
-                Arc::new(id_array),
-                Arc::new(desc_array),
-                Arc::new(seq_array),
+                Arc::new(seqname_builder.finish()),
+                Arc::new(start_builder.finish()),
+                Arc::new(end_builder.finish()),
+                Arc::new(name_builder.finish()),
+                Arc::new(strand_builder.finish()),
+                Arc::new(overlap_start_builder.finish()),
+                Arc::new(overlap_end_builder.finish()),
+                Arc::new(overlap_name_builder.finish()),
+                Arc::new(overlap_strand_builder.finish()),
             ],
         )?;
 
@@ -224,152 +731,2484 @@ fn write_records_to_file<P: AsRef<Path>, R: BufRead>(
     }
 
     writer.close()?;
+
     Ok(())
 }
 
-/// Converts a FASTA file to Parquet.
+/// Converts a jsonl file of flat objects to Parquet, inferring the schema from the first
+/// `options.schema_sample` lines.
 ///
-/// # Arguments
-/// * `input` The the path to the input fasta file.
-/// * `output` The the path to the output parquet file.
-/// * `parquet_compression` The parquet compression to use.
-/// * `bio_file_compression` The compression for the input bio file.
-pub fn fa2pq<P: AsRef<Path>>(
-    input: &P,
-    output: &P,
-    parquet_compression: Compression,
-    bio_file_compression: BioFileCompression,
-) -> Result<(), BrrrrError> {
-    match bio_file_compression {
-        BioFileCompression::GZIP => {
-            let file = fs::File::open(input)?;
-            let gz = GzDecoder::new(BufReader::new(file));
-            let reader = fasta::Reader::new(BufReader::new(gz));
-            write_records_to_file(reader, output, parquet_compression)
-        }
-        BioFileCompression::UNCOMPRESSED => {
-            let file = fs::File::open(input)?;
-            let reader = fasta::Reader::new(BufReader::new(file));
-            write_records_to_file(reader, output, parquet_compression)
-        }
-    }
-}
-/// Converts a FASTQ file to Parquet.
+/// Columns are ordered by when they're first seen, and are all nullable. A key that first appears
+/// after the sample with a non-null value is an error, rather than having its values dropped, as
+/// is a value that doesn't fit the inferred type.
 ///
 /// # Arguments
-/// * `input` The path to the input FASTQ file.
+/// * `input` The path to the input jsonl file.
 /// * `output` The path to the output Parquet file.
 /// * `parquet_compression` The Parquet compression to use.
-/// * `bio_file_compression` The compression type for the input FASTQ file.
-pub fn fq2pq<P: AsRef<Path>>(
+/// * `options` The options for inferring the schema.
+pub fn jsonl2pq<P: AsRef<Path>>(
     input: P,
     output: P,
     parquet_compression: Compression,
-    bio_file_compression: BioFileCompression,
+    options: &InferOptions,
 ) -> Result<(), BrrrrError> {
-    let file_schema = Schema::new(vec![
-        Field::new("id", DataType::Utf8, false),
-        Field::new("sequence", DataType::Utf8, false),
-        Field::new("description", DataType::Utf8, true),
-        Field::new("quality", DataType::Utf8, false),
-        Field::new("number", DataType::Int64, true),
-    ]);
-
     let props = WriterProperties::builder()
         .set_compression(parquet_compression)
         .set_statistics_enabled(true);
 
-    // Abstract reader for both compressed and uncompressed files
-    let reader: Box<dyn std::io::Read> = match bio_file_compression {
-        BioFileCompression::GZIP => {
-            let file = fs::File::open(input)?;
-            let gz = GzDecoder::new(BufReader::new(file));
-            Box::new(gz)
-        }
-        BioFileCompression::UNCOMPRESSED => {
-            let file = fs::File::open(input)?;
-            Box::new(file)
-        }
-    };
+    let input_file = fs::File::open(input)?;
+    let mut lines = jsonl_lines(BufReader::new(input_file));
+
+    let sample = lines
+        .by_ref()
+        .take(options.schema_sample)
+        .map(parse_jsonl_line)
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let mut fastq_reader = fastq::Reader::new(BufReader::new(reader));
-    let records = fastq_reader.records();
+    let mut inference = SchemaInference::default();
+    for object in &sample {
+        inference.observe_json(object, options.on_conflict)?;
+    }
+    let file_schema = inference.schema();
 
-    // Write to the Parquet file
     let file = fs::File::create(output)?;
     let mut writer =
         ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+
+    let known: HashSet<&str> = inference
+        .columns()
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let records = sample.into_iter().map(Ok).chain(lines.map(|(i, line)| {
+        let object = parse_jsonl_line((i, line))?;
+        let unseen = object
+            .iter()
+            .find(|(key, value)| !value.is_null() && !known.contains(key.as_str()));
+        match unseen {
+            Some((key, _)) => Err(BrrrrError::SchemaMismatch(format!(
+                "key {:?} on line {} isn't in the schema inferred from the first {} lines, \
+                     so a larger --schema-sample is needed",
+                key,
+                i + 1,
+                options.schema_sample
+            ))),
+            None => Ok(object),
+        }
+    }));
+
     let chunk_size = 2usize.pow(20);
-    let mut id_builder = StringBuilder::new(2048);
-    let mut description_builder = StringBuilder::new(2048);
-    let mut seq_builder = StringBuilder::new(2048);
-    let mut quality_builder = StringBuilder::new(2048);
-    let mut read_number_builder = Int64Builder::new(2048);
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut builders: Vec<ColumnBuilder> = inference
+            .columns()
+            .iter()
+            .map(|(_, column_type)| ColumnBuilder::new(*column_type))
+            .collect();
 
-    let mut read_number = 0;
+        for record in chunk {
+            let object = record?;
 
-    for chunk in records.into_iter().chunks(chunk_size).into_iter() {
-        for chunk_i in chunk {
-            match chunk_i {
-                Ok(record) => {
-                    let fastq_record = FastqRecord::from(record);
-                    // println!("Processing record: {:?}", fastq_record.id);
-
-                    id_builder.append_value(fastq_record.id)?;
-                    match fastq_record.description {
-                        Some(x) => description_builder.append_value(x)?,
-                        None => description_builder.append_null()?,
-                    }
-                    seq_builder.append_value(fastq_record.sequence)?;
-                    quality_builder.append_value(fastq_record.quality)?;
-                    read_number_builder.append_value(read_number)?;
-                    read_number += 1;
+            for ((name, _), builder) in inference.columns().iter().zip(builders.iter_mut()) {
+                builder.append_json(object.get(name))?;
+            }
+        }
+
+        let columns = builders.iter_mut().map(|b| b.finish()).collect();
+        let rb = RecordBatch::try_new(Arc::new(file_schema.clone()), columns)?;
+
+        writer.write(&rb)?;
+    }
+
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Converts a CSV file to Parquet, inferring the column types from the first
+/// `infer_options.schema_sample` rows.
+///
+/// Columns are all nullable, with empty fields as nulls.
+///
+/// # Arguments
+/// * `input` The path to the input CSV file.
+/// * `output` The path to the output Parquet file.
+/// * `parquet_compression` The Parquet compression to use.
+/// * `csv_options` The options for reading the CSV, e.g. the delimiter.
+/// * `infer_options` The options for inferring the schema.
+pub fn csv2pq<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
+    csv_options: &CsvOptions,
+    infer_options: &InferOptions,
+) -> Result<(), BrrrrError> {
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(csv_options.delimiter)
+        .has_headers(csv_options.has_header)
+        .from_path(input)
+        .map_err(csv_error)?;
+
+    let headers: Option<Vec<String>> = if csv_options.has_header {
+        let headers = reader.headers().map_err(csv_error)?;
+        Some(headers.iter().map(String::from).collect())
+    } else {
+        None
+    };
+
+    let mut records = reader.records().map(|r| r.map_err(csv_error));
+    let sample = records
+        .by_ref()
+        .take(infer_options.schema_sample)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let names = headers.unwrap_or_else(|| {
+        let width = sample.first().map_or(0, |r| r.len());
+        (1..=width).map(|i| format!("column_{}", i)).collect()
+    });
+
+    let mut inference = SchemaInference::from_names(&names);
+    for record in &sample {
+        inference.observe_strs(&names, record, infer_options.on_conflict)?;
+    }
+    let file_schema = inference.schema();
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+
+    let records = sample.into_iter().map(Ok).chain(records);
+
+    let chunk_size = 2usize.pow(20);
+    for chunk in records.chunks(chunk_size).into_iter() {
+        let mut builders: Vec<ColumnBuilder> = inference
+            .columns()
+            .iter()
+            .map(|(_, column_type)| ColumnBuilder::new(*column_type))
+            .collect();
+
+        for record in chunk {
+            let record = record?;
+
+            for (i, builder) in builders.iter_mut().enumerate() {
+                builder.append_str(record.get(i).unwrap_or(""))?;
+            }
+        }
+
+        let columns = builders.iter_mut().map(|b| b.finish()).collect();
+        let rb = RecordBatch::try_new(Arc::new(file_schema.clone()), columns)?;
+
+        writer.write(&rb)?;
+    }
+
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Writes per-record QC metrics to a sidecar Parquet alongside a FASTA or FASTQ conversion, so
+/// the main table stays lean.
+struct StatsWriter {
+    schema: Arc<Schema>,
+    writer: ArrowWriter<fs::File>,
+    id_builder: StringBuilder,
+    length_builder: Int64Builder,
+    gc_builder: Float64Builder,
+    mean_qual_builder: Float64Builder,
+    n_count_builder: Int64Builder,
+}
+
+impl StatsWriter {
+    /// Creates the stats Parquet at `path`, if one was asked for.
+    fn create(
+        path: Option<&Path>,
+        parquet_compression: Compression,
+    ) -> Result<Option<Self>, BrrrrError> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("length", DataType::Int64, false),
+            Field::new("gc", DataType::Float64, true),
+            Field::new("mean_qual", DataType::Float64, true),
+            Field::new("n_count", DataType::Int64, false),
+        ]));
+
+        let props = WriterProperties::builder()
+            .set_compression(parquet_compression)
+            .set_statistics_enabled(true);
+
+        let file = fs::File::create(path)?;
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(props.build()))?;
+
+        Ok(Some(Self {
+            schema,
+            writer,
+            id_builder: StringBuilder::new(2048),
+            length_builder: Int64Builder::new(2048),
+            gc_builder: Float64Builder::new(2048),
+            mean_qual_builder: Float64Builder::new(2048),
+            n_count_builder: Int64Builder::new(2048),
+        }))
+    }
+
+    /// Adds the metrics of one record. FASTA records have no quality, and get a null mean_qual.
+    fn append(
+        &mut self,
+        id: &str,
+        sequence: &[u8],
+        mean_qual: Option<f64>,
+    ) -> Result<(), BrrrrError> {
+        self.id_builder.append_value(id)?;
+        self.length_builder.append_value(sequence.len() as i64)?;
+        self.gc_builder.append_option(gc_content(sequence))?;
+        self.mean_qual_builder.append_option(mean_qual)?;
+        self.n_count_builder
+            .append_value(n_count(sequence) as i64)?;
+        Ok(())
+    }
+
+    /// Writes the metrics added since the last flush as a batch.
+    fn flush(&mut self) -> Result<(), BrrrrError> {
+        if self.id_builder.len() == 0 {
+            return Ok(());
+        }
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.id_builder.finish()),
+            Arc::new(self.length_builder.finish()),
+            Arc::new(self.gc_builder.finish()),
+            Arc::new(self.mean_qual_builder.finish()),
+            Arc::new(self.n_count_builder.finish()),
+        ];
+        let rb = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&rb)?;
+
+        Ok(())
+    }
+
+    /// Flushes the remaining metrics and closes the file.
+    fn close(mut self) -> Result<(), BrrrrError> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Writes collapsed sequences to Parquet, with `id`, `sequence`, and `count` columns.
+///
+/// # Arguments
+///
+/// * `collapsed` the sequences from [`crate::stats::collapse`].
+/// * `output` the path to the output Parquet file.
+/// * `parquet_compression` the Parquet compression to use.
+pub fn collapsed2pq<P: AsRef<Path>>(
+    collapsed: &[CollapsedSequence],
+    output: P,
+    parquet_compression: Compression,
+) -> Result<(), BrrrrError> {
+    let file_schema = Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("sequence", DataType::Utf8, false),
+        Field::new("count", DataType::Int64, false),
+    ]);
+
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+
+    let chunk_size = 2usize.pow(20);
+
+    for chunk in collapsed.chunks(chunk_size) {
+        let mut id_builder = StringBuilder::new(2048);
+        let mut sequence_builder = StringBuilder::new(2048);
+        let mut count_builder = Int64Builder::new(2048);
+
+        for c in chunk {
+            id_builder.append_value(&c.id)?;
+            sequence_builder.append_value(String::from_utf8_lossy(&c.sequence))?;
+            count_builder.append_value(c.count as i64)?;
+        }
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(id_builder.finish()),
+            Arc::new(sequence_builder.finish()),
+            Arc::new(count_builder.finish()),
+        ];
+        let rb = RecordBatch::try_new(Arc::new(file_schema.clone()), columns)?;
+
+        writer.write(&rb)?;
+    }
+
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Writes a k-mer spectrum to Parquet, with `multiplicity` and `count` columns.
+///
+/// # Arguments
+///
+/// * `spectrum` the (multiplicity, count) pairs from [`crate::kmers::spectrum`].
+/// * `output` the path to the output Parquet file.
+/// * `parquet_compression` the Parquet compression to use.
+pub fn spectrum2pq<P: AsRef<Path>>(
+    spectrum: &[(u64, u64)],
+    output: P,
+    parquet_compression: Compression,
+) -> Result<(), BrrrrError> {
+    let file_schema = Schema::new(vec![
+        Field::new("multiplicity", DataType::Int64, false),
+        Field::new("count", DataType::Int64, false),
+    ]);
+
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+
+    let multiplicity: Int64Array = spectrum.iter().map(|(m, _)| Some(*m as i64)).collect();
+    let count: Int64Array = spectrum.iter().map(|(_, c)| Some(*c as i64)).collect();
+    let columns: Vec<ArrayRef> = vec![Arc::new(multiplicity), Arc::new(count)];
+    let rb = RecordBatch::try_new(Arc::new(file_schema), columns)?;
+
+    writer.write(&rb)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Writes a GC histogram to Parquet, with `bin_start`, `bin_end`, and `count` columns.
+///
+/// # Arguments
+///
+/// * `histogram` the histogram from [`crate::stats::gc_histogram`].
+/// * `output` the path to the output Parquet file.
+/// * `parquet_compression` the Parquet compression to use.
+pub fn gchist2pq<P: AsRef<Path>>(
+    histogram: &GcHistogram,
+    output: P,
+    parquet_compression: Compression,
+) -> Result<(), BrrrrError> {
+    let file_schema = Schema::new(vec![
+        Field::new("bin_start", DataType::Float64, false),
+        Field::new("bin_end", DataType::Float64, false),
+        Field::new("count", DataType::Int64, false),
+    ]);
+
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let file = fs::File::create(output)?;
+    let mut writer =
+        ArrowWriter::try_new(file, Arc::new(file_schema.clone()), Some(props.build()))?;
+
+    let bins = 0..histogram.counts.len();
+    let bin_start: Float64Array = bins.clone().map(|i| Some(histogram.bounds(i).0)).collect();
+    let bin_end: Float64Array = bins.map(|i| Some(histogram.bounds(i).1)).collect();
+    let count: Int64Array = histogram.counts.iter().map(|c| Some(*c as i64)).collect();
+    let columns: Vec<ArrayRef> = vec![Arc::new(bin_start), Arc::new(bin_end), Arc::new(count)];
+    let rb = RecordBatch::try_new(Arc::new(file_schema), columns)?;
+
+    writer.write(&rb)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Writes minimizers to Parquet in batches, with `read_id`, `minimizer`, `position`, and `strand`
+/// columns.
+struct MinimizerWriter {
+    schema: Arc<Schema>,
+    writer: ArrowWriter<fs::File>,
+    read_id_builder: StringBuilder,
+    minimizer_builder: StringBuilder,
+    position_builder: Int64Builder,
+    strand_builder: StringBuilder,
+}
+
+impl MinimizerWriter {
+    fn create<P: AsRef<Path>>(
+        output: P,
+        parquet_compression: Compression,
+    ) -> Result<Self, BrrrrError> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("read_id", DataType::Utf8, false),
+            Field::new("minimizer", DataType::Utf8, false),
+            Field::new("position", DataType::Int64, false),
+            Field::new("strand", DataType::Utf8, false),
+        ]));
+
+        let props = WriterProperties::builder()
+            .set_compression(parquet_compression)
+            .set_statistics_enabled(true);
+
+        let file = fs::File::create(output)?;
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(props.build()))?;
+
+        Ok(Self {
+            schema,
+            writer,
+            read_id_builder: StringBuilder::new(2048),
+            minimizer_builder: StringBuilder::new(2048),
+            position_builder: Int64Builder::new(2048),
+            strand_builder: StringBuilder::new(2048),
+        })
+    }
+
+    fn append(&mut self, read_id: &str, m: &Minimizer, k: usize) -> Result<(), BrrrrError> {
+        self.read_id_builder.append_value(read_id)?;
+        self.minimizer_builder
+            .append_value(String::from_utf8_lossy(&kmers::decode(m.kmer, k)))?;
+        self.position_builder.append_value(m.position as i64)?;
+        self.strand_builder
+            .append_value(if m.forward { "+" } else { "-" })?;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.read_id_builder.len()
+    }
+
+    fn flush(&mut self) -> Result<(), BrrrrError> {
+        if self.len() == 0 {
+            return Ok(());
+        }
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.read_id_builder.finish()),
+            Arc::new(self.minimizer_builder.finish()),
+            Arc::new(self.position_builder.finish()),
+            Arc::new(self.strand_builder.finish()),
+        ];
+        let rb = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&rb)?;
+
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<(), BrrrrError> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Writes the (w, k) minimizers of each record of a FASTA or FASTQ input to Parquet, with
+/// `read_id`, `minimizer`, `position`, and `strand` columns.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `output` the path to the output Parquet file.
+/// * `parquet_compression` the Parquet compression to use.
+/// * `w` the number of consecutive k-mers in each window.
+/// * `k` the k-mer length, up to [`crate::kmers::MAX_K`].
+/// * `canonical` whether to select the lesser of each k-mer and its reverse complement.
+pub fn minimizers2pq<R: BufRead, P: AsRef<Path>>(
+    input: R,
+    output: P,
+    parquet_compression: Compression,
+    w: usize,
+    k: usize,
+    canonical: bool,
+) -> Result<(), BrrrrError> {
+    let chunk_size = 2usize.pow(20);
+    let mut writer = MinimizerWriter::create(output, parquet_compression)?;
+
+    stats::try_for_each_record(input, |id, sequence| {
+        for m in kmers::minimizers(sequence, w, k, canonical)? {
+            writer.append(id, &m, k)?;
+        }
+
+        if writer.len() >= chunk_size {
+            writer.flush()?;
+        }
+
+        Ok(())
+    })?;
+
+    writer.close()
+}
+
+/// Writes the FASTA records of `reader` as Parquet to `output`.
+fn write_fasta_records<R: BufRead, W: Write + Send>(
+    reader: R,
+    output: Output<W>,
+    parquet_compression: Compression,
+    options: &FastaOptions,
+) -> Result<(), BrrrrError> {
+    let chunk_size = match options.max_file_size {
+        Some(_) => SPLIT_CHUNK_SIZE,
+        None => 2usize.pow(20),
+    };
+    let mut batches = FastaBatches::new(reader, chunk_size, options);
+
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let mut resumed = output.resumed_records();
+    let writer = BatchWriter::try_new(output, batches.schema(), props.build())?;
+    let mut stats = StatsWriter::create(options.stats_parquet.as_deref(), parquet_compression)?;
+
+    write_on_thread(writer, |sender| {
+        for batch in batches.by_ref() {
+            let mut rb = batch?;
+
+            // The batches are the same as the interrupted run's, so the records it wrote are
+            // skipped by slicing them off.
+            if resumed > 0 {
+                let skipped = resumed.min(rb.num_rows());
+                resumed -= skipped;
+                rb = rb.slice(skipped, rb.num_rows() - skipped);
+                if rb.num_rows() == 0 {
+                    continue;
+                }
+            }
+
+            if let Some(stats) = stats.as_mut() {
+                append_fasta_stats(stats, &rb)?;
+            }
+
+            send_batch(sender, rb)?;
+        }
+        Ok(())
+    })?;
+
+    if let Some(stats) = stats {
+        stats.close()?;
+    }
+    fasta_ops::report_filtered(batches.dropped(), options.id_list.as_deref());
+
+    Ok(())
+}
+
+/// Appends the QC metrics of each record in a FASTA batch to `stats`.
+fn append_fasta_stats(stats: &mut StatsWriter, rb: &RecordBatch) -> Result<(), BrrrrError> {
+    let ids = as_string_array(rb.column(0));
+    let sequences = as_string_array(rb.column(2));
+    for i in 0..rb.num_rows() {
+        stats.append(ids.value(i), sequences.value(i).as_bytes(), None)?;
+    }
+    stats.flush()
+}
+
+/// The number of records in each row group of an output split by file size, small enough that
+/// the size of the current file is checked often.
+const SPLIT_CHUNK_SIZE: usize = 2usize.pow(14);
+
+/// Returns the path of the `part`th file of a split output in `dir`, e.g. `part-00000.parquet`.
+pub fn shard_path<P: AsRef<Path>>(dir: P, part: usize) -> PathBuf {
+    dir.as_ref().join(format!("part-{:05}.parquet", part))
+}
+
+/// The limits that split a Parquet output into numbered files.
+#[derive(Debug, Default, Clone, Copy)]
+struct Split {
+    max_records: Option<usize>,
+    max_file_size: Option<u64>,
+    /// Keep a checkpoint of the closed files, and carry on from it if there is one.
+    resume: bool,
+}
+
+/// Where a converter writes its Parquet: a single file, or a directory of numbered files, maybe
+/// in Hive-style partition directories.
+enum Output<W> {
+    Single(W),
+    Dir {
+        dir: PathBuf,
+        split: Split,
+        partition_by: Option<PartitionBy>,
+        /// The checkpoint of the interrupted run being resumed.
+        checkpoint: Option<Checkpoint>,
+    },
+}
+
+impl Output<fs::File> {
+    /// Returns the output at `path`, a directory if it's split or partitioned.
+    fn create(path: &Path, split: Split, partition_by: Option<&PartitionBy>) -> io::Result<Self> {
+        let limited = split.max_records.is_some() || split.max_file_size.is_some();
+        if split.resume && !limited {
+            // Without a limit, no file is closed until the end, so there'd be nothing to resume.
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only an output split by a number of records or a file size can be resumed",
+            ));
+        }
+        if !limited && partition_by.is_none() {
+            return Ok(Output::Single(fs::File::create(path)?));
+        }
+
+        let checkpoint = if split.resume {
+            Checkpoint::read(path)?
+        } else {
+            // A checkpoint left by an earlier run doesn't describe the files this one writes.
+            match fs::remove_file(path.join(CHECKPOINT_FILE_NAME)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => None,
+            }
+        };
+
+        Ok(Output::Dir {
+            dir: path.to_path_buf(),
+            split,
+            partition_by: partition_by.cloned(),
+            checkpoint,
+        })
+    }
+}
+
+impl<W> Output<W> {
+    /// Returns the number of records the interrupted run being resumed already wrote, which are
+    /// skipped. These are the first records after the filters, so they only match if the options
+    /// do.
+    fn resumed_records(&self) -> usize {
+        match self {
+            Output::Dir {
+                checkpoint: Some(checkpoint),
+                ..
+            } => checkpoint.rows,
+            _ => 0,
+        }
+    }
+}
+
+/// Writes record batches to an `Output`.
+enum BatchWriter<W: Write> {
+    Single(ArrowWriter<W>),
+    Split(Box<SplitWriter>),
+    Partitioned(Box<PartitionedWriter>),
+}
+
+impl<W: Write> BatchWriter<W> {
+    fn try_new(
+        output: Output<W>,
+        schema: SchemaRef,
+        props: WriterProperties,
+    ) -> Result<Self, BrrrrError> {
+        Ok(match output {
+            Output::Single(writer) => {
+                BatchWriter::Single(ArrowWriter::try_new(writer, schema, Some(props))?)
+            }
+            Output::Dir {
+                dir,
+                split,
+                partition_by,
+                checkpoint,
+            } => {
+                fs::create_dir_all(&dir)?;
+                let resumed = match checkpoint {
+                    Some(checkpoint) => checkpoint.restore(&dir, &schema)?,
+                    None => Vec::new(),
+                };
+
+                match partition_by {
+                    None => BatchWriter::Split(Box::new(SplitWriter::try_new(
+                        dir, schema, props, split, resumed,
+                    )?)),
+                    Some(partition_by) => {
+                        // Each file is in its partition's directory, e.g.
+                        // length_bucket=100-500/part-00000.parquet.
+                        let mut closed: HashMap<String, Vec<(PathBuf, i64)>> = HashMap::new();
+                        for (path, rows) in resumed {
+                            let partition = path
+                                .parent()
+                                .and_then(Path::file_name)
+                                .map(|p| p.to_string_lossy().into_owned())
+                                .unwrap_or_default();
+                            closed.entry(partition).or_default().push((path, rows));
+                        }
+
+                        BatchWriter::Partitioned(Box::new(PartitionedWriter {
+                            key: partition_by.key(&schema)?,
+                            dir,
+                            schema,
+                            props,
+                            split,
+                            writers: HashMap::new(),
+                            closed,
+                        }))
+                    }
                 }
-                Err(e) => {
-                    eprintln!("Error reading record: {}", e);
-                    return Err(e.into());
+            }
+        })
+    }
+
+    fn write(&mut self, rb: &RecordBatch) -> Result<(), BrrrrError> {
+        match self {
+            BatchWriter::Single(writer) => Ok(writer.write(rb)?),
+            BatchWriter::Split(writer) => {
+                let closed = writer.files.len();
+                writer.write(rb)?;
+
+                // The closed files hold the first rows, however far into the batch they go.
+                if writer.split.resume && writer.files.len() > closed {
+                    write_checkpoint(&writer.dir, &writer.schema, &writer.files)?;
                 }
+                Ok(())
             }
+            BatchWriter::Partitioned(writer) => writer.write(rb),
         }
+    }
+
+    fn close(self) -> Result<(), BrrrrError> {
+        let (dir, schema, mut files) = match self {
+            BatchWriter::Single(writer) => {
+                writer.close()?;
+                return Ok(());
+            }
+            BatchWriter::Split(writer) => {
+                (writer.dir.clone(), writer.schema.clone(), writer.close()?)
+            }
+            BatchWriter::Partitioned(writer) => {
+                (writer.dir.clone(), writer.schema.clone(), writer.close()?)
+            }
+        };
 
-        // Check if we have records to process before finalizing the batch
-        if id_builder.len() > 0 {
-            println!("batch len {}", id_builder.len());
-            let id_array = id_builder.finish();
-            let desc_array = description_builder.finish();
-            let seq_array = seq_builder.finish();
-            let quality_array = quality_builder.finish();
-            let read_number_array = read_number_builder.finish();
-            // print len of each array
-            println!(
-                "id_array len: {}, desc_array len: {}, seq_array len: {}, quality_array len: {}",
-                id_array.len(),
-                desc_array.len(),
-                seq_array.len(),
-                quality_array.len()
-            );
-
-            let rb = RecordBatch::try_new(
-                Arc::new(file_schema.clone()),
-                vec![
-                    Arc::new(id_array),
-                    Arc::new(seq_array),
-                    Arc::new(desc_array),
-                    Arc::new(quality_array),
-                    Arc::new(read_number_array),
-                ],
-            )?;
-
-            writer.write(&rb)?;
-
-            // Reset builders for the next chunk
-            id_builder = StringBuilder::new(2048);
-            description_builder = StringBuilder::new(2048);
-            seq_builder = StringBuilder::new(2048);
-            quality_builder = StringBuilder::new(2048);
+        files.sort();
+        write_manifest(&dir, &schema, &files)?;
+
+        match fs::remove_file(dir.join(CHECKPOINT_FILE_NAME)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
         }
     }
+}
 
-    writer.close()?;
+/// The name of the manifest written in the directory of a split or partitioned output.
+pub const MANIFEST_FILE_NAME: &str = "_manifest.json";
+
+/// Writes the manifest of a split or partitioned output in `dir`: the schema, the total row
+/// count, and the path, relative to `dir`, and row count of each file, so readers don't have to
+/// glob for the files. It starts with an underscore, so Spark and Hive-style readers skip it.
+fn write_manifest(dir: &Path, schema: &Schema, files: &[(PathBuf, i64)]) -> Result<(), BrrrrError> {
+    write_file_list(&dir.join(MANIFEST_FILE_NAME), dir, schema, files)
+}
+
+/// Writes the schema, total row count, and `files` of an output in `dir` to `path`, in the
+/// manifest's format.
+fn write_file_list(
+    path: &Path,
+    dir: &Path,
+    schema: &Schema,
+    files: &[(PathBuf, i64)],
+) -> Result<(), BrrrrError> {
+    let files: Vec<serde_json::Value> = files
+        .iter()
+        .map(|(path, rows)| {
+            let path = path.strip_prefix(dir).unwrap_or(path);
+            let path = path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .join("/");
+            serde_json::json!({ "path": path, "rows": rows })
+        })
+        .collect();
+    let rows: i64 = files.iter().filter_map(|f| f["rows"].as_i64()).sum();
+
+    let manifest = serde_json::json!({
+        "schema": schema.to_json(),
+        "rows": rows,
+        "files": files,
+    });
+
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &manifest).map_err(io::Error::from)?;
+    writeln!(writer)?;
+    writer.flush()?;
     Ok(())
 }
+
+/// The name of the checkpoint kept in the directory of a split or partitioned output that can be
+/// resumed. It's removed once the output is complete.
+pub const CHECKPOINT_FILE_NAME: &str = "_checkpoint.json";
+
+/// Saves the checkpoint of an output in `dir`: the closed `files`, in the manifest's format.
+///
+/// The files are synced to disk first, and the checkpoint is replaced by a rename, so it only
+/// ever lists complete files, however the run is interrupted.
+fn write_checkpoint(
+    dir: &Path,
+    schema: &Schema,
+    files: &[(PathBuf, i64)],
+) -> Result<(), BrrrrError> {
+    for (path, _) in files {
+        fs::File::open(path)?.sync_all()?;
+    }
+
+    let temp = dir.join(format!("{}.tmp", CHECKPOINT_FILE_NAME));
+    write_file_list(&temp, dir, schema, files)?;
+    fs::rename(temp, dir.join(CHECKPOINT_FILE_NAME))?;
+    Ok(())
+}
+
+/// A file listed in a checkpoint.
+#[derive(Deserialize)]
+struct CheckpointFile {
+    path: String,
+    rows: i64,
+}
+
+/// The closed files of an interrupted run, read from the checkpoint in its output directory.
+#[derive(Deserialize)]
+struct Checkpoint {
+    schema: serde_json::Value,
+    /// The total number of rows in the files.
+    rows: usize,
+    files: Vec<CheckpointFile>,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint in `dir`, if there is one.
+    fn read(dir: &Path) -> io::Result<Option<Self>> {
+        let path = dir.join(CHECKPOINT_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let reader = BufReader::new(fs::File::open(path)?);
+        Ok(Some(serde_json::from_reader(reader)?))
+    }
+
+    /// Checks that the output being resumed in `dir` has the interrupted run's `schema`, and
+    /// removes the Parquet files written after the checkpoint. Returns the path and row count of
+    /// each file in the checkpoint.
+    fn restore(self, dir: &Path, schema: &Schema) -> Result<Vec<(PathBuf, i64)>, BrrrrError> {
+        if self.schema != schema.to_json() {
+            return Err(BrrrrError::SchemaMismatch(String::from(
+                "the output being resumed has different columns, so the options don't match the \
+                 interrupted run's",
+            )));
+        }
+
+        let files: Vec<(PathBuf, i64)> = self
+            .files
+            .into_iter()
+            .map(|f| (dir.join(f.path), f.rows))
+            .collect();
+        if let Some((missing, _)) = files.iter().find(|(path, _)| !path.is_file()) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is in the checkpoint, but missing", missing.display()),
+            )
+            .into());
+        }
+
+        remove_unlisted(dir, &files)?;
+        Ok(files)
+    }
+}
+
+/// Removes the Parquet files under `dir` that aren't in `files`, and the directories that leaves
+/// empty.
+fn remove_unlisted(dir: &Path, files: &[(PathBuf, i64)]) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            remove_unlisted(&path, files)?;
+            // This only removes the directory if it's empty.
+            let _ = fs::remove_dir(&path);
+        } else if path.extension().is_some_and(|e| e == "parquet")
+            && !files.iter().any(|(file, _)| file == &path)
+        {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes record batches to `part-00000.parquet`, `part-00001.parquet`, ... in a directory,
+/// starting a new file once the current one holds `max_records` rows, or before it reaches
+/// `max_file_size` bytes. With neither limit, everything goes to `part-00000.parquet`.
+///
+/// Parquet's size isn't known until a row group is written, so with a size limit each batch is
+/// written as its own row group, and the next row group is assumed to be as large as the last. A
+/// file can still pass the limit by a row group and its footer, e.g. if the records get longer.
+struct SplitWriter {
+    dir: PathBuf,
+    schema: SchemaRef,
+    props: WriterProperties,
+    split: Split,
+    part: usize,
+    writer: ArrowWriter<fs::File>,
+    rows: usize,
+    size: u64,
+    row_group_size: u64,
+    /// The path and row count of each closed file.
+    files: Vec<(PathBuf, i64)>,
+}
+
+impl SplitWriter {
+    /// Creates a writer in `dir`, continuing after `files`, the path and row count of each file
+    /// it already closed, e.g. in an interrupted run being resumed.
+    fn try_new(
+        dir: PathBuf,
+        schema: SchemaRef,
+        props: WriterProperties,
+        split: Split,
+        files: Vec<(PathBuf, i64)>,
+    ) -> Result<Self, BrrrrError> {
+        fs::create_dir_all(&dir)?;
+        let part = files.len();
+        let path = shard_path(&dir, part);
+        let writer = ArrowWriter::try_new(
+            fs::File::create(&path)?,
+            schema.clone(),
+            Some(props.clone()),
+        )?;
+        let size = fs::metadata(&path)?.len();
+
+        Ok(Self {
+            dir,
+            schema,
+            props,
+            split,
+            part,
+            writer,
+            rows: 0,
+            size,
+            row_group_size: 0,
+            files,
+        })
+    }
+
+    /// Returns whether the next rows should go to a new file.
+    fn full(&self) -> bool {
+        self.split.max_records.is_some_and(|max| self.rows >= max)
+            || self
+                .split
+                .max_file_size
+                .is_some_and(|max| self.row_group_size > 0 && self.size + self.row_group_size > max)
+    }
+
+    /// Closes the current file and starts the next.
+    fn next_part(&mut self) -> Result<(), BrrrrError> {
+        let closed = shard_path(&self.dir, self.part);
+        self.part += 1;
+        let path = shard_path(&self.dir, self.part);
+        let writer = ArrowWriter::try_new(
+            fs::File::create(&path)?,
+            self.schema.clone(),
+            Some(self.props.clone()),
+        )?;
+        let metadata = std::mem::replace(&mut self.writer, writer).close()?;
+        self.files.push((closed, metadata.num_rows));
+
+        self.rows = 0;
+        self.size = fs::metadata(&path)?.len();
+        Ok(())
+    }
+
+    fn write(&mut self, rb: &RecordBatch) -> Result<(), BrrrrError> {
+        let mut offset = 0;
+
+        while offset < rb.num_rows() {
+            if self.full() {
+                self.next_part()?;
+            }
+
+            let remaining = rb.num_rows() - offset;
+            let len = self
+                .split
+                .max_records
+                .map_or(remaining, |max| remaining.min(max - self.rows));
+            self.writer.write(&rb.slice(offset, len))?;
+            self.rows += len;
+            offset += len;
+
+            if self.split.max_file_size.is_some() {
+                self.writer.flush()?;
+                let written = fs::metadata(shard_path(&self.dir, self.part))?.len();
+                self.row_group_size = written - self.size;
+                self.size = written;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the current file, and returns the path and row count of every file.
+    fn close(mut self) -> Result<Vec<(PathBuf, i64)>, BrrrrError> {
+        let metadata = self.writer.close()?;
+        self.files
+            .push((shard_path(&self.dir, self.part), metadata.num_rows));
+        Ok(self.files)
+    }
+}
+
+/// Routes the rows of record batches to a `SplitWriter` for each partition, in a directory named
+/// by the partition key, e.g. `length_bucket=100-500/part-00000.parquet`. A partition's directory
+/// is only made once a row is routed to it.
+///
+/// The rows of a batch go to several files, so with `resume`, once any file fills, every
+/// partition's file is closed at the end of the batch, and the checkpoint is saved there.
+struct PartitionedWriter {
+    dir: PathBuf,
+    schema: SchemaRef,
+    props: WriterProperties,
+    split: Split,
+    key: PartitionKey,
+    writers: HashMap<String, SplitWriter>,
+    /// The closed files of each partition without an open writer.
+    closed: HashMap<String, Vec<(PathBuf, i64)>>,
+}
+
+impl PartitionedWriter {
+    /// Returns the number of closed files, across every partition.
+    fn closed_files(&self) -> usize {
+        self.writers.values().map(|w| w.files.len()).sum::<usize>()
+            + self.closed.values().map(Vec::len).sum::<usize>()
+    }
+
+    fn write(&mut self, rb: &RecordBatch) -> Result<(), BrrrrError> {
+        let closed = self.closed_files();
+
+        let mut rows: HashMap<String, Vec<u32>> = HashMap::new();
+        for row in 0..rb.num_rows() {
+            rows.entry((self.key)(rb, row))
+                .or_default()
+                .push(row as u32);
+        }
+
+        for (partition, indices) in rows {
+            let batch = if indices.len() == rb.num_rows() {
+                rb.clone()
+            } else {
+                let indices = UInt32Array::from(indices);
+                let columns = rb
+                    .columns()
+                    .iter()
+                    .map(|column| take(column.as_ref(), &indices, None))
+                    .collect::<Result<Vec<_>, _>>()?;
+                RecordBatch::try_new(rb.schema(), columns)?
+            };
+
+            let writer = match self.writers.entry(partition) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let dir = self.dir.join(entry.key());
+                    let writer = SplitWriter::try_new(
+                        dir,
+                        self.schema.clone(),
+                        self.props.clone(),
+                        self.split,
+                        self.closed.remove(entry.key()).unwrap_or_default(),
+                    )?;
+                    entry.insert(writer)
+                }
+            };
+            writer.write(&batch)?;
+        }
+
+        if self.split.resume && self.closed_files() > closed {
+            self.checkpoint()?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes every partition's open file, and saves the checkpoint of all the closed files.
+    fn checkpoint(&mut self) -> Result<(), BrrrrError> {
+        for (partition, writer) in self.writers.drain() {
+            self.closed.insert(partition, writer.close()?);
+        }
+
+        let mut files: Vec<_> = self.closed.values().flatten().cloned().collect();
+        files.sort();
+        write_checkpoint(&self.dir, &self.schema, &files)
+    }
+
+    /// Closes every partition, and returns the path and row count of every file.
+    fn close(self) -> Result<Vec<(PathBuf, i64)>, BrrrrError> {
+        let mut files: Vec<_> = self.closed.into_values().flatten().collect();
+        for (_, writer) in self.writers {
+            files.extend(writer.close()?);
+        }
+        Ok(files)
+    }
+}
+
+/// Writes the FASTA records of `reader` to `output`, a single Parquet file, or a directory of
+/// them if `options` splits or partitions the output.
+fn write_fasta_output<R: BufRead>(
+    mut reader: R,
+    output: &Path,
+    parquet_compression: Compression,
+    options: &FastaOptions,
+) -> Result<(), BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Fasta)?;
+    }
+
+    if options.resume && options.stats_parquet.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a conversion writing a stats parquet can't be resumed",
+        )
+        .into());
+    }
+
+    let split = Split {
+        max_records: options.max_records,
+        max_file_size: options.max_file_size,
+        resume: options.resume,
+    };
+    let output = Output::create(output, split, options.partition_by.as_ref())?;
+    write_fasta_records(reader, output, parquet_compression, options)
+}
+
+/// Converts a FASTA file to Parquet.
+///
+/// Records are written in input order, across batches and split files.
+///
+/// # Arguments
+/// * `input` The the path to the input fasta file, or an `s3://bucket/key` with the s3 feature.
+/// * `output` The the path to the output parquet file.
+/// * `parquet_compression` The parquet compression to use.
+/// * `bio_file_compression` The compression for the input bio file.
+/// * `options` The options for the conversion, e.g. extra columns.
+pub fn fa2pq<P: AsRef<Path>>(
+    input: &P,
+    output: &P,
+    parquet_compression: Compression,
+    bio_file_compression: BioFileCompression,
+    options: &FastaOptions,
+) -> Result<(), BrrrrError> {
+    match bio_file_compression {
+        BioFileCompression::GZIP => {
+            let file = input::open(input)?;
+            // bgzip and `cat a.gz b.gz` write several members, which are read one after another.
+            let gz = MultiGzDecoder::new(BufReader::new(file));
+            let reader = BufReader::new(gz);
+            write_fasta_output(reader, output.as_ref(), parquet_compression, options)
+        }
+        BioFileCompression::BZIP2 => {
+            let file = input::open(input)?;
+            // pbzip2 and `cat a.bz2 b.bz2` write several streams, which are read one after another.
+            let bz = MultiBzDecoder::new(BufReader::new(file));
+            let reader = BufReader::new(bz);
+            write_fasta_output(reader, output.as_ref(), parquet_compression, options)
+        }
+        BioFileCompression::ZSTD => {
+            // The decoder reads through its own buffer sized to zstd's recommended input block,
+            // so the file isn't wrapped in another BufReader.
+            let file = input::open(input)?;
+            let zst = ZstdDecoder::new(file)?;
+            let reader = BufReader::new(zst);
+            write_fasta_output(reader, output.as_ref(), parquet_compression, options)
+        }
+        BioFileCompression::UNCOMPRESSED => {
+            let file = input::open(input)?;
+            let reader = BufReader::new(file);
+            write_fasta_output(reader, output.as_ref(), parquet_compression, options)
+        }
+    }
+}
+
+/// Converts a FASTA input to Parquet in memory, returning the bytes of the Parquet file, for
+/// library users that can't or don't want to write to a file.
+///
+/// # Arguments
+/// * `reader` The FASTA input.
+/// * `parquet_compression` The parquet compression to use.
+/// * `options` The options for the conversion, e.g. extra columns.
+pub fn fa2pq_bytes<R: BufRead>(
+    mut reader: R,
+    parquet_compression: Compression,
+    options: &FastaOptions,
+) -> Result<Vec<u8>, BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Fasta)?;
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    write_fasta_records(
+        reader,
+        Output::Single(&mut buffer),
+        parquet_compression,
+        options,
+    )?;
+    Ok(buffer.into_inner())
+}
+
+/// Converts a FASTQ file to Parquet.
+///
+/// Reads are written in input order, and the `number` column counts the written reads from 0,
+/// without gaps for filtered reads, across batches and split files.
+///
+/// # Arguments
+/// * `input` The path to the input FASTQ file, or an `s3://bucket/key` with the s3 feature.
+/// * `output` The path to the output Parquet file.
+/// * `parquet_compression` The Parquet compression to use.
+/// * `bio_file_compression` The compression type for the input FASTQ file.
+/// * `options` The options for the conversion, e.g. extra columns.
+pub fn fq2pq<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    parquet_compression: Compression,
+    bio_file_compression: BioFileCompression,
+    options: &FastqOptions,
+) -> Result<(), BrrrrError> {
+    let mut reader = BufReader::new(bio_file_compression.decoder(input::open(input)?)?);
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Fastq)?;
+    }
+
+    if options.resume && options.stats_parquet.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a conversion writing a stats parquet can't be resumed",
+        )
+        .into());
+    }
+
+    let split = Split {
+        max_records: options.max_records,
+        resume: options.resume,
+        ..Default::default()
+    };
+    write_fastq_records(
+        reader,
+        Output::create(output.as_ref(), split, options.partition_by.as_ref())?,
+        parquet_compression,
+        options,
+    )
+}
+
+/// Converts a FASTQ input to Parquet in memory, returning the bytes of the Parquet file, for
+/// library users that can't or don't want to write to a file.
+///
+/// # Arguments
+/// * `reader` The FASTQ input.
+/// * `parquet_compression` The Parquet compression to use.
+/// * `options` The options for the conversion, e.g. extra columns.
+pub fn fq2pq_bytes<R: BufRead>(
+    mut reader: R,
+    parquet_compression: Compression,
+    options: &FastqOptions,
+) -> Result<Vec<u8>, BrrrrError> {
+    if !options.no_sniff {
+        input::check_format(&mut reader, InputFormat::Fastq)?;
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    write_fastq_records(
+        reader,
+        Output::Single(&mut buffer),
+        parquet_compression,
+        options,
+    )?;
+    Ok(buffer.into_inner())
+}
+
+/// Writes the FASTQ records of `reader` as Parquet to `output`.
+fn write_fastq_records<R: BufRead, W: Write + Send>(
+    reader: R,
+    output: Output<W>,
+    parquet_compression: Compression,
+    options: &FastqOptions,
+) -> Result<(), BrrrrError> {
+    let file_schema = Arc::new(fastq_schema(options));
+
+    let props = WriterProperties::builder()
+        .set_compression(parquet_compression)
+        .set_statistics_enabled(true);
+
+    let mut pipeline = options.pipeline();
+    let mut stats = StatsWriter::create(options.stats_parquet.as_deref(), parquet_compression)?;
+    let resumed = output.resumed_records();
+
+    let records = pipeline
+        .kept(fastq_ops::records(reader, options))
+        .map(|record| record.map(FastqRecord::from).map_err(BrrrrError::from))
+        .skip(resumed);
+
+    // The read numbers carry on from the interrupted run's.
+    let mut read_number = resumed as i64;
+    write_parquet(
+        records,
+        file_schema.clone(),
+        |records| {
+            if let Some(stats) = stats.as_mut() {
+                for record in records {
+                    let mean_qual = (!record.has_placeholder_quality())
+                        .then(|| mean_quality(record.quality.as_bytes(), options.quality_offset()))
+                        .flatten();
+                    stats.append(&record.id, record.sequence.as_bytes(), mean_qual)?;
+                }
+                stats.flush()?;
+            }
+
+            let rb = build_fastq_batch(records, read_number, &file_schema, options)?;
+            read_number += records.len() as i64;
+            Ok(rb)
+        },
+        output,
+        props.build(),
+        Chunk::new(options.batch_bytes, |record: &FastqRecord| {
+            record.sequence.len()
+        }),
+    )?;
+
+    if let Some(stats) = stats {
+        stats.close()?;
+    }
+    fastq_ops::report_filtered(pipeline.dropped(), options.id_list.as_deref());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::Write;
+
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    use super::*;
+    use crate::gff_ops::ScoreType;
+    use crate::record_batches::fa_to_record_batches;
+    use crate::tabular::parse_delimiter;
+
+    #[test]
+    fn test_gff2pq_attributes() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_gff2pq_attributes.gff");
+        let output = temp_dir.join("test_gff2pq_attributes.parquet");
+
+        let attributes: Vec<_> = (0..20).map(|i| format!("k{:02}=v{:02}", i, i)).collect();
+        let mut f = fs::File::create(&input).unwrap();
+        writeln!(f, "chr1\t.\tgene\t1\t9\t.\t+\t.\t{}", attributes.join(";")).unwrap();
+
+        for sort_attributes in [false, true] {
+            let options = GffOptions {
+                sort_attributes,
+                ..Default::default()
+            };
+            gff2pq(&input, &output, Compression::UNCOMPRESSED, &options).unwrap();
+
+            let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+            let row = reader.get_row_iter(None).unwrap().next().unwrap();
+            let entries: Vec<_> = row
+                .get_map(8)
+                .unwrap()
+                .entries()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+
+            assert_eq!(entries.len(), 20);
+            for (key, value) in &entries {
+                assert_eq!(key.replace('k', "v"), *value);
+            }
+            if sort_attributes {
+                let keys: Vec<_> = entries.iter().map(|(key, _)| key.clone()).collect();
+                let mut sorted = keys.clone();
+                sorted.sort();
+                assert_eq!(keys, sorted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gff2pq_score_text() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_gff2pq_score_text.gff");
+        let output = temp_dir.join("test_gff2pq_score_text.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(
+            b"chr1\t.\tgene\t5\t9\t0.10\t+\t.\tID=b\nchr1\t.\tgene\t1\t9\t1e3\t+\t.\tID=a\n",
+        )
+        .unwrap();
+
+        let options = GffOptions {
+            sort: true,
+            score_type: ScoreType::Str,
+            ..Default::default()
+        };
+        gff2pq(&input, &output, Compression::UNCOMPRESSED, &options).unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let scores: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.get_string(5).unwrap().clone())
+            .collect();
+        assert_eq!(scores, vec!["1e3", "0.10"]);
+    }
+
+    #[test]
+    fn test_bedgraph2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_bedgraph2pq.bedgraph");
+        let output = temp_dir.join("test_bedgraph2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"browser position chr1:1-100\ntrack type=bedGraph\nchr1\t0\t10\t1.5\nchr1\t10\t20\t-2\n")
+            .unwrap();
+
+        bedgraph2pq(&input, &output, Compression::UNCOMPRESSED).unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(0).unwrap(), "chr1");
+        assert_eq!(rows[0].get_long(1).unwrap(), 0);
+        assert_eq!(rows[0].get_long(2).unwrap(), 10);
+        assert_eq!(rows[0].get_double(3).unwrap(), 1.5);
+        assert_eq!(rows[1].get_double(3).unwrap(), -2.0);
+    }
+
+    #[test]
+    fn test_wig2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_wig2pq.wig");
+        let output = temp_dir.join("test_wig2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(
+            b"track type=wiggle_0\nvariableStep chrom=chr1 span=5\n100 1.0\n200 2.0\nfixedStep chrom=chr2 start=10 step=20\n3\n4\n",
+        )
+        .unwrap();
+
+        wig2pq(&input, &output, Compression::UNCOMPRESSED).unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<(String, i64, i64, f64)> = reader
+            .into_iter()
+            .map(|r| {
+                (
+                    r.get_string(0).unwrap().clone(),
+                    r.get_long(1).unwrap(),
+                    r.get_long(2).unwrap(),
+                    r.get_double(3).unwrap(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                (String::from("chr1"), 100, 5, 1.0),
+                (String::from("chr1"), 200, 5, 2.0),
+                (String::from("chr2"), 10, 1, 3.0),
+                (String::from("chr2"), 30, 1, 4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_peak2pq.narrowPeak");
+        let output = temp_dir.join("test_peak2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"chr1\t9\t20\tpeak1\t500\t.\t4.5\t-1\t2.25\t5\n")
+            .unwrap();
+
+        peak2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            PeakFormat::Narrow,
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), 10);
+        assert_eq!(rows[0].get_string(3).unwrap(), "peak1");
+        assert_eq!(rows[0].get_long(4).unwrap(), 500);
+        assert!(rows[0].get_string(5).is_err());
+        assert_eq!(rows[0].get_double(6).unwrap(), 4.5);
+        assert!(rows[0].get_double(7).is_err());
+        assert_eq!(rows[0].get_double(8).unwrap(), 2.25);
+        assert_eq!(rows[0].get_long(9).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_join2pq() {
+        let temp_dir = env::temp_dir();
+        let left = temp_dir.join("test_join2pq.gff");
+        let right = temp_dir.join("test_join2pq.bed");
+        let output = temp_dir.join("test_join2pq.parquet");
+
+        fs::write(
+            &left,
+            b"sq0\tNOODLES\tgene\t1\t15\t.\t+\t.\tID=g0\nsq0\tNOODLES\tgene\t40\t50\t.\t+\t.\tID=g1\n",
+        )
+        .unwrap();
+        fs::write(&right, b"sq0\t0\t5\ta\nsq0\t10\t20\tb\n").unwrap();
+
+        join2pq(
+            &left,
+            &right,
+            &output,
+            Compression::UNCOMPRESSED,
+            IntervalFormat::Gff,
+            IntervalFormat::Bed,
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<(String, i64, String)> = reader
+            .into_iter()
+            .map(|r| {
+                (
+                    r.get_string(3).unwrap().clone(),
+                    r.get_long(1).unwrap(),
+                    r.get_string(7).unwrap().clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                (String::from("g0"), 0, String::from("a")),
+                (String::from("g0"), 0, String::from("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fa2pq_gc_skew() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_gc_skew.fa");
+        let output = temp_dir.join("test_fa2pq_gc_skew.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b">sq0\nGGGC\n>sq1\nATAT\n").unwrap();
+
+        let options = FastaOptions {
+            with_gc_skew: true,
+            ..Default::default()
+        };
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_double(3).unwrap(), 0.5);
+        assert!(rows[1].get_double(3).is_err());
+    }
+
+    #[test]
+    fn test_fa2pq_max_file_size() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_max_file_size.fa");
+        let output = temp_dir.join("test_fa2pq_max_file_size");
+        let _ = fs::remove_dir_all(&output);
+
+        let mut f = fs::File::create(&input).unwrap();
+        for i in 0..40000 {
+            writeln!(f, ">sq{}\nACGTACGTACGTACGTACGTACGTACGTACGT", i).unwrap();
+        }
+
+        // Every row group is past the limit, so each batch gets its own file.
+        let options = FastaOptions {
+            max_file_size: Some(1),
+            ..Default::default()
+        };
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let rows: Vec<i64> = (0..3)
+            .map(|part| {
+                let file = fs::File::open(shard_path(&output, part)).unwrap();
+                SerializedFileReader::new(file)
+                    .unwrap()
+                    .metadata()
+                    .file_metadata()
+                    .num_rows()
+            })
+            .collect();
+        assert_eq!(rows, vec![16384, 16384, 7232]);
+        assert!(!shard_path(&output, 3).exists());
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let output = env::temp_dir().join("test_write_parquet.parquet");
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "number",
+            DataType::Int64,
+            false,
+        )]));
+
+        // The batch builder's state carries on across chunks.
+        let mut next = 10;
+        let records = (0..5).map(Ok);
+        write_parquet(
+            records,
+            schema.clone(),
+            |records: &[i64]| {
+                let numbers: Int64Array = records.iter().map(|r| Some(r + next)).collect();
+                next += 100;
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(numbers)],
+                )?)
+            },
+            Output::Single(fs::File::create(&output).unwrap()),
+            WriterProperties::builder().build(),
+            Chunk::Records(2),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let numbers: Vec<i64> = reader
+            .into_iter()
+            .map(|row| row.get_long(0).unwrap())
+            .collect();
+        assert_eq!(numbers, vec![10, 11, 112, 113, 214]);
+
+        // By size, each batch ends once its records add up to 4.
+        let records = vec![3, 1, 5, 2, 2, 1].into_iter().map(Ok);
+        let mut sizes = Vec::new();
+        write_parquet(
+            records,
+            schema.clone(),
+            |records: &[i64]| {
+                sizes.push(records.len());
+                let numbers: Int64Array = records.iter().map(|r| Some(*r)).collect();
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(numbers)],
+                )?)
+            },
+            Output::Single(fs::File::create(&output).unwrap()),
+            WriterProperties::builder().build(),
+            Chunk::Bytes(4, |r: &i64| *r as usize),
+        )
+        .unwrap();
+        assert_eq!(sizes, vec![2, 1, 2, 1]);
+
+        let records = vec![Ok(0), Err(BrrrrError::SchemaMismatch(String::from("bad")))];
+        let result = write_parquet(
+            records.into_iter(),
+            schema.clone(),
+            |records: &[i64]| {
+                let numbers: Int64Array = records.iter().map(|r| Some(*r)).collect();
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(numbers)],
+                )?)
+            },
+            Output::Single(fs::File::create(&output).unwrap()),
+            WriterProperties::builder().build(),
+            Chunk::Records(2),
+        );
+        assert!(matches!(result, Err(BrrrrError::SchemaMismatch(_))));
+    }
+
+    /// An output that fails once more than its capacity has been written, like a full disk.
+    struct Full(usize);
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::other("disk full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_parquet_writer_error() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "number",
+            DataType::Int64,
+            false,
+        )]));
+        // A row group per batch, so the writer thread writes, and fails, before the input ends.
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(1)
+            .build();
+
+        let result = write_parquet(
+            (0..1000).map(Ok),
+            schema.clone(),
+            |records: &[i64]| {
+                let numbers: Int64Array = records.iter().map(|r| Some(*r)).collect();
+                Ok(RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(numbers)],
+                )?)
+            },
+            Output::Single(Full(64)),
+            props,
+            Chunk::Records(1),
+        );
+
+        // The writer's own error is reported, not the producer's failed send.
+        assert!(matches!(result, Err(BrrrrError::Parquet(_))));
+    }
+
+    #[test]
+    fn test_fq2pq_max_records() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_max_records.fq");
+        let output = temp_dir.join("test_fq2pq_max_records");
+        let _ = fs::remove_dir_all(&output);
+
+        let mut f = fs::File::create(&input).unwrap();
+        for i in 0..5 {
+            writeln!(f, "@r{}\nACGT\n+\nIIII", i).unwrap();
+        }
+
+        let options = FastqOptions {
+            max_records: Some(2),
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // The read numbers carry on across files.
+        let numbers: Vec<Vec<i64>> = (0..3)
+            .map(|part| {
+                let file = fs::File::open(shard_path(&output, part)).unwrap();
+                let reader = SerializedFileReader::new(file).unwrap();
+                let number = reader
+                    .metadata()
+                    .file_metadata()
+                    .schema_descr()
+                    .columns()
+                    .iter()
+                    .position(|c| c.name() == "number")
+                    .unwrap();
+                reader
+                    .into_iter()
+                    .map(|row| row.get_long(number).unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(numbers, vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(!shard_path(&output, 3).exists());
+    }
+
+    #[test]
+    fn test_fa2pq_partition_by_length() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_partition_by_length.fa");
+        let output = temp_dir.join("test_fa2pq_partition_by_length");
+        let _ = fs::remove_dir_all(&output);
+
+        fs::write(&input, ">a\nAC\n>b\nACGTACGT\n>c\nACGT\n").unwrap();
+
+        let options = FastaOptions {
+            partition_by: Some("length:0,5".parse().unwrap()),
+            ..Default::default()
+        };
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let ids = |partition: &str| -> Vec<String> {
+            let file = fs::File::open(shard_path(output.join(partition), 0)).unwrap();
+            SerializedFileReader::new(file)
+                .unwrap()
+                .into_iter()
+                .map(|row| row.get_string(0).unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids("length_bucket=0-5"), vec!["a", "c"]);
+        assert_eq!(ids("length_bucket=5-"), vec!["b"]);
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(manifest["rows"], 3);
+        assert_eq!(
+            manifest["files"],
+            serde_json::json!([
+                { "path": "length_bucket=0-5/part-00000.parquet", "rows": 2 },
+                { "path": "length_bucket=5-/part-00000.parquet", "rows": 1 },
+            ])
+        );
+        assert_eq!(manifest["schema"]["fields"][0]["name"], "id");
+    }
+
+    #[test]
+    fn test_fq2pq_window() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_window.fq");
+        let output = temp_dir.join("test_fq2pq_window.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        for i in 0..10 {
+            writeln!(f, "@r{}\nACGT\n+\nIIII", i).unwrap();
+        }
+        // The input is truncated after the window, so reading past it would fail.
+        write!(f, "@r10\nACGT\n+\n").unwrap();
+
+        let options = FastqOptions {
+            skip: 2,
+            limit: Some(3),
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // Stopping mid-chunk still closes the file.
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<(String, i64)> = reader
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get_string(0).unwrap().to_string(),
+                    row.get_long(4).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (String::from("r2"), 0),
+                (String::from("r3"), 1),
+                (String::from("r4"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fq2pq_resume() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_resume.fq");
+        let output = temp_dir.join("test_fq2pq_resume");
+        let _ = fs::remove_dir_all(&output);
+
+        let mut f = fs::File::create(&input).unwrap();
+        for i in 0..5 {
+            writeln!(f, "@r{}\nACGT\n+\nIIII", i).unwrap();
+        }
+
+        let options = FastqOptions {
+            max_records: Some(2),
+            resume: true,
+            ..Default::default()
+        };
+
+        // An interrupted run: the first three reads are written, but the output is never closed,
+        // so only part-00000.parquet is complete.
+        let split = Split {
+            max_records: Some(2),
+            resume: true,
+            ..Default::default()
+        };
+        let schema = Arc::new(fastq_schema(&options));
+        let records: Vec<FastqRecord> = (0..3)
+            .map(|i| FastqRecord {
+                id: format!("r{}", i),
+                description: None,
+                sequence: String::from("ACGT"),
+                quality: String::from("IIII"),
+            })
+            .collect();
+        let mut writer = BatchWriter::try_new(
+            Output::create(&output, split, None).unwrap(),
+            schema.clone(),
+            WriterProperties::builder().build(),
+        )
+        .unwrap();
+        writer
+            .write(&build_fastq_batch(&records, 0, &schema, &options).unwrap())
+            .unwrap();
+        drop(writer);
+        fs::write(shard_path(&output, 5), b"not parquet").unwrap();
+
+        let checkpoint: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.join(CHECKPOINT_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(checkpoint["rows"], 2);
+
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // The resumed run carries on with the third read, and its read number.
+        let rows: Vec<Vec<(String, i64)>> = (0..3)
+            .map(|part| {
+                let file = fs::File::open(shard_path(&output, part)).unwrap();
+                SerializedFileReader::new(file)
+                    .unwrap()
+                    .into_iter()
+                    .map(|row| {
+                        (
+                            row.get_string(0).unwrap().to_string(),
+                            row.get_long(4).unwrap(),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![(String::from("r0"), 0), (String::from("r1"), 1)],
+                vec![(String::from("r2"), 2), (String::from("r3"), 3)],
+                vec![(String::from("r4"), 4)],
+            ]
+        );
+        assert!(!shard_path(&output, 5).exists());
+        assert!(!output.join(CHECKPOINT_FILE_NAME).exists());
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(manifest["rows"], 5);
+
+        // Without a split, no file is closed before the end, so there'd be nothing to resume.
+        let options = FastqOptions {
+            resume: true,
+            ..Default::default()
+        };
+        let result = fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        );
+        assert!(matches!(result, Err(BrrrrError::Io(_))));
+    }
+
+    #[test]
+    fn test_fa2pq_resume_partitioned() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_resume_partitioned.fa");
+        let output = temp_dir.join("test_fa2pq_resume_partitioned");
+        let _ = fs::remove_dir_all(&output);
+
+        fs::write(&input, ">a\nAC\n>b\nACGTACGT\n>c\nACGT\n>d\nACGTAC\n").unwrap();
+
+        let options = FastaOptions {
+            partition_by: Some("length:0,5".parse().unwrap()),
+            max_records: Some(1),
+            resume: true,
+            ..Default::default()
+        };
+
+        // An interrupted run: a batch of the first three records fills length_bucket=0-5's first
+        // file, so every partition's file is closed and checkpointed, and then the run stops.
+        let split = Split {
+            max_records: Some(1),
+            resume: true,
+            ..Default::default()
+        };
+        let batch = fa_to_record_batches(&b">a\nAC\n>b\nACGTACGT\n>c\nACGT\n"[..], 10, &options)
+            .unwrap()
+            .remove(0);
+        let mut writer = BatchWriter::try_new(
+            Output::create(&output, split, options.partition_by.as_ref()).unwrap(),
+            batch.schema(),
+            WriterProperties::builder().build(),
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        drop(writer);
+
+        let checkpoint: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.join(CHECKPOINT_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(checkpoint["rows"], 3);
+
+        // The options must match, and an extra column doesn't.
+        let mismatched = FastaOptions {
+            with_gc_skew: true,
+            ..options.clone()
+        };
+        let result = fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &mismatched,
+        );
+        assert!(matches!(result, Err(BrrrrError::SchemaMismatch(_))));
+
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(output.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(manifest["rows"], 4);
+        assert_eq!(
+            manifest["files"],
+            serde_json::json!([
+                { "path": "length_bucket=0-5/part-00000.parquet", "rows": 1 },
+                { "path": "length_bucket=0-5/part-00001.parquet", "rows": 1 },
+                { "path": "length_bucket=5-/part-00000.parquet", "rows": 1 },
+                { "path": "length_bucket=5-/part-00001.parquet", "rows": 1 },
+            ])
+        );
+
+        let file = fs::File::open(shard_path(output.join("length_bucket=5-"), 1)).unwrap();
+        let ids: Vec<String> = SerializedFileReader::new(file)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.get_string(0).unwrap().to_string())
+            .collect();
+        assert_eq!(ids, vec!["d"]);
+    }
+
+    #[test]
+    fn test_fa2pq_gzip() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_gzip.fa.gz");
+        let output = temp_dir.join("test_fa2pq_gzip.parquet");
+
+        // Two members, as bgzip or concatenating .gz files writes.
+        let mut f = fs::File::create(&input).unwrap();
+        for part in [&b">sq0 desc\nACGT\n"[..], &b">sq1\nGG\n"[..]] {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(part).unwrap();
+            f.write_all(&encoder.finish().unwrap()).unwrap();
+        }
+        drop(f);
+
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::GZIP,
+            &FastaOptions::default(),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get_string(0).unwrap(), "sq1");
+    }
+
+    #[test]
+    fn test_fa2pq_bzip2() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_bzip2.fa.bz2");
+        let output = temp_dir.join("test_fa2pq_bzip2.parquet");
+
+        // Two streams, as pbzip2 or concatenating .bz2 files writes.
+        let mut f = fs::File::create(&input).unwrap();
+        for part in [&b">sq0 desc\nACGT\n"[..], &b">sq1\nGG\n"[..]] {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(part).unwrap();
+            f.write_all(&encoder.finish().unwrap()).unwrap();
+        }
+        drop(f);
+
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::BZIP2,
+            &FastaOptions::default(),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(0).unwrap(), "sq0");
+        assert_eq!(rows[0].get_string(1).unwrap(), "desc");
+        assert_eq!(rows[1].get_string(2).unwrap(), "GG");
+    }
+
+    #[test]
+    fn test_fa2pq_zstd() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fa2pq_zstd.fa.zst");
+        let output = temp_dir.join("test_fa2pq_zstd.parquet");
+
+        let compressed = zstd::stream::encode_all(&b">sq0\nACGT\n>sq1\nGG\n"[..], 0).unwrap();
+        fs::write(&input, compressed).unwrap();
+
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::ZSTD,
+            &FastaOptions::default(),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get_string(0).unwrap(), "sq1");
+        assert_eq!(rows[1].get_string(2).unwrap(), "GG");
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_dry_run.fq.zst");
+        let output = temp_dir.join("test_dry_run.parquet");
+        let _ = fs::remove_file(&output);
+
+        let compressed = zstd::stream::encode_all(&b"@r1\nACGT\n+\nIIII\n"[..], 0).unwrap();
+        fs::write(&input, compressed).unwrap();
+
+        let schema = fastq_schema(&FastqOptions::default());
+        let report = dry_run(&input, &output, BioFileCompression::ZSTD, schema.clone()).unwrap();
+        assert_eq!(report.detected_compression, BioFileCompression::ZSTD);
+        assert_eq!(report.detected_format, Some(InputFormat::Fastq));
+        assert_eq!(report.schema, schema);
+        assert!(!output.exists());
+
+        // Read as if it were uncompressed, the zstd frame isn't any format.
+        let uncompressed = BioFileCompression::UNCOMPRESSED;
+        let report = dry_run(&input, &output, uncompressed, schema.clone()).unwrap();
+        assert_eq!(report.detected_compression, BioFileCompression::ZSTD);
+        assert_eq!(report.detected_format, None);
+
+        let missing = temp_dir.join("test_dry_run_missing").join("out.parquet");
+        assert!(dry_run(&input, &missing, BioFileCompression::ZSTD, schema).is_err());
+
+        assert_eq!(
+            BioFileCompression::detect(b"BZh91AY"),
+            BioFileCompression::BZIP2
+        );
+        assert_eq!(
+            BioFileCompression::detect(&[0x1f, 0x8b, 8]),
+            BioFileCompression::GZIP
+        );
+        assert_eq!(
+            BioFileCompression::detect(b">sq0"),
+            BioFileCompression::UNCOMPRESSED
+        );
+    }
+
+    #[test]
+    fn test_pq_bytes() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_pq_bytes.fa");
+        let output = temp_dir.join("test_pq_bytes.parquet");
+
+        let fasta = b">sq0\nACGT\n>sq1\nGG\n";
+        fs::write(&input, fasta).unwrap();
+        fa2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &FastaOptions::default(),
+        )
+        .unwrap();
+
+        let bytes = fa2pq_bytes(
+            &fasta[..],
+            Compression::UNCOMPRESSED,
+            &FastaOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(bytes, fs::read(&output).unwrap());
+
+        let fastq = b"@r0\nAC\n+\nII\n" as &[u8];
+        let bytes = fq2pq_bytes(fastq, Compression::SNAPPY, &FastqOptions::default()).unwrap();
+        assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+
+        let gff = b"sq0\t.\tgene\t1\t4\t.\t+\t.\tID=g0\n" as &[u8];
+        let bytes = gff2pq_bytes(gff, Compression::SNAPPY, &GffOptions::default()).unwrap();
+        assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+    }
+
+    #[test]
+    fn test_fq2pq_mean_quality() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_mean_quality.fastq");
+        let output = temp_dir.join("test_fq2pq_mean_quality.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"@r0\nACGT\n+\n!+5I\n@r1\nAC\n+\nII\n")
+            .unwrap();
+
+        let options = FastqOptions {
+            with_mean_qual: true,
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // ! + 5 I are Phred 0, 10, 20, 40, and I I is 40, 40.
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_double(5).unwrap(), 17.5);
+        assert_eq!(rows[1].get_double(5).unwrap(), 40.0);
+
+        // The same qualities as Phred+64 are 31 lower, and floored at 0.
+        let options = FastqOptions {
+            with_mean_qual: true,
+            phred_offset: Some(64),
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows[0].get_double(5).unwrap(), 2.25);
+        assert_eq!(rows[1].get_double(5).unwrap(), 9.0);
+    }
+
+    #[test]
+    fn test_fq2pq_gc() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_gc.fastq");
+        let output = temp_dir.join("test_fq2pq_gc.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"@r0\nGcNA\n+\nIIII\n@r1\n\n+\n\n").unwrap();
+
+        let options = FastqOptions {
+            with_mean_qual: true,
+            with_gc: true,
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // The gc column comes after mean_quality. Lowercase counts, and N counts toward the
+        // length, as in the stats parquet.
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows[0].get_double(6).unwrap(), 0.5);
+        assert!(rows[1].get_double(6).is_err());
+    }
+
+    #[test]
+    fn test_fq2pq_stats_parquet() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_fq2pq_stats_parquet.fastq");
+        let output = temp_dir.join("test_fq2pq_stats_parquet.parquet");
+        let stats = temp_dir.join("test_fq2pq_stats_parquet.stats.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"@r0\nACGN\n+\n!+5I\n@r1\nGG\n+\nII\n")
+            .unwrap();
+
+        let options = FastqOptions {
+            stats_parquet: Some(stats.clone()),
+            ..Default::default()
+        };
+        fq2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            BioFileCompression::UNCOMPRESSED,
+            &options,
+        )
+        .unwrap();
+
+        // The main table doesn't grow.
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema()
+                .get_fields()
+                .len(),
+            5
+        );
+
+        let reader = SerializedFileReader::new(fs::File::open(&stats).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(0).unwrap(), "r0");
+        assert_eq!(rows[0].get_long(1).unwrap(), 4);
+        assert_eq!(rows[0].get_double(2).unwrap(), 0.5);
+        assert_eq!(rows[0].get_double(3).unwrap(), 17.5);
+        assert_eq!(rows[0].get_long(4).unwrap(), 1);
+        assert_eq!(rows[1].get_double(2).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_jsonl2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_jsonl2pq.jsonl");
+        let output = temp_dir.join("test_jsonl2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"{\"id\": \"a\", \"n\": 1, \"ok\": true}\n\n{\"id\": \"b\", \"n\": 2.5}\n{\"id\": \"c\", \"n\": 3}\n")
+            .unwrap();
+
+        let options = InferOptions {
+            schema_sample: 2,
+            ..Default::default()
+        };
+        jsonl2pq(&input, &output, Compression::UNCOMPRESSED, &options).unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].get_string(0).unwrap(), "a");
+        assert_eq!(rows[0].get_double(1).unwrap(), 1.0);
+        assert!(rows[0].get_bool(2).unwrap());
+        assert_eq!(rows[2].get_double(1).unwrap(), 3.0);
+        assert!(rows[2].get_bool(2).is_err());
+
+        // A key first seen after the sample fails, unless its value is null.
+        fs::write(&input, b"{\"id\": \"a\"}\n{\"id\": \"b\", \"n\": null}\n").unwrap();
+        let options = InferOptions {
+            schema_sample: 1,
+            ..Default::default()
+        };
+        jsonl2pq(&input, &output, Compression::UNCOMPRESSED, &options).unwrap();
+
+        fs::write(&input, b"{\"id\": \"a\"}\n{\"id\": \"b\", \"n\": 1}\n").unwrap();
+        match jsonl2pq(&input, &output, Compression::UNCOMPRESSED, &options) {
+            Err(BrrrrError::SchemaMismatch(m)) => assert!(m.contains("key \"n\" on line 2")),
+            r => panic!("expected a schema mismatch, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_csv2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_csv2pq.csv");
+        let output = temp_dir.join("test_csv2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"gene,length,score\ng0,100,0.5\ng1,,1\n")
+            .unwrap();
+
+        csv2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            &CsvOptions::default(),
+            &InferOptions::default(),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let fields = reader.metadata().file_metadata().schema().get_fields();
+        assert_eq!(fields[1].name(), "length");
+
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(0).unwrap(), "g0");
+        assert_eq!(rows[0].get_long(1).unwrap(), 100);
+        assert!(rows[1].get_long(1).is_err());
+        assert_eq!(rows[1].get_double(2).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_tsv2pq() {
+        let temp_dir = env::temp_dir();
+        let input = temp_dir.join("test_tsv2pq.tsv");
+        let output = temp_dir.join("test_tsv2pq.parquet");
+
+        let mut f = fs::File::create(&input).unwrap();
+        f.write_all(b"g0\t\"a\tquoted\tnote\"\t1\ng1\t\"say \"\"hi\"\"\"\t2\n")
+            .unwrap();
+
+        let csv_options = CsvOptions {
+            delimiter: parse_delimiter("\\t").unwrap(),
+            has_header: false,
+        };
+        csv2pq(
+            &input,
+            &output,
+            Compression::UNCOMPRESSED,
+            &csv_options,
+            &InferOptions::default(),
+        )
+        .unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+        let fields = reader.metadata().file_metadata().schema().get_fields();
+        assert_eq!(fields[1].name(), "column_2");
+
+        let rows: Vec<_> = reader.into_iter().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(1).unwrap(), "a\tquoted\tnote");
+        assert_eq!(rows[1].get_string(1).unwrap(), "say \"hi\"");
+        assert_eq!(rows[1].get_long(2).unwrap(), 2);
+    }
+}
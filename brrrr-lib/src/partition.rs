@@ -0,0 +1,121 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::io;
+use std::str::FromStr;
+
+#[cfg(feature = "parquet")]
+use arrow::array::as_string_array;
+#[cfg(feature = "parquet")]
+use arrow::datatypes::Schema;
+#[cfg(feature = "parquet")]
+use arrow::record_batch::RecordBatch;
+
+#[cfg(feature = "parquet")]
+use crate::errors::BrrrrError;
+
+/// Returns the partition of a row of a batch, as a Hive-style directory name, e.g.
+/// `length_bucket=100-500`.
+#[cfg(feature = "parquet")]
+pub type PartitionKey = Box<dyn Fn(&RecordBatch, usize) -> String + Send>;
+
+/// How a Parquet output is partitioned into Hive-style directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionBy {
+    /// By sequence length, into buckets starting at each of these ascending lengths, e.g.
+    /// `length_bucket=100-500`. The first bucket starts at 0, and the last has no upper bound,
+    /// e.g. `length_bucket=1000-`.
+    Length(Vec<usize>),
+}
+
+impl PartitionBy {
+    /// Returns the partition directory of a sequence of `len` bases, given the `bounds` of a
+    /// `Length` partition, e.g. `length_bucket=100-500`.
+    pub fn length_bucket(bounds: &[usize], len: usize) -> String {
+        // bounds starts with 0, so every length is in some bucket.
+        let i = bounds.partition_point(|&b| b <= len) - 1;
+        match bounds.get(i + 1) {
+            Some(end) => format!("length_bucket={}-{}", bounds[i], end),
+            None => format!("length_bucket={}-", bounds[i]),
+        }
+    }
+
+    /// Returns the function that partitions the rows of batches with `schema`.
+    #[cfg(feature = "parquet")]
+    pub fn key(&self, schema: &Schema) -> Result<PartitionKey, BrrrrError> {
+        match self {
+            PartitionBy::Length(bounds) => {
+                let sequence = schema.index_of("sequence")?;
+                let bounds = bounds.clone();
+                Ok(Box::new(move |batch, row| {
+                    let len = as_string_array(batch.column(sequence)).value(row).len();
+                    Self::length_bucket(&bounds, len)
+                }))
+            }
+        }
+    }
+}
+
+impl FromStr for PartitionBy {
+    type Err = io::Error;
+
+    /// Parses a `key:arguments` partition, e.g. `length:0,100,500,1000`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid partition {:?}: {}", s, reason),
+            )
+        };
+
+        match s.split_once(':') {
+            Some(("length", bounds)) => {
+                let mut bounds = bounds
+                    .split(',')
+                    .map(|b| b.trim().parse::<usize>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("lengths must be whole numbers"))?;
+
+                if !bounds.windows(2).all(|w| w[0] < w[1]) {
+                    return Err(invalid("lengths must be ascending"));
+                }
+                if bounds[0] != 0 {
+                    bounds.insert(0, 0);
+                }
+
+                Ok(PartitionBy::Length(bounds))
+            }
+            _ => Err(invalid("expected length:<lengths>, e.g. length:0,100,500")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_bucket() {
+        let partition: PartitionBy = "length:100,500,1000".parse().unwrap();
+        let PartitionBy::Length(bounds) = &partition;
+        assert_eq!(bounds, &vec![0, 100, 500, 1000]);
+
+        assert_eq!(PartitionBy::length_bucket(bounds, 0), "length_bucket=0-100");
+        assert_eq!(
+            PartitionBy::length_bucket(bounds, 100),
+            "length_bucket=100-500"
+        );
+        assert_eq!(
+            PartitionBy::length_bucket(bounds, 999),
+            "length_bucket=500-1000"
+        );
+        assert_eq!(
+            PartitionBy::length_bucket(bounds, 5000),
+            "length_bucket=1000-"
+        );
+
+        assert!("length:100,100".parse::<PartitionBy>().is_err());
+        assert!("length:a".parse::<PartitionBy>().is_err());
+        assert!("seqname".parse::<PartitionBy>().is_err());
+    }
+}
@@ -0,0 +1,300 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+//! The converters read each record, pass it through the same pipeline of steps, and then write
+//! the records it keeps, in this order regardless of the order flags are given in:
+//!
+//! 1. The readers check each record as they parse it, before the pipeline sees it, e.g.
+//!    [`FastqRecords`](crate::fastq_ops::FastqRecords) fails a read whose sequence and quality
+//!    lengths disagree.
+//! 2. [`Stage::Transform`] steps rewrite the record, e.g. splitting a FASTA id, uppercasing, or
+//!    reversing.
+//! 3. [`Stage::Compute`] steps measure the record for a filter, e.g. its longest homopolymer,
+//!    keeping the measure for its column, so it's only measured once.
+//! 4. [`Stage::Filter`] steps drop records, e.g. by homopolymer run, mean quality, source,
+//!    region, or id list, so filters see transformed records.
+//! 5. A window, see [`Pipeline::set_window`], skips and limits the records that are left. GFF
+//!    inputs are sorted, with `sort`, just before the window, so dropped features are never
+//!    sorted, and the window is of the sorted features.
+//! 6. The converters compute the other extra columns, e.g. `gc_skew` or `mean_quality`, and the
+//!    stats sidecar, from the records the pipeline keeps, so they see the transformed records
+//!    and dropped records are never measured.
+//!
+//! Steps at the same stage run in the order they're added.
+//!
+//! [`Stage::Transform`]: crate::pipeline::Stage::Transform
+//! [`Stage::Compute`]: crate::pipeline::Stage::Compute
+//! [`Stage::Filter`]: crate::pipeline::Stage::Filter
+//! [`Pipeline::set_window`]: crate::pipeline::Pipeline::set_window
+
+use std::io;
+
+/// A stage of the pipeline. Stages run in the order they're declared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Transform,
+    Compute,
+    Filter,
+}
+
+type Step<'a, T> = Box<dyn FnMut(T) -> io::Result<Option<T>> + 'a>;
+
+/// A sequence of steps applied to each record, ordered by [`Stage`].
+///
+/// Each step takes a record and returns it, possibly changed, or None to drop it.
+pub struct Pipeline<'a, T> {
+    steps: Vec<(Stage, Step<'a, T>)>,
+    dropped: usize,
+    /// The number of kept records still to skip.
+    skip: usize,
+    /// The number of kept records still to return, if they're limited.
+    remaining: Option<usize>,
+}
+
+impl<'a, T> Default for Pipeline<'a, T> {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            dropped: 0,
+            skip: 0,
+            remaining: None,
+        }
+    }
+}
+
+impl<'a, T> Pipeline<'a, T> {
+    /// Adds `step` at `stage`, after any steps already added at the same stage.
+    pub fn add<F>(&mut self, stage: Stage, step: F)
+    where
+        F: FnMut(T) -> io::Result<Option<T>> + 'a,
+    {
+        let position = self.steps.partition_point(|(s, _)| *s <= stage);
+        self.steps.insert(position, (stage, Box::new(step)));
+    }
+
+    /// Adds a [`Stage::Filter`] step that keeps records matching `predicate`.
+    pub fn add_filter<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&T) -> bool + 'a,
+    {
+        self.add(Stage::Filter, move |record| {
+            Ok(if predicate(&record) {
+                Some(record)
+            } else {
+                None
+            })
+        });
+    }
+
+    /// Skips the first `skip` records that pass every step, and then keeps at most `limit`, e.g.
+    /// to make a small test file from a large input. Skipped records don't count as dropped.
+    pub fn set_window(&mut self, skip: usize, limit: Option<usize>) {
+        self.skip = skip;
+        self.remaining = limit;
+    }
+
+    /// Returns whether the window's limit has been reached, so no more records will be kept and
+    /// the rest of the input needn't be read.
+    pub fn done(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    /// Passes `record` through each step and then the window, returning it if it's kept.
+    pub fn run(&mut self, record: T) -> io::Result<Option<T>> {
+        if self.done() {
+            return Ok(None);
+        }
+
+        Ok(self.apply(record)?.and_then(|record| self.window(record)))
+    }
+
+    /// Passes `record` through each step, but not the window, returning it if it's kept, e.g.
+    /// to drop records before they're sorted and window them after, with [`Pipeline::window`].
+    pub fn apply(&mut self, record: T) -> io::Result<Option<T>> {
+        let mut record = record;
+
+        for (_, step) in self.steps.iter_mut() {
+            match step(record)? {
+                Some(r) => record = r,
+                None => {
+                    self.dropped += 1;
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(record))
+    }
+
+    /// Passes a record the steps kept, see [`Pipeline::apply`], through the window, returning it
+    /// if it's kept.
+    pub fn window(&mut self, record: T) -> Option<T> {
+        if self.done() {
+            return None;
+        }
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+
+        Some(record)
+    }
+
+    /// Returns an iterator over the records of `records` the pipeline keeps, which stops reading
+    /// `records` once the window's limit is reached.
+    pub fn kept<I>(&mut self, records: I) -> Kept<'_, 'a, T, I>
+    where
+        I: Iterator<Item = io::Result<T>>,
+    {
+        Kept {
+            pipeline: self,
+            records,
+        }
+    }
+
+    /// Returns an iterator like [`Pipeline::kept`], over records paired with a value the steps
+    /// don't see, which is returned alongside each kept record.
+    pub fn kept_with<U, I>(&mut self, records: I) -> KeptWith<'_, 'a, T, I>
+    where
+        I: Iterator<Item = io::Result<(T, U)>>,
+    {
+        KeptWith {
+            pipeline: self,
+            records,
+        }
+    }
+
+    /// Returns the number of records dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// An iterator over the records a pipeline keeps, see [`Pipeline::kept`].
+pub struct Kept<'p, 'a, T, I> {
+    pipeline: &'p mut Pipeline<'a, T>,
+    records: I,
+}
+
+impl<'p, 'a, T, I> Iterator for Kept<'p, 'a, T, I>
+where
+    I: Iterator<Item = io::Result<T>>,
+{
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.pipeline.done() {
+            let kept = self
+                .records
+                .next()?
+                .and_then(|record| self.pipeline.run(record));
+            match kept {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        None
+    }
+}
+
+/// An iterator over the paired records a pipeline keeps, see [`Pipeline::kept_with`].
+pub struct KeptWith<'p, 'a, T, I> {
+    pipeline: &'p mut Pipeline<'a, T>,
+    records: I,
+}
+
+impl<'p, 'a, T, U, I> Iterator for KeptWith<'p, 'a, T, I>
+where
+    I: Iterator<Item = io::Result<(T, U)>>,
+{
+    type Item = io::Result<(T, U)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.pipeline.done() {
+            let (record, value) = match self.records.next()? {
+                Ok(pair) => pair,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.pipeline.run(record) {
+                Ok(Some(record)) => return Some(Ok((record, value))),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_order() {
+        let mut pipeline: Pipeline<(String, usize)> = Pipeline::default();
+
+        // Added out of order: the length filter must still see the trimmed, uppercased record,
+        // and its computed length.
+        pipeline.add_filter(|(_, len)| *len >= 3);
+        pipeline.add(Stage::Compute, |(s, _)| {
+            let len = s.len();
+            Ok(Some((s, len)))
+        });
+        pipeline.add(Stage::Transform, |(s, len)| {
+            Ok(Some((s.to_uppercase(), len)))
+        });
+        pipeline.add(Stage::Transform, |(s, len)| {
+            Ok(Some((s.trim_end_matches('N').to_string(), len)))
+        });
+        pipeline.add_filter(|(s, _)| s.starts_with('A'));
+
+        let run = |pipeline: &mut Pipeline<(String, usize)>, s: &str| {
+            pipeline.run((s.to_string(), 0)).unwrap().map(|(s, _)| s)
+        };
+        assert_eq!(run(&mut pipeline, "acgnn"), Some("ACG".into()));
+        assert_eq!(run(&mut pipeline, "acnnn"), None);
+        assert_eq!(run(&mut pipeline, "tcga"), None);
+        assert_eq!(pipeline.dropped(), 2);
+    }
+
+    #[test]
+    fn test_errors_stop_the_pipeline() {
+        let mut pipeline: Pipeline<&str> = Pipeline::default();
+        pipeline.add_filter(|_| false);
+        pipeline.add(Stage::Transform, |s| {
+            if s.is_empty() {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "empty record"))
+            } else {
+                Ok(Some(s))
+            }
+        });
+
+        assert!(pipeline.run("").is_err());
+        assert_eq!(pipeline.run("a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut pipeline: Pipeline<u32> = Pipeline::default();
+        pipeline.add_filter(|n| n % 2 == 0);
+        pipeline.set_window(1, Some(2));
+
+        // The window counts kept records, and the input isn't read past the last one.
+        let mut read = 0;
+        let records = (0..100).map(|n| {
+            read += 1;
+            Ok(n)
+        });
+        let kept: Vec<u32> = pipeline.kept(records).map(Result::unwrap).collect();
+        assert_eq!(kept, vec![2, 4]);
+        assert!(pipeline.done());
+        assert_eq!(pipeline.dropped(), 2);
+        assert_eq!(read, 5);
+    }
+}
@@ -0,0 +1,767 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+use arrow::array::*;
+use arrow::datatypes::*;
+use arrow::record_batch::RecordBatch;
+use itertools::Either;
+use noodles::fasta;
+
+use crate::errors::{BrrrrError, ParseError};
+use crate::fasta_ops::{FastaOptions, FastaRecords, SkipComments};
+use crate::fastq_ops::FastqOptions;
+use crate::gff_ops::{GffOptions, ScoreType};
+use crate::pipeline::Pipeline;
+use crate::sequence::{entropy, gc_content, gc_skew, max_homopolymer, mean_quality};
+use crate::translation::longest_orf;
+use crate::types::{FastaRecord, FastqRecord, GffRecord};
+
+/// Writes `schema` as pretty JSON, with the name, arrow type, and nullability of each column.
+///
+/// # Arguments
+///
+/// * `schema` the schema to write, e.g. from `fasta_schema`.
+/// * `output` an output that implements the Write trait.
+pub fn write_schema<W: Write>(schema: &Schema, output: &mut W) -> Result<(), BrrrrError> {
+    serde_json::to_writer_pretty(&mut *output, &schema.to_json()).map_err(io::Error::from)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Returns the schema of FASTA batches, with the extra columns `options` asks for.
+pub fn fasta_schema(options: &FastaOptions) -> Schema {
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("description", DataType::Utf8, true),
+        Field::new("sequence", DataType::Utf8, false),
+    ];
+    if options.with_header {
+        fields.push(Field::new("header", DataType::Utf8, true));
+    }
+    if options.with_gc_skew {
+        fields.push(Field::new("gc_skew", DataType::Float64, true));
+    }
+    if options.with_entropy {
+        fields.push(Field::new("complexity", DataType::Float64, true));
+    }
+    if options.with_max_homopolymer {
+        fields.push(Field::new("max_homopolymer", DataType::Int64, false));
+    }
+    if options.with_orf {
+        fields.push(Field::new("orf_length", DataType::Int64, true));
+        if options.with_orf_position {
+            fields.push(Field::new("orf_start", DataType::Int64, true));
+            fields.push(Field::new("orf_frame", DataType::Int64, true));
+        }
+    }
+
+    Schema::new(fields)
+}
+
+/// Builds a batch of `records`, with the columns of [`fasta_schema`] for `options`, for
+/// applications that already have parsed records, e.g. from their own reader.
+///
+/// # Arguments
+///
+/// * `records` the records of the batch.
+/// * `schema` the schema of the batch, from [`fasta_schema`].
+/// * `options` the options the schema was made from, which also configure the extra columns.
+pub fn build_fasta_batch(
+    records: &[FastaRecord],
+    schema: &Arc<Schema>,
+    options: &FastaOptions,
+) -> Result<RecordBatch, BrrrrError> {
+    let mut id_builder = StringBuilder::new(2048);
+    let mut description_builder = StringBuilder::new(2048);
+    let mut seq_builder = StringBuilder::new(2048);
+    let mut header_builder = StringBuilder::new(2048);
+    let mut gc_skew_builder = Float64Builder::new(2048);
+    let mut complexity_builder = Float64Builder::new(2048);
+    let mut max_homopolymer_builder = Int64Builder::new(2048);
+    let mut orf_length_builder = Int64Builder::new(2048);
+    let mut orf_start_builder = Int64Builder::new(2048);
+    let mut orf_frame_builder = Int64Builder::new(2048);
+
+    for record in records {
+        let sequence = record.sequence.as_bytes();
+
+        id_builder.append_value(&record.id)?;
+        match &record.description {
+            Some(x) => description_builder.append_value(x)?,
+            None => description_builder.append_null()?,
+        }
+        seq_builder.append_value(&record.sequence)?;
+
+        if options.with_header {
+            header_builder.append_option(record.header.as_ref())?;
+        }
+        if options.with_gc_skew {
+            gc_skew_builder.append_option(gc_skew(sequence))?;
+        }
+        if options.with_entropy {
+            complexity_builder.append_option(entropy(sequence, options.entropy_window))?;
+        }
+        if options.with_max_homopolymer {
+            let run = record
+                .max_homopolymer
+                .unwrap_or_else(|| max_homopolymer(sequence));
+            max_homopolymer_builder.append_value(run as i64)?;
+        }
+        if options.with_orf {
+            let orf = longest_orf(sequence, options.codon_table, options.orf_require_start);
+            orf_length_builder.append_option(orf.as_ref().map(|o| o.length as i64))?;
+            orf_start_builder.append_option(orf.as_ref().map(|o| o.start as i64))?;
+            orf_frame_builder.append_option(orf.as_ref().map(|o| o.frame as i64))?;
+        }
+    }
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(id_builder.finish()),
+        Arc::new(description_builder.finish()),
+        Arc::new(seq_builder.finish()),
+    ];
+    if options.with_header {
+        columns.push(Arc::new(header_builder.finish()));
+    }
+    if options.with_gc_skew {
+        columns.push(Arc::new(gc_skew_builder.finish()));
+    }
+    if options.with_entropy {
+        columns.push(Arc::new(complexity_builder.finish()));
+    }
+    if options.with_max_homopolymer {
+        columns.push(Arc::new(max_homopolymer_builder.finish()));
+    }
+    if options.with_orf {
+        columns.push(Arc::new(orf_length_builder.finish()));
+        if options.with_orf_position {
+            columns.push(Arc::new(orf_start_builder.finish()));
+            columns.push(Arc::new(orf_frame_builder.finish()));
+        }
+    }
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Iterates over the records of a FASTA input as Arrow record batches, each built from up to
+/// `chunk_size` input records, after the filters in the options. With `batch_bytes` in the
+/// options, each is instead built from the kept records whose sequences add up to that many bytes.
+pub struct FastaBatches<'a, R> {
+    reader: fasta::Reader<Either<R, SkipComments<R>>>,
+    schema: Arc<Schema>,
+    options: &'a FastaOptions,
+    pipeline: Pipeline<'a, FastaRecord>,
+    chunk_size: usize,
+}
+
+impl<'a, R: BufRead> FastaBatches<'a, R> {
+    /// Creates an iterator over the batches of `reader`.
+    pub fn new(reader: R, chunk_size: usize, options: &'a FastaOptions) -> Self {
+        Self {
+            reader: fasta::Reader::new(if options.allow_comments {
+                Either::Right(SkipComments::new(reader))
+            } else {
+                Either::Left(reader)
+            }),
+            schema: Arc::new(fasta_schema(options)),
+            options,
+            pipeline: options.pipeline(),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Returns the schema of the batches.
+    pub fn schema(&self) -> Arc<Schema> {
+        self.schema.clone()
+    }
+
+    /// Returns the number of records the filters in the options have dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.pipeline.dropped()
+    }
+}
+
+impl<'a, R: BufRead> Iterator for FastaBatches<'a, R> {
+    type Item = Result<RecordBatch, BrrrrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut kept = Vec::new();
+        let mut read = 0;
+        let mut bytes = 0;
+
+        let records = FastaRecords::new(&mut self.reader, self.options.with_header);
+        for result in records {
+            // With a limit, the rest of the input isn't read once it's reached.
+            if self.pipeline.done() {
+                break;
+            }
+            read += 1;
+
+            let record = match result {
+                Ok(r) => r,
+                Err(e) => return Some(Err(e.into())),
+            };
+            match self.pipeline.run(record) {
+                Ok(Some(record)) => {
+                    bytes += record.sequence.len();
+                    kept.push(record);
+                }
+                Ok(None) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            let full = match self.options.batch_bytes {
+                Some(batch_bytes) => bytes >= batch_bytes.max(1),
+                None => read >= self.chunk_size,
+            };
+            if full {
+                // A chunk the filters emptied isn't returned as an empty batch, so read the next.
+                if !kept.is_empty() {
+                    break;
+                }
+                read = 0;
+            }
+        }
+
+        if kept.is_empty() {
+            return None;
+        }
+        Some(build_fasta_batch(&kept, &self.schema, self.options))
+    }
+}
+
+/// Reads a FASTA input into Arrow record batches of up to `chunk_size` records each, for
+/// applications that work with Arrow directly, e.g. DataFusion. See [`FastaBatches`] to stream
+/// the batches instead of holding them all.
+///
+/// # Arguments
+///
+/// * `reader` the FASTA input.
+/// * `chunk_size` the number of input records read for each batch.
+/// * `options` the options for the conversion, e.g. extra columns.
+pub fn fa_to_record_batches<R: BufRead>(
+    reader: R,
+    chunk_size: usize,
+    options: &FastaOptions,
+) -> Result<Vec<RecordBatch>, BrrrrError> {
+    FastaBatches::new(reader, chunk_size, options).collect()
+}
+
+/// Returns the schema of FASTQ batches, with the extra columns `options` asks for. The quality
+/// column is only nullable with `allow_star_qual`, for reads with a placeholder `*` quality.
+pub fn fastq_schema(options: &FastqOptions) -> Schema {
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("sequence", DataType::Utf8, false),
+        Field::new("description", DataType::Utf8, true),
+        Field::new("quality", DataType::Utf8, options.allow_star_qual),
+        Field::new("number", DataType::Int64, true),
+    ];
+    if options.with_mean_qual {
+        fields.push(Field::new("mean_quality", DataType::Float64, true));
+    }
+    if options.with_gc {
+        fields.push(Field::new("gc", DataType::Float64, true));
+    }
+
+    Schema::new(fields)
+}
+
+/// Builds a batch of `records`, with the columns of [`fastq_schema`] for `options`.
+///
+/// # Arguments
+///
+/// * `records` the records of the batch.
+/// * `first_number` the `number` of the first record, the rest are numbered on from it.
+/// * `schema` the schema of the batch, from [`fastq_schema`].
+/// * `options` the options the schema was made from.
+pub fn build_fastq_batch(
+    records: &[FastqRecord],
+    first_number: i64,
+    schema: &Arc<Schema>,
+    options: &FastqOptions,
+) -> Result<RecordBatch, BrrrrError> {
+    let mut id_builder = StringBuilder::new(2048);
+    let mut description_builder = StringBuilder::new(2048);
+    let mut seq_builder = StringBuilder::new(2048);
+    let mut quality_builder = StringBuilder::new(2048);
+    let mut read_number_builder = Int64Builder::new(2048);
+    let mut mean_quality_builder = Float64Builder::new(2048);
+    let mut gc_builder = Float64Builder::new(2048);
+
+    for (number, record) in (first_number..).zip(records) {
+        id_builder.append_value(&record.id)?;
+        match &record.description {
+            Some(x) => description_builder.append_value(x)?,
+            None => description_builder.append_null()?,
+        }
+        seq_builder.append_value(&record.sequence)?;
+        if options.allow_star_qual && record.has_placeholder_quality() {
+            quality_builder.append_null()?;
+        } else {
+            quality_builder.append_value(&record.quality)?;
+        }
+        read_number_builder.append_value(number)?;
+        if options.with_mean_qual {
+            mean_quality_builder.append_option(
+                (!record.has_placeholder_quality())
+                    .then(|| mean_quality(record.quality.as_bytes(), options.quality_offset()))
+                    .flatten(),
+            )?;
+        }
+        if options.with_gc {
+            gc_builder.append_option(gc_content(record.sequence.as_bytes()))?;
+        }
+    }
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(id_builder.finish()),
+        Arc::new(seq_builder.finish()),
+        Arc::new(description_builder.finish()),
+        Arc::new(quality_builder.finish()),
+        Arc::new(read_number_builder.finish()),
+    ];
+    if options.with_mean_qual {
+        columns.push(Arc::new(mean_quality_builder.finish()));
+    }
+    if options.with_gc {
+        columns.push(Arc::new(gc_builder.finish()));
+    }
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Returns the schema of GFF batches, with the score and attribute value types `options` asks
+/// for.
+pub fn gff_schema(options: &GffOptions) -> Schema {
+    let score_type = match options.score_type {
+        ScoreType::Int => DataType::Int64,
+        ScoreType::Float => DataType::Float64,
+        ScoreType::Str => DataType::Utf8,
+    };
+    let value_type = if options.multivalue_attributes {
+        DataType::List(Box::new(Field::new("item", DataType::Utf8, true)))
+    } else {
+        DataType::Utf8
+    };
+
+    Schema::new(vec![
+        Field::new("seqname", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, true),
+        Field::new("feature", DataType::Utf8, false),
+        Field::new("start", DataType::Int64, false),
+        Field::new("end", DataType::Int64, false),
+        Field::new("score", score_type, true),
+        Field::new("strand", DataType::Utf8, true),
+        Field::new("frame", DataType::Utf8, true),
+        Field::new(
+            "attribute",
+            DataType::Map(
+                Box::new(Field::new(
+                    "entries",
+                    DataType::Struct(vec![
+                        Field::new("keys", DataType::Utf8, false),
+                        Field::new("values", value_type, true),
+                    ]),
+                    false,
+                )),
+                false,
+            ),
+            false,
+        ),
+    ])
+}
+
+/// Builds a batch of `records`, with the columns of [`gff_schema`].
+///
+/// # Arguments
+///
+/// * `records` the records of the batch.
+/// * `schema` the schema of the batch, from [`gff_schema`].
+/// * `options` the options the schema was made with.
+pub fn build_gff_batch(
+    records: &[GffRecord],
+    schema: &Arc<Schema>,
+    options: &GffOptions,
+) -> Result<RecordBatch, BrrrrError> {
+    let mut seqname_builder = StringBuilder::new(2048);
+    let mut source_builder = StringBuilder::new(2048);
+    let mut feature_builder = StringBuilder::new(2048);
+    let mut start_builder = Int64Builder::new(2048);
+    let mut end_builder = Int64Builder::new(2048);
+    let mut strand_builder = StringBuilder::new(2048);
+    let mut frame_builder = StringBuilder::new(2048);
+
+    for record in records {
+        seqname_builder.append_value(&record.seqname)?;
+        source_builder.append_option(record.source.as_ref())?;
+        feature_builder.append_value(&record.feature)?;
+        start_builder.append_value(record.start as i64)?;
+        end_builder.append_value(record.end as i64)?;
+
+        strand_builder.append_option(record.strand.as_ref())?;
+        frame_builder.append_option(record.frame.as_ref())?;
+    }
+
+    let attributes = if options.multivalue_attributes {
+        attribute_array(
+            records,
+            options,
+            ListBuilder::new(StringBuilder::new(2048)),
+            |builder, value| {
+                for item in value.split(',') {
+                    builder.values().append_value(item)?;
+                }
+                builder.append(true)
+            },
+        )?
+    } else {
+        attribute_array(
+            records,
+            options,
+            StringBuilder::new(2048),
+            |builder, value| builder.append_value(value),
+        )?
+    };
+
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(seqname_builder.finish()),
+            Arc::new(source_builder.finish()),
+            Arc::new(feature_builder.finish()),
+            Arc::new(start_builder.finish()),
+            Arc::new(end_builder.finish()),
+            score_array(records, options.score_type)?,
+            Arc::new(strand_builder.finish()),
+            Arc::new(frame_builder.finish()),
+            attributes,
+        ],
+    )?)
+}
+
+/// Builds the attribute column of a GFF batch, with `append_value` appending each value to
+/// `value_builder`.
+fn attribute_array<V, F>(
+    records: &[GffRecord],
+    options: &GffOptions,
+    value_builder: V,
+    append_value: F,
+) -> Result<ArrayRef, BrrrrError>
+where
+    V: ArrayBuilder,
+    F: Fn(&mut V, &str) -> arrow::error::Result<()>,
+{
+    let mut attribute_builder = MapBuilder::new(None, StringBuilder::new(2048), value_builder);
+
+    for record in records {
+        // Each key is appended with its value, from one pass over the attributes, so they can't
+        // fall out of step.
+        let mut attributes: Vec<_> = record.attribute.iter().collect();
+        if options.sort_attributes {
+            attributes.sort_unstable();
+        }
+        for (key, value) in attributes {
+            attribute_builder.keys().append_value(key)?;
+            append_value(attribute_builder.values(), value)?;
+        }
+
+        attribute_builder.append(true)?;
+    }
+
+    Ok(Arc::new(attribute_builder.finish()))
+}
+
+/// Builds the score column of a GFF batch.
+fn score_array(records: &[GffRecord], score_type: ScoreType) -> Result<ArrayRef, BrrrrError> {
+    // Scores are parsed as f32, so they're widened through their shortest text form, e.g. 0.1
+    // is written as 0.1 rather than 0.10000000149011612.
+    let scores = records.iter().map(|r| {
+        r.score
+            .map(|s| s.to_string().parse::<f64>().unwrap_or(s as f64))
+    });
+
+    Ok(match score_type {
+        ScoreType::Float => Arc::new(scores.collect::<Float64Array>()),
+        // The input's own text is parsed where it's known, since an f32 can't hold every integer
+        // score exactly, e.g. 16777217.
+        ScoreType::Int => Arc::new(
+            records
+                .iter()
+                .map(|record| {
+                    let not_integer = |score: &dyn std::fmt::Display| {
+                        ParseError::new(format!("score {} isn't an integer", score))
+                            .with_id(format!("{}:{}", record.seqname, record.start))
+                    };
+                    match (&record.score_text, record.score) {
+                        (Some(text), _) if text == "." => Ok(None),
+                        (Some(text), _) => {
+                            text.parse::<i64>().map(Some).map_err(|_| not_integer(text))
+                        }
+                        (None, Some(s)) if s.fract() != 0.0 => Err(not_integer(&s)),
+                        (None, score) => Ok(score.map(|s| s as i64)),
+                    }
+                })
+                .collect::<Result<Int64Array, _>>()
+                .map_err(BrrrrError::Parse)?,
+        ),
+        // The input's own text is kept where it's known, e.g. 1e3 rather than 1000.
+        ScoreType::Str => Arc::new(
+            records
+                .iter()
+                .map(|r| match &r.score_text {
+                    Some(text) => Some(text.clone()),
+                    None => Some(r.score.map_or_else(|| String::from("."), |s| s.to_string())),
+                })
+                .collect::<StringArray>(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fa_to_record_batches() {
+        let fasta = b">a\nACGT\n>b\nAAAAA\n>c desc\nGG\n" as &[u8];
+
+        let batches = fa_to_record_batches(fasta, 2, &FastaOptions::default()).unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[1].num_rows(), 1);
+
+        let descriptions = batches[1]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(descriptions.value(0), "desc");
+
+        // The second chunk is filtered away, and the iterator moves on to the last.
+        let fasta = b">a\nACGT\n>b\nAAAAA\n>c\nAAAAA\n>d\nGG\n" as &[u8];
+        let options = FastaOptions {
+            with_max_homopolymer: true,
+            max_homopolymer_filter: Some(4),
+            ..Default::default()
+        };
+        let mut batches = FastaBatches::new(fasta, 1, &options);
+        assert_eq!(batches.schema().fields().len(), 4);
+        let rows: Vec<_> = batches.by_ref().map(|b| b.unwrap().num_rows()).collect();
+        assert_eq!(rows, vec![1, 1]);
+        assert_eq!(batches.dropped(), 2);
+    }
+
+    #[test]
+    fn test_fasta_batches_by_bytes() {
+        let fasta = b">a\nACGTACGT\n>b\nAC\n>c\nACG\n>d\nACGTACGTAC\n>e\nA\n" as &[u8];
+        let options = FastaOptions {
+            batch_bytes: Some(5),
+            ..Default::default()
+        };
+
+        // The record count is ignored, and a batch ends once its sequences reach 5 bytes.
+        let rows: Vec<_> = FastaBatches::new(fasta, 1, &options)
+            .map(|b| b.unwrap().num_rows())
+            .collect();
+        assert_eq!(rows, vec![1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_fasta_batches_crlf() {
+        // Windows line endings are dropped with the newlines, even on comment lines.
+        let fasta = b">a one\r\n;note\r\nACGT\r\nAC\r\n>b\r\nGG\r\n" as &[u8];
+        let options = FastaOptions {
+            with_header: true,
+            allow_comments: true,
+            ..Default::default()
+        };
+
+        let batch = FastaBatches::new(fasta, 10, &options)
+            .next()
+            .unwrap()
+            .unwrap();
+        let column = |i: usize| {
+            let array = batch.column(i).as_any().downcast_ref::<StringArray>();
+            (0..2).map(|j| array.unwrap().value(j)).collect::<Vec<_>>()
+        };
+        assert_eq!(column(0), vec!["a", "b"]);
+        assert_eq!(column(1), vec!["one", ""]);
+        assert_eq!(column(2), vec!["ACGTAC", "GG"]);
+        assert_eq!(column(3), vec!["a one", "b"]);
+    }
+
+    #[test]
+    fn test_build_batches() {
+        let fastq = vec![FastqRecord {
+            id: "r0".to_string(),
+            description: None,
+            sequence: "AC".to_string(),
+            quality: "55".to_string(),
+        }];
+        let options = FastqOptions {
+            with_mean_qual: true,
+            ..Default::default()
+        };
+        let schema = Arc::new(fastq_schema(&options));
+        let batch = build_fastq_batch(&fastq, 7, &schema, &options).unwrap();
+        let numbers = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(numbers.value(0), 7);
+        assert_eq!(batch.num_columns(), 6);
+        assert!(!schema.field(3).is_nullable());
+
+        // Only with allow_star_qual is a placeholder quality written, as null.
+        let star = vec![FastqRecord {
+            quality: "*".to_string(),
+            ..fastq[0].clone()
+        }];
+        let options = FastqOptions {
+            allow_star_qual: true,
+            with_mean_qual: true,
+            ..Default::default()
+        };
+        let schema = Arc::new(fastq_schema(&options));
+        assert!(schema.field(3).is_nullable());
+        let batch = build_fastq_batch(&star, 0, &schema, &options).unwrap();
+        assert!(batch.column(3).is_null(0));
+        assert!(batch.column(5).is_null(0));
+
+        let gff = vec![GffRecord {
+            seqname: "chr1".to_string(),
+            source: Some("src".to_string()),
+            feature: "gene".to_string(),
+            start: 1,
+            end: 10,
+            score: None,
+            strand: Some("+".to_string()),
+            frame: None,
+            attribute: vec![("ID".to_string(), "g0".to_string())]
+                .into_iter()
+                .collect(),
+            score_text: None,
+        }];
+        let options = GffOptions::default();
+        let batch = build_gff_batch(&gff, &Arc::new(gff_schema(&options)), &options).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch.column(5).is_null(0));
+    }
+
+    #[test]
+    fn test_gff_attribute_pairs() {
+        let pairs = [
+            ("gene_name", "BRCA1"),
+            ("ID", "g0"),
+            ("Parent", "t1"),
+            ("biotype", "protein_coding"),
+            ("Alias", "a0"),
+        ];
+        let gff = vec![GffRecord {
+            seqname: "chr1".to_string(),
+            start: 1,
+            attribute: pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        }];
+
+        for sort_attributes in [false, true] {
+            let options = GffOptions {
+                sort_attributes,
+                ..Default::default()
+            };
+            let batch = build_gff_batch(&gff, &Arc::new(gff_schema(&options)), &options).unwrap();
+            let map = batch.column(8).as_any().downcast_ref::<MapArray>().unwrap();
+            let keys = map.keys();
+            let keys = as_string_array(&keys);
+            let values = map.values();
+            let values = as_string_array(&values);
+
+            let mut read: Vec<_> = (0..keys.len())
+                .map(|i| (keys.value(i), values.value(i)))
+                .collect();
+            if sort_attributes {
+                let keys: Vec<_> = read.iter().map(|(key, _)| *key).collect();
+                assert_eq!(keys, ["Alias", "ID", "Parent", "biotype", "gene_name"]);
+            }
+
+            read.sort_unstable();
+            let mut expected = pairs.to_vec();
+            expected.sort_unstable();
+            assert_eq!(read, expected);
+        }
+    }
+
+    #[test]
+    fn test_score_types() {
+        let record = |score| GffRecord {
+            seqname: "chr1".to_string(),
+            start: 5,
+            score,
+            ..Default::default()
+        };
+        let records = vec![record(Some(0.1)), record(None)];
+
+        let scores = score_array(&records, ScoreType::Float).unwrap();
+        let scores = scores.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(scores.value(0), 0.1);
+        assert!(scores.is_null(1));
+
+        let scores = score_array(&records, ScoreType::Str).unwrap();
+        let scores = as_string_array(&scores);
+        assert_eq!(scores.value(0), "0.1");
+        assert_eq!(scores.value(1), ".");
+
+        let texts = vec![GffRecord {
+            score_text: Some(String::from("1e3")),
+            ..record(Some(1000.0))
+        }];
+        let scores = score_array(&texts, ScoreType::Str).unwrap();
+        assert_eq!(as_string_array(&scores).value(0), "1e3");
+
+        let error = score_array(&records, ScoreType::Int).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "parse error, record chr1:5: score 0.1 isn't an integer"
+        );
+
+        let records = vec![record(Some(12.0)), record(None)];
+        let scores = score_array(&records, ScoreType::Int).unwrap();
+        let scores = scores.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(scores.value(0), 12);
+        assert!(scores.is_null(1));
+
+        // Integers past an f32's precision are read from the text.
+        let texts = vec![
+            GffRecord {
+                score_text: Some(String::from("16777217")),
+                ..record(Some(16777216.0))
+            },
+            GffRecord {
+                score_text: Some(String::from(".")),
+                ..record(None)
+            },
+        ];
+        let scores = score_array(&texts, ScoreType::Int).unwrap();
+        let scores = scores.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(scores.value(0), 16777217);
+        assert!(scores.is_null(1));
+
+        let texts = vec![GffRecord {
+            score_text: Some(String::from("2.5")),
+            ..record(Some(2.5))
+        }];
+        let error = score_array(&texts, ScoreType::Int).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "parse error, record chr1:5: score 2.5 isn't an integer"
+        );
+    }
+}
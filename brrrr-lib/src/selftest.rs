@@ -0,0 +1,272 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use noodles::fasta;
+use noodles::fastq;
+use parquet::basic::Compression;
+
+use crate::errors::BrrrrError;
+use crate::fasta_ops::FastaOptions;
+use crate::fastq_ops::FastqOptions;
+use crate::gff_ops::GffOptions;
+use crate::json_writer::fq2jsonl;
+use crate::parquet_reader::{pq2fa, pq2fq, pq2gff, GffOutputOptions};
+use crate::parquet_writer::{fa2pq, fq2pq, gff2pq, BioFileCompression};
+use crate::types::FastqRecord;
+
+/// The number of records generated for each synthetic input.
+const RECORD_COUNT: usize = 50;
+
+/// The result of one converter round trip.
+#[derive(Debug, Clone)]
+pub struct RoundTrip {
+    /// The conversions the input went through, e.g. `fa -> parquet -> fa`.
+    pub name: &'static str,
+    /// Whether the round-tripped output matched the input.
+    pub passed: bool,
+}
+
+/// A small linear congruential generator, so synthetic inputs, e.g. the selftest's and the
+/// benchmarks', are the same on every run.
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    /// Returns the next number, below `bound`.
+    pub fn next(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % bound
+    }
+
+    /// Returns a sequence of `len` bases drawn from `alphabet`, e.g. `b"ACGT"`.
+    pub fn sequence(&mut self, len: usize, alphabet: &[u8]) -> Vec<u8> {
+        (0..len)
+            .map(|_| alphabet[self.next(alphabet.len())])
+            .collect()
+    }
+}
+
+/// Runs each round trip in a temporary directory and returns whether each one matched.
+///
+/// The FASTA (`fa -> parquet -> fa`) and FASTQ (`fq -> parquet -> fq` and `fq -> jsonl -> fq`)
+/// round trips must match byte for byte. The GFF round trip (`gff -> parquet -> gff`) stores
+/// attributes as a map, so each record's attributes are sorted by key before comparing. Its
+/// directive and comment lines are kept in a sidecar file and prepended again.
+///
+/// An error is returned if a converter fails outright, rather than producing different output.
+pub fn run() -> Result<Vec<RoundTrip>, BrrrrError> {
+    let dir = tempfile::tempdir()?;
+    let mut rng = Lcg(42);
+
+    Ok(vec![
+        RoundTrip {
+            name: "fa -> parquet -> fa",
+            passed: fasta_round_trip(dir.path(), &mut rng)?,
+        },
+        RoundTrip {
+            name: "fq -> parquet -> fq",
+            passed: fastq_round_trip(dir.path(), &mut rng)?,
+        },
+        RoundTrip {
+            name: "fq -> jsonl -> fq",
+            passed: fastq_jsonl_round_trip(dir.path(), &mut rng)?,
+        },
+        RoundTrip {
+            name: "gff -> parquet -> gff",
+            passed: gff_round_trip(dir.path(), &mut rng)?,
+        },
+    ])
+}
+
+fn fasta_round_trip(dir: &Path, rng: &mut Lcg) -> Result<bool, BrrrrError> {
+    let input = dir.join("selftest.fa");
+    let parquet = dir.join("selftest.fa.parquet");
+    let output = dir.join("selftest.roundtrip.fa");
+
+    // Lengths go past the 80 base line width, so wrapped sequences are covered too.
+    let mut writer = fasta::Writer::new(fs::File::create(&input)?);
+    for i in 0..RECORD_COUNT {
+        let description = if i % 3 == 0 {
+            None
+        } else {
+            Some(format!("sample={}", i))
+        };
+        let definition = fasta::record::Definition::new(format!("seq{}", i), description);
+        let len = 1 + rng.next(250);
+        let sequence = fasta::record::Sequence::from(rng.sequence(len, b"ACGTN"));
+        writer.write_record(&fasta::Record::new(definition, sequence))?;
+    }
+    drop(writer);
+
+    fa2pq(
+        &input,
+        &parquet,
+        Compression::UNCOMPRESSED,
+        BioFileCompression::UNCOMPRESSED,
+        &FastaOptions::default(),
+    )?;
+    pq2fa(&parquet, &output)?;
+
+    Ok(fs::read(&input)? == fs::read(&output)?)
+}
+
+/// Writes synthetic FASTQ reads to `path`.
+fn write_fastq(path: &Path, rng: &mut Lcg) -> io::Result<()> {
+    let mut writer = fastq::Writer::new(fs::File::create(path)?);
+    for i in 0..RECORD_COUNT {
+        let len = 1 + rng.next(150);
+        let sequence = rng.sequence(len, b"ACGTN");
+        let quality: Vec<u8> = (0..len).map(|_| b'!' + rng.next(42) as u8).collect();
+        let name = format!("read{}", i);
+        writer.write_record(&fastq::Record::new(name.as_bytes(), sequence, quality))?;
+    }
+    Ok(())
+}
+
+fn fastq_round_trip(dir: &Path, rng: &mut Lcg) -> Result<bool, BrrrrError> {
+    let input = dir.join("selftest.fq");
+    let parquet = dir.join("selftest.fq.parquet");
+    let output = dir.join("selftest.roundtrip.fq");
+
+    write_fastq(&input, rng)?;
+
+    fq2pq(
+        &input,
+        &parquet,
+        Compression::UNCOMPRESSED,
+        BioFileCompression::UNCOMPRESSED,
+        &FastqOptions::default(),
+    )?;
+    pq2fq(&parquet, &output)?;
+
+    Ok(fs::read(&input)? == fs::read(&output)?)
+}
+
+/// There's no jsonl2fq, so the JSON lines are read back as [`FastqRecord`]s and written as FASTQ
+/// here, checking that fq2jsonl keeps every field.
+fn fastq_jsonl_round_trip(dir: &Path, rng: &mut Lcg) -> Result<bool, BrrrrError> {
+    let input = dir.join("selftest.jsonl.fq");
+    let jsonl = dir.join("selftest.fq.jsonl");
+    let output = dir.join("selftest.roundtrip.jsonl.fq");
+
+    write_fastq(&input, rng)?;
+
+    let mut jsonl_file = fs::File::create(&jsonl)?;
+    fq2jsonl(
+        BufReader::new(fs::File::open(&input)?),
+        &mut jsonl_file,
+        &FastqOptions::default(),
+    )?;
+    jsonl_file.flush()?;
+
+    let mut writer = fastq::Writer::new(fs::File::create(&output)?);
+    for line in BufReader::new(fs::File::open(&jsonl)?).lines() {
+        let record: FastqRecord = serde_json::from_str(&line?).map_err(io::Error::from)?;
+        writer.write_record(&fastq::Record::new(
+            record.id.as_bytes(),
+            record.sequence.as_bytes(),
+            record.quality.as_bytes(),
+        ))?;
+    }
+    drop(writer);
+
+    Ok(fs::read(&input)? == fs::read(&output)?)
+}
+
+fn gff_round_trip(dir: &Path, rng: &mut Lcg) -> Result<bool, BrrrrError> {
+    let input = dir.join("selftest.gff");
+    let parquet = dir.join("selftest.gff.parquet");
+    let directives = dir.join("selftest.gff.directives");
+    let output = dir.join("selftest.roundtrip.gff");
+
+    let mut file = fs::File::create(&input)?;
+    writeln!(file, "##gff-version 3")?;
+    writeln!(file, "##sequence-region chr1 1 12000")?;
+    writeln!(file, "# generated by brrrr selftest")?;
+    for i in 0..RECORD_COUNT {
+        let start = 1 + rng.next(10_000);
+        let end = start + rng.next(2_000);
+        let score = match rng.next(3) {
+            0 => String::from("."),
+            _ => rng.next(1_000).to_string(),
+        };
+        let strand = ["+", "-", "."][rng.next(3)];
+        let (feature, phase) = match i % 2 {
+            0 => ("gene", String::from(".")),
+            _ => ("CDS", rng.next(3).to_string()),
+        };
+        writeln!(
+            file,
+            "chr{}\tbrrrr\t{}\t{}\t{}\t{}\t{}\t{}\tID=f{};Name=n{};Parent=g{}",
+            1 + rng.next(3),
+            feature,
+            start,
+            end,
+            score,
+            strand,
+            phase,
+            i,
+            i,
+            i / 2
+        )?;
+    }
+    drop(file);
+
+    gff2pq(
+        &input,
+        &parquet,
+        Compression::UNCOMPRESSED,
+        &GffOptions {
+            keep_directives: Some(directives.clone()),
+            ..Default::default()
+        },
+    )?;
+    pq2gff(
+        &parquet,
+        &output,
+        &GffOutputOptions {
+            directives: Some(directives),
+            ..Default::default()
+        },
+    )?;
+
+    Ok(normalized_gff(&input)? == normalized_gff(&output)?)
+}
+
+/// Reads the GFF lines at `path` with each record's attributes sorted by key.
+fn normalized_gff(path: &Path) -> io::Result<Vec<String>> {
+    BufReader::new(fs::File::open(path)?)
+        .lines()
+        .map(|line| {
+            let line = line?;
+            let mut fields: Vec<&str> = line.splitn(9, '\t').collect();
+            let mut attributes: Vec<&str> = match fields.pop() {
+                Some(last) => last.split(';').collect(),
+                None => Vec::new(),
+            };
+            attributes.sort_unstable();
+            Ok(format!("{}\t{}", fields.join("\t"), attributes.join(";")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_pass() {
+        let round_trips = run().expect("selftest failed to run");
+
+        assert_eq!(round_trips.len(), 4);
+        for round_trip in round_trips {
+            assert!(round_trip.passed, "{} did not match", round_trip.name);
+        }
+    }
+}
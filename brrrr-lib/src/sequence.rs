@@ -0,0 +1,337 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::io;
+use std::str::FromStr;
+
+/// Returns the complement of a nucleotide, including IUPAC ambiguity codes. Case is preserved,
+/// and unrecognized bytes are returned unchanged.
+pub fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'U' => b'A',
+        b'G' => b'C',
+        b'C' => b'G',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        b'a' => b't',
+        b't' => b'a',
+        b'u' => b'a',
+        b'g' => b'c',
+        b'c' => b'g',
+        b'r' => b'y',
+        b'y' => b'r',
+        b'k' => b'm',
+        b'm' => b'k',
+        b'b' => b'v',
+        b'v' => b'b',
+        b'd' => b'h',
+        b'h' => b'd',
+        other => other,
+    }
+}
+
+/// Returns the reverse complement of `sequence`.
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence.iter().rev().map(|b| complement(*b)).collect()
+}
+
+/// Returns the GC skew, (G - C) / (G + C), of `sequence`, or None if it has no G or C. Case is
+/// ignored, and ambiguity codes are not counted.
+pub fn gc_skew(sequence: &[u8]) -> Option<f64> {
+    let (g, c) = sequence
+        .iter()
+        .fold((0i64, 0i64), |(g, c), b| match b.to_ascii_uppercase() {
+            b'G' => (g + 1, c),
+            b'C' => (g, c + 1),
+            _ => (g, c),
+        });
+
+    if g + c == 0 {
+        return None;
+    }
+
+    Some((g - c) as f64 / (g + c) as f64)
+}
+
+/// Returns the fraction of bases in `sequence` that are G or C, or None if it's empty. Case is
+/// ignored, and every other base, including N, counts toward the total.
+pub fn gc_content(sequence: &[u8]) -> Option<f64> {
+    if sequence.is_empty() {
+        return None;
+    }
+
+    let gc = sequence
+        .iter()
+        .filter(|b| matches!(b.to_ascii_uppercase(), b'G' | b'C'))
+        .count();
+
+    Some(gc as f64 / sequence.len() as f64)
+}
+
+/// Returns the number of N bases in `sequence`. Case is ignored.
+pub fn n_count(sequence: &[u8]) -> usize {
+    sequence
+        .iter()
+        .filter(|b| b.eq_ignore_ascii_case(&b'N'))
+        .count()
+}
+
+/// The offset of Phred+33 (Sanger, Illumina 1.8+) quality scores.
+pub const PHRED_OFFSET: u8 = 33;
+
+/// Returns the mean Phred score of the `quality` string, encoded with `offset`, or None if it's
+/// empty.
+pub fn mean_quality(quality: &[u8], offset: u8) -> Option<f64> {
+    if quality.is_empty() {
+        return None;
+    }
+
+    let total: u64 = quality
+        .iter()
+        .map(|q| u64::from(q.saturating_sub(offset)))
+        .sum();
+
+    Some(total as f64 / quality.len() as f64)
+}
+
+/// Returns the length of the longest run of a single base in `sequence`. Case is ignored.
+pub fn max_homopolymer(sequence: &[u8]) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+
+    for (i, b) in sequence.iter().enumerate() {
+        if i > 0 && b.eq_ignore_ascii_case(&sequence[i - 1]) {
+            run += 1;
+        } else {
+            run = 1;
+        }
+        longest = longest.max(run);
+    }
+
+    longest
+}
+
+/// Returns the Shannon entropy, in bits, of the symbol counts in `counts` over `total` symbols.
+fn entropy_of(counts: &[usize; 256], total: usize) -> f64 {
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Returns the Shannon entropy, in bits, of `sequence`, or None if it's empty. Case is ignored.
+///
+/// If `window` is given, returns the lowest entropy of any window of that many bases instead, so
+/// that a short low-complexity stretch isn't hidden by the rest of the sequence. Sequences
+/// shorter than the window are measured whole.
+pub fn entropy(sequence: &[u8], window: Option<usize>) -> Option<f64> {
+    if sequence.is_empty() {
+        return None;
+    }
+
+    let window = match window {
+        Some(w) if w > 0 && w < sequence.len() => w,
+        _ => sequence.len(),
+    };
+
+    let mut counts = [0usize; 256];
+    for b in &sequence[..window] {
+        counts[b.to_ascii_uppercase() as usize] += 1;
+    }
+
+    let mut lowest = entropy_of(&counts, window);
+    if window == sequence.len() {
+        return Some(lowest);
+    }
+
+    // The entropy of a window is log2(w) - sum(c * log2(c)) / w over its symbol counts c, so
+    // sliding it only changes the terms of the base leaving and the base entering.
+    let terms: Vec<f64> = (0..=window)
+        .map(|c| {
+            if c == 0 {
+                0.0
+            } else {
+                c as f64 * (c as f64).log2()
+            }
+        })
+        .collect();
+    let mut sum: f64 = counts.iter().map(|c| terms[*c]).sum();
+    let log_window = (window as f64).log2();
+
+    for i in window..sequence.len() {
+        let leaving = sequence[i - window].to_ascii_uppercase() as usize;
+        let entering = sequence[i].to_ascii_uppercase() as usize;
+        if leaving == entering {
+            continue;
+        }
+
+        sum -= terms[counts[leaving]] + terms[counts[entering]];
+        counts[leaving] -= 1;
+        counts[entering] += 1;
+        sum += terms[counts[leaving]] + terms[counts[entering]];
+
+        lowest = lowest.min((log_window - sum / window as f64).max(0.0));
+    }
+
+    Some(lowest)
+}
+
+/// Returns the bases `base` stands for, as a set of bits, A 1, C 2, G 4, and T 8, or 0 if it
+/// isn't a nucleotide or IUPAC ambiguity code. Case is ignored, and U is T.
+fn base_mask(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'A' => 1,
+        b'C' => 2,
+        b'G' => 4,
+        b'T' | b'U' => 8,
+        b'M' => 1 | 2,
+        b'R' => 1 | 4,
+        b'W' => 1 | 8,
+        b'S' => 2 | 4,
+        b'Y' => 2 | 8,
+        b'K' => 4 | 8,
+        b'V' => 1 | 2 | 4,
+        b'H' => 1 | 2 | 8,
+        b'D' => 1 | 4 | 8,
+        b'B' => 2 | 4 | 8,
+        b'N' => 1 | 2 | 4 | 8,
+        _ => 0,
+    }
+}
+
+/// A nucleotide motif, which may have IUPAC ambiguity codes, e.g. `GGNCC`.
+///
+/// A base of a sequence matches a code of the motif if every base it stands for is one the code
+/// stands for, e.g. `R` matches `A`, `G`, and `R`, but not `N`, so an `N` in a sequence only
+/// matches an `N` in the motif.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Motif {
+    masks: Vec<u8>,
+}
+
+impl Motif {
+    /// Returns the position of the first match of the motif in `sequence`, if there is one.
+    pub fn find(&self, sequence: &[u8]) -> Option<usize> {
+        sequence.windows(self.masks.len()).position(|window| {
+            window.iter().zip(&self.masks).all(|(base, mask)| {
+                let base = base_mask(*base);
+                base != 0 && base & !mask == 0
+            })
+        })
+    }
+
+    /// Returns the motif of the other strand.
+    pub fn reverse_complement(&self) -> Self {
+        let masks = self
+            .masks
+            .iter()
+            .rev()
+            .map(|m| (m & 1) << 3 | (m & 8) >> 3 | (m & 2) << 1 | (m & 4) >> 1)
+            .collect();
+        Self { masks }
+    }
+}
+
+impl FromStr for Motif {
+    type Err = io::Error;
+
+    /// Parses a motif of nucleotides and IUPAC ambiguity codes, in either case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let masks: Vec<u8> = s.bytes().map(base_mask).collect();
+        if masks.is_empty() || masks.contains(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid motif {:?}, expected IUPAC nucleotide codes", s),
+            ));
+        }
+        Ok(Self { masks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ATCGatcgNRY"), b"RYNcgatCGAT".to_vec());
+        assert_eq!(reverse_complement(b""), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_motif() {
+        let motif: Motif = "GGNCC".parse().unwrap();
+        assert_eq!(motif.find(b"ttggaccaa"), Some(2));
+        assert_eq!(motif.find(b"GGRCC"), Some(0));
+        assert_eq!(motif.find(b"GGACG"), None);
+        assert_eq!(motif.find(b"GG"), None);
+
+        // An N in the sequence could be any base, so only matches an N in the motif.
+        let motif: Motif = "ACR".parse().unwrap();
+        assert_eq!(motif.find(b"ACN"), None);
+        assert_eq!(motif.find(b"ACG"), Some(0));
+        assert_eq!(motif.reverse_complement(), "YGT".parse().unwrap());
+
+        assert!("".parse::<Motif>().is_err());
+        assert!("ACX".parse::<Motif>().is_err());
+    }
+
+    #[test]
+    fn test_gc_skew() {
+        assert_eq!(gc_skew(b"GGGc"), Some(0.5));
+        assert_eq!(gc_skew(b"GCNS"), Some(0.0));
+        assert_eq!(gc_skew(b"ATNN"), None);
+    }
+
+    #[test]
+    fn test_gc_content() {
+        assert_eq!(gc_content(b"GCgn"), Some(0.75));
+        assert_eq!(gc_content(b""), None);
+        assert_eq!(n_count(b"ANnG"), 2);
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b"", None), None);
+        assert_eq!(entropy(b"AAAA", None), Some(0.0));
+        assert_eq!(entropy(b"ACgt", None), Some(2.0));
+        assert_eq!(entropy(b"ACGTAAAA", Some(4)), Some(0.0));
+        assert_eq!(entropy(b"AC", Some(4)), Some(1.0));
+
+        // The sliding sums match measuring each window whole.
+        let sequence: Vec<u8> = (0..2000u32)
+            .map(|i| b"ACGTN"[((i * 7919) ^ (i >> 3)) as usize % 5])
+            .collect();
+        let window = 17;
+        let lowest = sequence
+            .windows(window)
+            .map(|w| entropy(w, None).unwrap())
+            .fold(f64::INFINITY, f64::min);
+        assert!((entropy(&sequence, Some(window)).unwrap() - lowest).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_max_homopolymer() {
+        assert_eq!(max_homopolymer(b""), 0);
+        assert_eq!(max_homopolymer(b"ACGT"), 1);
+        assert_eq!(max_homopolymer(b"ACaAAGGT"), 3);
+    }
+
+    #[test]
+    fn test_mean_quality() {
+        assert_eq!(mean_quality(b"", PHRED_OFFSET), None);
+        assert_eq!(mean_quality(b"!+5I", PHRED_OFFSET), Some(17.5));
+    }
+}
@@ -0,0 +1,950 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Write};
+
+use noodles::fasta;
+use noodles::fastq;
+use serde::Serialize;
+
+use crate::errors::{BrrrrError, ParseError};
+use crate::fastq_ops::{WrappedRead, WrappedReads};
+use crate::gff_ops::{self, GffOptions};
+use crate::input::InputFormat;
+use crate::sequence::{gc_content, Motif};
+use crate::types::FastaRecord;
+
+/// The sequence formats the whole-file reports read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceFormat {
+    Fasta,
+    Fastq,
+}
+
+impl SequenceFormat {
+    /// Detects the format of `input` from its first byte, without consuming it. Empty input is
+    /// treated as FASTA.
+    pub fn sniff<R: BufRead>(input: &mut R) -> io::Result<Self> {
+        match input.fill_buf()?.first() {
+            None | Some(b'>') => Ok(SequenceFormat::Fasta),
+            Some(b'@') => Ok(SequenceFormat::Fastq),
+            Some(_) => Err(ParseError::new("expected FASTA or FASTQ input")
+                .with_line(1)
+                .into()),
+        }
+    }
+}
+
+impl From<SequenceFormat> for InputFormat {
+    fn from(format: SequenceFormat) -> Self {
+        match format {
+            SequenceFormat::Fasta => InputFormat::Fasta,
+            SequenceFormat::Fastq => InputFormat::Fastq,
+        }
+    }
+}
+
+/// Calls `f` with the id and sequence of each record in `input`, a FASTA or FASTQ detected with
+/// [`SequenceFormat::sniff`], stopping at the first error `f` returns.
+pub fn try_for_each_record<R, F>(mut input: R, mut f: F) -> Result<(), BrrrrError>
+where
+    R: BufRead,
+    F: FnMut(&str, &[u8]) -> Result<(), BrrrrError>,
+{
+    match SequenceFormat::sniff(&mut input)? {
+        SequenceFormat::Fasta => {
+            let mut reader = fasta::Reader::new(input);
+            for result in reader.records() {
+                let record = result?;
+                f(record.name(), record.sequence().as_ref())?;
+            }
+        }
+        SequenceFormat::Fastq => {
+            let mut reader = fastq::Reader::new(input);
+            for result in reader.records() {
+                let record = result?;
+                let name = String::from_utf8_lossy(record.name());
+                f(&name, record.sequence())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Calls `f` with the id and sequence of each record in `input`, like [`try_for_each_record`].
+pub fn for_each_record<R, F>(input: R, mut f: F) -> Result<(), BrrrrError>
+where
+    R: BufRead,
+    F: FnMut(&str, &[u8]),
+{
+    try_for_each_record(input, |id, sequence| {
+        f(id, sequence);
+        Ok(())
+    })
+}
+
+/// Calls `f` with the sequence of each record in `input`, like [`for_each_record`].
+pub fn for_each_sequence<R, F>(input: R, mut f: F) -> Result<(), BrrrrError>
+where
+    R: BufRead,
+    F: FnMut(&[u8]),
+{
+    for_each_record(input, |_, sequence| f(sequence))
+}
+
+/// How the whole-file reports are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A single JSON object.
+    Json,
+    /// Tab-separated name and value lines.
+    Table,
+}
+
+/// The base counts across an input, case-folded.
+#[derive(Default, Debug, PartialEq, Eq, Serialize)]
+pub struct Composition {
+    #[serde(rename = "A")]
+    pub a: u64,
+    #[serde(rename = "C")]
+    pub c: u64,
+    #[serde(rename = "G")]
+    pub g: u64,
+    #[serde(rename = "T")]
+    pub t: u64,
+    #[serde(rename = "N")]
+    pub n: u64,
+    /// Every other byte, e.g. ambiguity codes or gaps.
+    pub other: u64,
+}
+
+impl Composition {
+    /// Adds the bases of `sequence` to the counts.
+    pub fn add(&mut self, sequence: &[u8]) {
+        for b in sequence {
+            match b.to_ascii_uppercase() {
+                b'A' => self.a += 1,
+                b'C' => self.c += 1,
+                b'G' => self.g += 1,
+                b'T' => self.t += 1,
+                b'N' => self.n += 1,
+                _ => self.other += 1,
+            }
+        }
+    }
+
+    /// Returns the total number of bases counted.
+    pub fn total(&self) -> u64 {
+        self.a + self.c + self.g + self.t + self.n + self.other
+    }
+
+    /// Returns the fraction of all bases that are G or C, or None if nothing was counted.
+    pub fn gc(&self) -> Option<f64> {
+        match self.total() {
+            0 => None,
+            total => Some((self.g + self.c) as f64 / total as f64),
+        }
+    }
+
+    /// Writes the counts, the total, and the GC fraction to `output`.
+    pub fn write<W: Write>(&self, format: ReportFormat, output: &mut W) -> io::Result<()> {
+        match format {
+            ReportFormat::Json => {
+                let mut value = serde_json::to_value(self)?;
+                value["total"] = self.total().into();
+                value["gc"] = self.gc().into();
+                serde_json::to_writer(&mut *output, &value)?;
+                writeln!(output)
+            }
+            ReportFormat::Table => {
+                for (name, count) in [
+                    ("A", self.a),
+                    ("C", self.c),
+                    ("G", self.g),
+                    ("T", self.t),
+                    ("N", self.n),
+                    ("other", self.other),
+                    ("total", self.total()),
+                ] {
+                    writeln!(output, "{}\t{}", name, count)?;
+                }
+                match self.gc() {
+                    Some(gc) => writeln!(output, "gc\t{:.4}", gc),
+                    None => writeln!(output, "gc\t."),
+                }
+            }
+        }
+    }
+}
+
+/// Counts the bases across every record of a FASTA or FASTQ input in a single pass.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+pub fn composition<R: BufRead>(input: R) -> Result<Composition, BrrrrError> {
+    let mut composition = Composition::default();
+    for_each_sequence(input, |sequence| composition.add(sequence))?;
+    Ok(composition)
+}
+
+/// Returns a 64-bit hash of `sequence`, for counting distinct sequences without holding them.
+pub(crate) fn sequence_hash(sequence: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sequence.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// How redundant the sequences of an input are.
+#[derive(Default, Debug, PartialEq, Eq, Serialize)]
+pub struct Uniqueness {
+    /// The number of records.
+    pub total: u64,
+    /// The number of distinct sequences.
+    pub distinct: u64,
+    /// The number of records whose sequence was already seen, i.e. total - distinct.
+    pub duplicates: u64,
+}
+
+impl Uniqueness {
+    /// Writes the counts to `output`.
+    pub fn write<W: Write>(&self, format: ReportFormat, output: &mut W) -> io::Result<()> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer(&mut *output, self)?;
+                writeln!(output)
+            }
+            ReportFormat::Table => {
+                writeln!(output, "total\t{}", self.total)?;
+                writeln!(output, "distinct\t{}", self.distinct)?;
+                writeln!(output, "duplicates\t{}", self.duplicates)
+            }
+        }
+    }
+}
+
+/// Counts the distinct sequences of a FASTA or FASTQ input. Sequences are compared exactly, so
+/// case matters.
+///
+/// Only a 64-bit hash of each sequence is kept, so memory grows with the number of distinct
+/// sequences rather than their length. Two different sequences can share a hash and be counted
+/// as one. The chance of any collision among n distinct sequences is about n^2 / 2^65, e.g. 1 in
+/// 37 million for a million sequences, and each collision undercounts by only one.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+pub fn uniq<R: BufRead>(input: R) -> Result<Uniqueness, BrrrrError> {
+    let mut seen = HashSet::new();
+    let mut total = 0;
+
+    for_each_sequence(input, |sequence| {
+        seen.insert(sequence_hash(sequence));
+        total += 1;
+    })?;
+
+    let distinct = seen.len() as u64;
+    Ok(Uniqueness {
+        total,
+        distinct,
+        duplicates: total - distinct,
+    })
+}
+
+/// A distinct sequence, and how many records had it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CollapsedSequence {
+    pub id: String,
+    pub sequence: Vec<u8>,
+    pub count: u64,
+}
+
+/// Collapses the records of a FASTA or FASTQ input to one per distinct sequence, in the order
+/// each sequence first appears, counting how many records had it.
+///
+/// Sequences are looked up by [`uniq`]'s 64-bit hash and then compared in full, so distinct
+/// sequences are never merged, and the first record with a sequence is kept to represent it. Ids
+/// are `seq_1`, `seq_2`, and so on, unless `keep_first_id` is set, in which case the
+/// representative's id is used.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `keep_first_id` whether to keep the id of each sequence's first record.
+pub fn collapse<R: BufRead>(
+    input: R,
+    keep_first_id: bool,
+) -> Result<Vec<CollapsedSequence>, BrrrrError> {
+    // Each hash maps to every collapsed sequence with it, which are compared in full, so a hash
+    // collision never merges two different sequences.
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut collapsed: Vec<CollapsedSequence> = Vec::new();
+
+    for_each_record(input, |id, sequence| {
+        let candidates = index.entry(sequence_hash(sequence)).or_default();
+        let found = candidates
+            .iter()
+            .copied()
+            .find(|&i| collapsed[i].sequence == sequence);
+        let i = found.unwrap_or_else(|| {
+            collapsed.push(CollapsedSequence {
+                id: if keep_first_id {
+                    id.to_string()
+                } else {
+                    format!("seq_{}", collapsed.len() + 1)
+                },
+                sequence: sequence.to_vec(),
+                count: 0,
+            });
+            candidates.push(collapsed.len() - 1);
+            collapsed.len() - 1
+        });
+        collapsed[i].count += 1;
+    })?;
+
+    Ok(collapsed)
+}
+
+/// Writes collapsed sequences as FASTA, with the count in each description, e.g.
+/// `>seq_1 count=12`.
+pub fn write_collapsed_fasta<W: Write>(
+    collapsed: &[CollapsedSequence],
+    output: W,
+) -> Result<(), BrrrrError> {
+    let mut writer = fasta::Writer::new(output);
+
+    for c in collapsed {
+        let definition =
+            fasta::record::Definition::new(c.id.clone(), Some(format!("count={}", c.count)));
+        let sequence = fasta::record::Sequence::from(c.sequence.clone());
+        writer.write_record(&fasta::Record::new(definition, sequence))?;
+    }
+
+    Ok(())
+}
+
+/// The default size of the Bloom filter behind the streaming dedup, 64 MiB.
+pub const DEFAULT_DEDUP_FILTER_BYTES: usize = 64 << 20;
+
+/// The number of bits set in a [`SeenFilter`] for each sequence.
+const SEEN_FILTER_HASHES: u64 = 4;
+
+/// A Bloom filter over sequence hashes, for finding repeated sequences in a fixed amount of
+/// memory, however many distinct sequences there are.
+///
+/// The filter never misses a sequence it has seen, but it can report a sequence it hasn't seen as
+/// seen, so a distinct sequence is wrongly treated as a duplicate. The chance of that grows as
+/// the filter fills: with 64 MiB it's about 1 in 300 million once 1 million distinct sequences
+/// are in, 1 in 38,000 at 10 million, and 1 in 13 at 100 million. Doubling the size keeps the
+/// same rates for twice the sequences.
+pub struct SeenFilter {
+    bits: Vec<u64>,
+}
+
+impl SeenFilter {
+    /// Creates an empty filter of `bytes` bytes, rounded up to a multiple of 8.
+    pub fn new(bytes: usize) -> Self {
+        Self {
+            bits: vec![0; bytes.div_ceil(8).max(1)],
+        }
+    }
+
+    /// Adds `sequence` to the filter, returning whether it was probably already in it.
+    pub fn insert(&mut self, sequence: &[u8]) -> bool {
+        let len = self.bits.len() as u64 * 64;
+        let hash = sequence_hash(sequence);
+        // Double hashing: the probes are h1, h1 + h2, h1 + 2 * h2, and so on.
+        let h1 = hash;
+        let h2 = hash.rotate_left(32) | 1;
+
+        let mut seen = true;
+        for i in 0..SEEN_FILTER_HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % len;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            seen &= self.bits[word] & mask != 0;
+            self.bits[word] |= mask;
+        }
+        seen
+    }
+}
+
+/// Counts the distinct sequences of a FASTA or FASTQ input like [`uniq`], but in the fixed
+/// memory of a [`SeenFilter`] of `filter_bytes` bytes, for inputs with too many distinct
+/// sequences to hold their hashes. The filter's false positives undercount the distinct
+/// sequences.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `filter_bytes` the size of the filter, e.g. [`DEFAULT_DEDUP_FILTER_BYTES`].
+pub fn uniq_stream<R: BufRead>(input: R, filter_bytes: usize) -> Result<Uniqueness, BrrrrError> {
+    let mut seen = SeenFilter::new(filter_bytes);
+    let mut total = 0;
+    let mut distinct = 0;
+
+    for_each_sequence(input, |sequence| {
+        if !seen.insert(sequence) {
+            distinct += 1;
+        }
+        total += 1;
+    })?;
+
+    Ok(Uniqueness {
+        total,
+        distinct,
+        duplicates: total - distinct,
+    })
+}
+
+/// Writes the first record with each distinct sequence of a FASTA or FASTQ input as FASTA, as
+/// it's read, using the fixed memory of a [`SeenFilter`] of `filter_bytes` bytes instead of
+/// holding every sequence like [`collapse`]. Since records are written before the input ends,
+/// there are no counts, and the filter's false positives drop a few distinct sequences.
+///
+/// Ids are `seq_1`, `seq_2`, and so on, unless `keep_first_id` is set, like [`collapse`].
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `output` where the FASTA is written.
+/// * `keep_first_id` whether to keep the id of each sequence's first record.
+/// * `filter_bytes` the size of the filter, e.g. [`DEFAULT_DEDUP_FILTER_BYTES`].
+pub fn dedup_stream<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    keep_first_id: bool,
+    filter_bytes: usize,
+) -> Result<(), BrrrrError> {
+    let mut seen = SeenFilter::new(filter_bytes);
+    let mut writer = fasta::Writer::new(output);
+    let mut distinct = 0;
+
+    try_for_each_record(input, |id, sequence| {
+        if seen.insert(sequence) {
+            return Ok(());
+        }
+        distinct += 1;
+
+        let name = if keep_first_id {
+            id.to_string()
+        } else {
+            format!("seq_{}", distinct)
+        };
+        let definition = fasta::record::Definition::new(name, None);
+        let sequence = fasta::record::Sequence::from(sequence.to_vec());
+        writer.write_record(&fasta::Record::new(definition, sequence))?;
+        Ok(())
+    })
+}
+
+/// Writes the sequence of each record in `input`, a FASTA or FASTQ, on its own line, without its
+/// header, for piping into line tools, e.g. `sort | uniq -c`. Wrapped FASTA sequences are joined
+/// onto one line.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `output` where the lines are written.
+/// * `ids_only` write each record's id instead of its sequence, as [`write_ids`] does.
+/// * `id_delimiter` the character ending an id, instead of whitespace.
+pub fn write_seqs<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    ids_only: bool,
+    id_delimiter: Option<char>,
+) -> Result<(), BrrrrError> {
+    let format = SequenceFormat::sniff(&mut input)?;
+    if ids_only {
+        return write_ids(input, output, format.into(), id_delimiter, "ID");
+    }
+
+    match format {
+        SequenceFormat::Fasta => {
+            let mut reader = fasta::Reader::new(input);
+            for result in reader.records() {
+                if output_closed(write_line(output, result?.sequence().as_ref()))? {
+                    break;
+                }
+            }
+        }
+        SequenceFormat::Fastq => {
+            let mut reader = fastq::Reader::new(input);
+            for result in reader.records() {
+                if output_closed(write_line(output, result?.sequence()))? {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `line` to `output`, followed by a newline.
+fn write_line<W: Write>(output: &mut W, line: &[u8]) -> io::Result<()> {
+    output.write_all(line)?;
+    output.write_all(b"\n")
+}
+
+/// Returns whether a write failed because the output's reader went away, e.g. with
+/// `brrrr seqs reads.fa | head -1`, so the caller can stop writing without an error, as the
+/// converters do. Any other error is returned.
+fn output_closed(result: io::Result<()>) -> io::Result<bool> {
+    match result {
+        Ok(()) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes the id of each record in `input` on its own line, e.g. to build a list of ids to filter
+/// by. A FASTA or FASTQ id is its header up to the first whitespace, or up to the first
+/// `id_delimiter` if there is one, as [`FastaRecord::split_id`] splits it. A GFF id is the value
+/// of its `attribute`, e.g. `ID`, and features without it are skipped.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed input.
+/// * `output` where the ids are written.
+/// * `format` the format of the input, e.g. from [`input::sniff`](crate::input::sniff).
+/// * `id_delimiter` the character ending a FASTA or FASTQ id, instead of whitespace.
+/// * `attribute` the GFF attribute holding the id.
+pub fn write_ids<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    format: InputFormat,
+    id_delimiter: Option<char>,
+    attribute: &str,
+) -> Result<(), BrrrrError> {
+    match format {
+        InputFormat::Fasta => {
+            let mut reader = fasta::Reader::new(input);
+            for result in reader.records() {
+                let mut record = FastaRecord::from(result?);
+                if let Some(delimiter) = id_delimiter {
+                    record.split_id(delimiter);
+                }
+                if output_closed(writeln!(output, "{}", record.id))? {
+                    break;
+                }
+            }
+        }
+        InputFormat::Fastq => {
+            let mut reader = fastq::Reader::new(input);
+            for result in reader.records() {
+                let record = result?;
+                let header = String::from_utf8_lossy(record.name());
+                let id = match id_delimiter {
+                    Some(delimiter) => header.split(delimiter).next(),
+                    None => header.split_whitespace().next(),
+                };
+                if output_closed(writeln!(output, "{}", id.unwrap_or_default()))? {
+                    break;
+                }
+            }
+        }
+        InputFormat::Gff => {
+            for result in gff_ops::records(input, &GffOptions::default())? {
+                let record = result?;
+                if let Some(entry) = record.attributes().iter().find(|e| e.key() == attribute) {
+                    if output_closed(writeln!(output, "{}", entry.value()))? {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the records of `input`, a FASTA or FASTQ detected with [`SequenceFormat::sniff`],
+/// whose sequence matches `motif`, like `seqkit grep -s`. Wrapped FASTA sequences are searched
+/// whole. Returns the number of records read, and the number of them that matched.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `output` where the matching records are written, in the input's format.
+/// * `motif` the motif to search for, which may have IUPAC ambiguity codes.
+/// * `both_strands` also match records whose reverse complement matches the motif.
+pub fn grep<R: BufRead, W: Write>(
+    mut input: R,
+    output: &mut W,
+    motif: &Motif,
+    both_strands: bool,
+) -> Result<(u64, u64), BrrrrError> {
+    let reverse = both_strands.then(|| motif.reverse_complement());
+    let matches = |sequence: &[u8]| {
+        motif.find(sequence).is_some()
+            || reverse.as_ref().is_some_and(|m| m.find(sequence).is_some())
+    };
+    let mut read = 0;
+    let mut matched = 0;
+
+    match SequenceFormat::sniff(&mut input)? {
+        SequenceFormat::Fasta => {
+            let mut reader = fasta::Reader::new(input);
+            let mut writer = fasta::Writer::new(output);
+            for result in reader.records() {
+                let record = result?;
+                read += 1;
+                if matches(record.sequence().as_ref()) {
+                    matched += 1;
+                    if output_closed(writer.write_record(&record))? {
+                        break;
+                    }
+                }
+            }
+        }
+        SequenceFormat::Fastq => {
+            let mut reader = fastq::Reader::new(input);
+            let mut writer = fastq::Writer::new(output);
+            for result in reader.records() {
+                let record = result?;
+                read += 1;
+                if matches(record.sequence()) {
+                    matched += 1;
+                    if output_closed(writer.write_record(&record))? {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((read, matched))
+}
+
+/// A histogram of per-record GC fraction, in equal-width bins over 0 to 1.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GcHistogram {
+    /// The number of records in each bin. A record with a GC fraction of exactly 1 goes in the
+    /// last bin.
+    pub counts: Vec<u64>,
+}
+
+impl GcHistogram {
+    /// Returns the lower and upper GC fraction of bin `i`.
+    pub fn bounds(&self, i: usize) -> (f64, f64) {
+        let width = 1.0 / self.counts.len() as f64;
+        (i as f64 * width, (i + 1) as f64 * width)
+    }
+
+    /// Writes the bins as tab-separated `bin_start`, `bin_end`, and `count` lines, with a header.
+    pub fn write_table<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "bin_start\tbin_end\tcount")?;
+        for (i, count) in self.counts.iter().enumerate() {
+            let (start, end) = self.bounds(i);
+            writeln!(output, "{:.3}\t{:.3}\t{}", start, end, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bins the GC fraction of every record of a FASTA or FASTQ input into `bins` equal-width bins,
+/// in a single pass. Empty records have no GC fraction, and aren't counted.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed FASTA or FASTQ input.
+/// * `bins` the number of bins, at least 1, or an error is returned.
+pub fn gc_histogram<R: BufRead>(input: R, bins: usize) -> Result<GcHistogram, BrrrrError> {
+    if bins == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "bins must be at least 1").into());
+    }
+
+    let mut counts = vec![0u64; bins];
+    for_each_sequence(input, |sequence| {
+        if let Some(gc) = gc_content(sequence) {
+            let bin = ((gc * bins as f64) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+    })?;
+
+    Ok(GcHistogram { counts })
+}
+
+/// Counts the records of `input` by scanning for where each starts, without building them, which
+/// is much faster than parsing them. FASTA records start at `>` lines. GFF records are the lines
+/// that aren't blank or `#` comments, up to a `##FASTA` directive. FASTQ reads may be wrapped over
+/// several lines, and end once their quality is as long as their sequence, as
+/// [`UnwrapFastq`](crate::fastq_ops::UnwrapFastq) reads them.
+///
+/// The records themselves aren't checked, beyond each FASTQ read starting with `@` and being whole.
+///
+/// # Arguments
+///
+/// * `input` an uncompressed input.
+/// * `format` the format of the input, e.g. from [`input::sniff`](crate::input::sniff).
+pub fn count_records<R: BufRead>(input: R, format: InputFormat) -> Result<u64, BrrrrError> {
+    match format {
+        InputFormat::Fasta => Ok(count_fasta(input)?),
+        InputFormat::Fastq => count_fastq(input),
+        InputFormat::Gff => Ok(count_gff(input)?),
+    }
+}
+
+/// Counts the `>` lines of a FASTA input, a buffer at a time.
+fn count_fasta<R: BufRead>(mut input: R) -> io::Result<u64> {
+    let mut count = 0;
+    let mut line_start = true;
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(count);
+        }
+        for &b in buf {
+            if line_start && b == b'>' {
+                count += 1;
+            }
+            line_start = b == b'\n';
+        }
+        let n = buf.len();
+        input.consume(n);
+    }
+}
+
+/// Counts the lines of a GFF input that aren't blank or comments, up to a `##FASTA` directive.
+fn count_gff<R: BufRead>(mut input: R) -> io::Result<u64> {
+    let mut count = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 || line.starts_with(b"##FASTA") {
+            return Ok(count);
+        }
+        if !line.starts_with(b"#") && !line.iter().all(u8::is_ascii_whitespace) {
+            count += 1;
+        }
+    }
+}
+
+/// Counts the reads of a FASTQ input, which may be wrapped over several lines.
+fn count_fastq<R: BufRead>(input: R) -> Result<u64, BrrrrError> {
+    let mut reads = WrappedReads::new(input);
+    let mut read = Vec::new();
+    let mut count = 0;
+
+    loop {
+        read.clear();
+        let header_line = reads.lines + 1;
+        match reads.next_read(&mut read)? {
+            WrappedRead::End => return Ok(count),
+            _ if !read.starts_with(b"@") => {
+                let error = ParseError::new("expected a read's @ header").with_line(header_line);
+                return Err(BrrrrError::Parse(error));
+            }
+            WrappedRead::CutShort => {
+                let error = ParseError::new("the read is cut short").with_line(reads.lines);
+                return Err(BrrrrError::Parse(error));
+            }
+            WrappedRead::Whole => count += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_records() {
+        let fasta = b">a\nAC\nGT\n>b\n\n>c\r\nA\r\n" as &[u8];
+        assert_eq!(count_records(fasta, InputFormat::Fasta).unwrap(), 3);
+        assert_eq!(count_records(&b""[..], InputFormat::Fasta).unwrap(), 0);
+
+        // The second read is wrapped, and its quality starts with @, like a header.
+        let fastq = b"@a\nACGT\n+\nIIII\n@b\nAC\nGT\n+\n@I\nII\n@c\n\n+\n\n" as &[u8];
+        assert_eq!(count_records(fastq, InputFormat::Fastq).unwrap(), 3);
+        let fastq = b"@a\nACGT\n+\nII\n" as &[u8];
+        assert!(count_records(fastq, InputFormat::Fastq).is_err());
+        let fastq = b"@a\nA\n+\nI\na\n" as &[u8];
+        assert!(matches!(
+            count_records(fastq, InputFormat::Fastq),
+            Err(BrrrrError::Parse(ParseError { line: Some(5), .. }))
+        ));
+
+        let gff = b"##gff-version 3\nchr1\t.\tgene\t1\t9\t.\t+\t.\tID=a\n\n# c\n\
+                    chr1\t.\tgene\t5\t9\t.\t+\t.\tID=b\n##FASTA\n>chr1\nACGT\n"
+            as &[u8];
+        assert_eq!(count_records(gff, InputFormat::Gff).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_composition() {
+        let fasta = b">a\nACgt\n>b\nNNR-\n" as &[u8];
+        let fastq = b"@a\nACgt\n+\nIIII\n@b\nNNR-\n+\nIIII\n" as &[u8];
+
+        let expected = Composition {
+            a: 1,
+            c: 1,
+            g: 1,
+            t: 1,
+            n: 2,
+            other: 2,
+        };
+        assert_eq!(composition(fasta).unwrap(), expected);
+        assert_eq!(composition(fastq).unwrap(), expected);
+        assert_eq!(expected.gc(), Some(0.25));
+
+        let mut output = Vec::new();
+        expected.write(ReportFormat::Json, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"A\":1,\"C\":1,\"G\":1,\"T\":1,\"N\":2,\"other\":2,\"total\":8,\"gc\":0.25}\n"
+        );
+
+        assert!(matches!(
+            composition(b"ACGT\n" as &[u8]),
+            Err(BrrrrError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_uniq() {
+        let fasta = b">a\nACGT\n>b\nACGT\n>c\nacgt\n>d\nAC\nGT\n" as &[u8];
+        assert_eq!(
+            uniq(fasta).unwrap(),
+            Uniqueness {
+                total: 4,
+                distinct: 2,
+                duplicates: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_collapse() {
+        let fastq = b"@r0\nAC\n+\nII\n@r1\nGG\n+\nII\n@r2\nAC\n+\nII\n" as &[u8];
+
+        let collapsed = collapse(fastq, false).unwrap();
+        assert_eq!(
+            collapsed,
+            vec![
+                CollapsedSequence {
+                    id: "seq_1".into(),
+                    sequence: b"AC".to_vec(),
+                    count: 2,
+                },
+                CollapsedSequence {
+                    id: "seq_2".into(),
+                    sequence: b"GG".to_vec(),
+                    count: 1,
+                },
+            ]
+        );
+
+        let mut output = Vec::new();
+        write_collapsed_fasta(&collapsed, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">seq_1 count=2\nAC\n>seq_2 count=1\nGG\n"
+        );
+
+        let ids: Vec<_> = collapse(fastq, true)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec!["r0", "r1"]);
+    }
+
+    #[test]
+    fn test_dedup_stream() {
+        let fasta = b">a\nACGT\n>b\nACGT\n>c\nacgt\n>d\nAC\nGT\n" as &[u8];
+        assert_eq!(
+            uniq_stream(fasta, DEFAULT_DEDUP_FILTER_BYTES).unwrap(),
+            uniq(fasta).unwrap()
+        );
+
+        let fastq = b"@r0\nAC\n+\nII\n@r1\nGG\n+\nII\n@r2\nAC\n+\nII\n" as &[u8];
+        let mut output = Vec::new();
+        dedup_stream(fastq, &mut output, true, 1024).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), ">r0\nAC\n>r1\nGG\n");
+
+        // A filter of a single word fills up, and reports everything as seen.
+        let mut seen = SeenFilter::new(1);
+        let distinct = (0..1000u32)
+            .filter(|i| !seen.insert(&i.to_le_bytes()))
+            .count();
+        assert!(distinct < 1000);
+    }
+
+    #[test]
+    fn test_write_seqs() {
+        let fasta = b">a one\nACGT\nAC\n>b\nGG\n" as &[u8];
+        let mut output = Vec::new();
+        write_seqs(fasta, &mut output, false, None).unwrap();
+        assert_eq!(output, b"ACGTAC\nGG\n");
+
+        let fastq = b"@r0 one\nAC\n+\nII\n@r1\nGG\n+\nII\n" as &[u8];
+        let mut output = Vec::new();
+        write_seqs(fastq, &mut output, true, None).unwrap();
+        assert_eq!(output, b"r0\nr1\n");
+
+        let fasta = b">gi|1 one\nACGT\n>b\nGG\n" as &[u8];
+        let mut output = Vec::new();
+        write_seqs(fasta, &mut output, true, Some('|')).unwrap();
+        assert_eq!(output, b"gi\nb\n");
+    }
+
+    #[test]
+    fn test_write_ids() {
+        let ids = |input: &[u8], format, id_delimiter, attribute| {
+            let mut output = Vec::new();
+            write_ids(input, &mut output, format, id_delimiter, attribute).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let fasta = b">gi|1|ref one\nAC\nGT\n>b\nGG\n" as &[u8];
+        assert_eq!(ids(fasta, InputFormat::Fasta, None, "ID"), "gi|1|ref\nb\n");
+        assert_eq!(ids(fasta, InputFormat::Fasta, Some('|'), "ID"), "gi\nb\n");
+
+        let fastq = b"@r0 one\nAC\n+\nII\n@r1/1\nGG\n+\nII\n" as &[u8];
+        assert_eq!(ids(fastq, InputFormat::Fastq, None, "ID"), "r0\nr1/1\n");
+        assert_eq!(
+            ids(fastq, InputFormat::Fastq, Some('/'), "ID"),
+            "r0 one\nr1\n"
+        );
+
+        let gff = b"##gff-version 3\n\
+            chr1\t.\tgene\t1\t9\t.\t+\t.\tID=g0;Name=A\n\
+            chr1\t.\texon\t1\t9\t.\t+\t.\tParent=g0\n\
+            chr1\t.\tgene\t20\t29\t.\t+\t.\tID=g1;Name=B\n" as &[u8];
+        assert_eq!(ids(gff, InputFormat::Gff, None, "ID"), "g0\ng1\n");
+        assert_eq!(ids(gff, InputFormat::Gff, None, "Name"), "A\nB\n");
+    }
+
+    #[test]
+    fn test_grep() {
+        let motif: Motif = "GGNCC".parse().unwrap();
+
+        let fasta = b">a\nAAGG\nTCCA\n>b\nGGTTA\n>c\nTTGGACCTT\n" as &[u8];
+        let mut output = Vec::new();
+        assert_eq!(grep(fasta, &mut output, &motif, false).unwrap(), (3, 2));
+        assert_eq!(output, b">a\nAAGGTCCA\n>c\nTTGGACCTT\n");
+
+        // CCGT only matches on the other strand, as ACGG.
+        let motif: Motif = "ACGG".parse().unwrap();
+        let fastq = b"@r0\nCCGT\n+\nIIII\n@r1\nAAAA\n+\nIIII\n" as &[u8];
+        let mut output = Vec::new();
+        assert_eq!(grep(fastq, &mut output, &motif, false).unwrap(), (2, 0));
+        assert_eq!(grep(fastq, &mut output, &motif, true).unwrap(), (2, 1));
+        assert_eq!(output, b"@r0\nCCGT\n+\nIIII\n");
+    }
+
+    #[test]
+    fn test_gc_histogram() {
+        let fasta = b">a\nAAAA\n>b\nACGT\n>c\nGGGG\n>d\n\n>e\nAACG\n" as &[u8];
+        let histogram = gc_histogram(fasta, 4).unwrap();
+        assert_eq!(histogram.counts, vec![1, 0, 2, 1]);
+        assert_eq!(histogram.bounds(1), (0.25, 0.5));
+
+        assert!(gc_histogram(fasta, 0).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use noodles::bgzf;
+
+/// The log2 width of the linear index's windows, i.e. 16 kbp.
+const MIN_SHIFT: u32 = 14;
+
+/// The number of levels of bins below the root bin.
+const DEPTH: u32 = 5;
+
+/// The pseudo-bin that holds a reference sequence's first and last offsets and record count,
+/// which htslib writes and `samtools idxstats` reads.
+const METADATA_BIN: u32 = 37450;
+
+/// The format code, and the 1-based sequence, start, and end columns, of the index header,
+/// which tabix calls the GFF preset.
+const GFF_HEADER: [i32; 4] = [0, 1, 4, 5];
+
+/// The bins, linear index, and record count of one reference sequence.
+#[derive(Debug, Default)]
+struct ReferenceIndex {
+    /// The chunks of the output, as virtual position ranges, in each bin.
+    bins: BTreeMap<u32, Vec<(u64, u64)>>,
+    /// The virtual position of the first record overlapping each 16 kbp window, if any.
+    linear: Vec<Option<u64>>,
+    /// The virtual positions of the start of the first record and the end of the last.
+    start: u64,
+    end: u64,
+    records: u64,
+}
+
+/// Builds a tabix index over the records of a bgzipped GFF output as they're written.
+///
+/// Each record is added with its interval and the virtual positions of the output before and
+/// after it, and the finished index is written with [`TabixIndexer::write`], e.g. to
+/// `<output>.tbi`.
+#[derive(Debug, Default)]
+pub struct TabixIndexer {
+    names: Vec<String>,
+    references: Vec<ReferenceIndex>,
+    /// The start of the last record, to check the records are sorted.
+    last_start: u64,
+}
+
+impl TabixIndexer {
+    /// Adds a record on `name` over the 0-based, half-open interval `[start, end)`, written to
+    /// the output between the virtual positions `chunk_start` and `chunk_end`.
+    ///
+    /// The records of each reference sequence must be added together, in order of start, or an
+    /// `InvalidInput` error is returned.
+    pub fn add(
+        &mut self,
+        name: &str,
+        start: u64,
+        end: u64,
+        chunk_start: bgzf::VirtualPosition,
+        chunk_end: bgzf::VirtualPosition,
+    ) -> io::Result<()> {
+        let (chunk_start, chunk_end) = (u64::from(chunk_start), u64::from(chunk_end));
+
+        if self.names.last().map(String::as_str) != Some(name) {
+            if self.names.iter().any(|n| n == name) {
+                return Err(unsorted(name));
+            }
+            self.names.push(name.to_string());
+            self.references.push(ReferenceIndex {
+                start: chunk_start,
+                ..Default::default()
+            });
+        } else if start < self.last_start {
+            return Err(unsorted(name));
+        }
+        self.last_start = start;
+
+        let reference = self
+            .references
+            .last_mut()
+            .expect("a reference sequence was just added");
+
+        // An empty interval is indexed like the base at its start.
+        let end = end.max(start + 1);
+        let chunks = reference.bins.entry(reg2bin(start, end)).or_default();
+        match chunks.last_mut() {
+            // Records written back to back in the same bin are one chunk.
+            Some(last) if last.1 == chunk_start => last.1 = chunk_end,
+            _ => chunks.push((chunk_start, chunk_end)),
+        }
+
+        let first_window = (start >> MIN_SHIFT) as usize;
+        let last_window = ((end - 1) >> MIN_SHIFT) as usize;
+        if reference.linear.len() <= last_window {
+            reference.linear.resize(last_window + 1, None);
+        }
+        for offset in &mut reference.linear[first_window..=last_window] {
+            offset.get_or_insert(chunk_start);
+        }
+
+        reference.end = chunk_end;
+        reference.records += 1;
+        Ok(())
+    }
+
+    /// Writes the index to `writer`, bgzipped as `.tbi` files are.
+    pub fn write<W: Write>(self, writer: W) -> io::Result<()> {
+        let mut writer = bgzf::Writer::new(writer);
+
+        let names: Vec<u8> = self
+            .names
+            .iter()
+            .flat_map(|name| name.bytes().chain([0]))
+            .collect();
+        let [format, seq_column, start_column, end_column] = GFF_HEADER;
+
+        writer.write_all(b"TBI\x01")?;
+        for field in [
+            self.names.len() as i32,
+            format,
+            seq_column,
+            start_column,
+            end_column,
+            // Lines starting with # are skipped, and no other header lines.
+            i32::from(b'#'),
+            0,
+            names.len() as i32,
+        ] {
+            writer.write_all(&field.to_le_bytes())?;
+        }
+        writer.write_all(&names)?;
+
+        for reference in self.references {
+            writer.write_all(&(reference.bins.len() as i32 + 1).to_le_bytes())?;
+            for (bin, chunks) in &reference.bins {
+                writer.write_all(&bin.to_le_bytes())?;
+                writer.write_all(&(chunks.len() as i32).to_le_bytes())?;
+                for (chunk_start, chunk_end) in chunks {
+                    writer.write_all(&chunk_start.to_le_bytes())?;
+                    writer.write_all(&chunk_end.to_le_bytes())?;
+                }
+            }
+
+            writer.write_all(&METADATA_BIN.to_le_bytes())?;
+            writer.write_all(&2i32.to_le_bytes())?;
+            for value in [reference.start, reference.end, reference.records, 0] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+
+            // Windows no record overlaps point at the previous window's first record.
+            writer.write_all(&(reference.linear.len() as i32).to_le_bytes())?;
+            let mut previous = reference.start;
+            for offset in reference.linear {
+                previous = offset.unwrap_or(previous);
+                writer.write_all(&previous.to_le_bytes())?;
+            }
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Returns the smallest bin that holds all of the 0-based, half-open interval `[start, end)`,
+/// as `reg2bin` in the SAM specification.
+fn reg2bin(start: u64, end: u64) -> u32 {
+    let end = end - 1;
+    let mut shift = MIN_SHIFT;
+    for level in (1..=DEPTH).rev() {
+        if start >> shift == end >> shift {
+            let level_offset = ((1 << (3 * level)) - 1) / 7;
+            return (level_offset + (start >> shift)) as u32;
+        }
+        shift += 3;
+    }
+    0
+}
+
+fn unsorted(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "records on {} are out of order, a tabix index needs them sorted by seqname and \
+             start, e.g. with gff2pq --sort",
+            name
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::io::{BufRead, Cursor, Read};
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn test_reg2bin() {
+        assert_eq!(reg2bin(0, 1), 4681);
+        assert_eq!(reg2bin(16_384, 20_000), 4682);
+        assert_eq!(reg2bin(16_000, 17_000), 585);
+        assert_eq!(reg2bin(0, 1 << 29), 0);
+    }
+
+    #[test]
+    fn test_tabix_index() {
+        let position = |offset: u64| bgzf::VirtualPosition::from(offset);
+
+        let mut index = TabixIndexer::default();
+        index
+            .add("chr1", 10, 20, position(0), position(30))
+            .unwrap();
+        index
+            .add("chr1", 15, 40, position(30), position(60))
+            .unwrap();
+        index
+            .add("chr2", 5, 10, position(60), position(90))
+            .unwrap();
+
+        // chr1 was already finished.
+        let err = index.add("chr1", 50, 60, position(90), position(120));
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(index
+            .add("chr2", 1, 2, position(90), position(120))
+            .is_err());
+
+        let mut compressed = Vec::new();
+        index.write(&mut compressed).unwrap();
+        let mut bytes = Vec::new();
+        MultiGzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut bytes)
+            .unwrap();
+
+        let int = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let long = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+        assert_eq!(&bytes[..4], b"TBI\x01");
+        // Two references, the GFF columns, # comments, and the names chr1 and chr2.
+        assert_eq!((int(4), int(8), int(12), int(16), int(20)), (2, 0, 1, 4, 5));
+        assert_eq!((int(24), int(28), int(32)), (35, 0, 10));
+        assert_eq!(&bytes[36..46], b"chr1\0chr2\0");
+
+        // chr1's two records share bin 4681 and are one chunk, then the metadata pseudo-bin.
+        assert_eq!((int(46), int(50), int(54)), (2, 4681, 1));
+        assert_eq!((long(58), long(66)), (0, 60));
+        assert_eq!((int(74), int(78)), (METADATA_BIN as i32, 2));
+        assert_eq!((long(82), long(90), long(98)), (0, 60, 2));
+        // One 16 kbp window, starting at the first record.
+        assert_eq!((int(114), long(118)), (1, 0));
+    }
+
+    fn int(reader: &mut &[u8]) -> i32 {
+        let mut field = [0; 4];
+        reader.read_exact(&mut field).unwrap();
+        i32::from_le_bytes(field)
+    }
+
+    fn long(reader: &mut &[u8]) -> u64 {
+        let mut field = [0; 8];
+        reader.read_exact(&mut field).unwrap();
+        u64::from_le_bytes(field)
+    }
+
+    /// Returns every bin that may hold records overlapping `[start, end)`, as `reg2bins` in the
+    /// SAM specification.
+    fn reg2bins(start: u64, end: u64) -> Vec<u32> {
+        let mut bins = vec![0];
+        let mut shift = MIN_SHIFT + 3 * DEPTH;
+        for level in 1..=DEPTH {
+            shift -= 3;
+            let level_offset = ((1 << (3 * level)) - 1) / 7;
+            let first = level_offset + (start >> shift);
+            let last = level_offset + ((end - 1) >> shift);
+            bins.extend((first..=last).map(|bin| bin as u32));
+        }
+        bins
+    }
+
+    /// Returns the 1-based starts and ends of the records of the bgzipped GFF `gff` overlapping
+    /// the 0-based, half-open `[start, end)` on `name`, read through its tabix index `tbi` the way
+    /// htslib queries one.
+    fn query(gff: &[u8], tbi: &[u8], name: &str, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut bytes = Vec::new();
+        MultiGzDecoder::new(tbi).read_to_end(&mut bytes).unwrap();
+        let mut reader = &bytes[4..];
+
+        let references = int(&mut reader);
+        for _ in 0..6 {
+            int(&mut reader);
+        }
+        let names_len = int(&mut reader) as usize;
+        let target = reader[..names_len]
+            .split(|&b| b == 0)
+            .position(|n| n == name.as_bytes())
+            .unwrap() as i32;
+        reader = &reader[names_len..];
+
+        let bins = reg2bins(start, end);
+        let mut chunks = Vec::new();
+        let mut min_offset = 0;
+        for reference in 0..references {
+            for _ in 0..int(&mut reader) {
+                let bin = int(&mut reader) as u32;
+                for _ in 0..int(&mut reader) {
+                    let chunk = (long(&mut reader), long(&mut reader));
+                    if reference == target && bins.contains(&bin) {
+                        chunks.push(chunk);
+                    }
+                }
+            }
+            let linear: Vec<_> = (0..int(&mut reader)).map(|_| long(&mut reader)).collect();
+            if reference == target {
+                min_offset = linear
+                    .get((start >> MIN_SHIFT) as usize)
+                    .copied()
+                    .unwrap_or(0);
+            }
+        }
+
+        let mut records = bgzf::Reader::new(Cursor::new(gff));
+        let mut hits = Vec::new();
+        chunks.sort_unstable();
+        for (chunk_start, chunk_end) in chunks {
+            if chunk_end <= min_offset {
+                continue;
+            }
+            records
+                .seek(bgzf::VirtualPosition::from(chunk_start.max(min_offset)))
+                .unwrap();
+
+            let mut line = String::new();
+            while u64::from(records.virtual_position()) < chunk_end {
+                line.clear();
+                if records.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let columns: Vec<_> = line.split('\t').collect();
+                let (first, last) = (columns[3].parse().unwrap(), columns[4].parse().unwrap());
+                if columns[0] == name && first <= end && last > start {
+                    hits.push((first, last));
+                }
+            }
+        }
+
+        hits.sort_unstable();
+        hits.dedup();
+        hits
+    }
+
+    #[test]
+    fn test_tabix_query() {
+        // A long gene over many exons, so records land in bins at several levels.
+        let mut records = vec![(String::from("chr1"), 1, 300_000)];
+        records.extend((0..8000).map(|i| (String::from("chr1"), i * 40 + 1, i * 40 + 60)));
+        records.extend((0..500).map(|i| (String::from("chr2"), i * 100 + 1, i * 100 + 10)));
+
+        let mut writer = bgzf::Writer::new(Vec::new());
+        let mut index = TabixIndexer::default();
+        for (name, start, end) in &records {
+            let chunk_start = writer.virtual_position();
+            writeln!(
+                writer,
+                "{}\t.\texon\t{}\t{}\t.\t+\t.\tID=x",
+                name, start, end
+            )
+            .unwrap();
+            index
+                .add(
+                    name,
+                    start - 1,
+                    *end,
+                    chunk_start,
+                    writer.virtual_position(),
+                )
+                .unwrap();
+        }
+        let gff = writer.finish().unwrap();
+        let mut tbi = Vec::new();
+        index.write(&mut tbi).unwrap();
+
+        for (name, start, end) in [
+            ("chr1", 0, 1),
+            ("chr1", 16_000, 17_000),
+            ("chr1", 150_000, 151_000),
+            ("chr1", 299_990, 400_000),
+            ("chr2", 25_000, 25_050),
+        ] {
+            let mut expected: Vec<_> = records
+                .iter()
+                .filter(|(n, first, last)| n == name && *first <= end && *last > start)
+                .map(|(_, first, last)| (*first, *last))
+                .collect();
+            expected.sort_unstable();
+            assert!(!expected.is_empty());
+            assert_eq!(query(&gff, &tbi, name, start, end), expected);
+        }
+    }
+}
@@ -0,0 +1,567 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::fmt;
+use std::io::{self, BufRead};
+use std::sync::Arc;
+
+use arrow::array::*;
+use arrow::datatypes::{DataType, Field, Schema};
+use serde_json::Value;
+
+use crate::errors::{BrrrrError, ParseError};
+
+/// The type of a column, as inferred from the values in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Only nulls have been seen.
+    Null,
+    Bool,
+    Int,
+    Float,
+    Str,
+}
+
+impl ColumnType {
+    /// Returns the type of a JSON value, or None if it's an object or array.
+    fn of_json(value: &Value) -> Option<ColumnType> {
+        match value {
+            Value::Null => Some(ColumnType::Null),
+            Value::Bool(_) => Some(ColumnType::Bool),
+            Value::Number(n) if n.is_i64() => Some(ColumnType::Int),
+            Value::Number(_) => Some(ColumnType::Float),
+            Value::String(_) => Some(ColumnType::Str),
+            Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+
+    /// Returns the narrowest type of a text field, treating an empty field as null.
+    fn of_str(value: &str) -> ColumnType {
+        if value.is_empty() {
+            ColumnType::Null
+        } else if value == "true" || value == "false" {
+            ColumnType::Bool
+        } else if value.parse::<i64>().is_ok() {
+            ColumnType::Int
+        } else if value.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else {
+            ColumnType::Str
+        }
+    }
+
+    /// Returns the type that holds values of both `self` and `other`, or None if they conflict.
+    /// Null fits any type, and ints widen to floats.
+    fn merge(self, other: ColumnType) -> Option<ColumnType> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (ColumnType::Null, t) | (t, ColumnType::Null) => Some(t),
+            (ColumnType::Int, ColumnType::Float) | (ColumnType::Float, ColumnType::Int) => {
+                Some(ColumnType::Float)
+            }
+            _ => None,
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnType::Bool => DataType::Boolean,
+            ColumnType::Int => DataType::Int64,
+            ColumnType::Float => DataType::Float64,
+            ColumnType::Null | ColumnType::Str => DataType::Utf8,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Null => "null",
+            ColumnType::Bool => "bool",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Str => "string",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What to do when a column has values of conflicting types, e.g. an int and a string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeConflict {
+    /// Promote the column to a string column.
+    Promote,
+    /// Fail the conversion.
+    Error,
+}
+
+/// Options for inferring the schema of a generic tabular input.
+#[derive(Clone, Debug)]
+pub struct InferOptions {
+    /// The number of rows to infer the schema from.
+    pub schema_sample: usize,
+    /// What to do when a column has values of conflicting types.
+    pub on_conflict: TypeConflict,
+}
+
+impl Default for InferOptions {
+    fn default() -> Self {
+        Self {
+            schema_sample: 1000,
+            on_conflict: TypeConflict::Promote,
+        }
+    }
+}
+
+/// Options for reading a delimited text input, e.g. CSV.
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// The byte that separates fields.
+    pub delimiter: u8,
+    /// Whether the first row holds the column names. If not, columns are named `column_1`,
+    /// `column_2`, and so on.
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_header: true,
+        }
+    }
+}
+
+/// Parses a delimiter flag into the single byte it stands for. Besides a literal character, the
+/// `\t` escape stands for a tab, so TSV can be given without a literal tab on the command line.
+pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" => Ok(b'\t'),
+        _ => match s.as_bytes() {
+            [b] => Ok(*b),
+            _ => Err(format!("delimiter {:?} must be a single byte, or \\t", s)),
+        },
+    }
+}
+
+/// Converts a csv error to a BrrrrError, keeping the line of a malformed record.
+pub fn csv_error(e: csv::Error) -> BrrrrError {
+    let line = e.position().map(|p| p.line());
+
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => BrrrrError::Io(e),
+        kind => {
+            let message = match kind {
+                csv::ErrorKind::UnequalLengths {
+                    expected_len, len, ..
+                } => format!("expected {} fields, found {}", expected_len, len),
+                csv::ErrorKind::Utf8 { err, .. } => err.to_string(),
+                _ => String::from("invalid record"),
+            };
+
+            let error = ParseError::new(message);
+            BrrrrError::Parse(match line {
+                Some(line) => error.with_line(line),
+                None => error,
+            })
+        }
+    }
+}
+
+/// A flat JSON object, i.e. one line of a jsonl input.
+pub(crate) type JsonObject = serde_json::Map<String, Value>;
+
+/// Parses the `i`th (0-based) line of a jsonl input.
+pub(crate) fn parse_jsonl_line(
+    (i, line): (usize, io::Result<String>),
+) -> Result<JsonObject, BrrrrError> {
+    serde_json::from_str(&line?)
+        .map_err(|e| BrrrrError::Parse(ParseError::new(e.to_string()).with_line(i as u64 + 1)))
+}
+
+/// Returns the lines of a jsonl input with their 0-based line numbers, skipping blank lines.
+pub(crate) fn jsonl_lines<R: BufRead>(
+    input: R,
+) -> impl Iterator<Item = (usize, io::Result<String>)> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+}
+
+/// Infers the schema of a jsonl input of flat objects from its first `options.schema_sample`
+/// lines, the same way `jsonl2pq` does.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `options` the options for inferring the schema.
+pub fn infer_jsonl<R: BufRead>(
+    input: R,
+    options: &InferOptions,
+) -> Result<SchemaInference, BrrrrError> {
+    let mut inference = SchemaInference::default();
+    for line in jsonl_lines(input).take(options.schema_sample) {
+        inference.observe_json(&parse_jsonl_line(line)?, options.on_conflict)?;
+    }
+    Ok(inference)
+}
+
+/// The types of the values seen in a column, and how many rows had a value.
+#[derive(Debug, Default)]
+struct Seen {
+    types: Vec<ColumnType>,
+    rows: usize,
+}
+
+/// Infers column names and types from a sample of rows, in the order columns are first seen.
+#[derive(Debug, Default)]
+pub struct SchemaInference {
+    columns: Vec<(String, ColumnType)>,
+    seen: Vec<Seen>,
+    rows: usize,
+}
+
+impl SchemaInference {
+    /// Creates a SchemaInference with known column names, whose types are yet to be seen.
+    pub fn from_names(names: &[String]) -> Self {
+        Self {
+            columns: names
+                .iter()
+                .map(|name| (name.clone(), ColumnType::Null))
+                .collect(),
+            seen: names.iter().map(|_| Seen::default()).collect(),
+            rows: 0,
+        }
+    }
+
+    /// Records `value` as being in the column `name`.
+    fn observe(
+        &mut self,
+        name: &str,
+        value_type: ColumnType,
+        on_conflict: TypeConflict,
+    ) -> Result<(), BrrrrError> {
+        let i = match self.columns.iter().position(|(n, _)| n == name) {
+            Some(i) => i,
+            None => {
+                self.columns.push((name.to_string(), value_type));
+                self.seen.push(Seen::default());
+                self.columns.len() - 1
+            }
+        };
+
+        let column_type = &mut self.columns[i].1;
+        match column_type.merge(value_type) {
+            Some(merged) => *column_type = merged,
+            None if on_conflict == TypeConflict::Promote => *column_type = ColumnType::Str,
+            None => {
+                return Err(BrrrrError::SchemaMismatch(format!(
+                    "column {} has both {} and {} values",
+                    name, column_type, value_type
+                )))
+            }
+        }
+
+        let seen = &mut self.seen[i];
+        if !seen.types.contains(&value_type) {
+            seen.types.push(value_type);
+        }
+        seen.rows += 1;
+
+        Ok(())
+    }
+
+    /// Records the fields of a flat JSON object. Nested objects and arrays aren't supported.
+    pub fn observe_json(
+        &mut self,
+        object: &serde_json::Map<String, Value>,
+        on_conflict: TypeConflict,
+    ) -> Result<(), BrrrrError> {
+        for (name, value) in object {
+            let value_type = ColumnType::of_json(value).ok_or_else(|| {
+                BrrrrError::SchemaMismatch(format!(
+                    "column {} has a nested value, only flat objects are supported",
+                    name
+                ))
+            })?;
+            self.observe(name, value_type, on_conflict)?;
+        }
+        self.rows += 1;
+
+        Ok(())
+    }
+
+    /// Records the text fields of a row, in the same order as `names`.
+    pub fn observe_strs<'a, I: IntoIterator<Item = &'a str>>(
+        &mut self,
+        names: &[String],
+        fields: I,
+        on_conflict: TypeConflict,
+    ) -> Result<(), BrrrrError> {
+        for (name, value) in names.iter().zip(fields) {
+            self.observe(name, ColumnType::of_str(value), on_conflict)?;
+        }
+        self.rows += 1;
+
+        Ok(())
+    }
+
+    /// Returns the inferred (name, type) of each column.
+    pub fn columns(&self) -> &[(String, ColumnType)] {
+        &self.columns
+    }
+
+    /// Returns how the type of each column was resolved, for columns that saw more than one type
+    /// of value, or had nulls or missing values, e.g. `score: int and float, widened to float`.
+    pub fn resolutions(&self) -> Vec<String> {
+        let mut resolutions = Vec::new();
+
+        for ((name, column_type), seen) in self.columns.iter().zip(&self.seen) {
+            let types: Vec<ColumnType> = seen
+                .types
+                .iter()
+                .copied()
+                .filter(|t| *t != ColumnType::Null)
+                .collect();
+            let names = types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+            match (types.len(), column_type) {
+                (0, _) => resolutions.push(format!("{}: only nulls, written as string", name)),
+                (1, _) => {}
+                (_, ColumnType::Float) => resolutions.push(format!(
+                    "{}: {}, widened to float",
+                    name,
+                    names.join(" and ")
+                )),
+                (_, _) => resolutions.push(format!(
+                    "{}: {}, promoted to string",
+                    name,
+                    names.join(" and ")
+                )),
+            }
+
+            let nulls = seen.types.contains(&ColumnType::Null);
+            let missing = self.rows - seen.rows.min(self.rows);
+            if !types.is_empty() && (nulls || missing > 0) {
+                resolutions.push(match missing {
+                    0 => format!("{}: has nulls", name),
+                    n => format!(
+                        "{}: has nulls, missing from {} of {} rows",
+                        name, n, self.rows
+                    ),
+                });
+            }
+        }
+
+        resolutions
+    }
+
+    /// Returns the arrow schema for the inferred columns, all of which are nullable.
+    pub fn schema(&self) -> Schema {
+        Schema::new(
+            self.columns
+                .iter()
+                .map(|(name, column_type)| Field::new(name, column_type.data_type(), true))
+                .collect(),
+        )
+    }
+}
+
+/// Builds an arrow array for a column of an inferred type.
+pub enum ColumnBuilder {
+    Bool(BooleanBuilder),
+    Int(Int64Builder),
+    Float(Float64Builder),
+    Str(StringBuilder),
+}
+
+impl ColumnBuilder {
+    /// Creates a builder for a column of `column_type`.
+    pub fn new(column_type: ColumnType) -> Self {
+        match column_type {
+            ColumnType::Bool => ColumnBuilder::Bool(BooleanBuilder::new(2048)),
+            ColumnType::Int => ColumnBuilder::Int(Int64Builder::new(2048)),
+            ColumnType::Float => ColumnBuilder::Float(Float64Builder::new(2048)),
+            ColumnType::Null | ColumnType::Str => ColumnBuilder::Str(StringBuilder::new(2048)),
+        }
+    }
+
+    fn mismatch(&self, value: &dyn fmt::Display) -> BrrrrError {
+        let expected = match self {
+            ColumnBuilder::Bool(_) => ColumnType::Bool,
+            ColumnBuilder::Int(_) => ColumnType::Int,
+            ColumnBuilder::Float(_) => ColumnType::Float,
+            ColumnBuilder::Str(_) => ColumnType::Str,
+        };
+        BrrrrError::SchemaMismatch(format!(
+            "value {} doesn't fit the inferred {} type",
+            value, expected
+        ))
+    }
+
+    /// Appends a JSON value, or a null if it's missing.
+    pub fn append_json(&mut self, value: Option<&Value>) -> Result<(), BrrrrError> {
+        let value = match value {
+            None | Some(Value::Null) => return self.append_null(),
+            Some(v) => v,
+        };
+
+        match (&mut *self, value) {
+            (ColumnBuilder::Bool(b), Value::Bool(v)) => b.append_value(*v)?,
+            (ColumnBuilder::Int(b), Value::Number(n)) if n.is_i64() => {
+                b.append_value(n.as_i64().unwrap())?
+            }
+            (ColumnBuilder::Float(b), Value::Number(n)) => b.append_option(n.as_f64())?,
+            (ColumnBuilder::Str(b), Value::String(s)) => b.append_value(s)?,
+            (ColumnBuilder::Str(b), Value::Bool(_) | Value::Number(_)) => {
+                b.append_value(value.to_string())?
+            }
+            _ => return Err(self.mismatch(value)),
+        }
+
+        Ok(())
+    }
+
+    /// Appends a text field, treating an empty field as null.
+    pub fn append_str(&mut self, value: &str) -> Result<(), BrrrrError> {
+        if value.is_empty() {
+            return self.append_null();
+        }
+
+        match self {
+            ColumnBuilder::Bool(b) => match value {
+                "true" => b.append_value(true)?,
+                "false" => b.append_value(false)?,
+                _ => return Err(self.mismatch(&value)),
+            },
+            ColumnBuilder::Int(b) => match value.parse() {
+                Ok(v) => b.append_value(v)?,
+                Err(_) => return Err(self.mismatch(&value)),
+            },
+            ColumnBuilder::Float(b) => match value.parse() {
+                Ok(v) => b.append_value(v)?,
+                Err(_) => return Err(self.mismatch(&value)),
+            },
+            ColumnBuilder::Str(b) => b.append_value(value)?,
+        }
+
+        Ok(())
+    }
+
+    fn append_null(&mut self) -> Result<(), BrrrrError> {
+        match self {
+            ColumnBuilder::Bool(b) => b.append_null()?,
+            ColumnBuilder::Int(b) => b.append_null()?,
+            ColumnBuilder::Float(b) => b.append_null()?,
+            ColumnBuilder::Str(b) => b.append_null()?,
+        }
+
+        Ok(())
+    }
+
+    /// Returns the built array, and resets the builder.
+    pub fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float(b) => Arc::new(b.finish()),
+            ColumnBuilder::Str(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_json() {
+        let rows = [
+            r#"{"id": "a", "n": 1, "x": 1, "ok": true, "maybe": null}"#,
+            r#"{"id": "b", "n": 2.5, "x": "two"}"#,
+        ];
+
+        let mut inference = SchemaInference::default();
+        for row in rows {
+            let object: serde_json::Map<String, Value> = serde_json::from_str(row).unwrap();
+            inference
+                .observe_json(&object, TypeConflict::Promote)
+                .unwrap();
+        }
+
+        assert_eq!(
+            inference.columns(),
+            &[
+                ("id".to_string(), ColumnType::Str),
+                ("n".to_string(), ColumnType::Float),
+                ("x".to_string(), ColumnType::Str),
+                ("ok".to_string(), ColumnType::Bool),
+                ("maybe".to_string(), ColumnType::Null),
+            ]
+        );
+
+        let mut inference = SchemaInference::default();
+        let mut result = Ok(());
+        for row in rows {
+            let object: serde_json::Map<String, Value> = serde_json::from_str(row).unwrap();
+            result = result.and_then(|_| inference.observe_json(&object, TypeConflict::Error));
+        }
+        assert!(matches!(result, Err(BrrrrError::SchemaMismatch(_))));
+    }
+
+    #[test]
+    fn test_infer_strs() {
+        let names = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut inference = SchemaInference::default();
+        inference
+            .observe_strs(&names, ["1", "true", ""], TypeConflict::Error)
+            .unwrap();
+        inference
+            .observe_strs(&names, ["1.5", "false", "x"], TypeConflict::Error)
+            .unwrap();
+
+        let types: Vec<_> = inference.columns().iter().map(|(_, t)| *t).collect();
+        assert_eq!(
+            types,
+            vec![ColumnType::Float, ColumnType::Bool, ColumnType::Str]
+        );
+    }
+
+    #[test]
+    fn test_resolutions() {
+        let input = b"{\"a\": 1, \"b\": 1, \"c\": null, \"d\": 1}
+{\"a\": 2.5, \"b\": \"x\", \"c\": null}
+
+{\"a\": 3, \"b\": 2, \"c\": null, \"d\": null}
+" as &[u8];
+
+        let inference = infer_jsonl(input, &InferOptions::default()).unwrap();
+        assert_eq!(
+            inference.resolutions(),
+            vec![
+                "a: int and float, widened to float",
+                "b: int and string, promoted to string",
+                "c: only nulls, written as string",
+                "d: has nulls, missing from 1 of 3 rows",
+            ]
+        );
+
+        let options = InferOptions {
+            on_conflict: TypeConflict::Error,
+            ..Default::default()
+        };
+        assert!(matches!(
+            infer_jsonl(input, &options),
+            Err(BrrrrError::SchemaMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(","), Ok(b','));
+        assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("\t"), Ok(b'\t'));
+        assert!(parse_delimiter(";;").is_err());
+    }
+}
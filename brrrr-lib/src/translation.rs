@@ -0,0 +1,451 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+use std::io;
+use std::str::FromStr;
+
+use crate::sequence::reverse_complement;
+
+/// A genetic code, named for its NCBI translation table.
+///
+/// See <https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi>.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CodonTable {
+    /// Table 1, the standard code.
+    #[default]
+    Standard,
+    /// Table 2, the vertebrate mitochondrial code.
+    VertebrateMitochondrial,
+    /// Table 3, the yeast mitochondrial code.
+    YeastMitochondrial,
+    /// Table 4, the mold, protozoan, and coelenterate mitochondrial code.
+    MoldMitochondrial,
+    /// Table 5, the invertebrate mitochondrial code.
+    InvertebrateMitochondrial,
+    /// Table 6, the ciliate, dasycladacean, and hexamita nuclear code.
+    Ciliate,
+    /// Table 9, the echinoderm and flatworm mitochondrial code.
+    EchinodermMitochondrial,
+    /// Table 10, the euplotid nuclear code.
+    Euplotid,
+    /// Table 11, the bacterial, archaeal, and plant plastid code.
+    Bacterial,
+    /// Table 12, the alternative yeast nuclear code.
+    AlternativeYeast,
+    /// Table 13, the ascidian mitochondrial code.
+    AscidianMitochondrial,
+    /// Table 14, the alternative flatworm mitochondrial code.
+    AlternativeFlatwormMitochondrial,
+}
+
+impl CodonTable {
+    /// Returns the NCBI id of the table.
+    pub fn id(&self) -> u8 {
+        match self {
+            CodonTable::Standard => 1,
+            CodonTable::VertebrateMitochondrial => 2,
+            CodonTable::YeastMitochondrial => 3,
+            CodonTable::MoldMitochondrial => 4,
+            CodonTable::InvertebrateMitochondrial => 5,
+            CodonTable::Ciliate => 6,
+            CodonTable::EchinodermMitochondrial => 9,
+            CodonTable::Euplotid => 10,
+            CodonTable::Bacterial => 11,
+            CodonTable::AlternativeYeast => 12,
+            CodonTable::AscidianMitochondrial => 13,
+            CodonTable::AlternativeFlatwormMitochondrial => 14,
+        }
+    }
+
+    /// Returns the amino acid of each codon, in NCBI's TCAG order.
+    fn amino_acids(&self) -> &'static [u8] {
+        match self {
+            CodonTable::Standard | CodonTable::Bacterial => {
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::VertebrateMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"
+            }
+            CodonTable::YeastMitochondrial => {
+                b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::MoldMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::InvertebrateMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG"
+            }
+            CodonTable::Ciliate => {
+                b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::EchinodermMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"
+            }
+            CodonTable::Euplotid => {
+                b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::AlternativeYeast => {
+                b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"
+            }
+            CodonTable::AscidianMitochondrial => {
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG"
+            }
+            CodonTable::AlternativeFlatwormMitochondrial => {
+                b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"
+            }
+        }
+    }
+
+    /// Returns the start codons of the table, marked with `M` in NCBI's TCAG order.
+    fn starts(&self) -> &'static [u8] {
+        match self {
+            CodonTable::Standard => {
+                b"---M---------------M---------------M----------------------------"
+            }
+            CodonTable::VertebrateMitochondrial => {
+                b"--------------------------------MMMM---------------M------------"
+            }
+            CodonTable::YeastMitochondrial => {
+                b"----------------------------------MM---------------M------------"
+            }
+            CodonTable::MoldMitochondrial => {
+                b"--MM---------------M------------MMMM---------------M------------"
+            }
+            CodonTable::InvertebrateMitochondrial => {
+                b"---M----------------------------MMMM---------------M------------"
+            }
+            CodonTable::Ciliate => {
+                b"-----------------------------------M----------------------------"
+            }
+            CodonTable::Bacterial => {
+                b"---M---------------M------------MMMM---------------M------------"
+            }
+            CodonTable::EchinodermMitochondrial => {
+                b"-----------------------------------M---------------M------------"
+            }
+            CodonTable::Euplotid => {
+                b"-----------------------------------M----------------------------"
+            }
+            CodonTable::AlternativeYeast => {
+                b"-------------------M---------------M----------------------------"
+            }
+            CodonTable::AscidianMitochondrial => {
+                b"---M------------------------------MM---------------M------------"
+            }
+            CodonTable::AlternativeFlatwormMitochondrial => {
+                b"-----------------------------------M----------------------------"
+            }
+        }
+    }
+
+    /// Returns the amino acid for `codon`, `*` for a stop, or `X` if the codon is ambiguous.
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        codon_index(codon).map_or(b'X', |i| self.amino_acids()[i])
+    }
+
+    /// Returns whether `codon` is a start codon.
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        codon_index(codon).is_some_and(|i| self.starts()[i] == b'M')
+    }
+
+    /// Returns whether `codon` is a stop codon.
+    pub fn is_stop(&self, codon: &[u8]) -> bool {
+        self.translate_codon(codon) == b'*'
+    }
+}
+
+impl FromStr for CodonTable {
+    type Err = io::Error;
+
+    /// Parses an NCBI translation table id, e.g. `1` or `11`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "1" => Ok(CodonTable::Standard),
+            "2" => Ok(CodonTable::VertebrateMitochondrial),
+            "3" => Ok(CodonTable::YeastMitochondrial),
+            "4" => Ok(CodonTable::MoldMitochondrial),
+            "5" => Ok(CodonTable::InvertebrateMitochondrial),
+            "6" => Ok(CodonTable::Ciliate),
+            "9" => Ok(CodonTable::EchinodermMitochondrial),
+            "10" => Ok(CodonTable::Euplotid),
+            "11" => Ok(CodonTable::Bacterial),
+            "12" => Ok(CodonTable::AlternativeYeast),
+            "13" => Ok(CodonTable::AscidianMitochondrial),
+            "14" => Ok(CodonTable::AlternativeFlatwormMitochondrial),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unsupported codon table: {:?}, expected one of 1-6 or 9-14",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// The reading frames to translate a sequence in, like transeq's `-frame`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frames {
+    /// A single frame, 1 to 3 on the forward strand, or -1 to -3 on the reverse complement.
+    One(i8),
+    /// All six frames, in the order 1, 2, 3, -1, -2, -3.
+    All,
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Frames::One(1)
+    }
+}
+
+impl Frames {
+    /// Returns each frame to translate, in order.
+    pub fn frames(&self) -> Vec<i8> {
+        match self {
+            Frames::One(frame) => vec![*frame],
+            Frames::All => vec![1, 2, 3, -1, -2, -3],
+        }
+    }
+}
+
+impl FromStr for Frames {
+    type Err = io::Error;
+
+    /// Parses a frame, `1`, `2`, `3`, `-1`, `-2`, or `-3`, or `all` for all six.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "all" => Ok(Frames::All),
+            frame => match frame.parse::<i8>() {
+                Ok(f @ (-3..=-1 | 1..=3)) => Ok(Frames::One(f)),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "unsupported frame: {:?}, expected 1, 2, 3, -1, -2, -3, or all",
+                        s
+                    ),
+                )),
+            },
+        }
+    }
+}
+
+/// Translates `sequence` to protein in `frame`, with `table`.
+///
+/// Frames 1 to 3 start at the first to third base of `sequence`, and -1 to -3 at the first to
+/// third base of its reverse complement. A trailing partial codon is dropped, stops are `*`, and
+/// ambiguous codons are `X`.
+pub fn translate(sequence: &[u8], frame: i8, table: CodonTable) -> Vec<u8> {
+    let offset = (frame.unsigned_abs() as usize).saturating_sub(1);
+    let translate_from = |strand: &[u8]| {
+        strand
+            .get(offset..)
+            .unwrap_or_default()
+            .chunks_exact(3)
+            .map(|codon| table.translate_codon(codon))
+            .collect()
+    };
+
+    if frame < 0 {
+        translate_from(&reverse_complement(sequence))
+    } else {
+        translate_from(sequence)
+    }
+}
+
+/// Returns the index of `codon` in NCBI's TCAG order, or None if it has an ambiguous base.
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    if codon.len() != 3 {
+        return None;
+    }
+
+    codon.iter().try_fold(0, |index, b| {
+        let base = match b.to_ascii_uppercase() {
+            b'T' | b'U' => 0,
+            b'C' => 1,
+            b'A' => 2,
+            b'G' => 3,
+            _ => return None,
+        };
+        Some(index * 4 + base)
+    })
+}
+
+/// An open reading frame within a sequence.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Orf {
+    /// The 0-based offset of the leftmost base of the ORF on the forward strand.
+    pub start: usize,
+    /// The length of the ORF in bases, not counting the stop codon.
+    pub length: usize,
+    /// The frame of the ORF, 1 to 3 on the forward strand and -1 to -3 on the reverse.
+    pub frame: i8,
+}
+
+/// Returns the longest ORF in `sequence` in a single frame, as (offset, length) on `sequence`.
+fn longest_in_frame(
+    sequence: &[u8],
+    offset: usize,
+    table: CodonTable,
+    require_start: bool,
+) -> Option<(usize, usize)> {
+    let mut longest: Option<(usize, usize)> = None;
+    let mut open = if require_start { None } else { Some(offset) };
+
+    let mut consider = |start: usize, end: usize| {
+        if end > start && longest.is_none_or(|(_, length)| end - start > length) {
+            longest = Some((start, end - start));
+        }
+    };
+
+    let mut i = offset;
+    while i + 3 <= sequence.len() {
+        let codon = &sequence[i..i + 3];
+
+        if table.is_stop(codon) {
+            if let Some(start) = open {
+                consider(start, i);
+            }
+            open = if require_start { None } else { Some(i + 3) };
+        } else if open.is_none() && table.is_start(codon) {
+            open = Some(i);
+        }
+
+        i += 3;
+    }
+
+    if let Some(start) = open {
+        consider(start, i);
+    }
+
+    longest
+}
+
+/// Returns the longest ORF in any of the six frames of `sequence`, or None if there isn't one.
+///
+/// An ORF runs up to a stop codon or the end of the sequence. If `require_start` is set, it must
+/// also begin with a start codon of `table`, otherwise it begins after the previous stop. Ties go
+/// to the first frame, in the order 1, 2, 3, -1, -2, -3.
+pub fn longest_orf(sequence: &[u8], table: CodonTable, require_start: bool) -> Option<Orf> {
+    let reverse = reverse_complement(sequence);
+    let mut longest: Option<Orf> = None;
+
+    for (strand, frame_sign) in [(sequence, 1), (reverse.as_slice(), -1)] {
+        for offset in 0..3 {
+            if let Some((start, length)) = longest_in_frame(strand, offset, table, require_start) {
+                if longest.as_ref().is_none_or(|orf| length > orf.length) {
+                    let start = if frame_sign > 0 {
+                        start
+                    } else {
+                        sequence.len() - start - length
+                    };
+
+                    longest = Some(Orf {
+                        start,
+                        length,
+                        frame: frame_sign * (offset as i8 + 1),
+                    });
+                }
+            }
+        }
+    }
+
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_codon() {
+        assert_eq!(CodonTable::Standard.translate_codon(b"ATG"), b'M');
+        assert_eq!(CodonTable::Standard.translate_codon(b"tga"), b'*');
+        assert_eq!(
+            CodonTable::VertebrateMitochondrial.translate_codon(b"TGA"),
+            b'W'
+        );
+        assert_eq!(CodonTable::Standard.translate_codon(b"ANG"), b'X');
+        assert!(CodonTable::Bacterial.is_start(b"GTG"));
+        assert!(!CodonTable::Standard.is_start(b"GTG"));
+        assert_eq!("11".parse::<CodonTable>().unwrap(), CodonTable::Bacterial);
+        assert!("7".parse::<CodonTable>().is_err());
+
+        // Every table parses from its own id, and its data covers all 64 codons.
+        for id in [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14] {
+            let table: CodonTable = id.to_string().parse().unwrap();
+            assert_eq!(table.id(), id);
+            assert_eq!(table.amino_acids().len(), 64);
+            assert_eq!(table.starts().len(), 64);
+        }
+
+        // Mitochondrial codes read AGA differently, and start at ATA.
+        assert_eq!(
+            CodonTable::EchinodermMitochondrial.translate_codon(b"AGA"),
+            b'S'
+        );
+        assert_eq!(
+            CodonTable::AscidianMitochondrial.translate_codon(b"AGA"),
+            b'G'
+        );
+        assert!(CodonTable::AscidianMitochondrial.is_start(b"ATA"));
+        assert!(!CodonTable::Standard.is_start(b"ATA"));
+        assert_eq!(
+            CodonTable::AlternativeFlatwormMitochondrial.translate_codon(b"TAA"),
+            b'Y'
+        );
+        assert_eq!(CodonTable::Euplotid.translate_codon(b"TGA"), b'C');
+    }
+
+    #[test]
+    fn test_translate() {
+        let sequence = b"ATGGCCTAAGC";
+        let table = CodonTable::Standard;
+
+        assert_eq!(translate(sequence, 1, table), b"MA*");
+        assert_eq!(translate(sequence, 2, table), b"WPK");
+        assert_eq!(translate(sequence, 3, table), b"GLS");
+        // The reverse complement is GCTTAGGCCAT.
+        assert_eq!(translate(sequence, -1, table), b"A*A");
+        assert_eq!(translate(sequence, -2, table), b"LRP");
+        assert_eq!(translate(sequence, -3, table), b"LGH");
+        assert_eq!(translate(b"AT", 3, table), b"");
+        assert_eq!(
+            translate(b"TGA", 1, CodonTable::VertebrateMitochondrial),
+            b"W"
+        );
+
+        assert_eq!("-2".parse::<Frames>().unwrap(), Frames::One(-2));
+        assert_eq!("all".parse::<Frames>().unwrap().frames().len(), 6);
+        assert!("0".parse::<Frames>().is_err());
+        assert!("4".parse::<Frames>().is_err());
+    }
+
+    #[test]
+    fn test_longest_orf() {
+        let sequence = b"CCATGAAATTTTAGCC";
+        assert_eq!(
+            longest_orf(sequence, CodonTable::Standard, true),
+            Some(Orf {
+                start: 2,
+                length: 9,
+                frame: 3
+            })
+        );
+
+        let reverse = reverse_complement(sequence);
+        assert_eq!(
+            longest_orf(&reverse, CodonTable::Standard, true),
+            Some(Orf {
+                start: 5,
+                length: 9,
+                frame: -3
+            })
+        );
+
+        assert_eq!(longest_orf(b"CCCC", CodonTable::Standard, true), None);
+        assert_eq!(
+            longest_orf(b"CCCC", CodonTable::Standard, false).map(|o| o.length),
+            Some(3)
+        );
+    }
+}
@@ -8,24 +8,68 @@ use noodles::sam::alignment;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::io;
 use std::str;
+use std::str::FromStr;
+
+use crate::errors::{at_line, ParseError};
+use crate::fastq_ops::is_placeholder_quality;
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct FastaRecord {
     pub id: String,
     pub description: Option<String>,
     pub sequence: String,
+    /// The header line after the `>`, as it was in the input, if it's kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// The longest single-base run of the sequence, once the pipeline has measured it for the
+    /// `max_homopolymer` column or filter, so it's only measured once.
+    #[serde(skip)]
+    pub max_homopolymer: Option<usize>,
 }
 
 impl From<fasta::Record> for FastaRecord {
     fn from(src: fasta::Record) -> FastaRecord {
         let seq = src.sequence();
-        let ss = str::from_utf8(&seq.as_ref()).unwrap();
+        let ss = str::from_utf8(seq.as_ref()).unwrap();
 
         FastaRecord {
             id: src.name().to_string(),
-            description: src.description().map_or(None, |i| Some(i.to_string())),
+            // A header with trailing whitespace has an empty description, which is no description.
+            description: src
+                .description()
+                .filter(|d| !d.is_empty())
+                .map(|d| d.to_string()),
             sequence: String::from(ss),
+            header: None,
+            max_homopolymer: None,
+        }
+    }
+}
+
+impl FastaRecord {
+    /// Re-splits the header into the id and description at the first `delimiter`, rather than
+    /// at the first whitespace, e.g. `gi|123|ref|NC_000.1| description` is id `gi` and
+    /// description `123|ref|NC_000.1| description` with `|`. A header without the delimiter is
+    /// all id, with no description.
+    ///
+    /// The reader has already split the header at its first whitespace, so that whitespace is
+    /// seen as a single space, e.g. a tab delimiter ends the id at the header's second tab if
+    /// its first whitespace is a tab.
+    pub fn split_id(&mut self, delimiter: char) {
+        let header = match self.description.take() {
+            Some(description) => format!("{} {}", self.id, description),
+            None => std::mem::take(&mut self.id),
+        };
+
+        match header.split_once(delimiter) {
+            Some((id, description)) => {
+                let description = description.trim();
+                self.id = id.to_string();
+                self.description = (!description.is_empty()).then(|| description.to_string());
+            }
+            None => self.id = header,
         }
     }
 }
@@ -35,14 +79,24 @@ pub struct FastqRecord {
     pub id: String,
     pub description: Option<String>,
     pub sequence: String,
+    /// The quality string, which is the `*` placeholder for a read without qualities if
+    /// `allow_star_qual` let it through.
     pub quality: String,
 }
 
+impl FastqRecord {
+    /// Returns whether the quality is the `*` placeholder some tools write for a read without
+    /// qualities.
+    pub fn has_placeholder_quality(&self) -> bool {
+        is_placeholder_quality(self.sequence.as_bytes(), self.quality.as_bytes())
+    }
+}
+
 impl From<fastq::Record> for FastqRecord {
     fn from(src: fastq::Record) -> FastqRecord {
         let seq = src.sequence();
 
-        let ss = str::from_utf8(&seq.as_ref()).unwrap();
+        let ss = str::from_utf8(seq).unwrap();
 
         let noodles_quality = str::from_utf8(src.quality_scores()).unwrap();
         let name = str::from_utf8(src.name()).unwrap();
@@ -56,17 +110,22 @@ impl From<fastq::Record> for FastqRecord {
     }
 }
 
+/// A GFF record, where each field GFF writes as `.` when it's missing (the source, score,
+/// strand, and frame) is `None`.
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct GffRecord {
     pub seqname: String,
-    pub source: String,
+    pub source: Option<String>,
     pub feature: String,
     pub start: usize,
     pub end: usize,
     pub score: Option<f32>,
-    pub strand: String,
+    pub strand: Option<String>,
     pub frame: Option<String>,
     pub attribute: HashMap<String, String>,
+    /// The score column as the input wrote it, e.g. `1e3` or `.`, if it's known.
+    #[serde(skip)]
+    pub score_text: Option<String>,
 }
 
 impl From<gff::Record> for GffRecord {
@@ -77,8 +136,11 @@ impl From<gff::Record> for GffRecord {
         let start = src.start();
         let end = src.end();
         let score = src.score();
-        let strand = src.strand();
-        let phase = src.phase().map_or(None, |f| Some(f.to_string()));
+        let strand = match src.strand() {
+            gff::record::Strand::None => None,
+            strand => Some(String::from(strand.as_ref())),
+        };
+        let phase = src.phase().map(|f| f.to_string());
 
         let mut gff_attrs = HashMap::<String, String>::new();
 
@@ -87,23 +149,358 @@ impl From<gff::Record> for GffRecord {
             let v = String::from(i.value());
 
             // TODO: this isn't faithful to GFF3, value should be a vector
-            if gff_attrs.contains_key(&k) {
-                continue;
-            } else {
-                gff_attrs.insert(k, v);
-            }
+            gff_attrs.entry(k).or_insert(v);
         }
 
         GffRecord {
             seqname: String::from(seqname),
-            source: String::from(source),
+            source: (source != ".").then(|| String::from(source)),
             feature: String::from(feature_type),
             start: usize::from(start),
             end: usize::from(end),
             score,
-            strand: String::from(strand.as_ref()),
+            strand,
             frame: phase,
             attribute: gff_attrs,
+            score_text: None,
+        }
+    }
+}
+
+/// Returns true if `line` is a BED-style header or blank line, i.e. a `track` or `browser` line,
+/// or a `#` comment.
+pub fn is_bed_header(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+/// Parses each line of a BED-like input with `parse`, skipping headers, and attaching the line
+/// number to any parse error.
+pub fn parse_bed_lines<R, T, F>(input: R, parse: F) -> impl Iterator<Item = io::Result<T>>
+where
+    R: io::BufRead,
+    F: Fn(&str) -> io::Result<T>,
+{
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if is_bed_header(l)))
+        .map(move |(i, line)| parse(&line?).map_err(|e| at_line(e, i as u64 + 1)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    ParseError::new(message).into()
+}
+
+/// Parses the next field as `T`, naming the field in the error.
+fn parse_field<'a, T: FromStr, I: Iterator<Item = &'a str>>(
+    fields: &mut I,
+    name: &str,
+) -> io::Result<T> {
+    let raw = fields
+        .next()
+        .ok_or_else(|| invalid_data(format!("missing field: {}", name)))?;
+
+    raw.parse()
+        .map_err(|_| invalid_data(format!("invalid {}: {:?}", name, raw)))
+}
+
+/// Parses the chrom, start, and end fields shared by BED-like formats.
+///
+/// Coordinates are 0-based and half-open, so `start` must not be greater than `end`.
+fn parse_bed_coordinates<'a, I: Iterator<Item = &'a str>>(
+    fields: &mut I,
+) -> io::Result<(String, i64, i64)> {
+    let chrom: String = parse_field(fields, "chrom")?;
+    let start: i64 = parse_field(fields, "start")?;
+    let end: i64 = parse_field(fields, "end")?;
+
+    if start < 0 || start > end {
+        return Err(invalid_data(format!(
+            "invalid interval: {}:{}-{}",
+            chrom, start, end
+        )));
+    }
+
+    Ok((chrom, start, end))
+}
+
+/// A bedGraph record: a value over a 0-based, half-open interval.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BedGraphRecord {
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub value: f64,
+}
+
+impl FromStr for BedGraphRecord {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let (chrom, start, end) = parse_bed_coordinates(&mut fields)?;
+        let value = parse_field(&mut fields, "value")?;
+
+        Ok(BedGraphRecord {
+            chrom,
+            start,
+            end,
+            value,
+        })
+    }
+}
+
+/// A BED3 to BED6 record. Coordinates are 0-based and half-open.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BedRecord {
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub name: Option<String>,
+    pub score: Option<String>,
+    pub strand: Option<String>,
+}
+
+impl FromStr for BedRecord {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split('\t');
+        let (chrom, start, end) = parse_bed_coordinates(&mut fields)?;
+
+        let mut optional = || fields.next().filter(|f| *f != ".").map(|f| f.to_string());
+        let name = optional();
+        let score = optional();
+        let strand = optional();
+
+        Ok(BedRecord {
+            chrom,
+            start,
+            end,
+            name,
+            score,
+            strand,
+        })
+    }
+}
+
+/// The ENCODE peak formats.
+#[derive(Debug, Copy, Clone)]
+pub enum PeakFormat {
+    /// narrowPeak (BED6+4), which ends with the peak's offset from `start`.
+    Narrow,
+    /// broadPeak (BED6+3).
+    Broad,
+}
+
+/// A narrowPeak or broadPeak record.
+///
+/// Coordinates are 0-based and half-open. `p_value`, `q_value`, and `peak` are `None` when the
+/// file holds the format's `-1` placeholder.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct PeakRecord {
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub name: Option<String>,
+    pub score: i64,
+    pub strand: Option<String>,
+    pub signal_value: f64,
+    pub p_value: Option<f64>,
+    pub q_value: Option<f64>,
+    pub peak: Option<i64>,
+}
+
+impl PeakRecord {
+    /// Parses a tab-delimited peak line of the given `format`.
+    pub fn parse(s: &str, format: PeakFormat) -> io::Result<Self> {
+        let mut fields = s.split('\t');
+        let (chrom, start, end) = parse_bed_coordinates(&mut fields)?;
+
+        let dot_to_none = |v: String| if v == "." { None } else { Some(v) };
+        let unassigned_to_none = |v: f64| if v == -1.0 { None } else { Some(v) };
+
+        let name = dot_to_none(parse_field(&mut fields, "name")?);
+        let score = parse_field(&mut fields, "score")?;
+        let strand = dot_to_none(parse_field(&mut fields, "strand")?);
+        let signal_value = parse_field(&mut fields, "signalValue")?;
+        let p_value = unassigned_to_none(parse_field(&mut fields, "pValue")?);
+        let q_value = unassigned_to_none(parse_field(&mut fields, "qValue")?);
+
+        let peak = match format {
+            PeakFormat::Narrow => match parse_field(&mut fields, "peak")? {
+                -1 => None,
+                p => Some(p),
+            },
+            PeakFormat::Broad => None,
+        };
+
+        Ok(PeakRecord {
+            chrom,
+            start,
+            end,
+            name,
+            score,
+            strand,
+            signal_value,
+            p_value,
+            q_value,
+            peak,
+        })
+    }
+}
+
+/// A WIG data point: `value` covers `span` bases starting at the 1-based `position`.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct WigRecord {
+    pub chrom: String,
+    pub position: i64,
+    pub span: i64,
+    pub value: f64,
+}
+
+/// The declaration the current WIG data lines belong to.
+enum WigStep {
+    Variable {
+        chrom: String,
+        span: i64,
+    },
+    Fixed {
+        chrom: String,
+        position: i64,
+        step: i64,
+        span: i64,
+    },
+}
+
+impl FromStr for WigStep {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let kind = words.next().unwrap_or_default();
+
+        let mut chrom = None;
+        let mut start = None;
+        let mut step = None;
+        let mut span = 1;
+
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| invalid_data(format!("invalid declaration: {:?}", s)))?;
+            let parse_int = |v: &str| {
+                v.parse::<i64>()
+                    .map_err(|_| invalid_data(format!("invalid {}: {:?}", key, v)))
+            };
+
+            match key {
+                "chrom" => chrom = Some(value.to_string()),
+                "start" => start = Some(parse_int(value)?),
+                "step" => step = Some(parse_int(value)?),
+                "span" => span = parse_int(value)?,
+                _ => return Err(invalid_data(format!("unknown {} key: {}", kind, key))),
+            }
+        }
+
+        let chrom = chrom.ok_or_else(|| invalid_data(format!("{} is missing chrom", kind)))?;
+
+        match kind {
+            "variableStep" => Ok(WigStep::Variable { chrom, span }),
+            "fixedStep" => Ok(WigStep::Fixed {
+                chrom,
+                position: start.ok_or_else(|| invalid_data("fixedStep is missing start".into()))?,
+                step: step.ok_or_else(|| invalid_data("fixedStep is missing step".into()))?,
+                span,
+            }),
+            _ => Err(invalid_data(format!("unknown declaration: {}", kind))),
+        }
+    }
+}
+
+/// Iterates over the data points of a WIG input, expanding `fixedStep` and `variableStep`
+/// declarations into one record per data line.
+pub struct WigRecords<R> {
+    lines: io::Lines<R>,
+    line_number: u64,
+    step: Option<WigStep>,
+}
+
+impl<R: io::BufRead> WigRecords<R> {
+    /// Creates a new WigRecords from a BufRead.
+    pub fn new(input: R) -> Self {
+        Self {
+            lines: input.lines(),
+            line_number: 0,
+            step: None,
+        }
+    }
+
+    fn parse_data_line(&mut self, line: &str) -> io::Result<WigRecord> {
+        let mut fields = line.split_whitespace();
+
+        match &mut self.step {
+            Some(WigStep::Variable { chrom, span }) => Ok(WigRecord {
+                chrom: chrom.clone(),
+                position: parse_field(&mut fields, "position")?,
+                span: *span,
+                value: parse_field(&mut fields, "value")?,
+            }),
+            Some(WigStep::Fixed {
+                chrom,
+                position,
+                step,
+                span,
+            }) => {
+                let record = WigRecord {
+                    chrom: chrom.clone(),
+                    position: *position,
+                    span: *span,
+                    value: parse_field(&mut fields, "value")?,
+                };
+                *position += *step;
+                Ok(record)
+            }
+            None => Err(invalid_data(format!(
+                "data line before a declaration: {:?}",
+                line
+            ))),
+        }
+    }
+}
+
+impl<R: io::BufRead> Iterator for WigRecords<R> {
+    type Item = io::Result<WigRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line_number += 1;
+
+            if is_bed_header(&line) {
+                continue;
+            }
+
+            if line.starts_with("variableStep") || line.starts_with("fixedStep") {
+                match line.parse() {
+                    Ok(step) => self.step = Some(step),
+                    Err(e) => return Some(Err(at_line(e, self.line_number))),
+                }
+                continue;
+            }
+
+            let line_number = self.line_number;
+            return Some(
+                self.parse_data_line(&line)
+                    .map_err(|e| at_line(e, line_number)),
+            );
         }
     }
 }
@@ -168,14 +565,14 @@ impl From<alignment::Record> for SamRecord {
         let quality_scores = src
             .quality_scores()
             .as_ref()
-            .into_iter()
+            .iter()
             .map(|f| f.to_string())
             .collect();
 
         let operations = src
             .cigar()
             .as_ref()
-            .into_iter()
+            .iter()
             .map(|f| Operation::new(f.kind().to_string(), f.len()))
             .collect();
 
@@ -203,3 +600,66 @@ impl From<alignment::Record> for SamRecord {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fasta_record_split_id() {
+        let header = ">gi|123|ref|NC_000.1| Homo sapiens chromosome 1";
+        let record = |header: &str| {
+            let definition = header.parse().unwrap();
+            let sequence = fasta::record::Sequence::from(b"ACGT".to_vec());
+            FastaRecord::from(fasta::Record::new(definition, sequence))
+        };
+
+        let whitespace = record(header);
+        assert_eq!(whitespace.id, "gi|123|ref|NC_000.1|");
+        assert_eq!(
+            whitespace.description.as_deref(),
+            Some("Homo sapiens chromosome 1")
+        );
+
+        let mut pipe = record(header);
+        pipe.split_id('|');
+        assert_eq!(pipe.id, "gi");
+        assert_eq!(
+            pipe.description.as_deref(),
+            Some("123|ref|NC_000.1| Homo sapiens chromosome 1")
+        );
+
+        let mut tab = record(">seq 1\tsample A");
+        tab.split_id('\t');
+        assert_eq!(tab.id, "seq 1");
+        assert_eq!(tab.description.as_deref(), Some("sample A"));
+
+        let mut no_delimiter = record(">gi|123");
+        no_delimiter.split_id(' ');
+        assert_eq!(no_delimiter.id, "gi|123");
+        assert_eq!(no_delimiter.description, None);
+
+        let trailing = record(">seq1 ");
+        assert_eq!(trailing.id, "seq1");
+        assert_eq!(trailing.description, None);
+    }
+
+    #[test]
+    fn test_gff_record_missing_fields() {
+        let record: gff::Record = "chr1\t.\tgene\t1\t10\t.\t.\t.\tID=g0".parse().unwrap();
+        let record = GffRecord::from(record);
+
+        assert_eq!(record.source, None);
+        assert_eq!(record.score, None);
+        assert_eq!(record.strand, None);
+        assert_eq!(record.frame, None);
+
+        let record: gff::Record = "chr1\tsrc\tCDS\t1\t10\t0.5\t-\t2\tID=c0".parse().unwrap();
+        let record = GffRecord::from(record);
+
+        assert_eq!(record.source.as_deref(), Some("src"));
+        assert_eq!(record.score, Some(0.5));
+        assert_eq!(record.strand.as_deref(), Some("-"));
+        assert_eq!(record.frame.as_deref(), Some("2"));
+    }
+}
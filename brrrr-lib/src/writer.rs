@@ -3,7 +3,13 @@
 
 use serde::ser::Serialize;
 
-use std::io::Result;
+use std::io::{BufRead, ErrorKind, Result};
+
+use noodles::fasta;
+
+use crate::errors::BrrrrError;
+use crate::pipeline::{Pipeline, Stage};
+use crate::types::FastaRecord;
 
 /// A RecordWriter writes FASTA records to the underlying source.
 ///
@@ -35,3 +41,71 @@ use std::io::Result;
 pub trait RecordWriter {
     fn write_serde_record<S: Serialize>(&mut self, r: S) -> Result<()>;
 }
+
+/// Converts a FASTA input with `output`, calling `transform` on each record before it's written,
+/// so library users can apply their own logic, e.g. renaming or masking, without forking brrrr.
+///
+/// To drop a record, the transform clears its id: records left with an empty id aren't written.
+///
+/// # Arguments
+///
+/// * `input` an input that implements the BufRead trait.
+/// * `output` the RecordWriter the records are written with, e.g. a `JsonRecordWriter`.
+/// * `transform` the function called on each record.
+///
+/// # Examples
+///
+/// ```
+/// use brrrr_lib::json_writer::JsonRecordWriter;
+/// use brrrr_lib::writer::convert_with;
+///
+/// let input = b">a\nacgt\n>b\nAAAA\n" as &[u8];
+/// let mut output = Vec::new();
+///
+/// convert_with(input, &mut JsonRecordWriter::new(&mut output), |record| {
+///     if record.id == "b" {
+///         record.id.clear();
+///     }
+///     record.sequence.make_ascii_uppercase();
+/// })
+/// .unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"id\":\"a\",\"description\":null,\"sequence\":\"ACGT\"}\n"
+/// );
+/// ```
+pub fn convert_with<R, W, F>(
+    input: R,
+    output: &mut W,
+    mut transform: F,
+) -> std::result::Result<(), BrrrrError>
+where
+    R: BufRead,
+    W: RecordWriter,
+    F: FnMut(&mut FastaRecord),
+{
+    let mut reader = fasta::Reader::new(input);
+    let mut pipeline = Pipeline::default();
+    pipeline.add(Stage::Transform, |mut record: FastaRecord| {
+        transform(&mut record);
+        Ok(Some(record))
+    });
+    pipeline.add_filter(|record| !record.id.is_empty());
+
+    for read_record in reader.records() {
+        let record = match pipeline.run(FastaRecord::from(read_record?))? {
+            Some(record) => record,
+            None => continue,
+        };
+
+        if let Err(e) = output.write_serde_record(record) {
+            match e.kind() {
+                ErrorKind::BrokenPipe => break,
+                _ => return Err(BrrrrError::from(e)),
+            }
+        }
+    }
+
+    Ok(())
+}
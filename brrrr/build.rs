@@ -0,0 +1,103 @@
+// (c) Copyright 2022 Trent Hauck
+// All Rights Reserved
+
+//! Captures build metadata for `brrrr --version`: the git commit, the build date, and the
+//! versions of the arrow, parquet, and noodles crates resolved in Cargo.lock.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the trimmed output of git with `args`, or None if git isn't there or fails.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+/// Returns today's UTC date as YYYY-MM-DD, honoring SOURCE_DATE_EPOCH for reproducible builds.
+fn build_date() -> String {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0)
+        });
+
+    // Converts days since the epoch to a civil date, per Howard Hinnant's days_from_civil.
+    let z = seconds.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Returns the version of `package` in Cargo.lock.
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+
+    lines.find(|l| l.trim() == name)?;
+    lines
+        .next()?
+        .trim()
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+        .map(String::from)
+}
+
+/// Returns the files to watch so the commit is captured again when it changes. HEAD only changes
+/// on switching branches, so this is also the branch it points to, which moves with each commit,
+/// and HEAD's reflog, which covers a branch ref that's packed instead. Files that don't exist are
+/// left out, since cargo would rerun on every build for them.
+fn git_watched_files() -> Vec<String> {
+    let mut names = vec![String::from("HEAD"), String::from("logs/HEAD")];
+    names.extend(git(&["symbolic-ref", "-q", "HEAD"]));
+
+    names
+        .iter()
+        .filter_map(|name| git(&["rev-parse", "--git-path", name]))
+        .filter(|path| Path::new(path).exists())
+        .collect()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=../Cargo.lock");
+    for path in git_watched_files() {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let unknown = || String::from("unknown");
+    let lock = fs::read_to_string("../Cargo.lock").unwrap_or_default();
+
+    println!(
+        "cargo:rustc-env=BRRRR_GIT_COMMIT={}",
+        git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(unknown)
+    );
+    println!("cargo:rustc-env=BRRRR_BUILD_DATE={}", build_date());
+
+    for package in ["arrow", "parquet", "noodles"] {
+        println!(
+            "cargo:rustc-env=BRRRR_{}_VERSION={}",
+            package.to_uppercase(),
+            locked_version(&lock, package).unwrap_or_else(unknown)
+        );
+    }
+}
@@ -1,26 +1,72 @@
 // (c) Copyright 2020 Trent Hauck
 // All Rights Reserved
 
+use std::error::Error;
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{stdin, stdout, BufReader};
-use std::path::PathBuf;
+use std::io::{self, stdin, stdout, BufRead, BufReader, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 
-use clap::{Parser, Subcommand};
+use arrow::datatypes::Schema;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{generate, Shell};
+use clap_mangen::Man;
 
+#[cfg(feature = "chemistry")]
+use brrrr_lib::chemistry;
+use brrrr_lib::convert;
 use brrrr_lib::csv_writer;
 use brrrr_lib::errors::BrrrrError;
+use brrrr_lib::fasta_ops::{
+    self, FastaOptions, SkipComments, SplitAssignment, SplitBy, SplitOptions,
+};
+use brrrr_lib::fastq_ops::FastqOptions;
+#[cfg(feature = "df")]
+use brrrr_lib::frame::{self, FrameFormat, FrameOptions, Predicate};
+use brrrr_lib::gff_ops::{self, GffOptions, ScoreType};
+use brrrr_lib::id_list::IdList;
+use brrrr_lib::input::{self, InputFormat};
+use brrrr_lib::intervals::{self, IntervalFormat, Region};
 use brrrr_lib::json_writer;
+use brrrr_lib::kmers::{self, MAX_K};
+use brrrr_lib::output::{CompressedWriter, OutputCompression, TrailingNewline};
 use brrrr_lib::parquet_reader;
 use brrrr_lib::parquet_writer;
+use brrrr_lib::partition::PartitionBy;
+use brrrr_lib::record_batches;
+use brrrr_lib::selftest;
+use brrrr_lib::sequence::Motif;
+use brrrr_lib::stats::{self, ReportFormat};
+use brrrr_lib::tabular::{self, parse_delimiter, CsvOptions, InferOptions, TypeConflict};
+use brrrr_lib::translation::{CodonTable, Frames};
+use brrrr_lib::types::PeakFormat;
 use parquet::basic::Compression;
 
+/// The `--version` output, with the build metadata captured by build.rs.
+const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit: ",
+    env!("BRRRR_GIT_COMMIT"),
+    "\nbuild date: ",
+    env!("BRRRR_BUILD_DATE"),
+    "\narrow: ",
+    env!("BRRRR_ARROW_VERSION"),
+    "\nparquet: ",
+    env!("BRRRR_PARQUET_VERSION"),
+    "\nnoodles: ",
+    env!("BRRRR_NOODLES_VERSION"),
+);
+
 /// The Enum that represents the underlying command-line tool.
 #[derive(Parser)]
 #[clap(
     name = "brrrr",
     about = "Commandline utilities for modern biology and chemistry informatics.",
     author = "Trent Hauck <trent@trenthauck.com>",
-    version = "0.14.0"
+    version,
+    long_version = LONG_VERSION
 )]
 struct Cli {
     #[clap(subcommand)]
@@ -29,57 +75,640 @@ struct Cli {
 
 #[derive(clap::ValueEnum, Clone)]
 enum ParquetCompression {
-    UNCOMPRESSED,
-    SNAPPY,
-    GZIP,
-    BROTLI,
-    LZO,
-    LZ4,
-    ZSTD,
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Brotli,
+    Lzo,
+    Lz4,
+    Zstd,
 }
 
-impl Into<Compression> for ParquetCompression {
-    fn into(self) -> Compression {
-        match self {
-            ParquetCompression::UNCOMPRESSED => Compression::UNCOMPRESSED,
-            ParquetCompression::GZIP => Compression::GZIP,
-            ParquetCompression::BROTLI => Compression::BROTLI,
-            ParquetCompression::SNAPPY => Compression::SNAPPY,
-            ParquetCompression::LZO => Compression::LZO,
-            ParquetCompression::LZ4 => Compression::LZ4,
-            ParquetCompression::ZSTD => Compression::ZSTD,
+impl From<ParquetCompression> for Compression {
+    fn from(c: ParquetCompression) -> Compression {
+        match c {
+            ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+            ParquetCompression::Gzip => Compression::GZIP,
+            ParquetCompression::Brotli => Compression::BROTLI,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Lzo => Compression::LZO,
+            ParquetCompression::Lz4 => Compression::LZ4,
+            ParquetCompression::Zstd => Compression::ZSTD,
         }
     }
 }
 
 #[derive(clap::ValueEnum, Clone)]
 enum CliBioFileCompression {
-    UNCOMPRESSED,
-    GZIP,
+    Uncompressed,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl From<CliBioFileCompression> for parquet_writer::BioFileCompression {
+    fn from(c: CliBioFileCompression) -> parquet_writer::BioFileCompression {
+        match c {
+            CliBioFileCompression::Uncompressed => parquet_writer::BioFileCompression::UNCOMPRESSED,
+            CliBioFileCompression::Gzip => parquet_writer::BioFileCompression::GZIP,
+            CliBioFileCompression::Bzip2 => parquet_writer::BioFileCompression::BZIP2,
+            CliBioFileCompression::Zstd => parquet_writer::BioFileCompression::ZSTD,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliMergeAttributes {
+    Drop,
+    Concat,
 }
 
-impl Into<parquet_writer::BioFileCompression> for CliBioFileCompression {
-    fn into(self) -> parquet_writer::BioFileCompression {
-        match self {
-            CliBioFileCompression::UNCOMPRESSED => parquet_writer::BioFileCompression::UNCOMPRESSED,
-            CliBioFileCompression::GZIP => parquet_writer::BioFileCompression::GZIP,
+impl From<CliMergeAttributes> for gff_ops::MergeAttributes {
+    fn from(c: CliMergeAttributes) -> gff_ops::MergeAttributes {
+        match c {
+            CliMergeAttributes::Drop => gff_ops::MergeAttributes::Drop,
+            CliMergeAttributes::Concat => gff_ops::MergeAttributes::Concat,
         }
     }
 }
 
+#[derive(clap::ValueEnum, Clone)]
+enum CliMergeFormat {
+    Gff,
+    Bed,
+}
+
+impl From<CliMergeFormat> for gff_ops::MergeFormat {
+    fn from(c: CliMergeFormat) -> gff_ops::MergeFormat {
+        match c {
+            CliMergeFormat::Gff => gff_ops::MergeFormat::Gff,
+            CliMergeFormat::Bed => gff_ops::MergeFormat::Bed,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliPeakFormat {
+    Narrow,
+    Broad,
+}
+
+impl From<CliPeakFormat> for PeakFormat {
+    fn from(c: CliPeakFormat) -> PeakFormat {
+        match c {
+            CliPeakFormat::Narrow => PeakFormat::Narrow,
+            CliPeakFormat::Broad => PeakFormat::Broad,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliIntervalFormat {
+    Gff,
+    Bed,
+}
+
+impl From<CliIntervalFormat> for IntervalFormat {
+    fn from(c: CliIntervalFormat) -> IntervalFormat {
+        match c {
+            CliIntervalFormat::Gff => IntervalFormat::Gff,
+            CliIntervalFormat::Bed => IntervalFormat::Bed,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliTypeConflict {
+    Promote,
+    Error,
+}
+
+impl From<CliTypeConflict> for TypeConflict {
+    fn from(c: CliTypeConflict) -> TypeConflict {
+        match c {
+            CliTypeConflict::Promote => TypeConflict::Promote,
+            CliTypeConflict::Error => TypeConflict::Error,
+        }
+    }
+}
+
+#[cfg(feature = "df")]
+#[derive(clap::ValueEnum, Clone)]
+enum CliFrameFormat {
+    Parquet,
+    Csv,
+}
+
+#[cfg(feature = "df")]
+impl From<CliFrameFormat> for FrameFormat {
+    fn from(f: CliFrameFormat) -> FrameFormat {
+        match f {
+            CliFrameFormat::Parquet => FrameFormat::Parquet,
+            CliFrameFormat::Csv => FrameFormat::Csv,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliSplitBy {
+    Records,
+    Bases,
+}
+
+impl From<CliSplitBy> for SplitBy {
+    fn from(c: CliSplitBy) -> SplitBy {
+        match c {
+            CliSplitBy::Records => SplitBy::Records,
+            CliSplitBy::Bases => SplitBy::Bases,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliSplitAssignment {
+    Contiguous,
+    RoundRobin,
+}
+
+impl From<CliSplitAssignment> for SplitAssignment {
+    fn from(c: CliSplitAssignment) -> SplitAssignment {
+        match c {
+            CliSplitAssignment::Contiguous => SplitAssignment::Contiguous,
+            CliSplitAssignment::RoundRobin => SplitAssignment::RoundRobin,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliReportFormat {
+    Json,
+    Table,
+}
+
+impl From<CliReportFormat> for ReportFormat {
+    fn from(c: CliReportFormat) -> ReportFormat {
+        match c {
+            CliReportFormat::Json => ReportFormat::Json,
+            CliReportFormat::Table => ReportFormat::Table,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliScoreType {
+    Int,
+    Float,
+    String,
+}
+
+impl From<CliScoreType> for ScoreType {
+    fn from(c: CliScoreType) -> ScoreType {
+        match c {
+            CliScoreType::Int => ScoreType::Int,
+            CliScoreType::Float => ScoreType::Float,
+            CliScoreType::String => ScoreType::Str,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliSchemaFormat {
+    Fasta,
+    Fastq,
+    Gff,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliInputFormat {
+    Fasta,
+    Fastq,
+    Gff,
+}
+
+impl From<CliInputFormat> for InputFormat {
+    fn from(f: CliInputFormat) -> InputFormat {
+        match f {
+            CliInputFormat::Fasta => InputFormat::Fasta,
+            CliInputFormat::Fastq => InputFormat::Fastq,
+            CliInputFormat::Gff => InputFormat::Gff,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliCollapseFormat {
+    Fasta,
+    Parquet,
+}
+
+/// The options for deduplicating in fixed memory, shared by uniq and collapse.
+#[derive(clap::Args)]
+struct DedupStreamArgs {
+    /// Find repeated sequences with a Bloom filter of fixed size, instead of holding a hash of
+    /// every distinct sequence, for inputs too large for memory. A few distinct sequences are
+    /// wrongly treated as duplicates, more as the filter fills. collapse then writes the first
+    /// record of each sequence as it's read, as FASTA without counts.
+    #[clap(long)]
+    dedup_stream: bool,
+    /// The size of the --dedup-stream filter in MiB. 64 MiB keeps errors to about 1 in 38,000
+    /// sequences for 10 million distinct sequences.
+    #[clap(long, default_value_t = 64, value_parser = clap::value_parser!(u64).range(1..))]
+    dedup_filter_mb: u64,
+}
+
+impl DedupStreamArgs {
+    /// Returns the size of the filter in bytes, if --dedup-stream is set.
+    fn filter_bytes(&self) -> Option<usize> {
+        self.dedup_stream
+            .then(|| self.dedup_filter_mb as usize * (1 << 20))
+    }
+}
+
+/// The options to write a window of the records, shared by the FASTA, FASTQ, and GFF converters.
+#[derive(clap::Args)]
+struct WindowArgs {
+    /// Skip this many of the records that would be written, e.g. with --limit to take a window.
+    /// fq2pq's number column still starts at 0.
+    #[clap(long, default_value = "0")]
+    skip: usize,
+    /// Stop after writing this many records, without reading the rest of the input.
+    #[clap(long)]
+    limit: Option<usize>,
+}
+
+/// The options to keep or drop records by id, shared by the FASTA, FASTQ, and GFF converters.
+#[derive(clap::Args)]
+struct IdListArgs {
+    /// Keep only the records whose id is in this file of ids, one per line, matched exactly. A
+    /// GFF feature's id is its ID attribute. How many of the ids were found is reported.
+    #[clap(long)]
+    id_list: Option<PathBuf>,
+    /// Drop the records whose id is in the --id-list file instead.
+    #[clap(long, requires = "id-list")]
+    exclude_ids: bool,
+}
+
+impl IdListArgs {
+    /// Reads the id list, if there is one.
+    fn read(&self) -> io::Result<Option<Arc<IdList>>> {
+        self.id_list
+            .as_ref()
+            .map(|path| IdList::read(path, self.exclude_ids).map(Arc::new))
+            .transpose()
+    }
+}
+
+/// The options shared by the GFF converters.
+#[derive(clap::Args)]
+struct GffArgs {
+    /// Sort features by (seqname, start, end) before writing.
+    #[clap(long)]
+    sort: bool,
+    /// Keep only features whose source column equals this, e.g. ensembl.
+    #[clap(long)]
+    source: Option<String>,
+    /// Keep only features overlapping this region, e.g. chr1:1000-50000. May be repeated.
+    #[clap(long)]
+    region: Vec<Region>,
+    /// Read the input even if its first line looks like another format, e.g. FASTA, instead of
+    /// failing.
+    #[clap(long)]
+    no_sniff: bool,
+    #[clap(flatten)]
+    id_list_args: IdListArgs,
+    #[clap(flatten)]
+    window_args: WindowArgs,
+}
+
+impl TryFrom<GffArgs> for GffOptions {
+    type Error = BrrrrError;
+
+    fn try_from(a: GffArgs) -> Result<GffOptions, BrrrrError> {
+        Ok(GffOptions {
+            sort: a.sort,
+            source: a.source,
+            regions: a.region,
+            id_list: a.id_list_args.read()?,
+            no_sniff: a.no_sniff,
+            skip: a.window_args.skip,
+            limit: a.window_args.limit,
+            ..Default::default()
+        })
+    }
+}
+
+/// The options shared by the FASTQ converters.
+#[derive(clap::Args)]
+struct FastqArgs {
+    /// Drop reads whose mean Phred score is below this.
+    #[clap(long)]
+    min_mean_qual: Option<f64>,
+    /// The offset the quality scores are encoded with, 33, or 64 for the Phred+64 of Illumina
+    /// 1.3 to 1.7. Mean qualities are decoded with it.
+    #[clap(long, default_value = "33", value_parser = phred_offset)]
+    phred_offset: u8,
+    /// Skip reads that can't be parsed, instead of failing on the first.
+    #[clap(long)]
+    skip_errors: bool,
+    /// Fail if more than this fraction of reads can't be parsed, e.g. 0.05, so the wrong kind of
+    /// input doesn't make a nearly empty output.
+    #[clap(long, requires = "skip-errors", value_parser = fraction)]
+    max_error_rate: Option<f64>,
+    /// Accept a lone * quality, which some tools write for a read without qualities, instead of
+    /// failing the read for a length mismatch. Parquet outputs write the quality as null.
+    #[clap(long)]
+    allow_star_qual: bool,
+    /// With --allow-star-qual, replace a * quality with this character repeated to the read's
+    /// length, e.g. I, instead of writing null.
+    #[clap(long, requires = "allow-star-qual", value_parser = quality_char)]
+    fill_qual: Option<u8>,
+    /// Reverse each read's sequence, and its quality with it, without complementing the bases.
+    #[clap(long)]
+    reverse: bool,
+    /// Read the input even if its first line looks like another format, e.g. FASTA, instead of
+    /// failing.
+    #[clap(long)]
+    no_sniff: bool,
+    /// Read reads whose sequence and quality are wrapped over several lines. Each quality ends
+    /// once it's as long as its sequence. Slower, so off by default.
+    #[clap(long)]
+    multiline_fastq: bool,
+    #[clap(flatten)]
+    case_args: CaseArgs,
+    #[clap(flatten)]
+    id_list_args: IdListArgs,
+    #[clap(flatten)]
+    window_args: WindowArgs,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum CliOutputCompression {
+    None,
+    Gzip,
+    Zstd,
+    Bgzf,
+}
+
+impl From<CliOutputCompression> for OutputCompression {
+    fn from(c: CliOutputCompression) -> OutputCompression {
+        match c {
+            CliOutputCompression::None => OutputCompression::None,
+            CliOutputCompression::Gzip => OutputCompression::Gzip,
+            CliOutputCompression::Zstd => OutputCompression::Zstd,
+            CliOutputCompression::Bgzf => OutputCompression::Bgzf,
+        }
+    }
+}
+
+/// The options to compress a text output, shared by the jsonl and csv converters.
+#[derive(clap::Args)]
+struct CompressionArgs {
+    /// Compress the output as a whole. bgzf is blocked gzip, which gzip tools read and tabix can
+    /// index.
+    #[clap(long, value_enum, default_value = "none")]
+    output_compression: CliOutputCompression,
+    /// Gzip the output, the same as --output-compression gzip.
+    #[clap(long, conflicts_with = "output-compression")]
+    gzip: bool,
+    /// The compression level of a compressed output, from 0, no compression, to 9, the smallest
+    /// and slowest. zstd always compresses, so it takes 0 as 1. Defaults to 6 for gzip and bgzf,
+    /// and to zstd's own default, 3, for zstd.
+    #[clap(
+        long,
+        alias = "gzip-level",
+        value_parser = clap::value_parser!(u32).range(0..=9)
+    )]
+    compression_level: Option<u32>,
+    #[clap(flatten)]
+    newline_args: NewlineArgs,
+}
+
+impl CompressionArgs {
+    /// Returns stdout, compressed if asked.
+    fn output(&self) -> io::Result<TrailingNewline<CompressedWriter<Stdout>>> {
+        let compression = if self.gzip {
+            OutputCompression::Gzip
+        } else {
+            self.output_compression.clone().into()
+        };
+        if compression == OutputCompression::None && self.compression_level.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--compression-level needs a compressed output, e.g. --gzip",
+            ));
+        }
+        let output = CompressedWriter::new(stdout(), compression, self.compression_level)?;
+        Ok(self.newline_args.output(output))
+    }
+}
+
+/// The option to leave off the last newline of a text output, shared by the jsonl, csv, FASTA,
+/// and FASTQ writers.
+#[derive(clap::Args)]
+struct NewlineArgs {
+    /// End the output without a newline after the last record, for tools that expect none.
+    #[clap(long)]
+    no_trailing_newline: bool,
+}
+
+impl NewlineArgs {
+    /// Wraps `output` to leave off its last newline, if asked.
+    fn output<W: Write>(&self, output: W) -> TrailingNewline<W> {
+        TrailingNewline::new(output, !self.no_trailing_newline)
+    }
+}
+
+/// The option to describe a parquet output, shared by the parquet converters.
+#[derive(clap::Args)]
+struct SchemaArgs {
+    /// After converting, write the schema of the parquet output as JSON to this path, with the
+    /// name, arrow type, and nullability of each column.
+    #[clap(long)]
+    write_schema: Option<PathBuf>,
+}
+
+impl SchemaArgs {
+    /// Writes the schema of the parquet file at `output`, if asked.
+    fn write(self, output: PathBuf) -> Result<(), BrrrrError> {
+        match self.write_schema {
+            Some(path) => parquet_reader::write_schema_json(output, path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The option to check a parquet conversion without running it, shared by the FASTA, FASTQ, and
+/// GFF parquet converters.
+#[derive(clap::Args)]
+struct DryRunArgs {
+    /// Check the input can be read and the output written, and print the input's compression
+    /// and format, as detected from its first bytes, and the output schema, without converting
+    /// or writing anything.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl DryRunArgs {
+    /// Prints what a conversion of `input` to `output` would read and write, if this is a dry
+    /// run, returning whether it was.
+    fn report(
+        &self,
+        input: &Path,
+        output: &Path,
+        compression: parquet_writer::BioFileCompression,
+        schema: Schema,
+    ) -> Result<bool, BrrrrError> {
+        if !self.dry_run {
+            return Ok(false);
+        }
+
+        let dry_run = parquet_writer::dry_run(input, output, compression, schema)?;
+        if dry_run.detected_compression != compression {
+            eprintln!(
+                "brrrr: the input looks {}, but would be read as {}",
+                dry_run.detected_compression.name(),
+                compression.name()
+            );
+        }
+        println!("output: {}", output.display());
+        dry_run.write(&mut stdout())?;
+        Ok(true)
+    }
+}
+
+/// The option to split a parquet output into numbered files, shared by the parquet converters.
+#[derive(clap::Args)]
+struct SplitArgs {
+    /// Write a directory of parquet files, part-00000.parquet, part-00001.parquet, ..., with this
+    /// many records each, instead of one file.
+    #[clap(long, value_parser = positive)]
+    max_records: Option<usize>,
+    /// Keep a checkpoint in the output directory, and if an interrupted run left one there,
+    /// carry on from it instead of starting over. Needs a split output, and the other options
+    /// must match the interrupted run's.
+    #[clap(long)]
+    resume: bool,
+}
+
+/// Returns a parquet file written to `output`, i.e. `output` itself, or the first file in it if
+/// it's a split or partitioned directory. Every file has the same schema.
+fn first_part(output: PathBuf) -> io::Result<PathBuf> {
+    if !output.is_dir() {
+        return Ok(output);
+    }
+
+    let mut entries = std::fs::read_dir(&output)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if let Ok(file) = first_part(entry) {
+            if file.extension().is_some_and(|e| e == "parquet") {
+                return Ok(file);
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no parquet files in {:?}", output),
+    ))
+}
+
+/// Whether the written sequence keeps its case, shared by the FASTA and FASTQ converters.
+#[derive(clap::Args)]
+struct CaseArgs {
+    /// Write the sequence as it is, keeping soft-masked (lowercase) bases. This is the default.
+    #[clap(long, overrides_with = "fold-case")]
+    preserve_case: bool,
+    /// Uppercase the written sequence, dropping soft-masking. Computed columns, e.g. gc_skew or
+    /// mean_quality, ignore case either way.
+    #[clap(long, overrides_with = "preserve-case")]
+    fold_case: bool,
+}
+
+impl TryFrom<FastqArgs> for FastqOptions {
+    type Error = BrrrrError;
+
+    fn try_from(a: FastqArgs) -> Result<FastqOptions, BrrrrError> {
+        Ok(FastqOptions {
+            min_mean_qual: a.min_mean_qual,
+            phred_offset: Some(a.phred_offset),
+            id_list: a.id_list_args.read()?,
+            skip_errors: a.skip_errors,
+            max_error_rate: a.max_error_rate,
+            allow_star_qual: a.allow_star_qual,
+            fill_qual: a.fill_qual,
+            fold_case: a.case_args.fold_case,
+            reverse: a.reverse,
+            no_sniff: a.no_sniff,
+            multiline: a.multiline_fastq,
+            skip: a.window_args.skip,
+            limit: a.window_args.limit,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses a Phred quality offset, 33 or 64.
+fn phred_offset(s: &str) -> Result<u8, String> {
+    match s {
+        "33" => Ok(33),
+        "64" => Ok(64),
+        _ => Err(format!("{:?} isn't a Phred offset, 33 or 64", s)),
+    }
+}
+
+/// Parses a fraction between 0 and 1, inclusive.
+fn fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        _ => Err(format!("{:?} isn't a fraction between 0 and 1", s)),
+    }
+}
+
+/// Parses a count greater than zero.
+fn positive(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{:?} isn't a count greater than zero", s)),
+    }
+}
+
+/// Parses a single quality character, i.e. a printable ASCII character from ! to ~.
+fn quality_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [c @ b'!'..=b'~'] => Ok(*c),
+        _ => Err(format!("{:?} isn't a single quality character", s)),
+    }
+}
+
 fn file_exists(p: &str) -> Result<(), String> {
     if !PathBuf::from(p).exists() {
-        return Err(format!("File path {:?} does not exist", p));
+        Err(format!("File path {:?} does not exist", p))
+    } else {
+        Ok(())
+    }
+}
+
+/// Like file_exists, but also accepts remote inputs like s3://bucket/key, which are checked when
+/// they're opened.
+fn input_exists(p: &str) -> Result<(), String> {
+    if input::is_remote(p) {
+        Ok(())
     } else {
-        return Ok(());
+        file_exists(p)
     }
 }
+
 #[derive(Subcommand)]
 enum Brrrr {
     #[clap(name = "fa2pq", about = "Converts a FASTA input to parquet.")]
     Fa2pq {
-        /// The path where the input should be read from.
-        #[clap(validator = file_exists)]
+        /// The path where the input should be read from, or an s3://bucket/key when built with
+        /// the s3 feature.
+        #[clap(validator = input_exists)]
         input_file_name: PathBuf,
         /// The path where the output should be written to.
         output_file_name: PathBuf,
@@ -89,6 +718,80 @@ enum Brrrr {
         /// The bio file compression.
         #[clap(short, long, value_enum, default_value = "uncompressed")]
         input_compression: CliBioFileCompression,
+        /// Add a header column with each record's header line, after the >, as it was in the
+        /// input.
+        #[clap(long)]
+        with_header: bool,
+        /// Add a gc_skew column with (G - C) / (G + C) for each record.
+        #[clap(long)]
+        with_gc_skew: bool,
+        /// Add a complexity column with the Shannon entropy of each record.
+        #[clap(long)]
+        with_entropy: bool,
+        /// Measure the entropy as the lowest over windows of this many bases, instead of over the
+        /// whole sequence.
+        #[clap(long, requires = "with-entropy")]
+        entropy_window: Option<usize>,
+        /// Add a max_homopolymer column with the longest single-base run of each record.
+        #[clap(long)]
+        with_max_homopolymer: bool,
+        /// Drop records whose longest single-base run is longer than this.
+        #[clap(long)]
+        max_homopolymer_filter: Option<usize>,
+        /// Add an orf_length column with the length of the longest ORF in any of the six frames.
+        #[clap(long)]
+        with_orf: bool,
+        /// Also add orf_start and orf_frame columns locating the longest ORF.
+        #[clap(long, requires = "with-orf")]
+        with_orf_position: bool,
+        /// The NCBI codon table used to find ORFs, one of 1-6 or 9-14, e.g. 11 for bacteria.
+        #[clap(long, default_value = "1")]
+        codon_table: CodonTable,
+        /// Only count ORFs that begin with a start codon.
+        #[clap(long, requires = "with-orf")]
+        orf_require_start: bool,
+        /// Also write per-record QC metrics (id, length, gc, mean_qual, n_count) to a separate
+        /// parquet file at this path.
+        #[clap(long)]
+        stats_parquet: Option<PathBuf>,
+        /// Skip lines starting with ;, which older FASTA files use for comments.
+        #[clap(long)]
+        allow_comments: bool,
+        /// Read the input even if its first line looks like another format, e.g. FASTQ, instead
+        /// of failing.
+        #[clap(long)]
+        no_sniff: bool,
+        /// End each id at the first of this character in the header, instead of at the first
+        /// whitespace, e.g. | splits >gi|123 desc into id gi and description 123 desc.
+        #[clap(long)]
+        id_delimiter: Option<char>,
+        /// Reverse each record's sequence, without complementing the bases.
+        #[clap(long)]
+        reverse: bool,
+        /// Write a directory of parquet files, part-00000.parquet, part-00001.parquet, ...,
+        /// rolling over to a new file before the current one reaches this many bytes.
+        #[clap(long)]
+        max_file_size: Option<u64>,
+        /// Build each parquet batch from records whose sequences add up to about this many bytes,
+        /// instead of from a fixed number of records, so memory use doesn't depend on their length.
+        #[clap(long, value_parser = positive)]
+        batch_bytes: Option<usize>,
+        #[clap(flatten)]
+        split_args: SplitArgs,
+        /// Write into Hive-style partition directories, e.g. length:0,100,500,1000 writes
+        /// length_bucket=100-500/ and so on, by sequence length.
+        #[clap(long)]
+        partition_by: Option<PartitionBy>,
+        #[clap(flatten)]
+        case_args: CaseArgs,
+        #[clap(flatten)]
+        id_list_args: IdListArgs,
+        #[clap(flatten)]
+        window_args: WindowArgs,
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+        #[clap(flatten)]
+        dry_run_args: DryRunArgs,
     },
     #[clap(name = "pq2fa", about = "Converts a parquet file to FASTA format.")]
     Pq2Fa {
@@ -97,6 +800,8 @@ enum Brrrr {
         input_file_name: PathBuf,
         /// The path where the output should be written to.
         output_file_name: PathBuf,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
     },
     #[clap(name = "pq2fq", about = "Converts a parquet file to FASTQ format.")]
     Pq2Fq {
@@ -105,25 +810,91 @@ enum Brrrr {
         input_file_name: PathBuf,
         /// The path where the output should be written to.
         output_file_name: PathBuf,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
     },
-    #[clap(name = "fq2pq", about = "Converts a FASTQ input to parquet.")]
-    Fq2pq {
+    #[clap(name = "pq2gff", about = "Converts a parquet file to GFF format.")]
+    Pq2Gff {
         /// The path where the input should be read from.
         #[clap(validator = file_exists)]
         input_file_name: PathBuf,
         /// The path where the output should be written to.
         output_file_name: PathBuf,
+        /// Write the directive and comment lines in this file, as gff2pq --keep-directives
+        /// writes them, before the records.
+        #[clap(long, validator = file_exists)]
+        directives: Option<PathBuf>,
+        /// Compress the output with BGZF, the blocked gzip that tabix and samtools read.
+        #[clap(long)]
+        bgzip: bool,
+        /// Also write a tabix index of the output to <output>.tbi. Needs --bgzip, and the
+        /// input sorted by seqname and start, e.g. with gff2pq --sort.
+        #[clap(long, requires = "bgzip")]
+        tabix: bool,
+    },
+    #[clap(name = "fq2pq", about = "Converts a FASTQ input to parquet.")]
+    Fq2pq {
+        /// The path where the input should be read from, or an s3://bucket/key when built with
+        /// the s3 feature.
+        #[clap(validator = input_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
         /// The compression mode for the parquet.
         #[clap(value_enum, default_value = "uncompressed")]
         compression: ParquetCompression,
         /// The bio file compression for the input FASTQ file.
         #[clap(short, long, value_enum, default_value = "uncompressed")]
         input_compression: CliBioFileCompression,
+        /// Add a mean_quality column with the mean Phred score of each record.
+        #[clap(long)]
+        with_mean_qual: bool,
+        /// Add a gc column with the GC fraction of each read, counting N toward its length.
+        #[clap(long)]
+        with_gc: bool,
+        /// Also write per-record QC metrics (id, length, gc, mean_qual, n_count) to a separate
+        /// parquet file at this path.
+        #[clap(long)]
+        stats_parquet: Option<PathBuf>,
+        /// Build each parquet batch from reads whose sequences add up to about this many bytes,
+        /// instead of from a fixed number of reads, so memory use doesn't depend on their length.
+        #[clap(long, value_parser = positive)]
+        batch_bytes: Option<usize>,
+        #[clap(flatten)]
+        fastq_args: FastqArgs,
+        #[clap(flatten)]
+        split_args: SplitArgs,
+        /// Write into Hive-style partition directories, e.g. length:0,100,500,1000 writes
+        /// length_bucket=100-500/ and so on, by sequence length.
+        #[clap(long)]
+        partition_by: Option<PartitionBy>,
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+        #[clap(flatten)]
+        dry_run_args: DryRunArgs,
     },
     #[clap(name = "fa2jsonl", about = "Converts a FASTA input to jsonl.")]
     Fa2jsonl {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        /// Skip lines starting with ;, which older FASTA files use for comments.
+        #[clap(long)]
+        allow_comments: bool,
+        /// Add a header field with each record's header line, after the >, as it was in the
+        /// input.
+        #[clap(long)]
+        with_header: bool,
+        /// Read the input even if its first line looks like another format, e.g. FASTQ, instead
+        /// of failing.
+        #[clap(long)]
+        no_sniff: bool,
+        #[clap(flatten)]
+        id_list_args: IdListArgs,
+        #[clap(flatten)]
+        window_args: WindowArgs,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
     },
     #[clap(name = "gff2pq", about = "Converts a GFF-like input to parquet.")]
     Gff2pq {
@@ -135,35 +906,777 @@ enum Brrrr {
         /// The compression mode for the parquet.
         #[clap(value_enum, default_value = "uncompressed")]
         compression: ParquetCompression,
+        #[clap(flatten)]
+        gff_args: GffArgs,
+        /// The type of the score column. With int, a score not written as an integer, e.g. 2.5 or
+        /// 1e3, is an error, and string writes . for a missing score.
+        #[clap(long, value_enum, default_value = "float")]
+        score_type: CliScoreType,
+        /// Write each feature's attributes sorted by key, so the output is the same from run to
+        /// run.
+        #[clap(long)]
+        sort_attrs: bool,
+        /// Write each attribute value as a list, split on commas, e.g. Parent=geneA,geneB as
+        /// [geneA, geneB], instead of as one string.
+        #[clap(long)]
+        multivalue_attrs: bool,
+        /// Write the ## directive and # comment lines of the input to this file, so pq2gff
+        /// --directives can restore them.
+        #[clap(long)]
+        keep_directives: Option<PathBuf>,
+        #[clap(flatten)]
+        split_args: SplitArgs,
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+        #[clap(flatten)]
+        dry_run_args: DryRunArgs,
+    },
+    #[clap(
+        name = "convert",
+        about = "Converts between formats picked from the file extensions, e.g. .fq.gz to .parquet."
+    )]
+    Convert {
+        /// The path where the input should be read from, e.g. reads.fq.gz.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to, e.g. reads.parquet.
+        output_file_name: PathBuf,
+        /// The compression mode for a parquet output.
+        #[clap(long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[clap(name = "bedgraph2pq", about = "Converts a bedGraph input to parquet.")]
+    Bedgraph2pq {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[clap(
+        name = "peak2pq",
+        about = "Converts a narrowPeak or broadPeak input to parquet."
+    )]
+    Peak2pq {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+        /// The peak format of the input.
+        #[clap(short, long, value_enum, default_value = "narrow")]
+        format: CliPeakFormat,
+    },
+    #[clap(
+        name = "join",
+        about = "Joins features to the overlapping intervals of another file, as parquet."
+    )]
+    Join {
+        /// The path to the features to join from.
+        #[clap(validator = file_exists)]
+        left_file_name: PathBuf,
+        /// The path to the intervals to join to.
+        #[clap(validator = file_exists)]
+        right_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+        /// The format of the features to join from.
+        #[clap(long, value_enum, default_value = "gff")]
+        left_format: CliIntervalFormat,
+        /// The format of the intervals to join to.
+        #[clap(long, value_enum, default_value = "bed")]
+        right_format: CliIntervalFormat,
+    },
+    #[clap(
+        name = "subseq",
+        about = "Extracts subsequences from a FASTA input by region."
+    )]
+    Subseq {
+        /// The path to an uncompressed FASTA file, optionally with a .fai index next to it.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// A region to extract, e.g. chr1:1000-2000, or chr1:1000-2000:- for the reverse strand.
+        #[clap(short, long)]
+        region: Vec<Region>,
+        /// A BED file of regions to extract.
+        #[clap(long, validator = file_exists)]
+        bed: Option<PathBuf>,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "translate",
+        about = "Translates a nucleotide FASTA input to protein, like transeq."
+    )]
+    Translate {
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The frame to translate in, 1, 2, or 3, -1, -2, or -3 for the reverse complement, or
+        /// all for all six, which writes each record once per frame with the frame on its id.
+        #[clap(long, default_value = "1", allow_hyphen_values = true)]
+        frame: Frames,
+        /// The NCBI codon table to translate with, one of 1-6 or 9-14, e.g. 11 for bacteria.
+        #[clap(long, default_value = "1")]
+        codon_table: CodonTable,
+        /// Skip lines starting with ;, which older FASTA files use for comments.
+        #[clap(long)]
+        allow_comments: bool,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "sixframe",
+        about = "Translates a nucleotide FASTA input in all six frames, optionally keeping only ORFs."
+    )]
+    Sixframe {
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// Write each stretch between stops of at least this many amino acids as its own record,
+        /// e.g. seq1_-2_101-400 for bases 101-400, instead of whole frames.
+        #[clap(long)]
+        min_orf: Option<usize>,
+        /// The NCBI codon table to translate with, one of 1-6 or 9-14, e.g. 11 for bacteria.
+        #[clap(long, default_value = "1")]
+        codon_table: CodonTable,
+        /// Skip lines starting with ;, which older FASTA files use for comments.
+        #[clap(long)]
+        allow_comments: bool,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "getfasta",
+        about = "Extracts the sequence of each GFF or BED feature from a genome FASTA."
+    )]
+    Getfasta {
+        /// The path to an uncompressed genome FASTA file, optionally with a .fai index next to it.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path to the GFF or BED features.
+        #[clap(validator = file_exists)]
+        features_file_name: PathBuf,
+        /// The format of the features.
+        #[clap(short, long, value_enum, default_value = "gff")]
+        format: CliIntervalFormat,
+        /// The GFF attribute to name the extracted records by.
+        #[clap(long, default_value = "ID")]
+        name_attr: String,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(name = "wig2pq", about = "Converts a WIG input to parquet.")]
+    Wig2pq {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
     },
     #[clap(name = "gff2jsonl", about = "Converts a GFF-like input to jsonl.")]
     Gff2jsonl {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        #[clap(flatten)]
+        gff_args: GffArgs,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
+    },
+    #[clap(
+        name = "gff_merge",
+        about = "Merges overlapping features of the same type and strand in a GFF-like input."
+    )]
+    GffMerge {
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The largest gap between two features that still merges them.
+        #[clap(short, long, default_value = "0")]
+        distance: usize,
+        /// How the attributes of merged features are combined.
+        #[clap(long, value_enum, default_value = "drop")]
+        merge_attrs: CliMergeAttributes,
+        /// The output format.
+        #[clap(short, long, value_enum, default_value = "gff")]
+        output_format: CliMergeFormat,
+    },
+    #[clap(
+        name = "gff_summary",
+        about = "Reports the number of features by type, source, and seqname in a GFF-like input."
+    )]
+    GffSummary {
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The report format.
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: CliReportFormat,
     },
     #[clap(name = "fq2jsonl", about = "Converts a FASTQ input to jsonl.")]
     Fq2jsonl {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        #[clap(flatten)]
+        fastq_args: FastqArgs,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
     },
     #[clap(name = "fa2csv", about = "Converts a FASTA input to csv.")]
     Fa2csv {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        /// Skip lines starting with ;, which older FASTA files use for comments.
+        #[clap(long)]
+        allow_comments: bool,
+        /// Read the input even if its first line looks like another format, e.g. FASTQ, instead
+        /// of failing.
+        #[clap(long)]
+        no_sniff: bool,
+        #[clap(flatten)]
+        id_list_args: IdListArgs,
+        #[clap(flatten)]
+        window_args: WindowArgs,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
     },
     #[clap(name = "fq2csv", about = "Converts a FASTQ input to csv.")]
     Fq2csv {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        #[clap(flatten)]
+        fastq_args: FastqArgs,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
     },
     #[clap(name = "bam2jsonl", about = "Converts a BAM input to jsonl.")]
     Bam2jsonl {
         #[clap(parse(from_os_str))]
         input: Option<PathBuf>,
+        #[clap(flatten)]
+        compression_args: CompressionArgs,
+    },
+    #[clap(
+        name = "completions",
+        about = "Prints a shell completion script for brrrr to stdout."
+    )]
+    Completions {
+        /// The shell to generate completions for.
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+    #[clap(
+        name = "selftest",
+        about = "Round trips synthetic inputs through the converters and checks the output matches."
+    )]
+    Selftest,
+    #[clap(
+        name = "schema",
+        about = "Prints the arrow schema brrrr writes to parquet for a format as JSON."
+    )]
+    Schema {
+        /// The input format.
+        #[clap(value_enum)]
+        format: CliSchemaFormat,
+        /// Include the header column, as fa2pq --with-header does.
+        #[clap(long)]
+        with_header: bool,
+        /// Include the gc_skew column, as fa2pq --with-gc-skew does.
+        #[clap(long)]
+        with_gc_skew: bool,
+        /// Include the complexity column, as fa2pq --with-entropy does.
+        #[clap(long)]
+        with_entropy: bool,
+        /// Include the max_homopolymer column, as fa2pq --with-max-homopolymer does.
+        #[clap(long)]
+        with_max_homopolymer: bool,
+        /// Include the orf_length column, as fa2pq --with-orf does.
+        #[clap(long)]
+        with_orf: bool,
+        /// Include the orf_start and orf_frame columns, as fa2pq --with-orf-position does.
+        #[clap(long, requires = "with-orf")]
+        with_orf_position: bool,
+        /// Include the mean_quality column, as fq2pq --with-mean-qual does.
+        #[clap(long)]
+        with_mean_qual: bool,
+        /// Include the gc column, as fq2pq --with-gc does.
+        #[clap(long)]
+        with_gc: bool,
+        /// The type of the GFF score column, as gff2pq --score-type sets.
+        #[clap(long, value_enum)]
+        score_type: Option<CliScoreType>,
+        /// Make the GFF attribute values lists, as gff2pq --multivalue-attrs does.
+        #[clap(long)]
+        multivalue_attrs: bool,
     },
+    #[clap(name = "man", about = "Prints a roff man page for brrrr to stdout.")]
+    Man {
+        /// Print the page for this subcommand, e.g. fa2pq, instead of the page for brrrr.
+        subcommand: Option<String>,
+    },
+    #[clap(
+        name = "jsonl2pq",
+        about = "Converts a jsonl input of flat objects to parquet, inferring the schema."
+    )]
+    Jsonl2pq {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+        /// The number of lines to infer the schema from. A key first seen after them is an error.
+        #[clap(long, default_value = "1000")]
+        schema_sample: usize,
+        /// What to do when a column has values of conflicting types.
+        #[clap(long, value_enum, default_value = "promote")]
+        on_conflict: CliTypeConflict,
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+    },
+    #[clap(
+        name = "infer_schema",
+        alias = "infer-schema",
+        about = "Prints the arrow schema jsonl2pq would infer for a jsonl input as JSON."
+    )]
+    InferSchema {
+        /// The path to a jsonl file of flat objects, or stdin if omitted.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The number of lines to infer the schema from.
+        #[clap(long, default_value = "1000")]
+        schema_sample: usize,
+        /// What to do when a column has values of conflicting types.
+        #[clap(long, value_enum, default_value = "promote")]
+        on_conflict: CliTypeConflict,
+    },
+    #[clap(
+        name = "csv2pq",
+        about = "Converts a CSV input to parquet, inferring the column types."
+    )]
+    Csv2pq {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The compression mode for the parquet.
+        #[clap(value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+        /// The single character that separates fields, e.g. ',' or '\t' for TSV.
+        #[clap(short, long, default_value = ",", parse(try_from_str = parse_delimiter))]
+        delimiter: u8,
+        /// The input has no header row, so columns are named column_1, column_2, and so on.
+        #[clap(long)]
+        no_header: bool,
+        /// The number of rows to infer the column types from.
+        #[clap(long, default_value = "1000")]
+        schema_sample: usize,
+        /// What to do when a column has values of conflicting types.
+        #[clap(long, value_enum, default_value = "promote")]
+        on_conflict: CliTypeConflict,
+        #[clap(flatten)]
+        schema_args: SchemaArgs,
+    },
+    #[clap(
+        name = "fa_split",
+        about = "Splits a FASTA input into roughly-equal parts, e.g. prefix.000.fa."
+    )]
+    FaSplit {
+        /// The path to an uncompressed FASTA file.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The prefix of the output files.
+        prefix: String,
+        /// The number of parts to write.
+        #[clap(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        parts: u64,
+        /// Balance the parts by record count or by total bases.
+        #[clap(long, value_enum, default_value = "records")]
+        by: CliSplitBy,
+        /// Assign contiguous runs of records to each part, or deal them out in turn.
+        #[clap(long, value_enum, default_value = "contiguous")]
+        assignment: CliSplitAssignment,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "fa_cat",
+        about = "Concatenates FASTA inputs, requiring ids to be unique across them."
+    )]
+    FaCat {
+        /// The paths to uncompressed FASTA files.
+        #[clap(required = true, validator = file_exists)]
+        input_file_names: Vec<PathBuf>,
+        /// Rename duplicate ids by appending _1, _2, and so on, instead of erroring.
+        #[clap(long)]
+        dedup_ids: bool,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "composition",
+        about = "Reports the base composition and GC of a FASTA or FASTQ input."
+    )]
+    Composition {
+        /// The path to an uncompressed FASTA or FASTQ file, or stdin if omitted.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The report format.
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: CliReportFormat,
+    },
+    #[clap(
+        name = "count",
+        about = "Counts the records of a FASTA, FASTQ, or GFF input, without parsing them."
+    )]
+    Count {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The format of the input, detected from its first line if not given. FASTQ reads may
+        /// be wrapped over several lines.
+        #[clap(short, long, value_enum)]
+        format: Option<CliInputFormat>,
+    },
+    #[clap(
+        name = "seqs",
+        about = "Writes just the sequences of a FASTA or FASTQ input, one per line."
+    )]
+    Seqs {
+        /// The path to the input, or stdin if omitted or -. Gzipped stdin is decompressed.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// Write each record's id instead of its sequence.
+        #[clap(long)]
+        ids_only: bool,
+        /// End each id at the first of this character in the header, instead of at the first
+        /// whitespace, as ids --id-delimiter does.
+        #[clap(long, requires = "ids-only")]
+        id_delimiter: Option<char>,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "ids",
+        about = "Writes just the ids of a FASTA, FASTQ, or GFF input, one per line."
+    )]
+    Ids {
+        /// The path to the input, or stdin if omitted or -. Gzipped inputs are decompressed.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The format of the input, detected from its first line if not given.
+        #[clap(short, long, value_enum)]
+        format: Option<CliInputFormat>,
+        /// End each FASTA or FASTQ id at the first of this character in the header, instead of
+        /// at the first whitespace, as fa2pq --id-delimiter does.
+        #[clap(long)]
+        id_delimiter: Option<char>,
+        /// The GFF attribute holding each feature's id. Features without it are skipped.
+        #[clap(long, default_value = "ID")]
+        attr: String,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "grep",
+        about = "Writes the records of a FASTA or FASTQ input whose sequence matches a motif."
+    )]
+    Grep {
+        /// The motif to search for, e.g. GGNCC. IUPAC ambiguity codes match any of their bases.
+        motif: Motif,
+        /// The path to the input, or stdin if omitted or -. Gzipped inputs are decompressed.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// Also match records whose reverse complement matches the motif.
+        #[clap(long)]
+        both_strands: bool,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "uniq",
+        about = "Reports the number of distinct and duplicate sequences in a FASTA or FASTQ input."
+    )]
+    Uniq {
+        /// The path to an uncompressed FASTA or FASTQ file, or stdin if omitted.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The report format.
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: CliReportFormat,
+        #[clap(flatten)]
+        dedup_stream_args: DedupStreamArgs,
+    },
+    #[clap(
+        name = "collapse",
+        about = "Collapses identical sequences in a FASTA or FASTQ input, counting each."
+    )]
+    Collapse {
+        /// The path to an uncompressed FASTA or FASTQ file.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The output format. FASTA records get the count in their description, e.g. count=12.
+        #[clap(short, long, value_enum, default_value = "fasta")]
+        format: CliCollapseFormat,
+        /// Name each sequence by the id of its first record, instead of seq_1, seq_2, etc.
+        #[clap(long)]
+        keep_first_id: bool,
+        /// The compression mode for parquet output.
+        #[clap(short, long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+        #[clap(flatten)]
+        dedup_stream_args: DedupStreamArgs,
+        #[clap(flatten)]
+        newline_args: NewlineArgs,
+    },
+    #[clap(
+        name = "kmer_spectrum",
+        about = "Writes the k-mer spectrum of a FASTA or FASTQ input to parquet."
+    )]
+    KmerSpectrum {
+        /// The path to an uncompressed FASTA or FASTQ file.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The k-mer length, up to 32. Every distinct k-mer is held in memory while counting.
+        #[clap(short, default_value_t = 21, value_parser = clap::value_parser!(u64).range(1..=MAX_K as u64))]
+        k: u64,
+        /// The compression mode for the parquet.
+        #[clap(short, long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[clap(
+        name = "minimizers",
+        about = "Writes the (w, k) minimizers of each record of a FASTA or FASTQ input to parquet."
+    )]
+    Minimizers {
+        /// The path to an uncompressed FASTA or FASTQ file.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The number of consecutive k-mers in each window.
+        #[clap(short, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        w: u64,
+        /// The k-mer length, up to 32.
+        #[clap(short, default_value_t = 15, value_parser = clap::value_parser!(u64).range(1..=MAX_K as u64))]
+        k: u64,
+        /// Select minimizers from the forward strand only, instead of the lesser of each k-mer and
+        /// its reverse complement.
+        #[clap(long)]
+        no_canonical: bool,
+        /// The compression mode for the parquet.
+        #[clap(short, long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[clap(
+        name = "gchist",
+        about = "Bins the GC fraction of each record of a FASTA or FASTQ input into a histogram."
+    )]
+    Gchist {
+        /// The path to an uncompressed FASTA or FASTQ file, or stdin if omitted.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+        /// The number of equal-width bins over 0 to 1.
+        #[clap(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+        bins: u64,
+        /// Write the histogram to this parquet file, instead of printing a table.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// The compression mode for parquet output.
+        #[clap(short, long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[cfg(feature = "df")]
+    #[clap(
+        name = "df",
+        about = "Selects columns and filters rows of a parquet file, writing parquet or CSV."
+    )]
+    Df {
+        /// The path where the input should be read from.
+        #[clap(validator = file_exists)]
+        input_file_name: PathBuf,
+        /// The path where the output should be written to.
+        output_file_name: PathBuf,
+        /// The columns to keep, in order, e.g. id,sequence. All columns are kept if omitted.
+        #[clap(long, use_value_delimiter = true)]
+        select: Vec<String>,
+        /// Keep only rows where a column compares to a value, e.g. "end > 5000",
+        /// "strand == '+'", or "length(sequence) >= 100". May be repeated, and rows must pass
+        /// all of them.
+        #[clap(long)]
+        filter: Vec<Predicate>,
+        /// The format of the output. CSV can't hold map columns, e.g. a GFF attribute column,
+        /// so leave them out with --select.
+        #[clap(long, value_enum, default_value = "parquet")]
+        format: CliFrameFormat,
+        /// The compression mode for parquet output.
+        #[clap(short, long, value_enum, default_value = "uncompressed")]
+        compression: ParquetCompression,
+    },
+    #[cfg(not(feature = "df"))]
+    #[clap(name = "df", hide = true)]
+    DfUnavailable(Unavailable),
+    #[cfg(feature = "chemistry")]
+    #[clap(
+        name = "oligo",
+        about = "Writes the length, GC fraction, melting temperature, and molecular weight of each \
+                 oligo in a FASTA or FASTQ input."
+    )]
+    Oligo {
+        /// The path to the input, or stdin if omitted or -.
+        #[clap(parse(from_os_str))]
+        input: Option<PathBuf>,
+    },
+    #[cfg(not(feature = "chemistry"))]
+    #[clap(name = "oligo", hide = true)]
+    OligoUnavailable(Unavailable),
+}
+
+/// The arguments of a subcommand whose feature brrrr was built without. It's registered hidden,
+/// so running it says which feature to build with instead of that the subcommand doesn't exist.
+#[derive(clap::Args)]
+#[allow(dead_code)]
+struct Unavailable {
+    #[clap(allow_hyphen_values = true, multiple_values = true, hide = true)]
+    args: Vec<OsString>,
+}
+
+/// Returns the error for running `subcommand` without the cargo `feature` it needs.
+#[allow(dead_code)]
+fn missing_feature(subcommand: &str, feature: &str) -> BrrrrError {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} needs the {} feature, which this brrrr was built without; reinstall with \
+             `cargo install brrrr --features {}`",
+            subcommand, feature, feature
+        ),
+    )
+    .into()
+}
+
+/// Finishes a compressed output to stdout and flushes it. A closed pipe, e.g. from
+/// `brrrr fa2jsonl big.fa | head -1`, isn't an error, as it isn't while the records are written.
+fn finish_stdout(output: CompressedWriter<Stdout>) -> Result<(), BrrrrError> {
+    ignore_broken_pipe(output.finish().and_then(|mut stdout| stdout.flush()))
 }
 
-fn main() -> Result<(), BrrrrError> {
+/// Returns the result of writing to stdout, with a closed pipe taken as success.
+fn ignore_broken_pipe(result: io::Result<()>) -> Result<(), BrrrrError> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Formats `error` with the chain of errors that caused it, e.g. `io error: No such file`.
+fn report(error: &BrrrrError) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+
+    message
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("brrrr: {}", report(&e));
+        process::exit(e.exit_code());
+    }
+}
+
+/// Opens a text converter's input, or stdin if there's no path or it's `-`. Gzipped stdin and
+/// remote inputs are recognized by their first bytes and decompressed.
+fn text_input(input: Option<PathBuf>) -> Result<Box<dyn BufRead>, BrrrrError> {
+    match input {
+        Some(input) if input.as_os_str() != "-" => input::open_text(input),
+        _ => Ok(input::stdin()?),
+    }
+}
+
+/// Opens a text input, or stdin if there's no path or it's -, decompressing it if it's gzipped,
+/// whatever its extension.
+fn gunzipped_input(input: Option<PathBuf>) -> Result<Box<dyn BufRead>, BrrrrError> {
+    match input {
+        Some(input) if input.as_os_str() != "-" => Ok(input::maybe_gunzip(input::open(input)?)?),
+        _ => Ok(input::stdin()?),
+    }
+}
+
+/// Opens a FASTA input, or stdin if there's no path, skipping ; comment lines if asked.
+fn fasta_input(
+    input: Option<PathBuf>,
+    allow_comments: bool,
+) -> Result<Box<dyn BufRead>, BrrrrError> {
+    let input = text_input(input)?;
+
+    Ok(if allow_comments {
+        Box::new(SkipComments::new(input))
+    } else {
+        input
+    })
+}
+
+/// Returns `format`, or if it isn't given, the format of `input` as detected from its first line.
+fn input_format(
+    input: &mut Box<dyn BufRead>,
+    format: Option<CliInputFormat>,
+) -> Result<InputFormat, BrrrrError> {
+    match format {
+        Some(format) => Ok(format.into()),
+        None => Ok(input::sniff(input.fill_buf()?).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't tell the input's format, pass --format",
+            )
+        })?),
+    }
+}
+
+/// Writes the roff man page for brrrr, or for its `subcommand`, to `output`.
+fn write_man<W: Write>(subcommand: Option<&str>, output: &mut W) -> io::Result<()> {
+    let command = Cli::command();
+    let page = match subcommand {
+        None => command,
+        Some(name) => command
+            .find_subcommand(name)
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown subcommand: {}", name),
+                )
+            })?
+            .name(format!("brrrr-{}", name)),
+    };
+
+    Man::new(page).render(output)
+}
+
+fn run() -> Result<(), BrrrrError> {
     let args = Cli::parse();
 
     match args.command {
@@ -172,78 +1685,790 @@ fn main() -> Result<(), BrrrrError> {
             output_file_name,
             output_compression,
             input_compression,
-        } => parquet_writer::fa2pq(
-            &input_file_name,
-            &output_file_name,
-            output_compression.into(),
-            input_compression.into(),
-        ),
+            with_header,
+            with_gc_skew,
+            with_entropy,
+            entropy_window,
+            with_max_homopolymer,
+            max_homopolymer_filter,
+            with_orf,
+            with_orf_position,
+            codon_table,
+            orf_require_start,
+            stats_parquet,
+            allow_comments,
+            no_sniff,
+            id_delimiter,
+            reverse,
+            max_file_size,
+            batch_bytes,
+            split_args,
+            partition_by,
+            case_args,
+            id_list_args,
+            window_args,
+            schema_args,
+            dry_run_args,
+        } => {
+            let input_compression = input_compression.into();
+            let options = FastaOptions {
+                with_gc_skew,
+                with_entropy,
+                entropy_window,
+                with_max_homopolymer,
+                max_homopolymer_filter,
+                with_orf,
+                with_orf_position,
+                codon_table,
+                orf_require_start,
+                stats_parquet,
+                allow_comments,
+                no_sniff,
+                fold_case: case_args.fold_case,
+                max_file_size,
+                max_records: split_args.max_records,
+                resume: split_args.resume,
+                partition_by,
+                id_delimiter,
+                id_list: id_list_args.read()?,
+                with_header,
+                reverse,
+                batch_bytes,
+                skip: window_args.skip,
+                limit: window_args.limit,
+            };
+            let schema = record_batches::fasta_schema(&options);
+            if dry_run_args.report(
+                &input_file_name,
+                &output_file_name,
+                input_compression,
+                schema,
+            )? {
+                return Ok(());
+            }
+
+            parquet_writer::fa2pq(
+                &input_file_name,
+                &output_file_name,
+                output_compression.into(),
+                input_compression,
+                &options,
+            )?;
+            schema_args.write(first_part(output_file_name)?)
+        }
         Brrrr::Pq2Fa {
             input_file_name,
             output_file_name,
-        } => parquet_reader::pq2fa(input_file_name, output_file_name),
+            newline_args,
+        } => {
+            let output = io::BufWriter::new(File::create(output_file_name)?);
+            let mut output = newline_args.output(output);
+            parquet_reader::write_fasta(input_file_name, &mut output)?;
+            Ok(output.into_inner().flush()?)
+        }
         Brrrr::Pq2Fq {
             input_file_name,
             output_file_name,
-        } => parquet_reader::pq2fq(input_file_name, output_file_name),
+            newline_args,
+        } => {
+            let output = io::BufWriter::new(File::create(output_file_name)?);
+            let mut output = newline_args.output(output);
+            parquet_reader::write_fastq(input_file_name, &mut output)?;
+            Ok(output.into_inner().flush()?)
+        }
+        Brrrr::Pq2Gff {
+            input_file_name,
+            output_file_name,
+            directives,
+            bgzip,
+            tabix,
+        } => parquet_reader::pq2gff(
+            input_file_name,
+            output_file_name,
+            &parquet_reader::GffOutputOptions {
+                directives: directives.clone(),
+                bgzip,
+                tabix,
+            },
+        ),
         Brrrr::Fq2pq {
             input_file_name,
             output_file_name,
             compression,
             input_compression,
-        } => parquet_writer::fq2pq(
+            with_mean_qual,
+            with_gc,
+            stats_parquet,
+            batch_bytes,
+            fastq_args,
+            split_args,
+            partition_by,
+            schema_args,
+            dry_run_args,
+        } => {
+            let input_compression = input_compression.into();
+            let options = FastqOptions {
+                with_mean_qual,
+                with_gc,
+                stats_parquet,
+                max_records: split_args.max_records,
+                resume: split_args.resume,
+                partition_by,
+                batch_bytes,
+                ..FastqOptions::try_from(fastq_args)?
+            };
+            let schema = record_batches::fastq_schema(&options);
+            if dry_run_args.report(
+                &input_file_name,
+                &output_file_name,
+                input_compression,
+                schema,
+            )? {
+                return Ok(());
+            }
+
+            parquet_writer::fq2pq(
+                &input_file_name,
+                &output_file_name,
+                compression.into(),
+                input_compression,
+                &options,
+            )?;
+            schema_args.write(first_part(output_file_name)?)
+        }
+        Brrrr::Fa2csv {
+            input,
+            allow_comments,
+            no_sniff,
+            id_list_args,
+            window_args,
+            compression_args,
+        } => {
+            let mut output = compression_args.output()?;
+            csv_writer::fa2csv(
+                fasta_input(input, allow_comments)?,
+                &mut output,
+                &FastaOptions {
+                    no_sniff,
+                    id_list: id_list_args.read()?,
+                    skip: window_args.skip,
+                    limit: window_args.limit,
+                    ..Default::default()
+                },
+            )?;
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Fq2csv {
+            input,
+            fastq_args,
+            compression_args,
+        } => {
+            let options = FastqOptions::try_from(fastq_args)?;
+            let mut output = compression_args.output()?;
+            csv_writer::fq2csv(text_input(input)?, &mut output, &options)?;
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Bam2jsonl {
+            input,
+            compression_args,
+        } => {
+            let mut output = compression_args.output()?;
+            if let Some(input) = input {
+                let f = File::open(input)?;
+                json_writer::bam2jsonl(BufReader::new(f), &mut output)?
+            } else {
+                json_writer::bam2jsonl(stdin().lock(), &mut output)?
+            }
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Fa2jsonl {
+            input,
+            allow_comments,
+            with_header,
+            no_sniff,
+            id_list_args,
+            window_args,
+            compression_args,
+        } => {
+            let mut output = compression_args.output()?;
+            json_writer::fa2jsonl(
+                fasta_input(input, allow_comments)?,
+                &mut output,
+                &FastaOptions {
+                    with_header,
+                    no_sniff,
+                    id_list: id_list_args.read()?,
+                    skip: window_args.skip,
+                    limit: window_args.limit,
+                    ..Default::default()
+                },
+            )?;
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Gff2jsonl {
+            input,
+            gff_args,
+            compression_args,
+        } => {
+            let options = GffOptions::try_from(gff_args)?;
+            let mut output = compression_args.output()?;
+            json_writer::gff2jsonl(text_input(input)?, &mut output, &options)?;
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Gff2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+            gff_args,
+            score_type,
+            sort_attrs,
+            multivalue_attrs,
+            keep_directives,
+            split_args,
+            schema_args,
+            dry_run_args,
+        } => {
+            let options = GffOptions {
+                max_records: split_args.max_records,
+                resume: split_args.resume,
+                score_type: score_type.into(),
+                sort_attributes: sort_attrs,
+                multivalue_attributes: multivalue_attrs,
+                keep_directives,
+                ..GffOptions::try_from(gff_args)?
+            };
+            let schema = record_batches::gff_schema(&options);
+            let input_compression = parquet_writer::BioFileCompression::UNCOMPRESSED;
+            if dry_run_args.report(
+                &input_file_name,
+                &output_file_name,
+                input_compression,
+                schema,
+            )? {
+                return Ok(());
+            }
+
+            parquet_writer::gff2pq(
+                &input_file_name,
+                &output_file_name,
+                compression.into(),
+                &options,
+            )?;
+            schema_args.write(first_part(output_file_name)?)
+        }
+        Brrrr::GffMerge {
+            input,
+            distance,
+            merge_attrs,
+            output_format,
+        } => {
+            let options = gff_ops::MergeOptions {
+                distance,
+                attributes: merge_attrs.into(),
+                format: output_format.into(),
+            };
+            match input {
+                None => gff_ops::merge(stdin().lock(), &mut stdout(), &options),
+                Some(input) => {
+                    let f = File::open(input)?;
+                    gff_ops::merge(BufReader::new(f), &mut stdout(), &options)
+                }
+            }
+        }
+        Brrrr::GffSummary { input, format } => {
+            let summary = match input {
+                None => gff_ops::summary(stdin().lock())?,
+                Some(input) => gff_ops::summary(BufReader::new(File::open(input)?))?,
+            };
+            summary.write(format.into(), &mut stdout())?;
+            Ok(())
+        }
+        Brrrr::Bedgraph2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+        } => parquet_writer::bedgraph2pq(input_file_name, output_file_name, compression.into()),
+        Brrrr::Peak2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+            format,
+        } => parquet_writer::peak2pq(
             input_file_name,
             output_file_name,
             compression.into(),
-            input_compression.into(),
+            format.into(),
         ),
-        Brrrr::Fa2csv { input } => match input {
-            None => csv_writer::fa2csv(stdin().lock(), &mut stdout()),
-            Some(input) => {
-                let f = File::open(input)?;
-                csv_writer::fa2csv(BufReader::new(f), &mut stdout())
+        Brrrr::Join {
+            left_file_name,
+            right_file_name,
+            output_file_name,
+            compression,
+            left_format,
+            right_format,
+        } => parquet_writer::join2pq(
+            left_file_name,
+            right_file_name,
+            output_file_name,
+            compression.into(),
+            left_format.into(),
+            right_format.into(),
+        ),
+        Brrrr::Subseq {
+            input_file_name,
+            mut region,
+            bed,
+            newline_args,
+        } => {
+            if let Some(bed) = bed {
+                let f = File::open(bed)?;
+                for interval in intervals::records(BufReader::new(f), IntervalFormat::Bed)? {
+                    region.push(Region::from(interval?));
+                }
             }
-        },
-        Brrrr::Fq2csv { input } => match input {
-            None => csv_writer::fq2csv(stdin().lock(), &mut stdout()),
-            Some(input) => {
-                let f = File::open(input)?;
-                csv_writer::fq2csv(BufReader::new(f), &mut stdout())
+            fasta_ops::subseq(input_file_name, &region, &mut newline_args.output(stdout()))
+        }
+        Brrrr::Translate {
+            input,
+            frame,
+            codon_table,
+            allow_comments,
+            newline_args,
+        } => fasta_ops::translate(
+            fasta_input(input, allow_comments)?,
+            &mut newline_args.output(stdout()),
+            frame,
+            codon_table,
+        ),
+        Brrrr::Sixframe {
+            input,
+            min_orf,
+            codon_table,
+            allow_comments,
+            newline_args,
+        } => fasta_ops::sixframe(
+            fasta_input(input, allow_comments)?,
+            &mut newline_args.output(stdout()),
+            codon_table,
+            min_orf,
+        ),
+        Brrrr::Getfasta {
+            input_file_name,
+            features_file_name,
+            format,
+            name_attr,
+            newline_args,
+        } => {
+            let f = File::open(features_file_name)?;
+            fasta_ops::getfasta(
+                input_file_name,
+                BufReader::new(f),
+                format.into(),
+                &name_attr,
+                &mut newline_args.output(stdout()),
+            )
+        }
+        Brrrr::Wig2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+        } => parquet_writer::wig2pq(input_file_name, output_file_name, compression.into()),
+        Brrrr::Fq2jsonl {
+            input,
+            fastq_args,
+            compression_args,
+        } => {
+            let options = FastqOptions::try_from(fastq_args)?;
+            let mut output = compression_args.output()?;
+            json_writer::fq2jsonl(text_input(input)?, &mut output, &options)?;
+            finish_stdout(output.into_inner())
+        }
+        Brrrr::Completions { shell } => {
+            generate(shell, &mut Cli::command(), "brrrr", &mut stdout());
+            Ok(())
+        }
+        Brrrr::Selftest => {
+            let round_trips = selftest::run()?;
+            for round_trip in &round_trips {
+                let status = if round_trip.passed { "ok" } else { "FAILED" };
+                println!("{}\t{}", round_trip.name, status);
             }
-        },
-        Brrrr::Bam2jsonl { input } => {
-            if let Some(input) = input {
-                let f = File::open(input)?;
-                json_writer::bam2jsonl(BufReader::new(f), &mut stdout())
-            } else {
-                json_writer::bam2jsonl(stdin().lock(), &mut stdout())
+
+            let failed = round_trips.iter().filter(|r| !r.passed).count();
+            if failed > 0 {
+                return Err(io::Error::other(format!(
+                    "{} of {} round trips failed",
+                    failed,
+                    round_trips.len()
+                ))
+                .into());
             }
+            Ok(())
         }
-        Brrrr::Fa2jsonl { input } => match input {
-            None => json_writer::fa2jsonl(stdin().lock(), &mut stdout()),
-            Some(input) => {
-                let f = File::open(input)?;
-                json_writer::fa2jsonl(BufReader::new(f), &mut stdout())
+        Brrrr::Schema {
+            format,
+            with_header,
+            with_gc_skew,
+            with_entropy,
+            with_max_homopolymer,
+            with_orf,
+            with_orf_position,
+            with_mean_qual,
+            with_gc,
+            score_type,
+            multivalue_attrs,
+        } => {
+            let gff_only = score_type.is_some() || multivalue_attrs;
+            let fasta_only =
+                with_header || with_gc_skew || with_entropy || with_max_homopolymer || with_orf;
+            let fastq_only = with_mean_qual || with_gc;
+            let schema = match format {
+                CliSchemaFormat::Fasta if !fastq_only && !gff_only => {
+                    record_batches::fasta_schema(&FastaOptions {
+                        with_header,
+                        with_gc_skew,
+                        with_entropy,
+                        with_max_homopolymer,
+                        with_orf,
+                        with_orf_position,
+                        ..Default::default()
+                    })
+                }
+                CliSchemaFormat::Fastq if !fasta_only && !gff_only => {
+                    record_batches::fastq_schema(&FastqOptions {
+                        with_mean_qual,
+                        with_gc,
+                        ..Default::default()
+                    })
+                }
+                CliSchemaFormat::Gff if !fasta_only && !fastq_only => {
+                    record_batches::gff_schema(&GffOptions {
+                        score_type: score_type.map_or_else(ScoreType::default, ScoreType::from),
+                        multivalue_attributes: multivalue_attrs,
+                        ..Default::default()
+                    })
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "a column flag doesn't apply to this format",
+                    )
+                    .into())
+                }
+            };
+            record_batches::write_schema(&schema, &mut stdout())
+        }
+        Brrrr::Man { subcommand } => Ok(write_man(subcommand.as_deref(), &mut stdout())?),
+        Brrrr::Jsonl2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+            schema_sample,
+            on_conflict,
+            schema_args,
+        } => {
+            parquet_writer::jsonl2pq(
+                &input_file_name,
+                &output_file_name,
+                compression.into(),
+                &InferOptions {
+                    schema_sample,
+                    on_conflict: on_conflict.into(),
+                },
+            )?;
+            schema_args.write(output_file_name)
+        }
+        Brrrr::InferSchema {
+            input,
+            schema_sample,
+            on_conflict,
+        } => {
+            let options = InferOptions {
+                schema_sample,
+                on_conflict: on_conflict.into(),
+            };
+            let inference = match input {
+                None => tabular::infer_jsonl(stdin().lock(), &options)?,
+                Some(input) => tabular::infer_jsonl(BufReader::new(File::open(input)?), &options)?,
+            };
+
+            // Resolutions go to stderr, so stdout is just the schema.
+            for resolution in inference.resolutions() {
+                eprintln!("{}", resolution);
             }
-        },
-        Brrrr::Gff2jsonl { input } => match input {
-            None => json_writer::gff2jsonl(stdin().lock(), &mut stdout()),
-            Some(input) => {
-                let f = File::open(input)?;
-                json_writer::gff2jsonl(BufReader::new(f), &mut stdout())
+            record_batches::write_schema(&inference.schema(), &mut stdout())
+        }
+        Brrrr::Csv2pq {
+            input_file_name,
+            output_file_name,
+            compression,
+            delimiter,
+            no_header,
+            schema_sample,
+            on_conflict,
+            schema_args,
+        } => {
+            parquet_writer::csv2pq(
+                &input_file_name,
+                &output_file_name,
+                compression.into(),
+                &CsvOptions {
+                    delimiter,
+                    has_header: !no_header,
+                },
+                &InferOptions {
+                    schema_sample,
+                    on_conflict: on_conflict.into(),
+                },
+            )?;
+            schema_args.write(output_file_name)
+        }
+        Brrrr::FaSplit {
+            input_file_name,
+            prefix,
+            parts,
+            by,
+            assignment,
+            newline_args,
+        } => {
+            let options = SplitOptions {
+                parts: parts as usize,
+                by: by.into(),
+                assignment: assignment.into(),
+                trailing_newline: !newline_args.no_trailing_newline,
+            };
+            fasta_ops::split(input_file_name, &prefix, &options)?;
+            Ok(())
+        }
+        Brrrr::FaCat {
+            input_file_names,
+            dedup_ids,
+            newline_args,
+        } => {
+            let renamed =
+                fasta_ops::cat(&input_file_names, newline_args.output(stdout()), dedup_ids)?;
+            if renamed > 0 {
+                eprintln!("Renamed {} ids.", renamed);
             }
-        },
-        Brrrr::Gff2pq {
+            Ok(())
+        }
+        Brrrr::Composition { input, format } => {
+            let composition = match input {
+                None => stats::composition(stdin().lock())?,
+                Some(input) => stats::composition(BufReader::new(File::open(input)?))?,
+            };
+            composition.write(format.into(), &mut stdout())?;
+            Ok(())
+        }
+        Brrrr::Count { input, format } => {
+            let mut input = text_input(input)?;
+            let format = input_format(&mut input, format)?;
+            println!("{}", stats::count_records(input, format)?);
+            Ok(())
+        }
+        Brrrr::Ids {
+            input,
+            format,
+            id_delimiter,
+            attr,
+            newline_args,
+        } => {
+            let mut input = gunzipped_input(input)?;
+            let format = input_format(&mut input, format)?;
+            let mut output = newline_args.output(io::BufWriter::new(stdout()));
+            stats::write_ids(input, &mut output, format, id_delimiter, &attr)?;
+            ignore_broken_pipe(output.into_inner().flush())
+        }
+        Brrrr::Convert {
             input_file_name,
             output_file_name,
             compression,
-        } => parquet_writer::gff2pq(input_file_name, output_file_name, compression.into()),
-        Brrrr::Fq2jsonl { input } => match input {
-            None => json_writer::fq2jsonl(stdin().lock(), &mut stdout()),
-            Some(input) => {
-                let f = File::open(input)?;
-                json_writer::fq2jsonl(BufReader::new(f), &mut stdout())
+        } => convert::convert(&input_file_name, &output_file_name, compression.into()),
+        Brrrr::Seqs {
+            input,
+            ids_only,
+            id_delimiter,
+            newline_args,
+        } => {
+            let mut output = newline_args.output(io::BufWriter::new(stdout()));
+            stats::write_seqs(text_input(input)?, &mut output, ids_only, id_delimiter)?;
+            ignore_broken_pipe(output.into_inner().flush())
+        }
+        Brrrr::Grep {
+            motif,
+            input,
+            both_strands,
+            newline_args,
+        } => {
+            let mut output = newline_args.output(io::BufWriter::new(stdout()));
+            let (read, matched) =
+                stats::grep(gunzipped_input(input)?, &mut output, &motif, both_strands)?;
+            ignore_broken_pipe(output.into_inner().flush())?;
+            eprintln!("Matched {} of {} records.", matched, read);
+            Ok(())
+        }
+        Brrrr::Uniq {
+            input,
+            format,
+            dedup_stream_args,
+        } => {
+            let input: Box<dyn BufRead> = match input {
+                None => Box::new(stdin().lock()),
+                Some(input) => Box::new(BufReader::new(File::open(input)?)),
+            };
+            let uniqueness = match dedup_stream_args.filter_bytes() {
+                None => stats::uniq(input)?,
+                Some(filter_bytes) => stats::uniq_stream(input, filter_bytes)?,
+            };
+            uniqueness.write(format.into(), &mut stdout())?;
+            Ok(())
+        }
+        Brrrr::Collapse {
+            input_file_name,
+            output_file_name,
+            format,
+            keep_first_id,
+            compression,
+            dedup_stream_args,
+            newline_args,
+        } => {
+            let input = BufReader::new(File::open(input_file_name)?);
+            if let Some(filter_bytes) = dedup_stream_args.filter_bytes() {
+                if let CliCollapseFormat::Parquet = format {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "--dedup-stream only writes FASTA",
+                    )
+                    .into());
+                }
+                let mut output =
+                    newline_args.output(io::BufWriter::new(File::create(output_file_name)?));
+                stats::dedup_stream(input, &mut output, keep_first_id, filter_bytes)?;
+                return Ok(output.into_inner().flush()?);
+            }
+            let collapsed = stats::collapse(input, keep_first_id)?;
+            match format {
+                CliCollapseFormat::Fasta => {
+                    let output = io::BufWriter::new(File::create(output_file_name)?);
+                    let mut output = newline_args.output(output);
+                    stats::write_collapsed_fasta(&collapsed, &mut output)?;
+                    Ok(output.into_inner().flush()?)
+                }
+                CliCollapseFormat::Parquet => {
+                    parquet_writer::collapsed2pq(&collapsed, output_file_name, compression.into())
+                }
+            }
+        }
+        Brrrr::KmerSpectrum {
+            input_file_name,
+            output_file_name,
+            k,
+            compression,
+        } => {
+            let input = BufReader::new(File::open(input_file_name)?);
+            let spectrum = kmers::spectrum(input, k as usize)?;
+            parquet_writer::spectrum2pq(&spectrum, output_file_name, compression.into())
+        }
+        Brrrr::Minimizers {
+            input_file_name,
+            output_file_name,
+            w,
+            k,
+            no_canonical,
+            compression,
+        } => parquet_writer::minimizers2pq(
+            BufReader::new(File::open(input_file_name)?),
+            output_file_name,
+            compression.into(),
+            w as usize,
+            k as usize,
+            !no_canonical,
+        ),
+        Brrrr::Gchist {
+            input,
+            bins,
+            output,
+            compression,
+        } => {
+            let histogram = match input {
+                None => stats::gc_histogram(stdin().lock(), bins as usize)?,
+                Some(input) => {
+                    stats::gc_histogram(BufReader::new(File::open(input)?), bins as usize)?
+                }
+            };
+            match output {
+                Some(output) => parquet_writer::gchist2pq(&histogram, output, compression.into()),
+                None => Ok(histogram.write_table(&mut stdout())?),
             }
-        },
+        }
+        #[cfg(feature = "df")]
+        Brrrr::Df {
+            input_file_name,
+            output_file_name,
+            select,
+            filter,
+            format,
+            compression,
+        } => frame::df(
+            input_file_name,
+            output_file_name,
+            format.into(),
+            compression.into(),
+            &FrameOptions {
+                select,
+                filters: filter,
+            },
+        ),
+        #[cfg(not(feature = "df"))]
+        Brrrr::DfUnavailable(_) => Err(missing_feature("df", "df")),
+        #[cfg(feature = "chemistry")]
+        Brrrr::Oligo { input } => {
+            let mut output = io::BufWriter::new(stdout());
+            chemistry::write_oligo_table(text_input(input)?, &mut output)?;
+            Ok(output.flush()?)
+        }
+        #[cfg(not(feature = "chemistry"))]
+        Brrrr::OligoUnavailable(_) => Err(missing_feature("oligo", "chemistry")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_completions() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut script = Vec::new();
+            generate(shell, &mut Cli::command(), "brrrr", &mut script);
+            let script = String::from_utf8(script).unwrap();
+
+            // Subcommands and their flags both complete.
+            assert!(script.contains("fa2pq"), "{} has no fa2pq", shell);
+            assert!(script.contains("with-mean-qual"), "{} has no flags", shell);
+        }
+    }
+
+    #[test]
+    fn test_man() {
+        let mut page = Vec::new();
+        write_man(None, &mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains(".TH brrrr 1"));
+        assert!(page.contains("Converts between formats picked from the file extensions"));
+
+        let mut page = Vec::new();
+        write_man(Some("fa2pq"), &mut page).unwrap();
+        assert!(String::from_utf8(page)
+            .unwrap()
+            .contains(".TH brrrr-fa2pq 1"));
+
+        let error = write_man(Some("fa2pqq"), &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
     }
 }
@@ -13,3 +13,486 @@ fn file_doesnt_exist() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn parse_error_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::env::temp_dir().join("parse_error_is_reported.bedgraph");
+    std::fs::write(&input, "chr1\t0\t10\t1.5\nchr1\tx\t20\t2\n")?;
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    cmd.arg("bedgraph2pq")
+        .arg(&input)
+        .arg(std::env::temp_dir().join("parse_error_is_reported.parquet"));
+    let output = cmd.output()?;
+    assert_eq!(output.status.code(), Some(7));
+    assert!(String::from_utf8(output.stderr)?.contains("parse error, line 2: invalid start"));
+
+    Ok(())
+}
+
+#[test]
+fn selftest_round_trips_pass() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd.arg("selftest").output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "fa -> parquet -> fa\tok\nfq -> parquet -> fq\tok\nfq -> jsonl -> fq\tok\n\
+         gff -> parquet -> gff\tok\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn schema_reflects_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd.args(["schema", "fastq", "--with-mean-qual"]).output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("\"name\": \"quality\""));
+    assert!(stdout.contains("\"name\": \"mean_quality\""));
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd.args(["schema", "gff", "--with-mean-qual"]).output()?;
+    assert_eq!(output.status.code(), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn gzip_output_decompresses() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let input = std::env::temp_dir().join("gzip_output_decompresses.fa");
+    std::fs::write(&input, ">a\nACGT\n")?;
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd
+        .args(["fa2jsonl", "--gzip", "--gzip-level", "9"])
+        .arg(&input)
+        .output()?;
+    assert!(output.status.success());
+
+    let mut jsonl = String::new();
+    flate2::read::GzDecoder::new(output.stdout.as_slice()).read_to_string(&mut jsonl)?;
+    assert_eq!(
+        jsonl,
+        "{\"id\":\"a\",\"description\":null,\"sequence\":\"ACGT\"}\n"
+    );
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd
+        .args(["fa2jsonl", "--gzip", "--gzip-level", "10"])
+        .arg(&input)
+        .output()?;
+    assert_eq!(output.status.code(), Some(2));
+
+    // A level without a compressed output is an error, rather than being ignored.
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd
+        .args(["fa2jsonl", "--gzip-level", "3"])
+        .arg(&input)
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)?.contains("--compression-level needs a compressed"));
+
+    Ok(())
+}
+
+#[test]
+fn fq2pq_keeps_input_order() -> Result<(), Box<dyn std::error::Error>> {
+    use parquet::file::reader::SerializedFileReader;
+    use parquet::record::RowAccessor;
+
+    let dir = std::env::temp_dir();
+    let input = dir.join("fq2pq_keeps_input_order.fq");
+    let output = dir.join("fq2pq_keeps_input_order");
+    let _ = std::fs::remove_dir_all(&output);
+
+    // The odd reads are filtered out, and each batch holds two reads, so the order and numbers
+    // have to carry on across batches, filtered reads, and files.
+    let mut fastq = String::new();
+    for i in 0..10 {
+        let quality = if i % 2 == 0 { "IIII" } else { "####" };
+        fastq.push_str(&format!("@r{}\nACGT\n+\n{}\n", i, quality));
+    }
+    std::fs::write(&input, fastq)?;
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    cmd.arg("fq2pq").arg(&input).arg(&output).args([
+        "--min-mean-qual",
+        "20",
+        "--batch-bytes",
+        "8",
+        "--max-records",
+        "3",
+    ]);
+    cmd.assert().success();
+
+    let mut rows = Vec::new();
+    for part in ["part-00000.parquet", "part-00001.parquet"] {
+        let reader = SerializedFileReader::new(std::fs::File::open(output.join(part))?)?;
+        for row in reader.into_iter() {
+            rows.push((row.get_string(0)?.to_string(), row.get_long(4)?));
+        }
+    }
+    let expected: Vec<(String, i64)> = (0..5).map(|i| (format!("r{}", i * 2), i)).collect();
+    assert_eq!(rows, expected);
+    assert!(!output.join("part-00002.parquet").exists());
+
+    Ok(())
+}
+
+#[test]
+fn skip_and_limit_take_a_window() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::env::temp_dir().join("skip_and_limit_take_a_window.fa");
+    std::fs::write(&input, ">a\nAC\n>b\nGT\n>c\nAA\n>d\nCC\n")?;
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd
+        .args(["fa2csv", "--skip", "1", "--limit", "2"])
+        .arg(&input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "id,description,sequence\nb,,GT\nc,,AA\n"
+    );
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let output = cmd.args(["fa2csv", "--skip", "4"]).arg(&input).output()?;
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn gzipped_stdin_is_decompressed() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let fastq = b"@r1\nACGT\n+\nIIII\n";
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(fastq)?;
+    let gzipped = encoder.finish()?;
+
+    for (input, args) in [
+        (&fastq[..], &["fq2jsonl", "-"][..]),
+        (&gzipped[..], &["fq2jsonl"]),
+    ] {
+        let output = assert_cmd::Command::cargo_bin("brrrr")?
+            .args(args)
+            .write_stdin(input)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            "{\"id\":\"r1\",\"description\":null,\"sequence\":\"ACGT\",\"quality\":\"IIII\"}\n"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_writes_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join("dry_run_writes_nothing");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("genes.gff");
+    std::fs::write(
+        &input,
+        "##gff-version 3\nchr1\tsrc\tgene\t1\t10\t.\t+\t.\tID=g1\n",
+    )?;
+    let output = dir.join("genes.parquet");
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let result = cmd
+        .args(["gff2pq", "--dry-run", "--max-records", "10"])
+        .arg(&input)
+        .arg(&output)
+        .output()?;
+    assert!(result.status.success());
+    let stdout = String::from_utf8(result.stdout)?;
+    assert!(stdout.contains("input compression: uncompressed\ninput format: GFF\n"));
+    assert!(stdout.contains("\"name\": \"attribute\""));
+    assert!(!output.exists());
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let result = cmd
+        .args(["gff2pq", "--dry-run"])
+        .arg(&input)
+        .arg(dir.join("missing").join("genes.parquet"))
+        .output()?;
+    assert_eq!(result.status.code(), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn format_mismatch_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join("format_mismatch_is_reported");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("reads.fa");
+    std::fs::write(&input, ">r1\nACGT\n")?;
+    let output = dir.join("reads.parquet");
+
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let result = cmd.arg("fq2pq").arg(&input).arg(&output).output()?;
+    assert_eq!(result.status.code(), Some(6));
+    assert!(String::from_utf8(result.stderr)?
+        .contains("format mismatch: expected FASTQ input, but it looks like FASTA"));
+    assert!(!output.exists());
+
+    // Without the sniff, the FASTQ parser fails on the first record instead.
+    let mut cmd = Command::cargo_bin("brrrr")?;
+    let result = cmd
+        .args(["fq2pq", "--no-sniff"])
+        .arg(&input)
+        .arg(&output)
+        .output()?;
+    assert_eq!(result.status.code(), Some(1));
+
+    // Stdin is sniffed too, plain or gzipped.
+    let gff = b"##gff-version 3\nchr1\tsrc\tgene\t1\t10\t.\t+\t.\tID=g1\n";
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, gff)?;
+    for input in [gff.to_vec(), encoder.finish()?] {
+        let result = assert_cmd::Command::cargo_bin("brrrr")?
+            .args(["fq2jsonl", "-"])
+            .write_stdin(input)
+            .output()?;
+        assert_eq!(result.status.code(), Some(6));
+        assert!(String::from_utf8(result.stderr)?
+            .contains("format mismatch: expected FASTQ input, but it looks like GFF"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn trailing_newline_can_be_left_off() -> Result<(), Box<dyn std::error::Error>> {
+    let fasta = b">a\nACGT\n>b\nGG\n";
+
+    for args in [
+        &["fa2jsonl"][..],
+        &["fa2csv"],
+        &["translate"],
+        &["grep", "GG"],
+    ] {
+        let with = assert_cmd::Command::cargo_bin("brrrr")?
+            .args(args)
+            .write_stdin(&fasta[..])
+            .output()?;
+        let without = assert_cmd::Command::cargo_bin("brrrr")?
+            .args(args)
+            .arg("--no-trailing-newline")
+            .write_stdin(&fasta[..])
+            .output()?;
+        assert!(with.status.success() && without.status.success());
+        assert_eq!(with.stdout.last(), Some(&b'\n'), "{:?}", args);
+        assert_eq!(
+            without.stdout,
+            with.stdout[..with.stdout.len() - 1],
+            "{:?}",
+            args
+        );
+    }
+
+    // The writers of output files, from a FASTA file and from its parquet.
+    let dir = std::env::temp_dir();
+    let input = dir.join("trailing_newline_can_be_left_off.fa");
+    let parquet = dir.join("trailing_newline_can_be_left_off.parquet");
+    std::fs::write(&input, fasta)?;
+    Command::cargo_bin("brrrr")?
+        .arg("fa2pq")
+        .arg(&input)
+        .arg(&parquet)
+        .assert()
+        .success();
+
+    for (command, input) in [("pq2fa", &parquet), ("collapse", &input)] {
+        let output = dir.join(format!("trailing_newline_can_be_left_off.{}.fa", command));
+        for flag in [None, Some("--no-trailing-newline")] {
+            Command::cargo_bin("brrrr")?
+                .arg(command)
+                .args(flag)
+                .arg(input)
+                .arg(&output)
+                .assert()
+                .success();
+            let written = std::fs::read(&output)?;
+            assert_eq!(
+                written.last() == Some(&b'\n'),
+                flag.is_none(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fq2jsonl_combines_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let fastq = b"@r1\naCGt\n+\n!!II\n@r2\nacgg\n+\nII5I\n@r3\nttAA\n+\nIIII\n";
+
+    // r1's mean quality is too low, r2 is skipped by the window, and r3 is kept, uppercased and
+    // reversed, in whatever order the flags are given in.
+    for args in [
+        [
+            "--fold-case",
+            "--reverse",
+            "--min-mean-qual",
+            "30",
+            "--skip",
+            "1",
+        ],
+        [
+            "--skip",
+            "1",
+            "--min-mean-qual",
+            "30",
+            "--reverse",
+            "--fold-case",
+        ],
+    ] {
+        let output = assert_cmd::Command::cargo_bin("brrrr")?
+            .arg("fq2jsonl")
+            .args(args)
+            .write_stdin(&fastq[..])
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            "{\"id\":\"r3\",\"description\":null,\"sequence\":\"AATT\",\"quality\":\"IIII\"}\n"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn count_detects_the_format_on_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    for (input, count) in [
+        (
+            &b"##gff-version 3\nchr1\t.\tgene\t1\t9\t.\t+\t.\tID=a\n"[..],
+            "1\n",
+        ),
+        (b">a\nACGT\n>b\nGG\n", "2\n"),
+        (b"@a\nAC\nGT\n+\nII\nII\n", "1\n"),
+    ] {
+        let output = assert_cmd::Command::cargo_bin("brrrr")?
+            .arg("count")
+            .write_stdin(input)
+            .output()?;
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(String::from_utf8(output.stdout)?, count);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn ids_reads_gzipped_files() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let input = std::env::temp_dir().join("ids_reads_gzipped_files.fa.gz");
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(&input)?,
+        flate2::Compression::default(),
+    );
+    encoder.write_all(b">gi|1 one\nACGT\n>b\nGG\n")?;
+    encoder.finish()?;
+
+    let output = Command::cargo_bin("brrrr")?
+        .args(["ids", "--id-delimiter", "|"])
+        .arg(&input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "gi\nb\n");
+
+    Ok(())
+}
+
+#[test]
+fn ids_detects_gff() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::env::temp_dir().join("ids_detects_gff.gff");
+    std::fs::write(
+        &input,
+        "##gff-version 3\nchr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+         chr1\t.\texon\t1\t5\t.\t+\t.\tParent=g1\n",
+    )?;
+
+    let output = Command::cargo_bin("brrrr")?
+        .arg("ids")
+        .arg(&input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "g1\n");
+
+    Ok(())
+}
+
+#[test]
+fn convert_rejects_unknown_extensions() -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::env::temp_dir().join("convert_rejects_unknown_extensions.fa");
+    std::fs::write(&input, ">a\nACGT\n")?;
+
+    let output = Command::cargo_bin("brrrr")?
+        .arg("convert")
+        .arg(&input)
+        .arg(std::env::temp_dir().join("convert_rejects_unknown_extensions.txt"))
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)?.contains("from its extension"));
+
+    Ok(())
+}
+
+#[cfg(not(feature = "chemistry"))]
+#[test]
+fn missing_feature_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::cargo_bin("brrrr")?
+        .args(["oligo", "primers.fa", "--unknown-flag"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)?.contains("--features chemistry"));
+
+    Ok(())
+}
+
+#[test]
+fn closed_stdout_is_not_an_error() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    // Far more output than a pipe holds, so brrrr is still writing when the reader goes.
+    let input = std::env::temp_dir().join("closed_stdout_is_not_an_error.fa");
+    let records: String = (0..100_000)
+        .map(|i| format!(">r{}\nACGTACGTACGTACGT\n", i))
+        .collect();
+    std::fs::write(&input, records)?;
+
+    for args in [
+        vec!["fa2jsonl"],
+        vec!["fa2jsonl", "--gzip"],
+        vec!["seqs"],
+        vec!["ids"],
+        vec!["grep", "ACGT"],
+    ] {
+        let mut child = Command::cargo_bin("brrrr")?
+            .args(&args)
+            .arg(&input)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child.stdout.take().unwrap().read_exact(&mut [0; 16])?;
+        assert!(child.wait()?.success(), "{:?} failed", args);
+    }
+
+    Ok(())
+}